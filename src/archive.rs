@@ -0,0 +1,535 @@
+use std::path::Path;
+
+use crate::command::Limited;
+use crate::compression::Compression;
+use crate::config::Config;
+use crate::format::ByteFormatter;
+
+/// An archive format whose entries can be walked like files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    /// A tarball, possibly compressed as a whole
+    Tar(Option<Compression>),
+}
+
+impl ArchiveFormat {
+    /// The archive format of a file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar(None))
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::Tar(Some(Compression::Gzip)))
+        } else if name.ends_with(".tar.zst") {
+            Some(ArchiveFormat::Tar(Some(Compression::Zstd)))
+        } else {
+            None
+        }
+    }
+}
+
+/// A file stored in an archive
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// Path within the archive, with '/' separators
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: usize,
+    // Where the entry's data starts in the archive, and how it is stored
+    offset: usize,
+    storage: Storage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Storage {
+    /// `size` bytes at `offset`, as in a tarball
+    Plain,
+    /// A zip entry, with its compression method and compressed size; `offset` is its local header
+    Zip { method: u16, compressed_size: usize },
+}
+
+/// The files in a zip or tar archive, read into memory
+///
+/// Entries are listed up front and only decompressed when read, so one too
+/// large to include costs nothing.
+pub struct Archive {
+    bytes: Vec<u8>,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl Archive {
+    /// Read an archive and list its files, leaving out directories, links and other special entries
+    pub fn open(path: &Path, format: ArchiveFormat) -> Result<Self, String> {
+        let too_large = || format!("archive is over {}", ByteFormatter::format(Config::MAX_ARCHIVE_SIZE));
+        let bytes = match format {
+            ArchiveFormat::Tar(Some(compression)) => match compression.decompress(path, Config::MAX_ARCHIVE_SIZE) {
+                Some(Limited::Complete(bytes)) => bytes,
+                Some(Limited::TooLarge(_)) => return Err(too_large()),
+                None => return Err(format!("not a valid {} file", compression)),
+            },
+            ArchiveFormat::Zip | ArchiveFormat::Tar(None) => {
+                let size = path.metadata().map_err(|error| error.to_string())?.len();
+                if size > Config::MAX_ARCHIVE_SIZE as u64 {
+                    return Err(too_large());
+                }
+                std::fs::read(path).map_err(|error| error.to_string())?
+            }
+        };
+        let entries = match format {
+            ArchiveFormat::Zip => zip_entries(&bytes)?,
+            ArchiveFormat::Tar(_) => tar_entries(&bytes)?,
+        };
+        Ok(Self { bytes, entries })
+    }
+
+    /// The files in the archive, in the order they are stored
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// The content of an entry, or `None` if it is encrypted, corrupt or compressed with an unsupported method
+    pub fn read(&self, entry: &ArchiveEntry) -> Option<Vec<u8>> {
+        match entry.storage {
+            Storage::Plain => self.bytes.get(entry.offset..entry.offset + entry.size).map(<[u8]>::to_vec),
+            Storage::Zip { method, compressed_size } => {
+                // The local header repeats the name and has its own extra field
+                let header = self.bytes.get(entry.offset..entry.offset + 30)?;
+                if u32_at(header, 0) != 0x04034b50 {
+                    return None;
+                }
+                let start = entry.offset + 30 + u16_at(header, 26) as usize + u16_at(header, 28) as usize;
+                let data = self.bytes.get(start..start + compressed_size)?;
+                match method {
+                    0 => (data.len() == entry.size).then(|| data.to_vec()),
+                    8 => inflate(data, entry.size).filter(|output| output.len() == entry.size),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// The files listed in a zip archive's central directory
+fn zip_entries(bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    // The end of central directory record is last, followed by a comment of up to 64KB
+    const END_LEN: usize = 22;
+    let end = (0..=bytes.len().saturating_sub(END_LEN))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&offset| bytes.len() >= offset + END_LEN && u32_at(bytes, offset) == 0x06054b50)
+        .ok_or("not a zip archive")?;
+    let count = u16_at(bytes, end + 10) as usize;
+    let mut offset = u32_at(bytes, end + 16) as usize;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let header = bytes
+            .get(offset..offset + 46)
+            .filter(|header| u32_at(header, 0) == 0x02014b50)
+            .ok_or("corrupt zip central directory")?;
+        let flags = u16_at(header, 8);
+        let method = u16_at(header, 10);
+        let compressed_size = u32_at(header, 20) as usize;
+        let size = u32_at(header, 24) as usize;
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+        let local_offset = u32_at(header, 42) as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or("corrupt zip central directory")?;
+        offset += 46 + name_len + extra_len + comment_len;
+
+        let name = String::from_utf8_lossy(name).into_owned();
+        // Directories end in '/'; encrypted entries (flag bit 0) can't be read
+        if name.ends_with('/') || flags & 1 != 0 {
+            continue;
+        }
+        entries.push(ArchiveEntry {
+            name,
+            size,
+            offset: local_offset,
+            storage: Storage::Zip { method, compressed_size },
+        });
+    }
+    Ok(entries)
+}
+
+/// The regular files in a tarball, with the long names of GNU and pax headers
+fn tar_entries(bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    const BLOCK: usize = 512;
+
+    let mut entries = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut offset = 0;
+    while let Some(header) = bytes.get(offset..offset + BLOCK) {
+        // The archive ends with zero blocks
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+        let size_field = field(124..136);
+        let size = usize::from_str_radix(size_field.trim(), 8)
+            .map_err(|_| format!("corrupt tar header at byte {}", offset))?;
+        let data = offset + BLOCK;
+        let content = bytes.get(data..data + size).ok_or("truncated tar archive")?;
+
+        let name = match (field(257..263).as_str(), field(345..500)) {
+            ("ustar", prefix) if !prefix.is_empty() => format!("{}/{}", prefix, field(0..100)),
+            _ => field(0..100),
+        };
+        match header[156] {
+            b'0' | 0 => entries.push(ArchiveEntry {
+                name: long_name.take().unwrap_or(name),
+                size,
+                offset: data,
+                storage: Storage::Plain,
+            }),
+            // GNU long name: the data is the name of the next entry
+            b'L' => long_name = Some(String::from_utf8_lossy(content).trim_end_matches('\0').to_string()),
+            // pax extended header: "<length> <key>=<value>\n" records, of which the path matters
+            b'x' => {
+                long_name = String::from_utf8_lossy(content)
+                    .lines()
+                    .find_map(|record| record.split_once(" path=").map(|(_, path)| path.to_string()));
+            }
+            _ => long_name = None,
+        }
+        offset = data + size.div_ceil(BLOCK) * BLOCK;
+    }
+    // A leading "./" is how `tar -C dir .` names the files
+    for entry in &mut entries {
+        if let Some(name) = entry.name.strip_prefix("./") {
+            entry.name = name.to_string();
+        }
+    }
+    Ok(entries)
+}
+
+/// Reads a DEFLATE stream bit by bit, least significant bit first
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Option<usize> {
+        let mut value = 0;
+        for index in 0..count {
+            let byte = *self.bytes.get(self.pos)?;
+            value |= (((byte >> self.bit) & 1) as usize) << index;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Some(value)
+    }
+
+    /// Skip to the next byte boundary, as stored blocks start on one
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code: how many codes have each length, and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<usize> {
+        // Codes of each length follow on from the shorter ones
+        let (mut code, mut first, mut index) = (0usize, 0usize, 0usize);
+        for length in 1..16 {
+            code |= reader.bits(1)?;
+            let count = self.counts[length] as usize;
+            if code < first + count {
+                return self.symbols.get(index + code - first).map(|&symbol| symbol as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+/// Decompress a raw DEFLATE stream (RFC 1951), as stored in zip entries, stopping past `limit` bytes
+fn inflate(data: &[u8], limit: usize) -> Option<Vec<u8>> {
+    const LENGTH_BASE: [usize; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+    const DISTANCE_BASE: [usize; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+        6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u32; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+    ];
+    // The order code length code lengths are sent in
+    const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let mut reader = BitReader { bytes: data, pos: 0, bit: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        let (literals, distances) = match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data.get(reader.pos..reader.pos + 4)?;
+                let len = u16_at(header, 0);
+                if len != !u16_at(header, 2) {
+                    return None;
+                }
+                let start = reader.pos + 4;
+                output.extend_from_slice(data.get(start..start + len as usize)?);
+                reader.pos = start + len as usize;
+                if output.len() > limit {
+                    return None;
+                }
+                if last {
+                    return Some(output);
+                }
+                continue;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+            }
+            2 => {
+                let literal_count = reader.bits(5)? + 257;
+                let distance_count = reader.bits(5)? + 1;
+                let code_length_count = reader.bits(4)? + 4;
+                let mut code_lengths = [0u8; 19];
+                for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+                    code_lengths[symbol] = reader.bits(3)? as u8;
+                }
+                let code_length_code = Huffman::new(&code_lengths);
+
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (length, repeat) = match code_length_code.decode(&mut reader)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 => (*lengths.last()?, 3 + reader.bits(2)?),
+                        17 => (0, 3 + reader.bits(3)?),
+                        _ => (0, 11 + reader.bits(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(length, repeat));
+                }
+                if lengths.len() != literal_count + distance_count {
+                    return None;
+                }
+                (Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..]))
+            }
+            _ => return None,
+        };
+
+        loop {
+            match literals.decode(&mut reader)? {
+                symbol @ 0..=255 => output.push(symbol as u8),
+                256 => break,
+                symbol => {
+                    let index = symbol - 257;
+                    let length = LENGTH_BASE.get(index)? + reader.bits(LENGTH_EXTRA[index])?;
+                    let index = distances.decode(&mut reader)?;
+                    let distance = DISTANCE_BASE.get(index)? + reader.bits(DISTANCE_EXTRA[index])?;
+                    let start = output.len().checked_sub(distance)?;
+                    // The copy may overlap what it is writing, repeating a short run
+                    for offset in 0..length {
+                        output.push(output[start + offset]);
+                    }
+                }
+            }
+            if output.len() > limit {
+                return None;
+            }
+        }
+        if last {
+            return Some(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A zip archive with each entry stored (method 0) or deflated (method 8) as given
+    fn zip(entries: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut central = Vec::new();
+        for &(name, method, data, size) in entries {
+            let offset = bytes.len() as u32;
+            bytes.extend_from_slice(&0x04034b50u32.to_le_bytes());
+            bytes.extend_from_slice(&[20, 0, 0, 0]);
+            bytes.extend_from_slice(&method.to_le_bytes());
+            bytes.extend_from_slice(&[0; 8]);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(size as u32).to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(data);
+
+            central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(size as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let central_offset = bytes.len() as u32;
+        let central_len = central.len() as u32;
+        bytes.extend_from_slice(&central);
+        bytes.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&central_len.to_le_bytes());
+        bytes.extend_from_slice(&central_offset.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    /// A tar header block for a regular file (or another type) of `size` bytes
+    fn tar_header(name: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = kind;
+        header[257..262].copy_from_slice(b"ustar");
+        header
+    }
+
+    #[test]
+    fn test_inflate() {
+        // Stored, fixed Huffman and dynamic Huffman blocks
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'], 100).unwrap(), b"abc");
+        let fixed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+        assert_eq!(inflate(&fixed, 100).unwrap(), b"hello hello hello");
+        let dynamic = [
+            0x15, 0xc8, 0xb1, 0x11, 0x00, 0x30, 0x0c, 0xc2, 0xc0, 0x9e, 0x2d, 0x85, 0xce, 0xfb, 0xaf, 0x10, 0x82, 0x2a,
+            0x1e, 0x4a, 0x2e, 0x42, 0x50, 0xae, 0x0c, 0x36, 0x17, 0x97, 0x7f, 0x66, 0x7e, 0xad, 0xb4, 0xf1, 0x01,
+        ];
+        let expected = "aaba|e|caa|accaebaabaaaacacaae|abaaebacaababcabb|c".replace('|', "\n");
+        assert_eq!(inflate(&dynamic, 100).unwrap(), expected.as_bytes());
+
+        // Over the limit, truncated, or not DEFLATE at all
+        assert_eq!(inflate(&fixed, 10), None);
+        assert_eq!(inflate(&fixed[..5], 100), None);
+        assert_eq!(inflate(&[0x07], 100), None);
+    }
+
+    #[test]
+    fn test_zip() {
+        let dir = std::env::temp_dir().join("rcat_test_archive_zip");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.zip");
+        let fixed: &[u8] = &[0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+        let bytes = zip(&[
+            ("src/", 0, b"", 0),
+            ("src/main.rs", 0, b"fn main() {}", 12),
+            ("greeting.txt", 8, fixed, 17),
+            ("odd.bin", 12, b"BZh", 3),
+        ]);
+        fs::write(&path, bytes).unwrap();
+
+        let archive = Archive::open(&path, ArchiveFormat::from_path(&path).unwrap()).unwrap();
+        let names: Vec<&str> = archive.entries().iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["src/main.rs", "greeting.txt", "odd.bin"]);
+        let read = |index: usize| archive.read(&archive.entries()[index]);
+        assert_eq!(read(0).unwrap(), b"fn main() {}");
+        assert_eq!(read(1).unwrap(), b"hello hello hello");
+        // bzip2 compression isn't supported
+        assert_eq!(read(2), None);
+
+        fs::write(&path, "not a zip").unwrap();
+        assert!(Archive::open(&path, ArchiveFormat::Zip).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tar() {
+        let dir = std::env::temp_dir().join("rcat_test_archive_tar");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.tar");
+        let long_name = format!("deep/{}/file.txt", "nested".repeat(20));
+        let mut bytes = tar_header("./docs/", b'5', 0);
+        bytes.extend(tar_header("./docs/readme.md", b'0', 5));
+        bytes.extend(b"hello".iter().chain([0; 507].iter()));
+        bytes.extend(tar_header("././@LongLink", b'L', long_name.len()));
+        bytes.extend(long_name.as_bytes().iter().copied().chain(std::iter::repeat_n(0, 512 - long_name.len())));
+        bytes.extend(tar_header("deep/truncated", b'0', 2));
+        bytes.extend(b"ok".iter().chain([0; 510].iter()));
+        bytes.extend([0; 1024]);
+        fs::write(&path, bytes).unwrap();
+
+        let archive = Archive::open(&path, ArchiveFormat::from_path(&path).unwrap()).unwrap();
+        let entries = archive.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "docs/readme.md");
+        assert_eq!(archive.read(&entries[0]).unwrap(), b"hello");
+        assert_eq!(entries[1].name, long_name);
+        assert_eq!(archive.read(&entries[1]).unwrap(), b"ok");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ArchiveFormat::from_path(Path::new("a/B.ZIP")), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_path(Path::new("x.tar")), Some(ArchiveFormat::Tar(None)));
+        assert_eq!(ArchiveFormat::from_path(Path::new("x.tgz")), Some(ArchiveFormat::Tar(Some(Compression::Gzip))));
+        assert_eq!(ArchiveFormat::from_path(Path::new("x.tar.zst")), Some(ArchiveFormat::Tar(Some(Compression::Zstd))));
+        assert_eq!(ArchiveFormat::from_path(Path::new("x.gz")), None);
+    }
+}
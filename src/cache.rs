@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::encoding::{Encoding, TextInfo};
+use crate::file_processor::{FileContent, Strategy};
+
+/// Identifies the cache format and the rcat version that wrote it; any other cache is ignored
+const HEADER: &str = concat!("rcat-cache ", env!("CARGO_PKG_VERSION"));
+
+/// `$XDG_CACHE_HOME/rcat`, falling back to `~/.cache/rcat`
+pub fn cache_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("rcat")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("rcat")),
+    }
+}
+
+/// A file's modification time and size, which must both match for its cached content to be used
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    secs: u64,
+    nanos: u32,
+    size: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            secs: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+struct Entry {
+    stamp: Stamp,
+    // Of the content, to tell whether a file that was touched actually changed
+    hash: u64,
+    content: FileContent,
+}
+
+/// Processed file content kept between runs, so unchanged files aren't read again
+///
+/// Entries are keyed by canonical path and processing strategy, and only
+/// used while the file's modification time and size are unchanged. Each set
+/// of roots has its own cache file; saving it keeps only the files looked up
+/// during the run, so deleted files don't pile up.
+pub struct FileCache {
+    file: PathBuf,
+    // Entries read from the cache file
+    stored: HashMap<String, Entry>,
+    // Entries looked up or added during this run, which are saved
+    fresh: HashMap<String, Entry>,
+}
+
+impl FileCache {
+    /// The cache for a walk of `roots`, in the cache directory
+    ///
+    /// Returns `None` if there is no cache directory (`HOME` isn't set).
+    pub fn for_roots(roots: &[PathBuf]) -> Option<Self> {
+        let mut roots: Vec<PathBuf> = roots
+            .iter()
+            .map(|root| root.canonicalize().unwrap_or_else(|_| root.clone()))
+            .collect();
+        roots.sort();
+        let mut hasher = DefaultHasher::new();
+        roots.hash(&mut hasher);
+        let file = cache_dir()?.join(format!("files-{:016x}.cache", hasher.finish()));
+        Some(Self::load(file))
+    }
+
+    /// Load the cache kept in `file`, starting empty if it is missing or unreadable
+    pub fn load(file: PathBuf) -> Self {
+        let stored = fs::read(&file).ok().and_then(|bytes| parse(&bytes)).unwrap_or_default();
+        Self {
+            file,
+            stored,
+            fresh: HashMap::new(),
+        }
+    }
+
+    /// The content cached for a file processed with `strategy`, if the file hasn't changed since
+    pub fn get(&mut self, path: &Path, strategy: Strategy) -> Option<FileContent> {
+        let key = key(path, strategy)?;
+        let stamp = Stamp::of(path)?;
+        if let Some(entry) = self.fresh.get(&key).filter(|entry| entry.stamp == stamp) {
+            return Some(entry.content.clone());
+        }
+        // A stale entry stays, for `insert` to compare the new content with
+        if self.stored.get(&key)?.stamp != stamp {
+            return None;
+        }
+        let entry = self.stored.remove(&key)?;
+        let content = entry.content.clone();
+        self.fresh.insert(key, entry);
+        Some(content)
+    }
+
+    /// Remember a file's processed content, returning whether it differs from what the last run recorded
+    ///
+    /// A file the last run didn't see counts as changed. Unreadable files
+    /// aren't cached, since that is often temporary.
+    pub fn insert(&mut self, path: &Path, strategy: Strategy, content: &FileContent) -> bool {
+        let (Some(key), Some(stamp)) = (key(path, strategy), Stamp::of(path)) else {
+            return true;
+        };
+        let hash = content_hash(content);
+        let changed = self.stored.remove(&key).is_none_or(|previous| previous.hash != hash);
+        if !matches!(content, FileContent::Unreadable(_)) {
+            let content = content.clone();
+            self.fresh.insert(key, Entry { stamp, hash, content });
+        }
+        changed
+    }
+
+    /// Write the entries used during this run to the cache file
+    pub fn save(&self) -> io::Result<()> {
+        let mut output = format!("{}\n", HEADER).into_bytes();
+        for (key, entry) in &self.fresh {
+            let (kind, text, info) = match &entry.content {
+                FileContent::Text(text, info) => ("text", text.as_str(), *info),
+                _ => ("binary", "", TextInfo::default()),
+            };
+            let header = format!(
+                "{} {} {} {:016x} {} {} {} {} {} {}\n",
+                entry.stamp.secs,
+                entry.stamp.nanos,
+                entry.stamp.size,
+                entry.hash,
+                kind,
+                info.encoding,
+                u8::from(info.bom),
+                u8::from(info.mixed_line_endings),
+                key.len(),
+                text.len()
+            );
+            output.extend_from_slice(header.as_bytes());
+            output.extend_from_slice(key.as_bytes());
+            output.extend_from_slice(text.as_bytes());
+        }
+
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written aside and renamed, so a concurrent run never reads half a cache
+        let partial = self.file.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&partial, output)?;
+        fs::rename(&partial, &self.file)
+    }
+}
+
+fn content_hash(content: &FileContent) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match content {
+        FileContent::Text(text, _) => text.hash(&mut hasher),
+        FileContent::Binary => "binary".hash(&mut hasher),
+        FileContent::Unreadable(_) => "unreadable".hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// The key of a file: its strategy and canonical path, as UTF-8
+fn key(path: &Path, strategy: Strategy) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    Some(format!("{}:{}", strategy.name(), path.to_str()?))
+}
+
+/// Read the entries of a cache file, or `None` if it isn't one this version wrote
+fn parse(bytes: &[u8]) -> Option<HashMap<String, Entry>> {
+    const ENCODINGS: [Encoding; 4] = [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Latin1];
+
+    let mut rest = bytes.strip_prefix(HEADER.as_bytes())?.strip_prefix(b"\n")?;
+    let mut entries = HashMap::new();
+    while !rest.is_empty() {
+        let line_end = rest.iter().position(|&b| b == b'\n')?;
+        let line = std::str::from_utf8(&rest[..line_end]).ok()?;
+        let fields: Vec<&str> = line.split(' ').collect();
+        let [secs, nanos, size, hash, kind, encoding, bom, mixed, key_len, text_len] = fields[..] else {
+            return None;
+        };
+        let stamp = Stamp {
+            secs: secs.parse().ok()?,
+            nanos: nanos.parse().ok()?,
+            size: size.parse().ok()?,
+        };
+        let hash = u64::from_str_radix(hash, 16).ok()?;
+        let key_len: usize = key_len.parse().ok()?;
+        let text_len: usize = text_len.parse().ok()?;
+        let body = rest.get(line_end + 1..line_end + 1 + key_len + text_len)?;
+        let key = String::from_utf8(body[..key_len].to_vec()).ok()?;
+        let content = match kind {
+            "text" => {
+                let info = TextInfo {
+                    encoding: ENCODINGS.into_iter().find(|known| known.to_string() == encoding)?,
+                    bom: bom == "1",
+                    mixed_line_endings: mixed == "1",
+                };
+                FileContent::Text(String::from_utf8(body[key_len..].to_vec()).ok()?, info)
+            }
+            "binary" => FileContent::Binary,
+            _ => return None,
+        };
+        entries.insert(key, Entry { stamp, hash, content });
+        rest = &rest[line_end + 1 + key_len + text_len..];
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join("rcat_test_cache");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("files.cache");
+        let source = dir.join("source.txt");
+        let image = dir.join("image.png");
+        fs::write(&source, "caf\u{e9}").unwrap();
+        fs::write(&image, [0u8; 4]).unwrap();
+
+        let info = TextInfo {
+            encoding: Encoding::Latin1,
+            bom: false,
+            mixed_line_endings: true,
+        };
+        let mut cache = FileCache::load(file.clone());
+        assert!(cache.get(&source, Strategy::Text).is_none());
+        assert!(cache.insert(&source, Strategy::Text, &FileContent::Text("café\nline".to_string(), info)));
+        assert!(cache.insert(&image, Strategy::Text, &FileContent::Binary));
+        cache.save().unwrap();
+
+        let mut cache = FileCache::load(file.clone());
+        match cache.get(&source, Strategy::Text) {
+            Some(FileContent::Text(text, cached_info)) => {
+                assert_eq!(text, "café\nline");
+                assert_eq!(cached_info, info);
+            }
+            other => panic!("unexpected cached content: {:?}", other),
+        }
+        assert!(matches!(cache.get(&image, Strategy::Text), Some(FileContent::Binary)));
+        // Another strategy processes the file differently
+        assert!(cache.get(&source, Strategy::Notebook).is_none());
+
+        // A changed file is read again, and counts as changed if its content is
+        fs::write(&source, "changed content").unwrap();
+        assert!(cache.get(&source, Strategy::Text).is_none());
+        let mut cache = FileCache::load(file.clone());
+        assert!(!cache.insert(&source, Strategy::Text, &FileContent::Text("café\nline".to_string(), info)));
+        let mut cache = FileCache::load(file.clone());
+        assert!(cache.insert(&source, Strategy::Text, &FileContent::Text("changed content".to_string(), info)));
+
+        // A cache written by another version, or corrupt, is ignored
+        fs::write(&file, "rcat-cache 0.0.0\n").unwrap();
+        assert!(FileCache::load(file.clone()).get(&image, Strategy::Text).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,370 @@
+use std::ops::Range;
+use std::path::Path;
+
+use crate::config::parse_size;
+use crate::error::Error;
+use crate::file_processor::FileProcessor;
+use crate::tokens::{Tokenizer, parse_token_count};
+use crate::walker::IncludedFile;
+
+/// How large a chunk may be, as given to `--split`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkLimit {
+    Bytes(usize),
+    Tokens(usize),
+}
+
+impl ChunkLimit {
+    /// Parse a size with a byte unit ("500KB", "2MB") or else a token count ("8000", "128k")
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        if text.trim().to_uppercase().ends_with('B') {
+            parse_size(text).map(ChunkLimit::Bytes)
+        } else {
+            parse_token_count(text).map(ChunkLimit::Tokens)
+        }
+    }
+}
+
+/// Splits collected output into chunks no larger than a given size
+///
+/// Whole file sections are packed into chunks as they fit. A section that is
+/// larger than a chunk on its own is split at line boundaries into parts,
+/// each starting with a continuation header like `--- src/big.rs (part 2/3) ---`,
+/// so oversized files are never dropped.
+pub struct Chunker;
+
+impl Chunker {
+    /// Split `content` into chunks of at most `max_size` bytes
+    ///
+    /// `files` must describe the sections of `content`, as returned in `WalkResult`.
+    pub fn split(content: &str, files: &[IncludedFile], max_size: usize) -> Vec<String> {
+        Self::split_by(content, files, max_size, &str::len)
+    }
+
+    /// Split `content` into chunks within `limit`, each headed by `--- chunk 1/4 ---`
+    ///
+    /// Token limits are counted with `tokenizer`. The headers count towards
+    /// the limit, so every chunk can be pasted on its own.
+    pub fn split_numbered(
+        content: &str,
+        files: &[IncludedFile],
+        limit: ChunkLimit,
+        tokenizer: &dyn Tokenizer,
+    ) -> Vec<String> {
+        let count_tokens = |text: &str| tokenizer.count(text);
+        let (max, measure): (usize, &dyn Fn(&str) -> usize) = match limit {
+            ChunkLimit::Bytes(max) => (max, &str::len),
+            ChunkLimit::Tokens(max) => (max, &count_tokens),
+        };
+
+        // Reserve room for the widest chunk header possible, as for part headers
+        let widest_header = Self::chunk_header(content.len(), content.len());
+        let budget = max.saturating_sub(measure(&widest_header) + measure("\n")).max(1);
+
+        let chunks = Self::split_by(content, files, budget, measure);
+        let total = chunks.len();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| format!("{}\n{}", Self::chunk_header(index + 1, total), chunk))
+            .collect()
+    }
+
+    /// Header line opening one of `total` chunks
+    pub fn chunk_header(index: usize, total: usize) -> String {
+        format!("--- chunk {}/{} ---", index, total)
+    }
+
+    /// Split `content` into chunks whose `measure` is at most `max`
+    fn split_by(content: &str, files: &[IncludedFile], max: usize, measure: &dyn Fn(&str) -> usize) -> Vec<String> {
+        let mut pieces: Vec<String> = Vec::new();
+        let mut cursor = 0;
+
+        for file in files {
+            // Anything between sections (tree, truncation markers) passes through as is
+            Self::push_between(&mut pieces, &content[cursor..file.span.start]);
+
+            let section = &content[file.span.clone()];
+            if measure(section) <= max {
+                pieces.push(section.to_string());
+            } else {
+                let body = file.body.start - file.span.start..file.body.end - file.span.start;
+                pieces.extend(Self::split_section(&file.path, section, body, max, measure));
+            }
+            cursor = file.span.end;
+        }
+        Self::push_between(&mut pieces, &content[cursor..]);
+
+        Self::pack(pieces, max, measure)
+    }
+
+    /// Push non-file content, dropping the newlines that separated it from its neighbours
+    fn push_between(pieces: &mut Vec<String>, between: &str) {
+        let between = between.strip_prefix('\n').unwrap_or(between);
+        let between = between.strip_suffix('\n').unwrap_or(between);
+        if !between.is_empty() {
+            pieces.push(between.to_string());
+        }
+    }
+
+    /// Split one oversized file section into parts with continuation headers
+    ///
+    /// `body` is where the content sits within the section; a footer after it
+    /// ends the last part.
+    fn split_section(
+        path: &Path,
+        section: &str,
+        body: Range<usize>,
+        max: usize,
+        measure: &dyn Fn(&str) -> usize,
+    ) -> Vec<String> {
+        let footer = &section[body.end..];
+        let body = &section[body];
+
+        // Reserve room for the widest part header possible: a body never has more parts than bytes
+        let widest_header = FileProcessor::format_part_header(path, body.len(), body.len());
+        let budget = max.saturating_sub(measure(&widest_header) + measure("\n") + measure(footer)).max(1);
+
+        let bodies = Self::split_lines(body, budget, measure);
+        let total = bodies.len();
+        bodies
+            .into_iter()
+            .enumerate()
+            .map(|(index, part)| {
+                format!(
+                    "{}\n{}{}",
+                    FileProcessor::format_part_header(path, index + 1, total),
+                    part,
+                    if index + 1 == total { footer } else { "" }
+                )
+            })
+            .collect()
+    }
+
+    /// Split text at line boundaries into parts whose `measure` is at most `budget`
+    ///
+    /// Lines longer than the budget are split mid-line at a character boundary.
+    /// A part is measured as the sum of its lines.
+    fn split_lines<'a>(text: &'a str, budget: usize, measure: &dyn Fn(&str) -> usize) -> Vec<&'a str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut end = 0;
+        let mut used = 0;
+
+        for line in text.split_inclusive('\n') {
+            let line_size = measure(line);
+            if used + line_size <= budget {
+                end += line.len();
+                used += line_size;
+                continue;
+            }
+
+            if end > start {
+                parts.push(&text[start..end]);
+                start = end;
+            }
+
+            let line_end = end + line.len();
+            while measure(&text[start..line_end]) > budget {
+                let cut = Self::longest_fit(text, start..line_end, budget, measure);
+                parts.push(&text[start..cut]);
+                start = cut;
+            }
+            end = line_end;
+            used = measure(&text[start..end]);
+        }
+
+        if end > start || parts.is_empty() {
+            parts.push(&text[start..end]);
+        }
+        parts
+    }
+
+    /// End of the longest prefix of `text[range]` within `budget`, at a character boundary
+    ///
+    /// Always takes at least one character, even if the budget is smaller.
+    fn longest_fit(text: &str, range: Range<usize>, budget: usize, measure: &dyn Fn(&str) -> usize) -> usize {
+        let first_char = text[range.start..].chars().next().map_or(1, char::len_utf8);
+        let (mut low, mut high) = (range.start + first_char, range.end);
+        while low < high {
+            let mut middle = (low + high).div_ceil(2);
+            while !text.is_char_boundary(middle) {
+                middle += 1;
+            }
+            if measure(&text[range.start..middle]) <= budget {
+                low = middle;
+            } else {
+                high = middle - 1;
+                while !text.is_char_boundary(high) {
+                    high -= 1;
+                }
+            }
+        }
+        low
+    }
+
+    /// Greedily pack pieces into chunks, joining pieces within a chunk with newlines
+    fn pack(pieces: Vec<String>, max: usize, measure: &dyn Fn(&str) -> usize) -> Vec<String> {
+        let mut chunks: Vec<String> = Vec::new();
+        let mut current = String::new();
+        let mut used = 0;
+        let separator = measure("\n");
+
+        for piece in pieces {
+            let piece_size = measure(&piece);
+            if !current.is_empty() && used + separator + piece_size > max {
+                chunks.push(std::mem::take(&mut current));
+                used = 0;
+            }
+            if !current.is_empty() {
+                current.push('\n');
+                used += separator;
+            }
+            current.push_str(&piece);
+            used += piece_size;
+        }
+
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::TokenEstimator;
+    use std::path::PathBuf;
+
+    /// Build walker-style content and file spans from (path, body) pairs
+    fn collect(sections: &[(&str, &str)]) -> (String, Vec<IncludedFile>) {
+        let mut content = String::new();
+        let mut files = Vec::new();
+
+        for (path, body) in sections {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            let section = format!("--- {} ---\n{}", path, body);
+            let start = content.len();
+            content.push_str(&section);
+            files.push(IncludedFile {
+                path: PathBuf::from(path),
+                size: section.len(),
+                tokens: 0,
+                span: start..content.len(),
+                body: content.len() - body.len()..content.len(),
+                forced: false,
+                identical_to: None,
+            });
+        }
+        (content, files)
+    }
+
+    #[test]
+    fn test_small_files_share_a_chunk() {
+        let (content, files) = collect(&[("a.txt", "one\n"), ("b.txt", "two\n")]);
+        let chunks = Chunker::split(&content, &files, 1000);
+        assert_eq!(chunks, vec![content]);
+    }
+
+    #[test]
+    fn test_files_packed_into_chunks() {
+        let (content, files) = collect(&[("a.txt", "one\n"), ("b.txt", "two\n")]);
+        let chunks = Chunker::split(&content, &files, 20);
+        assert_eq!(chunks, vec!["--- a.txt ---\none\n", "--- b.txt ---\ntwo\n"]);
+    }
+
+    #[test]
+    fn test_giant_file_split_at_lines() {
+        let body: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let (content, files) = collect(&[("src/big.rs", &body)]);
+        let chunks = Chunker::split(&content, &files, 200);
+
+        assert!(chunks.len() > 1);
+        let total = chunks.len();
+        let mut rebuilt = String::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.len() <= 200, "chunk {} is {} bytes", index, chunk.len());
+
+            let header = format!("--- src/big.rs (part {}/{}) ---\n", index + 1, total);
+            let part = chunk.strip_prefix(&header).unwrap();
+            assert!(part.ends_with('\n'), "part {} split mid-line", index + 1);
+            rebuilt.push_str(part);
+        }
+        assert_eq!(rebuilt, body);
+    }
+
+    #[test]
+    fn test_footer_ends_last_part() {
+        let body: String = (0..30).map(|i| format!("line {}\n", i)).collect();
+        let (mut content, mut files) = collect(&[("big.rs", &body)]);
+        content.push_str("</file>");
+        files[0].span.end = content.len();
+
+        let chunks = Chunker::split(&content, &files, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 100));
+        assert!(chunks.last().unwrap().ends_with("line 29\n</file>"));
+        assert_eq!(chunks.iter().filter(|chunk| chunk.contains("</file>")).count(), 1);
+    }
+
+    #[test]
+    fn test_long_line_hard_split() {
+        let body = "é".repeat(300);
+        let (content, files) = collect(&[("wide.txt", &body)]);
+        let chunks = Chunker::split(&content, &files, 100);
+
+        let rebuilt: String = chunks
+            .iter()
+            .map(|chunk| {
+                assert!(chunk.len() <= 100);
+                chunk.split_once('\n').unwrap().1
+            })
+            .collect();
+        assert_eq!(rebuilt, body);
+    }
+
+    #[test]
+    fn test_chunk_limit_parse() {
+        assert_eq!(ChunkLimit::parse("500KB").unwrap(), ChunkLimit::Bytes(500 * 1024));
+        assert_eq!(ChunkLimit::parse("200b").unwrap(), ChunkLimit::Bytes(200));
+        assert_eq!(ChunkLimit::parse("128k").unwrap(), ChunkLimit::Tokens(128_000));
+        assert_eq!(ChunkLimit::parse("8000").unwrap(), ChunkLimit::Tokens(8000));
+        assert!(ChunkLimit::parse("lots").is_err());
+    }
+
+    #[test]
+    fn test_numbered_chunks() {
+        let body: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let (content, files) = collect(&[("a.txt", "one\n"), ("src/big.rs", &body)]);
+
+        let chunks = Chunker::split_numbered(&content, &files, ChunkLimit::Bytes(300), &TokenEstimator);
+        let total = chunks.len();
+        assert!(total > 2);
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.len() <= 300, "chunk {} is {} bytes", index, chunk.len());
+            assert!(chunk.starts_with(&format!("--- chunk {}/{} ---\n--- ", index + 1, total)));
+        }
+
+        let chunks = Chunker::split_numbered(&content, &files, ChunkLimit::Tokens(60), &TokenEstimator);
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            assert!(TokenEstimator.count(chunk) <= 60, "chunk is {} tokens", TokenEstimator.count(chunk));
+        }
+        assert!(chunks.last().unwrap().ends_with("line 99\n"));
+    }
+
+    #[test]
+    fn test_non_file_content_kept() {
+        let (body, mut files) = collect(&[("a.txt", "one\n")]);
+        let tree = "--- Directory tree ---\na.txt\n";
+        let content = format!("{}\n{}\n--- TRUNCATED ---", tree, body);
+        files[0].span = tree.len() + 1..tree.len() + 1 + body.len();
+        files[0].body = files[0].span.end - "one\n".len()..files[0].span.end;
+
+        let chunks = Chunker::split(&content, &files, 1000);
+        assert_eq!(chunks, vec![content]);
+    }
+}
@@ -1,34 +1,77 @@
-use std::io::{self, Write};
-use std::process::{Command, Stdio};
+use std::env;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Child, ChildStderr, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// How content reaches the clipboard
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The platform clipboard utility (pbcopy, xclip, clip)
+    System,
+    /// OSC 52 escape sequences written to the terminal, which sets the clipboard
+    /// on the machine running the terminal (works over SSH)
+    Osc52,
+}
+
+impl Backend {
+    /// Pick OSC 52 when forced, or when running over SSH without a display
+    pub fn detect(force_osc52: bool) -> Self {
+        let over_ssh = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+        let has_display = env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some();
+
+        if force_osc52 || (over_ssh && !has_display) {
+            Backend::Osc52
+        } else {
+            Backend::System
+        }
+    }
+}
+
+/// Check if the clipboard backend is usable
+pub fn validate_clipboard(backend: Backend) -> Result<(), Error> {
+    if backend == Backend::Osc52 {
+        if !io::stderr().is_terminal() && open_tty().is_err() {
+            return Err(Error::Clipboard(
+                "OSC 52 needs a terminal to write to, but none is attached".to_string(),
+            ));
+        }
+        return Ok(());
+    }
 
-/// Check if clipboard utility is available
-pub fn validate_clipboard() -> Result<(), String> {
     #[cfg(target_os = "macos")]
     {
         if !is_command_available("pbcopy") {
-            return Err(
+            return Err(Error::Clipboard(
                 "pbcopy not found. This should be installed by default on macOS.".to_string(),
-            );
+            ));
         }
     }
 
     #[cfg(target_os = "linux")]
     {
         if !is_command_available("xclip") {
-            return Err("xclip not found. Install it with:\n  \
+            return Err(Error::Clipboard(
+                "xclip not found. Install it with:\n  \
                 Ubuntu/Debian: sudo apt install xclip\n  \
                 Fedora: sudo dnf install xclip\n  \
                 Arch: sudo pacman -S xclip"
-                .to_string());
+                    .to_string(),
+            ));
         }
     }
 
     #[cfg(target_os = "windows")]
     {
         if !is_command_available("clip") {
-            return Err(
+            return Err(Error::Clipboard(
                 "clip.exe not found. This should be installed by default on Windows.".to_string(),
-            );
+            ));
         }
     }
 
@@ -36,7 +79,7 @@ pub fn validate_clipboard() -> Result<(), String> {
 }
 
 /// Check if a command is available in PATH
-fn is_command_available(cmd: &str) -> bool {
+pub fn is_command_available(cmd: &str) -> bool {
     #[cfg(target_os = "windows")]
     {
         Command::new("where")
@@ -56,47 +99,383 @@ fn is_command_available(cmd: &str) -> bool {
     }
 }
 
-pub fn copy_to_clipboard(content: &str) -> io::Result<()> {
-    #[cfg(target_os = "macos")]
-    {
-        let mut child = Command::new("pbcopy").stdin(Stdio::piped()).spawn()?;
+/// Copy content to the clipboard using the given backend
+///
+/// The content is read a piece at a time and passed on as it's read, so it
+/// needn't all be in memory (see `ClipboardWriter`). A clipboard utility that
+/// takes none of it for `timeout` is stopped.
+pub fn copy_to_clipboard(mut content: impl Read, backend: Backend, timeout: Duration) -> Result<(), Error> {
+    let mut writer = ClipboardWriter::open(backend, timeout)?;
+    match io::copy(&mut content, &mut writer) {
+        Ok(_) => writer.finish(),
+        // A utility that quit early breaks the pipe; why it quit says more
+        Err(error) => writer.finish().and(Err(Error::Clipboard(error.to_string()))),
+    }
+}
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(content.as_bytes())?;
-        }
+/// Writes to the clipboard as content is produced, taking effect on `finish`
+///
+/// With the system utility, writes go straight into its input. With OSC 52
+/// they're encoded and sent to the terminal as they come, so the clipboard is
+/// only set once the closing sequence is written.
+pub struct ClipboardWriter {
+    sink: Sink,
+}
+
+enum Sink {
+    Utility(Utility),
+    Osc52(Osc52Writer<Box<dyn Write>>),
+}
 
-        child.wait()?;
-        Ok(())
+/// A running clipboard utility, watched so a hung one is stopped
+struct Utility {
+    program: String,
+    child: Arc<Mutex<Child>>,
+    stdin: Option<ChildStdin>,
+    stderr: Option<ChildStderr>,
+    timeout: Duration,
+    watchdog: Watchdog,
+}
+
+impl ClipboardWriter {
+    /// Start writing to the clipboard through the given backend
+    ///
+    /// `timeout` is how long the clipboard utility may go without taking any
+    /// content, or without exiting once it has all of it, before it's killed.
+    pub fn open(backend: Backend, timeout: Duration) -> Result<Self, Error> {
+        match backend {
+            Backend::System => Self::spawn(utility_command(), timeout),
+            Backend::Osc52 => Osc52Writer::new(osc52_target())
+                .map(|writer| Self { sink: Sink::Osc52(writer) })
+                .map_err(|error| Error::Clipboard(error.to_string())),
+        }
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        let mut child = Command::new("xclip")
-            .arg("-selection")
-            .arg("clipboard")
+    /// Start a clipboard utility that reads the content from its input
+    fn spawn(mut command: Command, timeout: Duration) -> Result<Self, Error> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        // xclip forks a process that holds the selection, which mustn't keep
+        // rcat's stdout open for whoever reads it
+        let mut child = command
             .stdin(Stdio::piped())
-            .spawn()?;
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| Error::Clipboard(format!("Couldn't start {} - {}", program, error)))?;
+        let stdin = child.stdin.take();
+        let stderr = child.stderr.take();
+        let child = Arc::new(Mutex::new(child));
+        let watchdog = Watchdog::start(Arc::clone(&child), timeout);
+        Ok(Self {
+            sink: Sink::Utility(Utility {
+                program,
+                child,
+                stdin,
+                stderr,
+                timeout,
+                watchdog,
+            }),
+        })
+    }
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(content.as_bytes())?;
+    /// Finish the content, waiting for the clipboard utility to take it
+    ///
+    /// A utility that exits with a failure status is an error, with what it
+    /// printed to stderr as the explanation, as is one stopped for hanging.
+    pub fn finish(self) -> Result<(), Error> {
+        let mut utility = match self.sink {
+            Sink::Utility(utility) => utility,
+            Sink::Osc52(writer) => return writer.finish().map_err(|error| Error::Clipboard(error.to_string())),
+        };
+        // Closing its input tells the utility the content is complete
+        drop(utility.stdin.take());
+        let program = &utility.program;
+
+        // Polled rather than waited on, so the watchdog can kill it meanwhile
+        let status = loop {
+            match utility.child.lock().unwrap().try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => {}
+                Err(error) => return Err(Error::Clipboard(format!("{} failed - {}", program, error))),
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let timed_out = utility.watchdog.stop();
+        if status.success() {
+            return Ok(());
+        }
+        if timed_out {
+            return Err(Error::Clipboard(format!(
+                "{} stopped responding and was killed after {}s",
+                program,
+                utility.timeout.as_secs_f64()
+            )));
+        }
+
+        // Read only once it has failed: xclip succeeds by forking a process
+        // that keeps stderr open for as long as it holds the selection
+        let mut message = String::new();
+        if let Some(stderr) = &mut utility.stderr {
+            let _ = stderr.read_to_string(&mut message);
+        }
+        Err(Error::Clipboard(match message.trim() {
+            "" => format!("{} failed ({})", program, status),
+            message => format!("{} failed ({}): {}", program, status, message),
+        }))
+    }
+}
+
+impl Write for ClipboardWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Utility(utility) => match &mut utility.stdin {
+                Some(stdin) => {
+                    let written = stdin.write(bytes)?;
+                    utility.watchdog.progress();
+                    Ok(written)
+                }
+                None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "clipboard input is closed")),
+            },
+            Sink::Osc52(writer) => writer.write(bytes),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Utility(utility) => utility.stdin.as_mut().map_or(Ok(()), |stdin| stdin.flush()),
+            Sink::Osc52(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Kills a child process that goes `timeout` without progress, and reaps it
+///
+/// Without this, a clipboard utility stuck on a stalled X connection would
+/// block rcat forever, on a full pipe or in waiting for it to exit.
+struct Watchdog {
+    // Each message puts off the deadline; dropping the sender stops the watchdog
+    progress: Option<mpsc::Sender<()>>,
+    timed_out: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Watchdog {
+    fn start(child: Arc<Mutex<Child>>, timeout: Duration) -> Self {
+        let (progress, updates) = mpsc::channel();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&timed_out);
+        let thread = thread::spawn(move || {
+            loop {
+                match updates.recv_timeout(timeout) {
+                    Ok(()) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => break,
+                }
+            }
+            flag.store(true, Ordering::SeqCst);
+            let mut child = child.lock().unwrap();
+            // Reaped right away, so a killed utility doesn't linger as a zombie
+            if child.kill().is_ok() {
+                let _ = child.wait();
+            }
+        });
+        Self {
+            progress: Some(progress),
+            timed_out,
+            thread: Some(thread),
+        }
+    }
+
+    /// Note that the child made progress, restarting the timeout
+    fn progress(&self) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(());
         }
+    }
 
-        child.wait()?;
-        Ok(())
+    /// Stop watching, returning whether the child was killed for timing out
+    fn stop(&mut self) -> bool {
+        drop(self.progress.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.timed_out.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The platform clipboard utility, reading the content from its input
+fn utility_command() -> Command {
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("pbcopy")
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut command = Command::new("xclip");
+        command.arg("-selection").arg("clipboard");
+        command
     }
 
     #[cfg(target_os = "windows")]
     {
-        let mut child = Command::new("cmd")
-            .args(&["/C", "clip"])
-            .stdin(Stdio::piped())
-            .spawn()?;
+        let mut command = Command::new("cmd");
+        command.args(["/C", "clip"]);
+        command
+    }
+}
+
+/// Where OSC 52 sequences go: the tty itself, so they still reach the
+/// terminal when stderr is redirected, or else stderr
+fn osc52_target() -> Box<dyn Write> {
+    match open_tty() {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(io::stderr()),
+    }
+}
+
+/// Writes an OSC 52 "set clipboard" sequence, base64-encoding content as it comes
+struct Osc52Writer<W: Write> {
+    output: W,
+    // Up to two bytes left over from the last write, as base64 encodes three at a time
+    pending: Vec<u8>,
+    // tmux swallows OSC 52 unless it's wrapped in a passthrough sequence
+    tmux: bool,
+}
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(content.as_bytes())?;
+impl<W: Write> Osc52Writer<W> {
+    fn new(output: W) -> io::Result<Self> {
+        Self::with_tmux(output, env::var_os("TMUX").is_some())
+    }
+
+    fn with_tmux(mut output: W, tmux: bool) -> io::Result<Self> {
+        if tmux {
+            output.write_all(b"\x1bPtmux;\x1b")?;
+        }
+        output.write_all(b"\x1b]52;c;")?;
+        Ok(Self {
+            output,
+            pending: Vec::new(),
+            tmux,
+        })
+    }
+
+    /// Encode what's left and close the sequence, which sets the clipboard
+    fn finish(mut self) -> io::Result<()> {
+        self.output.write_all(base64_encode(&self.pending).as_bytes())?;
+        self.output.write_all(b"\x07")?;
+        if self.tmux {
+            self.output.write_all(b"\x1b\\")?;
         }
+        self.output.flush()
+    }
+}
+
+impl<W: Write> Write for Osc52Writer<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(bytes);
+        let whole = self.pending.len() / 3 * 3;
+        self.output.write_all(base64_encode(&self.pending[..whole]).as_bytes())?;
+        self.pending.drain(..whole);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(unix)]
+fn open_tty() -> io::Result<std::fs::File> {
+    std::fs::OpenOptions::new().write(true).open("/dev/tty")
+}
+
+#[cfg(not(unix))]
+fn open_tty() -> io::Result<std::fs::File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no tty device"))
+}
+
+/// Standard base64 with padding, as OSC 52 expects
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        encoded.push(ALPHABET[(n >> 18) as usize & 63] as char);
+        encoded.push(ALPHABET[(n >> 12) as usize & 63] as char);
+        encoded.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+        encoded.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] as char } else { '=' });
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_utility_failure() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "cat > /dev/null; echo \"Error: Can't open display\" >&2; exit 3"]);
+        let mut writer = ClipboardWriter::spawn(command, Duration::from_secs(10)).unwrap();
+        writer.write_all(b"content").unwrap();
+        let error = writer.finish().unwrap_err().to_string();
+        assert_eq!(error, "sh failed (exit status: 3): Error: Can't open display");
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "cat > /dev/null"]);
+        let mut writer = ClipboardWriter::spawn(command, Duration::from_secs(10)).unwrap();
+        writer.write_all(b"content").unwrap();
+        assert!(writer.finish().is_ok());
+
+        let error = ClipboardWriter::spawn(Command::new("rcat-no-such-utility"), Duration::from_secs(10)).err().unwrap();
+        assert!(error.to_string().starts_with("Couldn't start rcat-no-such-utility - "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_utility_timeout() {
+        // Never reads its input nor exits, like xclip on a stalled X connection
+        let mut command = Command::new("sh");
+        command.args(["-c", "exec sleep 30"]);
+        let started = std::time::Instant::now();
+        let mut writer = ClipboardWriter::spawn(command, Duration::from_millis(100)).unwrap();
+        writer.write_all(b"content").unwrap();
+        let error = writer.finish().unwrap_err().to_string();
+        assert_eq!(error, "sh stopped responding and was killed after 0.1s");
+
+        // More than the pipe holds, so a write blocks until the utility is killed
+        let mut command = Command::new("sh");
+        command.args(["-c", "exec sleep 30"]);
+        let mut writer = ClipboardWriter::spawn(command, Duration::from_millis(100)).unwrap();
+        assert!(io::copy(&mut &[b'x'; 1 << 20][..], &mut writer).is_err());
+        assert!(writer.finish().unwrap_err().to_string().contains("killed"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_osc52_writer() {
+        let mut output = Vec::new();
+        let mut writer = Osc52Writer::with_tmux(&mut output, false).unwrap();
+        // Split mid-group, so leftover bytes carry over to the next write
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"w").unwrap();
+        writer.write_all(b"orld").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(output, b"\x1b]52;c;aGVsbG8gd29ybGQ=\x07");
 
-        child.wait()?;
-        Ok(())
+        let mut output = Vec::new();
+        Osc52Writer::with_tmux(&mut output, true).unwrap().finish().unwrap();
+        assert_eq!(output, b"\x1bPtmux;\x1b\x1b]52;c;\x07\x1b\\");
     }
 }
@@ -0,0 +1,110 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+
+use crate::encoding::Decoder;
+use crate::error::{Error, Result};
+
+/// What a shell command printed, and how it exited
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Standard output, decoded like a file's content
+    pub stdout: String,
+    /// Exit code, or `None` if the command was killed by a signal
+    pub code: Option<i32>,
+}
+
+impl CommandOutput {
+    /// Whether the command exited with status 0
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Run a command line through the platform's shell (`sh -c`, or `cmd /C` on Windows)
+///
+/// A command that runs but fails still returns its output, since failing
+/// tests or builds are often what the output is wanted for. Only a command
+/// that can't be started at all is an error.
+pub fn run(command_line: &str) -> Result<CommandOutput> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let output = Command::new(shell)
+        .arg(flag)
+        .arg(command_line)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|error| Error::Command(format!("Failed to run '{}': {}", command_line, error)))?;
+
+    let stdout = Decoder::decode(&output.stdout)
+        .map(|(text, _)| text)
+        .unwrap_or_else(|| String::from_utf8_lossy(&output.stdout).into_owned());
+    Ok(CommandOutput {
+        stdout,
+        code: output.status.code(),
+    })
+}
+
+/// What a program printed, cut off at a size limit
+#[derive(Debug, PartialEq, Eq)]
+pub enum Limited {
+    /// Everything the program printed
+    Complete(Vec<u8>),
+    /// How much the program printed, when it was over the limit
+    TooLarge(usize),
+}
+
+/// Run a program to completion, keeping at most `limit` bytes of its standard output
+///
+/// Past the limit, the rest is only counted so the full size can be
+/// reported. Returns `None` if the program can't be started or exits with
+/// an error.
+pub fn capture(command: &mut Command, limit: usize) -> Option<Limited> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).spawn().ok()?;
+
+    let mut stdout = child.stdout.take()?;
+    let mut data = Vec::new();
+    let read = stdout.by_ref().take(limit as u64 + 1).read_to_end(&mut data);
+    let rest = match read {
+        Ok(_) if data.len() > limit => io::copy(&mut stdout, &mut io::sink()).ok(),
+        Ok(_) => Some(0),
+        Err(_) => None,
+    };
+    drop(stdout);
+    let status = child.wait().ok()?;
+    if !status.success() {
+        return None;
+    }
+
+    match rest? {
+        0 if data.len() <= limit => Some(Limited::Complete(data)),
+        rest => Some(Limited::TooLarge(data.len() + rest as usize)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let output = run("echo one; echo two").unwrap();
+        assert_eq!(output.stdout, "one\ntwo\n");
+        assert!(output.success());
+
+        let output = run("printf partial; exit 3").unwrap();
+        assert_eq!(output.stdout, "partial");
+        assert_eq!(output.code, Some(3));
+        assert!(!output.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture() {
+        let mut command = Command::new("printf");
+        command.arg("12345");
+        assert_eq!(capture(&mut command, 5), Some(Limited::Complete(b"12345".to_vec())));
+        assert_eq!(capture(&mut command, 3), Some(Limited::TooLarge(5)));
+        assert_eq!(capture(&mut Command::new("false"), 5), None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::command::{self, Limited};
+
+/// A single-file compression format whose content can be included decompressed
+///
+/// Decompression shells out to the format's own tool (`gzip` or `zstd`), so a
+/// missing tool just leaves the file binary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of a file, from its extension
+    ///
+    /// Compressed tarballs (`.tar.gz`) hold many files rather than one, so they don't count.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let compression = match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "gz" => Compression::Gzip,
+            "zst" => Compression::Zstd,
+            _ => return None,
+        };
+        let inner = Self::inner_path(path);
+        let tarball = inner
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("tar"));
+        (!tarball).then_some(compression)
+    }
+
+    /// The path without its compression extension, as in `app.log` for `app.log.gz`
+    pub fn inner_path(path: &Path) -> PathBuf {
+        path.with_extension("")
+    }
+
+    /// The command that writes the decompressed file to standard output
+    fn command(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Compression::Gzip => ("gzip", &["-d", "-c"]),
+            Compression::Zstd => ("zstd", &["-d", "-c", "-q"]),
+        }
+    }
+
+    /// Decompress a file, keeping at most `limit` bytes of its content
+    ///
+    /// Returns `None` if the tool isn't installed or the file isn't valid in this format.
+    pub fn decompress(self, path: &Path, limit: usize) -> Option<Limited> {
+        let (program, args) = self.command();
+        command::capture(Command::new(program).args(args).arg(path).stderr(Stdio::null()), limit)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Compression::from_path(Path::new("logs/app.log.gz")), Some(Compression::Gzip));
+        assert_eq!(Compression::from_path(Path::new("fixture.json.ZST")), Some(Compression::Zstd));
+        assert_eq!(Compression::from_path(Path::new("release.tar.gz")), None);
+        assert_eq!(Compression::from_path(Path::new("release.tgz")), None);
+        assert_eq!(Compression::from_path(Path::new("gz")), None);
+        assert_eq!(Compression::inner_path(Path::new("logs/app.log.gz")), PathBuf::from("logs/app.log"));
+    }
+}
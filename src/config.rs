@@ -1,3 +1,7 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
 /// Configuration constants for the application
 pub struct Config;
 
@@ -8,12 +12,162 @@ impl Config {
     /// Default maximum file size to process (500KB)
     pub const DEFAULT_MAX_FILE_SIZE: usize = 500 * 1024;
 
+    /// Default time the clipboard utility may go without taking content before it's killed
+    pub const DEFAULT_CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Largest archive (after decompressing a compressed tarball) whose entries are walked (100MB)
+    pub const MAX_ARCHIVE_SIZE: usize = 100 * 1024 * 1024;
+
+    /// How long fetching a URL given as an input may take, in seconds
+    pub const FETCH_TIMEOUT_SECS: u64 = 60;
+
     /// Buffer size for binary file detection
     pub const BINARY_CHECK_BUFFER_SIZE: usize = 8192;
+
+    /// Extensions of formats that are always binary, detected without reading the file
+    pub const BINARY_EXTENSIONS: &'static [&'static str] = &[
+        "png",
+        "jpg",
+        "jpeg",
+        "gif",
+        "bmp",
+        "ico",
+        "webp",
+        "tif",
+        "tiff",
+        "psd",
+        "pdf",
+        "zip",
+        "gz",
+        "tgz",
+        "bz2",
+        "xz",
+        "zst",
+        "7z",
+        "rar",
+        "jar",
+        "war",
+        "class",
+        "exe",
+        "dll",
+        "so",
+        "dylib",
+        "o",
+        "a",
+        "lib",
+        "obj",
+        "wasm",
+        "pyc",
+        "woff",
+        "woff2",
+        "ttf",
+        "otf",
+        "eot",
+        "mp3",
+        "mp4",
+        "m4a",
+        "mov",
+        "avi",
+        "mkv",
+        "wav",
+        "flac",
+        "ogg",
+        "webm",
+        "sqlite",
+        "db",
+    ];
+
+    /// Extensions of text formats that aren't programming languages (see `template::language`)
+    ///
+    /// Files with these or a language's extension are only binary if they contain null bytes.
+    pub const TEXT_EXTENSIONS: &'static [&'static str] = &[
+        "txt",
+        "text",
+        "csv",
+        "tsv",
+        "log",
+        "rst",
+        "adoc",
+        "svg",
+        "ini",
+        "cfg",
+        "conf",
+        "properties",
+    ];
+
+    /// Share of control characters (other than whitespace) in the sniffed bytes above which a file is binary
+    pub const BINARY_MAX_CONTROL_PERCENT: usize = 10;
+
+    /// Share of non-ASCII bytes in invalid UTF-8 above which a file is binary rather than Latin-1 text
+    pub const BINARY_MAX_NON_ASCII_PERCENT: usize = 30;
+
+    /// Path shown in the header of text read from standard input
+    pub const STDIN_PATH: &'static str = "<stdin>";
+
+    /// Directories skipped by default: dependencies, build output and caches
+    pub const DEFAULT_EXCLUDED_DIRS: &'static [&'static str] = &[
+        "node_modules",
+        "target",
+        "dist",
+        "build",
+        ".venv",
+        "venv",
+        "__pycache__",
+        "vendor",
+    ];
+
+    /// File patterns skipped by default: minified bundles and lockfiles
+    pub const DEFAULT_EXCLUDED_FILES: &'static [&'static str] = &[
+        "*.min.js",
+        "*.min.css",
+        "package-lock.json",
+        "yarn.lock",
+        "pnpm-lock.yaml",
+        "Cargo.lock",
+        "poetry.lock",
+        "Pipfile.lock",
+        "Gemfile.lock",
+        "composer.lock",
+        "go.sum",
+    ];
+
+    /// File patterns that often hold credentials, skipped unless `--unsafe` is given
+    pub const SENSITIVE_FILES: &'static [&'static str] = &[
+        ".env",
+        ".env.*",
+        "*.pem",
+        "*.key",
+        "*.p12",
+        "*.pfx",
+        "id_rsa",
+        "id_dsa",
+        "id_ecdsa",
+        "id_ed25519",
+        "credentials.json",
+        ".netrc",
+        ".pgpass",
+        ".htpasswd",
+    ];
+
+    /// Templates matched by `SENSITIVE_FILES` that are meant to be shared
+    pub const SENSITIVE_FILE_EXCEPTIONS: &'static [&'static str] = &[".env.example", ".env.sample", ".env.template"];
+
+    /// Phrases in a leading comment that mark a file as generated, compared in lowercase
+    pub const GENERATED_MARKERS: &'static [&'static str] = &[
+        "@generated",
+        "do not edit",
+        "code generated by",
+        "auto-generated",
+        "autogenerated",
+        "automatically generated",
+    ];
+
+    /// How many lines at the top of a file are searched for `GENERATED_MARKERS`
+    pub const GENERATED_MARKER_LINES: usize = 10;
 }
 
 /// Parse human-readable size string (e.g., "10MB", "1GB", "500KB")
-pub fn parse_size(size_str: &str) -> Result<usize, String> {
+pub fn parse_size(size_str: &str) -> Result<usize, Error> {
     let size_str = size_str.trim().to_uppercase();
 
     // Find where the number ends and unit begins
@@ -26,10 +180,10 @@ pub fn parse_size(size_str: &str) -> Result<usize, String> {
     let number: f64 = number_part
         .trim()
         .parse()
-        .map_err(|_| format!("Invalid number: {}", number_part.trim()))?;
+        .map_err(|_| Error::Config(format!("Invalid number: {}", number_part.trim())))?;
 
     if number < 0.0 {
-        return Err("Size cannot be negative".to_string());
+        return Err(Error::Config("Size cannot be negative".to_string()));
     }
 
     // Parse the unit
@@ -38,18 +192,99 @@ pub fn parse_size(size_str: &str) -> Result<usize, String> {
         "KB" | "K" => 1024,
         "MB" | "M" => 1024 * 1024,
         "GB" | "G" => 1024 * 1024 * 1024,
-        _ => return Err(format!("Unknown unit: {}. Use B, KB, MB, or GB", unit_part)),
+        _ => {
+            return Err(Error::Config(format!(
+                "Unknown unit: {}. Use B, KB, MB, or GB",
+                unit_part
+            )));
+        }
     };
 
     let size = (number * multiplier as f64) as usize;
 
     if size == 0 {
-        return Err("Size must be greater than 0".to_string());
+        return Err(Error::Config("Size must be greater than 0".to_string()));
     }
 
     Ok(size)
 }
 
+/// Parse a timeout like "10s", "500ms" or "2m", taking a bare number as seconds
+pub fn parse_timeout(text: &str) -> Result<Duration, Error> {
+    let text = text.trim();
+    let invalid = || Error::Config(format!("Invalid timeout: {} (expected e.g. 10s, 500ms or 2m)", text));
+
+    let unit_pos = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let number: f64 = text[..unit_pos].parse().map_err(|_| invalid())?;
+    let seconds = match &text[unit_pos..] {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" | "min" => number * 60.0,
+        _ => return Err(invalid()),
+    };
+    if seconds <= 0.0 {
+        return Err(Error::Config("Timeout must be greater than 0".to_string()));
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Parse a point in time for `--since`: a duration before `now` ("2d", "3h", "90m", "1w")
+/// or a date in UTC ("2024-01-01", "2024-01-01T09:30")
+pub fn parse_since(text: &str, now: SystemTime) -> Result<SystemTime, Error> {
+    let text = text.trim();
+    let invalid = || {
+        Error::Config(format!(
+            "Invalid time: {} (expected a duration like 2d or 3h, or a date like 2024-01-01)",
+            text
+        ))
+    };
+
+    if let Some(unit_pos) = text.find(|c: char| !c.is_ascii_digit())
+        && unit_pos > 0
+        && !text[unit_pos..].starts_with('-')
+    {
+        let number: u64 = text[..unit_pos].parse().map_err(|_| invalid())?;
+        let seconds = match &text[unit_pos..] {
+            "s" => 1,
+            "m" | "min" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        return now.checked_sub(Duration::from_secs(number * seconds)).ok_or_else(invalid);
+    }
+
+    let (date, time) = text.split_once(['T', ' ']).unwrap_or((text, "00:00"));
+    let numbers = |part: &str, separator: char| -> Option<Vec<u64>> {
+        part.split(separator).map(|number| number.parse().ok()).collect()
+    };
+    let (Some(date), Some(time)) = (numbers(date, '-'), numbers(time, ':')) else {
+        return Err(invalid());
+    };
+    let ([year, month, day], [hour, minute, seconds @ ..]) = (&date[..], &time[..]) else {
+        return Err(invalid());
+    };
+    let (year, month, day, hour, minute) = (*year, *month, *day, *hour, *minute);
+    let second = match seconds {
+        [] => 0,
+        [second] => *second,
+        _ => return Err(invalid()),
+    };
+    let date_valid = (1970..=9999).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day);
+    if !date_valid || hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, counting years from March
+    let (year, month) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era_days = year / 400 * 146_097;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let days = era_days + year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year - 719_468;
+    Ok(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,4 +309,36 @@ mod tests {
         assert!(parse_size("-5MB").is_err());
         assert!(parse_size("5TB").is_err());
     }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_timeout("2.5s").unwrap(), Duration::from_millis(2500));
+        assert_eq!(parse_timeout("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_timeout(" 2m ").unwrap(), Duration::from_secs(120));
+
+        assert!(parse_timeout("0").is_err());
+        assert!(parse_timeout("soon").is_err());
+        assert!(parse_timeout("5h").is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(parse_since("2d", now).unwrap(), now - Duration::from_secs(2 * 86_400));
+        assert_eq!(parse_since("90m", now).unwrap(), now - Duration::from_secs(90 * 60));
+        assert_eq!(parse_since(" 1w ", now).unwrap(), now - Duration::from_secs(7 * 86_400));
+
+        let seconds = |text: &str| parse_since(text, now).unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(seconds("1970-01-02"), 86_400);
+        assert_eq!(seconds("2024-01-01"), 1_704_067_200);
+        assert_eq!(seconds("2024-02-29T12:30"), 1_709_209_800);
+        assert_eq!(seconds("2000-03-01 00:00:01"), 951_868_801);
+
+        assert!(parse_since("2y", now).is_err());
+        assert!(parse_since("yesterday", now).is_err());
+        assert!(parse_since("2024-13-01", now).is_err());
+        assert!(parse_since("2024-01", now).is_err());
+        assert!(parse_since("", now).is_err());
+    }
 }
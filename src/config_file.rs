@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::file_processor::Strategy;
+use crate::preset::Preset;
+use crate::serialize::Value;
+use crate::toml;
+
+/// Project settings read from a `.rcat.toml` file
+///
+/// The nearest file in the current directory or one of its parents applies.
+/// Unknown keys are rejected so typos don't go unnoticed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    /// Where the settings were read from
+    pub path: PathBuf,
+    /// Presets defined under `[preset.<name>]`, in file order
+    pub presets: Vec<(String, Preset)>,
+    /// Default for `--prefix`: text, or a file relative to the config file
+    pub prefix: Option<String>,
+    /// Default for `--suffix`, like `prefix`
+    pub suffix: Option<String>,
+    /// Redact secrets unless `--no-redact` is given
+    pub redact: bool,
+    /// Processors from the `[processors]` table: file name patterns and how to process matching files
+    pub processors: Vec<(String, Strategy)>,
+    /// Profiles defined under `[profile.<name>]`, in file order
+    pub profiles: Vec<(String, Profile)>,
+}
+
+/// Options selected together with `--profile <name>`
+///
+/// Each key is a long option without its dashes, and its value is turned
+/// into command-line arguments: a string or number is the option's value,
+/// an array repeats the option for each item, `true` gives the flag alone
+/// and `false` its `--no-` form. The arguments go before those actually
+/// given, so options on the command line take precedence.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub args: Vec<String>,
+}
+
+impl ConfigFile {
+    /// Name of the file looked for
+    pub const FILE_NAME: &'static str = ".rcat.toml";
+
+    /// Path of the nearest config file in `start` or its parents
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(Self::FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Load the config file that applies to the current directory, if there is one
+    pub fn discover() -> Result<Option<Self>> {
+        let Ok(cwd) = std::env::current_dir() else {
+            return Ok(None);
+        };
+        Self::find(&cwd).map(|path| Self::load(&path)).transpose()
+    }
+
+    /// Read and validate a config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|source| Error::Walk {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config =
+            Self::parse(&text).map_err(|message| Error::Config(format!("{}: {}", path.display(), message)))?;
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        let document = toml::parse(text)?;
+        let mut config = Self::default();
+
+        for (key, value) in entries(&document) {
+            match key.as_str() {
+                "preset" => {
+                    for (name, table) in expect_table(value, "preset")? {
+                        config.presets.push((name.clone(), parse_preset(name, table)?));
+                    }
+                }
+                "profile" => {
+                    for (name, table) in expect_table(value, "profile")? {
+                        config.profiles.push((name.clone(), parse_profile(name, table)?));
+                    }
+                }
+                "prefix" => config.prefix = Some(string(value, "prefix")?),
+                "suffix" => config.suffix = Some(string(value, "suffix")?),
+                "redact" => config.redact = boolean(value, "redact")?,
+                "processors" => {
+                    for (pattern, name) in expect_table(value, "processors")? {
+                        let key_path = format!("processors.\"{}\"", pattern);
+                        let strategy = Strategy::parse(&string(name, &key_path)?)
+                            .map_err(|_| format!("'{}' must be text, notebook, lockfile or skip", key_path))?;
+                        config.processors.push((pattern.clone(), strategy));
+                    }
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// A preset by name, preferring one defined in the file over a built-in one
+    pub fn preset(&self, name: &str) -> Option<Preset> {
+        self.presets
+            .iter()
+            .find(|(defined, _)| defined == name)
+            .map(|(_, preset)| preset.clone())
+            .or_else(|| Preset::builtin(name))
+    }
+
+    /// A profile by name
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|(defined, _)| defined == name).map(|(_, profile)| profile)
+    }
+}
+
+fn parse_profile(name: &str, table: &Value) -> std::result::Result<Profile, String> {
+    let mut args = Vec::new();
+    for (key, value) in expect_table(table, &format!("profile.{}", name))? {
+        let key_path = format!("profile.{}.{}", name, key);
+        let valid = key.starts_with(|c: char| c.is_ascii_lowercase())
+            && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        // Profiles can't pick other profiles or stand in for commands
+        if !valid || matches!(key.as_str(), "profile" | "help") {
+            return Err(format!("'{}' is not an option a profile can set", key_path));
+        }
+
+        let option = format!("--{}", key);
+        let argument = |value: &Value| match value {
+            Value::String(text) => Ok(text.clone()),
+            Value::Int(number) => Ok(number.to_string()),
+            Value::Float(number) => Ok(number.to_string()),
+            _ => Err(format!("'{}' must be a string, number, boolean or array", key_path)),
+        };
+        match value {
+            Value::Bool(true) => args.push(option),
+            Value::Bool(false) => args.push(format!("--no-{}", key)),
+            Value::Array(items) => {
+                for item in items {
+                    args.push(option.clone());
+                    args.push(argument(item)?);
+                }
+            }
+            value => {
+                args.push(option);
+                args.push(argument(value)?);
+            }
+        }
+    }
+    Ok(Profile { args })
+}
+
+fn parse_preset(name: &str, table: &Value) -> std::result::Result<Preset, String> {
+    let mut preset = Preset::default();
+    for (key, value) in expect_table(table, &format!("preset.{}", name))? {
+        let key_path = format!("preset.{}.{}", name, key);
+        match key.as_str() {
+            "exclude" => preset.exclude = string_list(value, &key_path)?,
+            "include" => preset.include = string_list(value, &key_path)?,
+            _ => return Err(format!("unknown key '{}'", key_path)),
+        }
+    }
+    Ok(preset)
+}
+
+fn entries(value: &Value) -> &[(String, Value)] {
+    match value {
+        Value::Object(entries) => entries,
+        _ => &[],
+    }
+}
+
+fn expect_table<'a>(value: &'a Value, key: &str) -> std::result::Result<&'a [(String, Value)], String> {
+    match value {
+        Value::Object(entries) => Ok(entries),
+        _ => Err(format!("'{}' must be a table", key)),
+    }
+}
+
+fn string(value: &Value, key: &str) -> std::result::Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        _ => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+fn boolean(value: &Value, key: &str) -> std::result::Result<bool, String> {
+    match value {
+        Value::Bool(flag) => Ok(*flag),
+        _ => Err(format!("'{}' must be true or false", key)),
+    }
+}
+
+fn string_list(value: &Value, key: &str) -> std::result::Result<Vec<String>, String> {
+    let error = || format!("'{}' must be an array of strings", key);
+    let Value::Array(items) = value else {
+        return Err(error());
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::String(text) => Ok(text.clone()),
+            _ => Err(error()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let config = ConfigFile::parse(
+            r#"
+[preset.web]
+exclude = [".next", "coverage"]
+include = ["*.ts", "*.tsx"]
+
+[preset.rust]
+exclude = ["target", "benches"]
+"#,
+        )
+        .unwrap();
+
+        let web = config.preset("web").unwrap();
+        assert_eq!(web.exclude, vec![".next", "coverage"]);
+        assert_eq!(web.include, vec!["*.ts", "*.tsx"]);
+        // Presets in the file override built-in ones of the same name
+        assert_eq!(config.preset("rust").unwrap().exclude, vec!["target", "benches"]);
+        assert_eq!(config.preset("go"), Preset::builtin("go"));
+        assert!(config.preset("cobol").is_none());
+    }
+
+    #[test]
+    fn test_prefix_and_suffix() {
+        let config = ConfigFile::parse(
+            r#"
+prefix = """
+You are reviewing this codebase.
+Point out bugs first."""
+suffix = "prompts/review-end.md"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.prefix.as_deref(),
+            Some("You are reviewing this codebase.\nPoint out bugs first.")
+        );
+        assert_eq!(config.suffix.as_deref(), Some("prompts/review-end.md"));
+        assert_eq!(ConfigFile::parse("prefix = 1").unwrap_err(), "'prefix' must be a string");
+    }
+
+    #[test]
+    fn test_redact() {
+        assert!(ConfigFile::parse("redact = true").unwrap().redact);
+        assert!(!ConfigFile::parse("").unwrap().redact);
+        assert_eq!(ConfigFile::parse("redact = \"yes\"").unwrap_err(), "'redact' must be true or false");
+    }
+
+    #[test]
+    fn test_processors() {
+        let config = ConfigFile::parse(
+            r#"
+[processors]
+"*.snap" = "skip"
+"*.ipynb" = "text"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.processors,
+            vec![("*.snap".to_string(), Strategy::Skip), ("*.ipynb".to_string(), Strategy::Text)]
+        );
+        assert_eq!(
+            ConfigFile::parse("[processors]\n\"*.csv\" = \"table\"").unwrap_err(),
+            "'processors.\"*.csv\"' must be text, notebook, lockfile or skip"
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config = ConfigFile::parse(
+            r#"
+[profile.review]
+diff = "main"
+exclude = ["*.snap", "fixtures"]
+max-tokens = 100000
+tree = true
+dedup = false
+
+[profile.full]
+all = true
+format = "xml"
+"#,
+        )
+        .unwrap();
+
+        let args = |name| config.profile(name).unwrap().args.join(" ");
+        assert_eq!(
+            args("review"),
+            "--diff main --exclude *.snap --exclude fixtures --max-tokens 100000 --tree --no-dedup"
+        );
+        assert_eq!(args("full"), "--all --format xml");
+        assert!(config.profile("quick").is_none());
+
+        assert_eq!(
+            ConfigFile::parse("[profile.loop]
+profile = \"loop\"").unwrap_err(),
+            "'profile.loop.profile' is not an option a profile can set"
+        );
+        assert_eq!(
+            ConfigFile::parse("[profile.web]
+exclude = [[\"dist\"]]").unwrap_err(),
+            "'profile.web.exclude' must be a string, number, boolean or array"
+        );
+        assert!(ConfigFile::parse("[profile.web]
+\"--all\" = true").is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(ConfigFile::parse("colour = true").unwrap_err(), "unknown key 'colour'");
+        assert_eq!(
+            ConfigFile::parse("[preset.web]\nexclude = \"dist\"").unwrap_err(),
+            "'preset.web.exclude' must be an array of strings"
+        );
+        assert!(ConfigFile::parse("[preset.web]\nexcludes = []").is_err());
+        assert!(ConfigFile::parse("preset = 1").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::stats::Phase;
+use crate::walker::{WalkOptions, walk_and_collect};
+
+/// Longest header line `read_from` accepts: three lengths and spaces
+const MAX_HEADER_LEN: u64 = 64;
+
+/// Largest field `read_from` accepts, far beyond any bundle worth copying
+const MAX_FIELD_LEN: usize = 1 << 30;
+
+/// A collected bundle served by the daemon
+pub struct Bundle {
+    pub content: String,
+    pub stats: String,
+    /// Description of the limit that truncated the content, if any
+    pub truncated_by: Option<String>,
+    // What the walk looked at, checked for changes to know when to collect again
+    watched: Vec<PathBuf>,
+}
+
+impl Bundle {
+    /// Collect a fresh bundle from the given roots, rendered by `options.formatter`
+    fn collect(roots: &[PathBuf], options: &WalkOptions) -> io::Result<Self> {
+        let result = walk_and_collect(roots, options.clone()).map_err(io::Error::other)?;
+        let content = result.stats.time(Phase::Format, || options.formatter.render(&result).into_owned());
+        // Skipped files may come in once they change, but skipped directories
+        // (node_modules, target, ...) were never looked into
+        let skipped = result.stats.skipped().iter().filter(|entry| !entry.is_dir);
+        let mut watched = result.seen;
+        watched.extend(skipped.map(|entry| entry.path.clone()));
+        Ok(Self {
+            content,
+            stats: result.stats.format_stats(),
+            truncated_by: result.truncated_by.map(|limit| limit.to_string()),
+            watched,
+        })
+    }
+
+    /// Write the bundle to a stream: a header line of lengths followed by each field
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        let truncated_by = self.truncated_by.as_deref().unwrap_or_default();
+        writeln!(
+            stream,
+            "{} {} {}",
+            self.content.len(),
+            self.stats.len(),
+            truncated_by.len()
+        )?;
+        stream.write_all(self.content.as_bytes())?;
+        stream.write_all(self.stats.as_bytes())?;
+        stream.write_all(truncated_by.as_bytes())?;
+        stream.flush()
+    }
+
+    /// Read a bundle written by `write_to`
+    fn read_from(stream: impl Read) -> io::Result<Self> {
+        let mut reader = BufReader::new(stream);
+        let mut header = String::new();
+        reader.by_ref().take(MAX_HEADER_LEN).read_line(&mut header)?;
+
+        let fields: Vec<usize> = header
+            .split_whitespace()
+            .map(|field| field.parse().map_err(|_| invalid_data("malformed daemon header")))
+            .collect::<io::Result<_>>()?;
+        let [content_len, stats_len, truncated_by_len] = fields[..] else {
+            return Err(invalid_data("malformed daemon header"));
+        };
+        if fields.iter().any(|&len| len > MAX_FIELD_LEN) {
+            return Err(invalid_data("daemon header claims an oversized bundle"));
+        }
+
+        let content = read_string(&mut reader, content_len)?;
+        let stats = read_string(&mut reader, stats_len)?;
+        let truncated_by = read_string(&mut reader, truncated_by_len)?;
+
+        Ok(Self {
+            content,
+            stats,
+            truncated_by: (!truncated_by.is_empty()).then_some(truncated_by),
+            watched: Vec::new(),
+        })
+    }
+}
+
+/// Default socket location: `$XDG_RUNTIME_DIR/rcat.sock`, or in a private per-user directory in the temp dir
+///
+/// The directory is created if needed. Anyone can create files in the temp
+/// dir, so one that another user created or can write to is refused.
+pub fn socket_path() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR")
+        && !dir.is_empty()
+    {
+        return Ok(PathBuf::from(dir).join("rcat.sock"));
+    }
+
+    let dir = env::temp_dir().join(format!("rcat-{}", current_uid()));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+        _ => {}
+    }
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} isn't a private directory owned by you", dir.display()),
+        ));
+    }
+    Ok(dir.join("rcat.sock"))
+}
+
+/// Background process that keeps the latest bundle of its roots in memory
+pub struct Daemon {
+    listener: UnixListener,
+    socket: PathBuf,
+    roots: Vec<PathBuf>,
+    options: WalkOptions,
+    bundle: Arc<Mutex<Arc<Bundle>>>,
+}
+
+impl Daemon {
+    /// Collect the initial bundle and bind the socket
+    pub fn bind(socket: &Path, roots: Vec<PathBuf>, options: WalkOptions) -> io::Result<Self> {
+        if socket.exists() {
+            // A live daemon answers connections; anything else is a stale socket file
+            if UnixStream::connect(socket).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("a daemon is already listening on {}", socket.display()),
+                ));
+            }
+            fs::remove_file(socket)?;
+        }
+
+        let bundle = Bundle::collect(&roots, &options)?;
+        let listener = UnixListener::bind(socket)?;
+
+        Ok(Self {
+            listener,
+            socket: socket.to_path_buf(),
+            roots,
+            options,
+            bundle: Arc::new(Mutex::new(Arc::new(bundle))),
+        })
+    }
+
+    /// Watch the roots and serve bundles until the process is killed
+    pub fn run(self, poll_interval: Duration) -> io::Result<()> {
+        let bundle = Arc::clone(&self.bundle);
+        let roots = self.roots.clone();
+        let options = self.options.clone();
+
+        thread::spawn(move || {
+            let mut watched = bundle.lock().unwrap().watched.clone();
+            let mut last_fingerprint = fingerprint(&watched);
+            loop {
+                thread::sleep(poll_interval);
+
+                let current = fingerprint(&watched);
+                if current == last_fingerprint {
+                    continue;
+                }
+
+                match Bundle::collect(&roots, &options) {
+                    Ok(fresh) => {
+                        // The fresh walk may have seen other paths, such as a new directory
+                        watched = fresh.watched.clone();
+                        last_fingerprint = fingerprint(&watched);
+                        *bundle.lock().unwrap() = Arc::new(fresh);
+                    }
+                    Err(error) => {
+                        last_fingerprint = current;
+                        eprintln!("rcat daemon: failed to rebuild bundle - {}", error);
+                    }
+                }
+            }
+        });
+
+        for stream in self.listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            let current = Arc::clone(&self.bundle.lock().unwrap());
+            // A client that hangs up early shouldn't take the daemon down
+            let _ = current.write_to(&mut stream);
+        }
+
+        Ok(())
+    }
+
+    /// Path of the socket this daemon is bound to
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket);
+    }
+}
+
+/// Fetch the latest bundle from a running daemon
+///
+/// A socket owned by another user is refused rather than trusted to serve
+/// the bundle.
+pub fn fetch(socket: &Path) -> io::Result<Bundle> {
+    if let Ok(metadata) = fs::symlink_metadata(socket)
+        && metadata.uid() != current_uid()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is owned by another user", socket.display()),
+        ));
+    }
+    let stream = UnixStream::connect(socket).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!(
+                "no daemon listening on {} ({}). Start one with 'rcat daemon <path>...'",
+                socket.display(),
+                error
+            ),
+        )
+    })?;
+    Bundle::read_from(stream)
+}
+
+/// Cheap fingerprint of what a walk looked at: sizes and modification times
+///
+/// Adding, removing or renaming an entry changes its directory's modification
+/// time, so the directories walked cover new files without listing them.
+fn fingerprint(watched: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for path in watched {
+        path.hash(&mut hasher);
+        // Gone is a change too; paths inside archives never exist
+        let Ok(metadata) = fs::metadata(path) else {
+            continue;
+        };
+        metadata.len().hash(&mut hasher);
+        if let Ok(modified) = metadata.modified() {
+            modified.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// The effective user ID of this process
+fn current_uid() -> u32 {
+    unsafe extern "C" {
+        fn geteuid() -> u32;
+    }
+    // SAFETY: geteuid takes no arguments and always succeeds
+    unsafe { geteuid() }
+}
+
+fn read_string(reader: &mut impl Read, len: usize) -> io::Result<String> {
+    // Grow with what actually arrives rather than trusting the header up front
+    let mut buffer = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buffer)?;
+    if buffer.len() < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "daemon sent a partial bundle"));
+    }
+    String::from_utf8(buffer).map_err(|_| invalid_data("daemon sent invalid UTF-8"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputFormat;
+    use crate::template::SectionTemplate;
+
+    #[test]
+    fn test_serve_and_fetch() {
+        let dir = PathBuf::from("test_daemon");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("served.txt"), "served content").unwrap();
+
+        let socket = dir.join("rcat.sock");
+        let daemon = Daemon::bind(&socket, vec![dir.clone()], WalkOptions::default()).unwrap();
+        thread::spawn(move || daemon.run(Duration::from_secs(60)));
+
+        let bundle = fetch(&socket).unwrap();
+        assert!(bundle.content.contains("served content"));
+        assert!(bundle.stats.contains("Processed 1 files"));
+        assert!(bundle.truncated_by.is_none());
+
+        // The daemon's options shape what it serves: the format and the limits
+        fs::write(dir.join("zz_unserved.txt"), "left out by the limit").unwrap();
+        let socket = dir.join("limited.sock");
+        let options = WalkOptions {
+            formatter: OutputFormat::Json.formatter(SectionTemplate::default()),
+            max_files: Some(1),
+            ..WalkOptions::default()
+        };
+        let daemon = Daemon::bind(&socket, vec![dir.clone()], options).unwrap();
+        thread::spawn(move || daemon.run(Duration::from_secs(60)));
+
+        let bundle = fetch(&socket).unwrap();
+        let json = crate::json::parse(&bundle.content).unwrap();
+        assert!(json.get("files").is_some(), "{:?}", json);
+        assert!(bundle.content.contains("served content"));
+        assert!(!bundle.content.contains("left out by the limit"));
+        assert_eq!(bundle.truncated_by.as_deref(), Some("1 file"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_formatted_bundle() {
+        let dir = PathBuf::from("test_daemon_formatted");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "formatted content").unwrap();
+
+        let collect = |format: OutputFormat| {
+            let options = WalkOptions {
+                formatter: format.formatter(SectionTemplate::default()),
+                ..WalkOptions::default()
+            };
+            Bundle::collect(std::slice::from_ref(&dir), &options).unwrap().content
+        };
+
+        // The bundle is the rendered output, not the plain sections
+        let json = crate::json::parse(&collect(OutputFormat::Json)).unwrap();
+        assert!(json.get("files").is_some(), "{:?}", json);
+        let yaml = collect(OutputFormat::Yaml);
+        assert!(yaml.contains("files:\n") && yaml.contains("formatted content"), "{}", yaml);
+        let xml = collect(OutputFormat::Xml);
+        assert!(xml.contains("<index>") && xml.contains("<documents>"), "{}", xml);
+        assert!(xml.contains("formatted content\n</document>"), "{}", xml);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_malformed() {
+        let error = Bundle::read_from(&b"99999999999 0 0\n"[..]).err().unwrap();
+        assert!(error.to_string().contains("oversized"), "{}", error);
+        // A header promising more than is sent fails instead of waiting on a huge buffer
+        let error = Bundle::read_from(&b"100000 0 0\nshort"[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(Bundle::read_from(&b"1 2\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_watched_paths() {
+        let dir = PathBuf::from("test_daemon_watched");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("node_modules/pkg")).unwrap();
+        fs::write(dir.join("node_modules/pkg/index.js"), "module").unwrap();
+        fs::write(dir.join("kept.txt"), "kept").unwrap();
+        std::os::unix::fs::symlink(".", dir.join("loop")).unwrap();
+
+        let options = WalkOptions {
+            default_excludes: true,
+            ..WalkOptions::default()
+        };
+        let bundle = Bundle::collect(std::slice::from_ref(&dir), &options).unwrap();
+
+        // Only what the walk saw: not the symlink loop or inside excluded directories
+        assert!(bundle.watched.contains(&dir.join("kept.txt")));
+        assert!(!bundle.watched.iter().any(|path| path.starts_with(dir.join("node_modules"))));
+        assert!(!bundle.watched.iter().any(|path| path.starts_with(dir.join("loop"))));
+
+        let before = fingerprint(&bundle.watched);
+        fs::write(dir.join("node_modules/pkg/index.js"), "changed module").unwrap();
+        assert_eq!(fingerprint(&bundle.watched), before);
+        fs::write(dir.join("kept.txt"), "kept and changed").unwrap();
+        assert_ne!(fingerprint(&bundle.watched), before);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use rcat::ConfigFile;
+use rcat::cache;
+use rcat::clipboard::{self, Backend};
+
+/// Outcome of a single environment check
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Pass,
+    /// Usable, but something may not work as expected
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        }
+    }
+}
+
+/// Result of one check, with a hint on how to fix it when it didn't pass
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check { name, status: Status::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check { name, status: Status::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check { name, status: Status::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Run every check, print the results and return whether none failed
+pub fn run() -> bool {
+    let checks = [
+        system_clipboard(),
+        osc52(),
+        git(),
+        config_file(),
+        cache_dir(),
+        watch(),
+    ];
+
+    for check in &checks {
+        eprintln!("[{}] {}: {}", check.status.label(), check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            for line in hint.lines() {
+                eprintln!("       {}", line.trim());
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|check| check.status == Status::Fail).count();
+    let warned = checks.iter().filter(|check| check.status == Status::Warn).count();
+    eprintln!();
+    eprintln!("{} checks: {} failed, {} warnings", checks.len(), failed, warned);
+
+    failed == 0
+}
+
+fn system_clipboard() -> Check {
+    const NAME: &str = "System clipboard";
+
+    match clipboard::validate_clipboard(Backend::System) {
+        Ok(()) if Backend::detect(false) == Backend::Osc52 => Check::warn(
+            NAME,
+            "utility found, but OSC 52 is used by default over SSH without a display",
+            "Forward X11 (ssh -X) to use the remote clipboard instead",
+        ),
+        Ok(()) => Check::pass(NAME, "utility found"),
+        Err(error) => {
+            let error = error.to_string();
+            let (detail, hint) = error.split_once('\n').unwrap_or((&error, "Use --stdout or --osc52 instead"));
+            // Only a failure when it's the backend copies would use by default
+            if Backend::detect(false) == Backend::System {
+                Check::fail(NAME, detail, hint)
+            } else {
+                Check::warn(NAME, detail, hint)
+            }
+        }
+    }
+}
+
+fn osc52() -> Check {
+    const NAME: &str = "OSC 52";
+
+    if clipboard::validate_clipboard(Backend::Osc52).is_err() {
+        let hint = "Run rcat from an interactive terminal, or use --stdout";
+        return if Backend::detect(false) == Backend::Osc52 {
+            Check::fail(NAME, "no terminal attached", hint)
+        } else {
+            Check::warn(NAME, "no terminal attached", hint)
+        };
+    }
+
+    let term = env::var("TERM").unwrap_or_default();
+    if term == "dumb" {
+        return Check::warn(NAME, "TERM is 'dumb'", "Escape sequences are likely to be shown as text");
+    }
+
+    // Terminals can't be asked whether they honour OSC 52, so report what is known
+    if env::var_os("TMUX").is_some() {
+        return Check::warn(
+            NAME,
+            "running inside tmux",
+            "Enable passthrough with: tmux set -g allow-passthrough on (tmux 3.3+)",
+        );
+    }
+    if env::var_os("STY").is_some() {
+        return Check::warn(NAME, "running inside GNU screen", "GNU screen does not forward OSC 52");
+    }
+
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let known = ["iTerm.app", "WezTerm", "vscode", "ghostty"].contains(&program.as_str())
+        || term.contains("kitty")
+        || term.contains("alacritty")
+        || env::var_os("WT_SESSION").is_some();
+
+    if known {
+        Check::pass(NAME, "terminal supports OSC 52")
+    } else {
+        Check::warn(
+            NAME,
+            "terminal attached, support unknown",
+            "Try: rcat --osc52 <path>, then paste; some terminals need clipboard access enabled",
+        )
+    }
+}
+
+fn git() -> Check {
+    const NAME: &str = "git";
+
+    match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            Check::pass(NAME, String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        _ => Check::warn(
+            NAME,
+            "not found in PATH",
+            "Install git to use --git; .gitignore files are still read without it",
+        ),
+    }
+}
+
+fn config_file() -> Check {
+    const NAME: &str = "Config file";
+
+    let Some(path) = env::current_dir().ok().and_then(|cwd| ConfigFile::find(&cwd)) else {
+        return Check::pass(NAME, "none found, using defaults");
+    };
+
+    match ConfigFile::load(&path) {
+        Ok(_) => Check::pass(NAME, format!("{} loaded", path.display())),
+        Err(error) => Check::fail(
+            NAME,
+            format!("{} is invalid", path.display()),
+            error.to_string(),
+        ),
+    }
+}
+
+fn cache_dir() -> Check {
+    const NAME: &str = "Cache directory";
+
+    let Some(dir) = cache::cache_dir() else {
+        return Check::fail(NAME, "HOME is not set", "Set HOME or XDG_CACHE_HOME");
+    };
+
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    let result = fs::create_dir_all(&dir)
+        .and_then(|()| fs::write(&probe, b"ok"))
+        .and_then(|()| fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => Check::pass(NAME, format!("{} is writable", dir.display())),
+        Err(error) => Check::fail(
+            NAME,
+            format!("{} is not writable: {}", dir.display(), error),
+            "Fix its permissions, or point XDG_CACHE_HOME at a writable directory",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn watch() -> Check {
+    use rcat::daemon;
+    use std::os::unix::net::UnixListener;
+
+    const NAME: &str = "Watch mode";
+
+    let socket = match daemon::socket_path() {
+        Ok(socket) => socket,
+        Err(error) => {
+            return Check::fail(
+                NAME,
+                format!("no safe place for the daemon's socket: {}", error),
+                "Point XDG_RUNTIME_DIR at a private writable directory",
+            );
+        }
+    };
+    let probe = socket.with_file_name(format!("rcat-doctor-{}.sock", std::process::id()));
+
+    match UnixListener::bind(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass(NAME, format!("daemon can listen on {}", socket.display()))
+        }
+        Err(error) => Check::fail(
+            NAME,
+            format!("cannot create a socket next to {}: {}", socket.display(), error),
+            "Point XDG_RUNTIME_DIR at a writable directory",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+fn watch() -> Check {
+    Check::warn(
+        "Watch mode",
+        "the daemon is only supported on Unix platforms",
+        "Run rcat directly instead of 'copy --fast'",
+    )
+}
+
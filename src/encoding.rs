@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::config::Config;
+
+/// Character encoding a text file was decoded from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Fallback for bytes that aren't valid UTF-8; every byte maps to one character
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+/// Data-quality facts about a decoded text file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextInfo {
+    /// Encoding the file was decoded from
+    pub encoding: Encoding,
+    /// Whether the file started with a byte order mark (stripped from the output)
+    pub bom: bool,
+    /// Whether the file mixes CRLF and bare LF line endings
+    pub mixed_line_endings: bool,
+}
+
+impl TextInfo {
+    /// Whether the file wasn't UTF-8 and had to be transcoded
+    pub fn transcoded(&self) -> bool {
+        self.encoding != Encoding::Utf8
+    }
+}
+
+/// Detects a file's encoding and decodes it to UTF-8
+pub struct Decoder;
+
+impl Decoder {
+    const UTF8_BOM: &'static [u8] = &[0xEF, 0xBB, 0xBF];
+    const UTF16_LE_BOM: &'static [u8] = &[0xFF, 0xFE];
+    const UTF16_BE_BOM: &'static [u8] = &[0xFE, 0xFF];
+
+    /// Whether the bytes start with a UTF-16 byte order mark
+    ///
+    /// UTF-16 text is full of null bytes, so this must be checked before binary detection.
+    pub fn has_utf16_bom(bytes: &[u8]) -> bool {
+        bytes.starts_with(Self::UTF16_LE_BOM) || bytes.starts_with(Self::UTF16_BE_BOM)
+    }
+
+    /// Whether the bytes are UTF-16 text, with or without a byte order mark
+    pub fn is_utf16(bytes: &[u8]) -> bool {
+        Self::has_utf16_bom(bytes) || Self::sniff_utf16(bytes).is_some()
+    }
+
+    /// The byte order of UTF-16 text without a byte order mark, guessed from where its null bytes fall
+    ///
+    /// Text that is mostly ASCII has a null high byte in nearly every UTF-16
+    /// code unit, and hardly any null low bytes.
+    fn sniff_utf16(bytes: &[u8]) -> Option<Encoding> {
+        if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+            return None;
+        }
+        let sample = &bytes[..bytes.len().min(Config::BINARY_CHECK_BUFFER_SIZE)];
+        let units = sample.len() / 2;
+        let (mut first_null, mut second_null) = (0, 0);
+        for pair in sample.chunks_exact(2) {
+            first_null += usize::from(pair[0] == 0);
+            second_null += usize::from(pair[1] == 0);
+        }
+        let mostly = |count: usize| count * 10 >= units * 9;
+        let rarely = |count: usize| count * 10 < units;
+        if mostly(second_null) && rarely(first_null) {
+            Some(Encoding::Utf16Le)
+        } else if mostly(first_null) && rarely(second_null) {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+    /// Decode bytes to text, falling back to Latin-1 when they aren't valid UTF-8
+    ///
+    /// UTF-16 is recognized by its byte order mark or, without one, by the
+    /// pattern of its null bytes. Returns `None` only for UTF-16 with unpaired surrogates.
+    pub fn decode(bytes: &[u8]) -> Option<(String, TextInfo)> {
+        let (text, encoding, bom) = if let Some(rest) = bytes.strip_prefix(Self::UTF16_LE_BOM) {
+            (Self::decode_utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le, true)
+        } else if let Some(rest) = bytes.strip_prefix(Self::UTF16_BE_BOM) {
+            (Self::decode_utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be, true)
+        } else if let Some(encoding) = Self::sniff_utf16(bytes) {
+            let from_bytes = match encoding {
+                Encoding::Utf16Be => u16::from_be_bytes,
+                _ => u16::from_le_bytes,
+            };
+            (Self::decode_utf16(bytes, from_bytes)?, encoding, false)
+        } else {
+            let (rest, bom) = match bytes.strip_prefix(Self::UTF8_BOM) {
+                Some(rest) => (rest, true),
+                None => (bytes, false),
+            };
+            match std::str::from_utf8(rest) {
+                Ok(text) => (text.to_string(), Encoding::Utf8, bom),
+                Err(_) => (rest.iter().map(|&b| b as char).collect(), Encoding::Latin1, bom),
+            }
+        };
+
+        let info = TextInfo {
+            encoding,
+            bom,
+            mixed_line_endings: Self::has_mixed_line_endings(&text),
+        };
+        Some((text, info))
+    }
+
+    fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Option<String> {
+        let units = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]]));
+        char::decode_utf16(units).collect::<Result<String, _>>().ok()
+    }
+
+    /// Whether text has both CRLF and bare LF line endings
+    fn has_mixed_line_endings(text: &str) -> bool {
+        let lines = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+        crlf > 0 && crlf < lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8() {
+        let (text, info) = Decoder::decode(b"plain\n").unwrap();
+        assert_eq!(text, "plain\n");
+        assert_eq!(info, TextInfo::default());
+
+        let (text, info) = Decoder::decode(b"\xEF\xBB\xBFwith bom").unwrap();
+        assert_eq!(text, "with bom");
+        assert!(info.bom);
+        assert!(!info.transcoded());
+    }
+
+    #[test]
+    fn test_decode_transcoded() {
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("héllo".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert!(Decoder::has_utf16_bom(&utf16));
+        let (text, info) = Decoder::decode(&utf16).unwrap();
+        assert_eq!(text, "héllo");
+        assert_eq!(info.encoding, Encoding::Utf16Le);
+
+        let (text, info) = Decoder::decode(b"caf\xE9").unwrap();
+        assert_eq!(text, "café");
+        assert_eq!(info.encoding, Encoding::Latin1);
+        assert!(info.transcoded());
+
+        // UTF-16 without a byte order mark
+        let utf16: Vec<u8> = "no bom\r\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert!(Decoder::is_utf16(&utf16) && !Decoder::has_utf16_bom(&utf16));
+        let (text, info) = Decoder::decode(&utf16).unwrap();
+        assert_eq!(text, "no bom\r\n");
+        assert_eq!(info.encoding, Encoding::Utf16Be);
+        assert!(!info.bom);
+        assert!(!Decoder::is_utf16(b"\x00\x01\x02\x03\x00\x00\x05\x00"));
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        assert!(!Decoder::decode(b"a\r\nb\r\n").unwrap().1.mixed_line_endings);
+        assert!(!Decoder::decode(b"a\nb\n").unwrap().1.mixed_line_endings);
+        assert!(Decoder::decode(b"a\r\nb\n").unwrap().1.mixed_line_endings);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors returned by rcat's library functions
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A file or directory couldn't be read during the walk
+    Walk { path: PathBuf, source: io::Error },
+    /// The clipboard is unavailable or copying to it failed
+    Clipboard(String),
+    /// A glob or regex pattern is invalid
+    Pattern(String),
+    /// An option value (size, token count, format, ...) is invalid
+    Config(String),
+    /// A git command failed, e.g. for an unknown ref
+    Git(String),
+    /// A command to include the output of couldn't be started
+    Command(String),
+    /// A URL to include couldn't be fetched
+    Fetch(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Walk { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Pattern(message) => write!(f, "Invalid pattern: {}", message),
+            Error::Clipboard(message)
+            | Error::Config(message)
+            | Error::Git(message)
+            | Error::Command(message)
+            | Error::Fetch(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Walk { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Result type for rcat's library functions
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_size;
+    use crate::regex::Regex;
+    use crate::walker::{WalkOptions, walk_and_collect};
+
+    #[test]
+    fn test_error_variants() {
+        assert!(matches!(Regex::new("(unclosed"), Err(Error::Pattern(_))));
+        assert!(matches!(parse_size("10XB"), Err(Error::Config(_))));
+
+        let result = walk_and_collect(
+            &[PathBuf::from(".")],
+            WalkOptions {
+                diff_ref: Some("-bad".to_string()),
+                ..WalkOptions::default()
+            },
+        );
+        assert!(matches!(result, Err(Error::Git(_))));
+    }
+}
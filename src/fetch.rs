@@ -0,0 +1,40 @@
+use std::process::Command;
+
+use crate::command::{self, Limited};
+use crate::config::Config;
+use crate::error::{Error, Result};
+
+/// Whether a command-line argument is an HTTP(S) URL rather than a path
+pub fn is_url(arg: &str) -> bool {
+    let lower = arg.to_lowercase();
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme) && lower.len() > scheme.len())
+}
+
+/// Fetch a URL with `curl`, keeping at most `limit` bytes of the body
+///
+/// Redirects are followed. A URL that can't be fetched, including one the
+/// server answers with an error status, is an error; curl prints why.
+pub fn fetch(url: &str, limit: usize) -> Result<Limited> {
+    let mut curl = Command::new("curl");
+    curl.args(["--fail", "--silent", "--show-error", "--location", "--max-time"])
+        .arg(Config::FETCH_TIMEOUT_SECS.to_string())
+        .arg("--")
+        .arg(url);
+    command::capture(&mut curl, limit).ok_or_else(|| Error::Fetch(format!("Failed to fetch {} with curl", url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/spec.yaml"));
+        assert!(is_url("HTTP://localhost:8080"));
+        assert!(!is_url("https://"));
+        assert!(!is_url("ftp://example.com/file"));
+        assert!(!is_url("src/http.rs"));
+    }
+}
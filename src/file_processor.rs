@@ -3,36 +3,227 @@ use std::io::Read;
 use std::path::Path;
 
 use crate::config::Config;
+use crate::encoding::{Decoder, TextInfo};
+use crate::error::{Error, Result};
+use crate::format::ByteFormatter;
+use crate::glob::GlobMatcher;
+use crate::json;
+use crate::serialize::Value;
+use crate::skip::UnreadableCause;
+use crate::template;
 
 /// Result of processing a file
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FileContent {
-    /// Text file with its content
-    Text(String),
+    /// Text file with its content, decoded to UTF-8
+    Text(String, TextInfo),
     /// Binary file marker
     Binary,
-    /// File that couldn't be read
-    Unreadable,
+    /// File that couldn't be read, and why
+    Unreadable(UnreadableCause),
 }
 
+/// A 1-based, inclusive range of lines, as in `src/main.rs:40-120`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    /// Last line to include, or `None` for the rest of the file
+    pub end: Option<usize>,
+}
+
+/// Rewrites the text of a file into what is worth showing, or fails if the text isn't in the expected format
+pub type Processor = fn(&str) -> std::result::Result<String, String>;
+
+/// How a file's content is turned into its section
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Include the text as it is
+    Text,
+    /// Keep the markdown and code cells of a Jupyter notebook
+    Notebook,
+    /// List the packages and versions pinned by a lockfile
+    Lockfile,
+    /// Leave the file out
+    Skip,
+}
+
+impl Strategy {
+    /// Parse a strategy name as used in `.rcat.toml`
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "text" => Ok(Strategy::Text),
+            "notebook" => Ok(Strategy::Notebook),
+            "lockfile" => Ok(Strategy::Lockfile),
+            "skip" => Ok(Strategy::Skip),
+            _ => Err(Error::Config(format!(
+                "Unknown processor: {} (expected text, notebook, lockfile or skip)",
+                name
+            ))),
+        }
+    }
+
+    /// The strategy's name, as `parse` reads it
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Text => "text",
+            Strategy::Notebook => "notebook",
+            Strategy::Lockfile => "lockfile",
+            Strategy::Skip => "skip",
+        }
+    }
+
+    /// The processor that rewrites a file's text, for strategies that do
+    ///
+    /// A file the processor fails on is included as it is.
+    pub fn processor(self) -> Option<Processor> {
+        match self {
+            Strategy::Notebook => Some(notebook),
+            Strategy::Lockfile => Some(lockfile),
+            Strategy::Text | Strategy::Skip => None,
+        }
+    }
+}
+
+/// Which `Strategy` applies to which files, by file name pattern
+///
+/// The last registered pattern matching a file's name decides, so patterns
+/// from `.rcat.toml` override the built-in ones. Files no pattern matches
+/// are plain text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessorRegistry {
+    entries: Vec<(String, Strategy)>,
+}
+
+impl ProcessorRegistry {
+    /// Strategies registered by default, for formats whose raw text is mostly noise
+    pub const BUILTIN: &'static [(&'static str, Strategy)] = &[
+        ("*.ipynb", Strategy::Notebook),
+        ("Cargo.lock", Strategy::Lockfile),
+        ("package-lock.json", Strategy::Lockfile),
+        ("yarn.lock", Strategy::Lockfile),
+        ("poetry.lock", Strategy::Lockfile),
+    ];
+
+    /// Apply `strategy` to files whose name matches `pattern`, overriding earlier registrations
+    pub fn register(&mut self, pattern: impl Into<String>, strategy: Strategy) {
+        self.entries.push((pattern.into(), strategy));
+    }
+
+    /// The last registered pattern matching a file's name, and its strategy
+    pub fn lookup(&self, path: &Path) -> Option<(&str, Strategy)> {
+        let name = path.file_name()?.to_str()?;
+        self.entries
+            .iter()
+            .rev()
+            .find(|(pattern, _)| GlobMatcher::matches(name, pattern))
+            .map(|(pattern, strategy)| (pattern.as_str(), *strategy))
+    }
+
+    /// The strategy for a file
+    pub fn strategy(&self, path: &Path) -> Strategy {
+        self.lookup(path).map_or(Strategy::Text, |(_, strategy)| strategy)
+    }
+}
+
+impl Default for ProcessorRegistry {
+    fn default() -> Self {
+        Self {
+            entries: Self::BUILTIN
+                .iter()
+                .map(|(pattern, strategy)| (pattern.to_string(), *strategy))
+                .collect(),
+        }
+    }
+}
+
+/// Magic bytes at the start of common binary formats, and the name of each format
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"BM", "BMP image"),
+    (b"\x00\x00\x01\x00", "ICO image"),
+    (b"II*\x00", "TIFF image"),
+    (b"MM\x00*", "TIFF image"),
+    (b"%PDF", "PDF document"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"\x1F\x8B", "gzip archive"),
+    (b"BZh", "bzip2 archive"),
+    (b"\xFD7zXZ\x00", "xz archive"),
+    (b"\x28\xB5\x2F\xFD", "zstd archive"),
+    (b"7z\xBC\xAF\x27\x1C", "7-Zip archive"),
+    (b"Rar!\x1A\x07", "RAR archive"),
+    (b"\x7FELF", "ELF executable"),
+    (b"MZ", "Windows executable"),
+    (b"\xCF\xFA\xED\xFE", "Mach-O executable"),
+    (b"\xCA\xFE\xBA\xBE", "Java class or Mach-O universal binary"),
+    (b"\x00asm", "WebAssembly module"),
+    (b"SQLite format 3\x00", "SQLite database"),
+    (b"wOFF", "WOFF font"),
+    (b"wOF2", "WOFF2 font"),
+    (b"\x00\x01\x00\x00", "TrueType font"),
+    (b"OTTO", "OpenType font"),
+    (b"ID3", "MP3 audio"),
+    (b"OggS", "Ogg media"),
+    (b"fLaC", "FLAC audio"),
+];
+
 /// Processes a file and returns its content or type
 pub struct FileProcessor;
 
 impl FileProcessor {
-    /// Process a file at the given path
-    pub fn process(path: &Path) -> FileContent {
-        if Self::is_binary(path) {
-            FileContent::Binary
-        } else {
-            match std::fs::read_to_string(path) {
-                Ok(content) => FileContent::Text(content),
-                Err(_) => FileContent::Unreadable,
-            }
+    /// Process a file at the given path, rewriting its text as `strategy` says
+    pub fn process(path: &Path, strategy: Strategy) -> FileContent {
+        if Self::has_binary_extension(path) {
+            return FileContent::Binary;
+        }
+        match std::fs::read(path) {
+            Ok(bytes) => Self::process_bytes(path, &bytes, strategy),
+            Err(error) => FileContent::Unreadable(UnreadableCause::from_io(&error)),
         }
     }
 
-    /// Check if a file is binary by looking for null bytes
+    /// Process content read from somewhere other than `path`, such as a decompressed file
+    ///
+    /// `path` is only used to tell what kind of file the content is.
+    pub fn process_bytes(path: &Path, bytes: &[u8], strategy: Strategy) -> FileContent {
+        // UTF-16 text is full of null bytes, so recognize it before sniffing for binary
+        let sniffed = &bytes[..bytes.len().min(Config::BINARY_CHECK_BUFFER_SIZE)];
+        if !Decoder::is_utf16(bytes) && Self::looks_binary(path, sniffed) {
+            return FileContent::Binary;
+        }
+
+        match Decoder::decode(bytes) {
+            Some((text, info)) => match strategy.processor().and_then(|processor| processor(&text).ok()) {
+                Some(processed) => FileContent::Text(processed, info),
+                None => FileContent::Text(text, info),
+            },
+            None => FileContent::Unreadable(UnreadableCause::InvalidEncoding),
+        }
+    }
+
+    /// The generated-code marker (see `Config::GENERATED_MARKERS`) in a comment near the top of `text`, if any
+    ///
+    /// Only lines starting like a comment count, so prose that mentions
+    /// generated code isn't mistaken for it.
+    pub fn generated_marker(text: &str) -> Option<&'static str> {
+        const COMMENT_STARTS: &[&str] = &["//", "/*", "*", "#", "--", ";", "<!--", "%"];
+
+        text.lines()
+            .take(Config::GENERATED_MARKER_LINES)
+            .filter(|line| COMMENT_STARTS.iter().any(|start| line.trim_start().starts_with(start)))
+            .find_map(|line| {
+                let line = line.to_lowercase();
+                Config::GENERATED_MARKERS.iter().copied().find(|marker| line.contains(marker))
+            })
+    }
+
+    /// Check if a file is binary, by its extension or the start of its content
     pub fn is_binary(path: &Path) -> bool {
+        if Self::has_binary_extension(path) {
+            return true;
+        }
         let mut file = match File::open(path) {
             Ok(f) => f,
             Err(_) => return false,
@@ -41,17 +232,387 @@ impl FileProcessor {
         let mut buffer = vec![0u8; Config::BINARY_CHECK_BUFFER_SIZE];
 
         match file.read(&mut buffer) {
-            Ok(bytes_read) => buffer[..bytes_read].contains(&0),
+            Ok(bytes_read) => {
+                let sniffed = &buffer[..bytes_read];
+                !Decoder::is_utf16(sniffed) && Self::looks_binary(path, sniffed)
+            }
             Err(_) => false,
         }
     }
 
-    /// Format file content for output
-    pub fn format_content(path: &Path, content: FileContent) -> Option<String> {
-        match content {
-            FileContent::Text(text) => Some(format!("--- {} ---\n{}", path.display(), text)),
-            FileContent::Binary => Some(format!("--- {} ---\n<BINARY_FILE>", path.display())),
-            FileContent::Unreadable => None,
+    /// Placeholder for a binary file, like `<BINARY_FILE: PNG image, 640x480, 12.5 KB>`
+    ///
+    /// The type is recognized from the file's magic bytes and, for images, the
+    /// dimensions from its header; each is left out when unknown.
+    pub fn binary_info(path: &Path) -> String {
+        // Enough for the JPEG frame header after typical metadata segments
+        const HEADER_LEN: u64 = 64 * 1024;
+
+        let Ok(size) = path.metadata().map(|metadata| metadata.len() as usize) else {
+            return "<BINARY_FILE>".to_string();
+        };
+        let mut header = Vec::new();
+        if let Ok(file) = File::open(path) {
+            // A short or failed read just leaves less to recognize
+            let _ = file.take(HEADER_LEN).read_to_end(&mut header);
+        }
+        Self::describe_binary(&header, size)
+    }
+
+    /// The binary placeholder for content of `size` bytes starting with `header`, as `binary_info` describes it
+    pub fn describe_binary(header: &[u8], size: usize) -> String {
+        let mut details = Vec::new();
+        if let Some(kind) = binary_type(header) {
+            details.push(kind.to_string());
+        }
+        if let Some((width, height)) = image_dimensions(header) {
+            details.push(format!("{}x{}", width, height));
+        }
+        details.push(ByteFormatter::format(size));
+        format!("<BINARY_FILE: {}>", details.join(", "))
+    }
+
+    /// Whether a file's extension is one of `Config::BINARY_EXTENSIONS`
+    fn has_binary_extension(path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| Config::BINARY_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+    }
+
+    /// Whether the first bytes of a file look like binary data rather than text
+    ///
+    /// Null bytes always mean binary. Otherwise, unless the extension says the
+    /// file is text, so do many control characters, or invalid UTF-8 with too
+    /// many non-ASCII bytes to be Latin-1 text.
+    fn looks_binary(path: &Path, sniffed: &[u8]) -> bool {
+        if sniffed.contains(&0) {
+            return true;
+        }
+        let text_extension = template::language(path).is_some()
+            || path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| Config::TEXT_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+        if text_extension || sniffed.is_empty() {
+            return false;
+        }
+
+        // Tabs, line breaks, form feeds, backspaces and ANSI escapes appear in text
+        let control = sniffed
+            .iter()
+            .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x08 | 0x0c | 0x1b)) || b == 0x7f)
+            .count();
+        if control * 100 > sniffed.len() * Config::BINARY_MAX_CONTROL_PERCENT {
+            return true;
+        }
+
+        // A character cut off at the end of the sample doesn't make it invalid
+        let valid_utf8 = match std::str::from_utf8(sniffed) {
+            Ok(_) => true,
+            Err(error) => error.error_len().is_none(),
+        };
+        let non_ascii = sniffed.iter().filter(|b| !b.is_ascii()).count();
+        !valid_utf8 && non_ascii * 100 > sniffed.len() * Config::BINARY_MAX_NON_ASCII_PERCENT
+    }
+
+    /// The lines of `text` within `range`, and the number of the last one included
+    ///
+    /// The slice keeps its line endings. A range past the end of the text is empty.
+    pub fn slice_lines(text: &str, range: LineRange) -> (&str, usize) {
+        let mut start = text.len();
+        let mut end = text.len();
+        let mut last = range.start.saturating_sub(1);
+
+        let mut offset = 0;
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let number = index + 1;
+            if number == range.start {
+                start = offset;
+            }
+            offset += line.len();
+            if number >= range.start {
+                last = number;
+                end = offset;
+            }
+            if range.end.is_some_and(|end| number >= end) {
+                break;
+            }
+        }
+
+        (&text[start..end], last)
+    }
+
+    /// Header line that starts each file's section
+    pub fn format_header(path: &Path) -> String {
+        format!("--- {} ---", path.display())
+    }
+
+    /// Stand-in for a file whose content is identical to one already shown
+    pub fn format_identical_header(path: &Path, original: &Path) -> String {
+        format!("--- {} (identical to {}) ---", path.display(), original.display())
+    }
+
+    /// Header line for one part of a file split across chunks
+    pub fn format_part_header(path: &Path, part: usize, total: usize) -> String {
+        format!("--- {} (part {}/{}) ---", path.display(), part, total)
+    }
+}
+
+/// The format of a binary file, recognized from its first bytes
+fn binary_type(header: &[u8]) -> Option<&'static str> {
+    // RIFF containers and ISO media name their format after a size field
+    if header.starts_with(b"RIFF") {
+        return match header.get(8..12)? {
+            b"WEBP" => Some("WebP image"),
+            b"WAVE" => Some("WAV audio"),
+            b"AVI " => Some("AVI video"),
+            _ => None,
+        };
+    }
+    if header.get(4..8) == Some(b"ftyp") {
+        return Some("MP4/QuickTime media");
+    }
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, name)| *name)
+}
+
+/// Width and height of a PNG, GIF, BMP, JPEG or WebP image, read from its header
+fn image_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    let u16_le = |at: usize| Some(u16::from_le_bytes(header.get(at..at + 2)?.try_into().ok()?) as u32);
+    let u16_be = |at: usize| Some(u16::from_be_bytes(header.get(at..at + 2)?.try_into().ok()?) as u32);
+    let u24_le = |at: usize| Some(u32::from_le_bytes([*header.get(at)?, *header.get(at + 1)?, *header.get(at + 2)?, 0]));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(header.get(at..at + 4)?.try_into().ok()?));
+    let i32_le = |at: usize| Some(i32::from_le_bytes(header.get(at..at + 4)?.try_into().ok()?).unsigned_abs());
+
+    match binary_type(header)? {
+        "PNG image" => Some((u32_be(16)?, u32_be(20)?)),
+        "GIF image" => Some((u16_le(6)?, u16_le(8)?)),
+        // Rows are stored top-down when the height is negative
+        "BMP image" => Some((i32_le(18)?, i32_le(22)?)),
+        "WebP image" => match header.get(12..16)? {
+            b"VP8X" => Some((u24_le(24)? + 1, u24_le(27)? + 1)),
+            b"VP8 " => Some((u16_le(26)? & 0x3FFF, u16_le(28)? & 0x3FFF)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(header.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            _ => None,
+        },
+        "JPEG image" => {
+            // Walk the segments up to the start-of-frame marker that holds the size
+            let mut at = 2;
+            loop {
+                if *header.get(at)? != 0xFF {
+                    return None;
+                }
+                let marker = *header.get(at + 1)?;
+                let is_frame = (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+                if is_frame {
+                    return Some((u16_be(at + 7)?, u16_be(at + 5)?));
+                }
+                at += 2 + u16_be(at + 2)? as usize;
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The markdown and code cells of a Jupyter notebook, with the text of their outputs
+///
+/// Cells are marked as in Jupytext's percent format (`# %%`, `# %% [markdown]`).
+/// Outputs follow their cell as `# ` comments; images and other rich outputs,
+/// stored as base64, are replaced with a note of their type.
+fn notebook(text: &str) -> std::result::Result<String, String> {
+    let document = json::parse(text)?;
+    let Some(Value::Array(cells)) = document.get("cells") else {
+        return Err("not a notebook: no 'cells' array".to_string());
+    };
+
+    let mut sections = Vec::new();
+    for cell in cells {
+        let kind = match cell.get("cell_type") {
+            Some(Value::String(kind)) => kind.as_str(),
+            _ => "code",
+        };
+        let mut section = match kind {
+            "code" => "# %%\n".to_string(),
+            other => format!("# %% [{}]\n", other),
+        };
+        section.push_str(joined_text(cell.get("source")).trim_end());
+        section.push('\n');
+
+        if let Some(Value::Array(outputs)) = cell.get("outputs") {
+            let lines: Vec<String> = outputs.iter().flat_map(output_lines).collect();
+            if !lines.is_empty() {
+                section.push_str("# Output:\n");
+                for line in lines {
+                    section.push_str(format!("# {}", line).trim_end());
+                    section.push('\n');
+                }
+            }
+        }
+        sections.push(section);
+    }
+    Ok(sections.join("\n"))
+}
+
+/// The lines of text a notebook cell output shows
+fn output_lines(output: &Value) -> Vec<String> {
+    let text = match output.get("output_type") {
+        Some(Value::String(kind)) if kind == "stream" => joined_text(output.get("text")),
+        Some(Value::String(kind)) if kind == "error" => {
+            let field = |key| match output.get(key) {
+                Some(Value::String(value)) => value.as_str(),
+                _ => "",
+            };
+            format!("{}: {}", field("ename"), field("evalue"))
+        }
+        _ => match output.get("data") {
+            Some(Value::Object(data)) => match data.iter().find(|(kind, _)| kind == "text/plain") {
+                Some((_, plain)) => joined_text(Some(plain)),
+                None => data.iter().map(|(kind, _)| format!("[{} output]", kind)).collect::<Vec<_>>().join("\n"),
+            },
+            _ => String::new(),
+        },
+    };
+    text.trim_end().lines().map(str::to_string).collect()
+}
+
+/// Notebook text, which is stored as one string or as a list of lines
+fn joined_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines
+            .iter()
+            .filter_map(|line| match line {
+                Value::String(line) => Some(line.as_str()),
+                _ => None,
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+/// The packages a lockfile pins, one `name version` line each, sorted by name
+///
+/// Reads `Cargo.lock` and `poetry.lock` (`[[package]]` tables),
+/// `package-lock.json` and `yarn.lock` (classic and Berry), telling them
+/// apart by their content.
+fn lockfile(text: &str) -> std::result::Result<String, String> {
+    let mut packages = if text.trim_start().starts_with('{') {
+        npm_packages(text)?
+    } else if text.contains("[[package]]") {
+        toml_packages(text)
+    } else {
+        yarn_packages(text)
+    };
+    if packages.is_empty() {
+        return Err("no packages found".to_string());
+    }
+    packages.sort();
+    packages.dedup();
+
+    let mut output = format!("{} locked packages:\n", packages.len());
+    for (name, version) in packages {
+        output.push_str(&format!("{} {}\n", name, version));
+    }
+    Ok(output)
+}
+
+/// Packages in the `[[package]]` tables of `Cargo.lock` or `poetry.lock`
+fn toml_packages(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    // Name and version of the package table being read
+    let mut current: Option<(Option<String>, Option<String>)> = None;
+    for line in text.lines().chain(["[end]"]) {
+        let line = line.trim();
+        if line.starts_with('[') {
+            if let Some((Some(name), Some(version))) = current.take() {
+                packages.push((name, version));
+            }
+            if line == "[[package]]" {
+                current = Some((None, None));
+            }
+            continue;
+        }
+        let Some((name, version)) = current.as_mut() else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "name" => *name = Some(value),
+            "version" => *version = Some(value),
+            _ => {}
+        }
+    }
+    packages
+}
+
+/// Packages in a `package-lock.json`, from `packages` (v2 and later) or `dependencies` (v1)
+fn npm_packages(text: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    let document = json::parse(text)?;
+    let version_of = |entry: &Value| match entry.get("version") {
+        Some(Value::String(version)) => Some(version.clone()),
+        _ => None,
+    };
+
+    let mut packages = Vec::new();
+    if let Some(Value::Object(entries)) = document.get("packages") {
+        for (path, entry) in entries {
+            // The root project is listed under "", dependencies by their `node_modules` path
+            let Some((_, name)) = path.rsplit_once("node_modules/") else {
+                continue;
+            };
+            if let Some(version) = version_of(entry) {
+                packages.push((name.to_string(), version));
+            }
+        }
+    } else if let Some(dependencies) = document.get("dependencies") {
+        let mut pending = vec![dependencies];
+        while let Some(Value::Object(entries)) = pending.pop() {
+            for (name, entry) in entries {
+                if let Some(version) = version_of(entry) {
+                    packages.push((name.clone(), version));
+                }
+                if let Some(nested) = entry.get("dependencies") {
+                    pending.push(nested);
+                }
+            }
+        }
+    }
+    Ok(packages)
+}
+
+/// Packages in a `yarn.lock`: an entry per set of descriptors like `"react@^18.0.0":`, then its `version`
+fn yarn_packages(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    let mut name = None;
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            // The first descriptor names the package: `@scope/name@range`
+            let descriptor = line.trim_end_matches(':').split(',').next().unwrap_or("").trim().trim_matches('"');
+            name = descriptor
+                .get(1..)
+                .and_then(|rest| rest.find('@'))
+                .map(|at| descriptor[..at + 1].to_string())
+                .filter(|name| name != "__metadata");
+            continue;
+        }
+        let line = line.trim();
+        if let Some(version) = line.strip_prefix("version").filter(|rest| rest.starts_with([' ', ':']))
+            && let Some(name) = name.take()
+        {
+            let version = version.trim_start_matches(':').trim().trim_matches('"');
+            packages.push((name, version.to_string()));
         }
     }
+    packages
 }
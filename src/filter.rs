@@ -0,0 +1,669 @@
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::file_processor::{ProcessorRegistry, Strategy};
+use crate::fuzzy::FuzzyQuery;
+use crate::glob::GlobMatcher;
+use crate::regex::Regex;
+use crate::skip::SkipReason;
+use crate::walker::{PatternMatcher, WalkOptions};
+
+/// When during a walk a filter decides
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// As an entry is met while reading its directory, from its path and type
+    Path,
+    /// Just before a file is read, once its size is known
+    Read,
+}
+
+/// A path as filters see it
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<'a> {
+    pub path: &'a Path,
+    /// The path relative to the root it was reached from, with `/` separators
+    pub relative: &'a str,
+    pub is_dir: bool,
+    pub is_file: bool,
+    /// Size in bytes, known at `Stage::Read`
+    pub size: Option<usize>,
+    /// Last modification time, when known at `Stage::Read`
+    pub modified: Option<SystemTime>,
+    /// Read whatever its size: a file given with a line range, or one whose
+    /// processor (or compression) decides how much of it is kept
+    pub any_size: bool,
+}
+
+impl<'a> Entry<'a> {
+    /// An entry met while reading a directory, as seen at `Stage::Path`
+    pub fn new(path: &'a Path, relative: &'a str, is_dir: bool, is_file: bool) -> Self {
+        Self {
+            path,
+            relative,
+            is_dir,
+            is_file,
+            size: None,
+            modified: None,
+            any_size: false,
+        }
+    }
+}
+
+/// What the walker knows beyond an entry itself, for filters that need it
+///
+/// Ignore files are read as their directories are entered and git is asked
+/// once per root, so the answers depend on where the walk has got to.
+pub trait WalkContext {
+    /// Why git or ignore files leave an entry out, if they do
+    fn ignored(&self, entry: &Entry<'_>) -> Option<SkipReason>;
+
+    /// Why `.gitattributes` marks an entry as generated or vendored, if it does
+    fn generated(&self, entry: &Entry<'_>) -> Option<SkipReason>;
+
+    /// Whether a directory or link leads to another filesystem than its root is on
+    fn on_other_filesystem(&self, entry: &Entry<'_>) -> bool;
+
+    /// Whether a file was modified after `since`, by git history under roots judged by it
+    fn modified_after(&self, entry: &Entry<'_>, since: SystemTime) -> bool;
+}
+
+/// Decides whether a walk leaves a path out
+///
+/// The built-in filters are set up from `WalkOptions` (see `FilterPipeline::for_options`);
+/// implement this and add it to `WalkOptions::filters` to leave out more.
+/// Custom filters run after the built-in ones, and can give their reason as
+/// `SkipReason::Custom`.
+pub trait Filter: Send + Sync {
+    /// A short name, like `hidden`
+    fn name(&self) -> &str;
+
+    /// When the filter decides, by default as entries are met
+    fn stage(&self) -> Stage {
+        Stage::Path
+    }
+
+    /// Whether it also applies below `WalkOptions::also` paths, which bypass most filters
+    fn applies_to_forced(&self) -> bool {
+        false
+    }
+
+    /// Why the entry is left out, or `None` to pass it on to the next filter
+    fn check(&self, entry: &Entry<'_>, context: &dyn WalkContext) -> Option<SkipReason>;
+}
+
+/// Filters applied in order, the first to leave a path out deciding
+#[derive(Clone, Default)]
+pub struct FilterPipeline {
+    filters: Vec<Arc<dyn Filter>>,
+}
+
+impl FilterPipeline {
+    /// The filters a walk with these options applies: the built-in ones that
+    /// are switched on, then `options.filters`
+    ///
+    /// Filters that apply below `also` paths come first, so bypassing the
+    /// rest for those paths keeps the order.
+    pub fn for_options(options: &WalkOptions) -> Self {
+        let mut pipeline = Self::default();
+        if !options.follow_symlinks {
+            pipeline.push(SymlinkFilter);
+        }
+        if options.one_file_system {
+            pipeline.push(FilesystemFilter);
+        }
+        if options.skip_sensitive {
+            pipeline.push(SensitiveFilter);
+        }
+        if !options.exclude_patterns.is_empty() || !options.exclude_regexes.is_empty() {
+            pipeline.push(ExcludeFilter::new(&options.exclude_patterns, &options.exclude_regexes));
+        }
+        pipeline.push(ProcessorFilter(options.processors.clone()));
+        if !options.excluded_extensions.is_empty() {
+            pipeline.push(ExcludedExtensionFilter(options.excluded_extensions.clone()));
+        }
+        if options.default_excludes {
+            pipeline.push(DefaultExcludeFilter);
+        }
+        pipeline.push(IgnoreFilter);
+        pipeline.push(GeneratedFilter);
+        if !options.include_hidden {
+            pipeline.push(HiddenFilter);
+        }
+        if !options.path_regexes.is_empty() {
+            pipeline.push(PathRegexFilter(options.path_regexes.clone()));
+        }
+        if !options.fuzzy_filters.is_empty() {
+            pipeline.push(FuzzyFilter(options.fuzzy_filters.clone()));
+        }
+        if !options.include_patterns.is_empty() {
+            pipeline.push(IncludeFilter::new(&options.include_patterns));
+        }
+        if !options.extensions.is_empty() {
+            pipeline.push(ExtensionFilter(options.extensions.clone()));
+        }
+        pipeline.push(MaxSizeFilter(options.max_file_size));
+        if options.min_file_size > 0 {
+            pipeline.push(MinSizeFilter(options.min_file_size));
+        }
+        if let Some(since) = options.modified_since {
+            pipeline.push(ModifiedFilter(since));
+        }
+        pipeline.filters.extend(options.filters.iter().cloned());
+        pipeline
+    }
+
+    /// Add a filter after the others
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.filters.push(Arc::new(filter));
+    }
+
+    /// The names of the filters, in the order they apply
+    pub fn names(&self) -> Vec<&str> {
+        self.filters.iter().map(|filter| filter.name()).collect()
+    }
+
+    /// The first filter at `stage` to leave an entry out, with its reason
+    ///
+    /// Only filters that apply to forced paths are asked when `forced`.
+    pub fn decide(
+        &self,
+        stage: Stage,
+        entry: &Entry<'_>,
+        context: &dyn WalkContext,
+        forced: bool,
+    ) -> Option<(&dyn Filter, SkipReason)> {
+        self.filters
+            .iter()
+            .filter(|filter| filter.stage() == stage && (!forced || filter.applies_to_forced()))
+            .find_map(|filter| Some((filter.as_ref(), filter.check(entry, context)?)))
+    }
+}
+
+/// What a `FnFilter` closure decides about a path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Pass it on to the next filter
+    Keep,
+    /// Leave it out, for this reason (recorded as `SkipReason::Custom`)
+    Skip(String),
+}
+
+/// The closure of a `FnFilter`
+pub type FilterFn = Box<dyn Fn(&Path, &Metadata) -> FilterDecision + Send + Sync>;
+
+/// A named filter deciding from a path and its metadata, for library users
+///
+/// Like other filters it sees directories as well as files, and leaving out
+/// a directory leaves out everything below it. Paths without metadata, such
+/// as archive entries, are kept. Add one to `WalkOptions::filters`, e.g. to
+/// skip files owned by root or keep only the files a database knows about.
+pub struct FnFilter {
+    name: String,
+    decide: FilterFn,
+}
+
+impl FnFilter {
+    pub fn new(
+        name: impl Into<String>,
+        decide: impl Fn(&Path, &Metadata) -> FilterDecision + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            decide: Box::new(decide),
+        }
+    }
+}
+
+impl Filter for FnFilter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let metadata = fs::metadata(entry.path).ok()?;
+        match (self.decide)(entry.path, &metadata) {
+            FilterDecision::Keep => None,
+            FilterDecision::Skip(reason) => Some(SkipReason::Custom {
+                filter: self.name.clone(),
+                reason,
+            }),
+        }
+    }
+}
+
+/// Symbolic links, when links aren't followed
+pub struct SymlinkFilter;
+
+impl Filter for SymlinkFilter {
+    fn name(&self) -> &str {
+        "symlink"
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        entry.path.is_symlink().then_some(SkipReason::Symlink)
+    }
+}
+
+/// Directories and links on another filesystem than their root
+pub struct FilesystemFilter;
+
+impl Filter for FilesystemFilter {
+    fn name(&self) -> &str {
+        "one-file-system"
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, context: &dyn WalkContext) -> Option<SkipReason> {
+        // Other filesystems are only entered through mount points and links
+        let crossing = entry.is_dir || entry.path.is_symlink();
+        (crossing && context.on_other_filesystem(entry)).then_some(SkipReason::OtherFilesystem)
+    }
+}
+
+/// Files that often hold credentials (see `Config::SENSITIVE_FILES`)
+pub struct SensitiveFilter;
+
+impl Filter for SensitiveFilter {
+    fn name(&self) -> &str {
+        "sensitive"
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        if !entry.is_file {
+            return None;
+        }
+        let pattern = sensitive_pattern(entry.path)?;
+        Some(SkipReason::Sensitive {
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Paths matching an exclude glob or regex
+pub struct ExcludeFilter {
+    patterns: PatternMatcher,
+    regexes: Vec<Regex>,
+}
+
+impl ExcludeFilter {
+    pub fn new(patterns: &[String], regexes: &[Regex]) -> Self {
+        Self {
+            patterns: PatternMatcher::new(patterns.to_vec()),
+            regexes: regexes.to_vec(),
+        }
+    }
+}
+
+impl Filter for ExcludeFilter {
+    fn name(&self) -> &str {
+        "exclude"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let pattern = self.patterns.matching_pattern(entry.relative, entry.is_dir).or_else(|| {
+            self.regexes
+                .iter()
+                .find(|regex| regex.is_match(entry.relative))
+                .map(Regex::as_str)
+        })?;
+        Some(SkipReason::Excluded {
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Files whose processor is `Strategy::Skip`
+pub struct ProcessorFilter(pub ProcessorRegistry);
+
+impl Filter for ProcessorFilter {
+    fn name(&self) -> &str {
+        "processor"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        match self.0.lookup(entry.path) {
+            Some((pattern, Strategy::Skip)) if entry.is_file => Some(SkipReason::Excluded {
+                pattern: pattern.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Files with one of these extensions
+pub struct ExcludedExtensionFilter(pub Vec<String>);
+
+impl Filter for ExcludedExtensionFilter {
+    fn name(&self) -> &str {
+        "exclude-ext"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        if !entry.is_file {
+            return None;
+        }
+        let extension = matching_extension(entry.path, &self.0)?;
+        Some(SkipReason::Excluded {
+            pattern: format!("*.{}", extension),
+        })
+    }
+}
+
+/// Dependency and build directories and lockfiles (see `Config::DEFAULT_EXCLUDED_DIRS`)
+pub struct DefaultExcludeFilter;
+
+impl Filter for DefaultExcludeFilter {
+    fn name(&self) -> &str {
+        "default-exclude"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let pattern = default_exclude(entry.path, entry.is_dir)?;
+        Some(SkipReason::DefaultExcluded {
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Paths left out by ignore files, or by git with `git_tracked` or `diff_ref`
+pub struct IgnoreFilter;
+
+impl Filter for IgnoreFilter {
+    fn name(&self) -> &str {
+        "ignore"
+    }
+
+    fn check(&self, entry: &Entry<'_>, context: &dyn WalkContext) -> Option<SkipReason> {
+        context.ignored(entry)
+    }
+}
+
+/// Paths `.gitattributes` marks as generated or vendored
+pub struct GeneratedFilter;
+
+impl Filter for GeneratedFilter {
+    fn name(&self) -> &str {
+        "generated"
+    }
+
+    fn check(&self, entry: &Entry<'_>, context: &dyn WalkContext) -> Option<SkipReason> {
+        context.generated(entry)
+    }
+}
+
+/// Names starting with '.'
+pub struct HiddenFilter;
+
+impl Filter for HiddenFilter {
+    fn name(&self) -> &str {
+        "hidden"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        is_hidden(entry.path).then_some(SkipReason::Hidden)
+    }
+}
+
+/// Files whose path matches none of these regexes
+///
+/// Like the other include filters, this only narrows down files; directories are still traversed.
+pub struct PathRegexFilter(pub Vec<Regex>);
+
+impl Filter for PathRegexFilter {
+    fn name(&self) -> &str {
+        "path-regex"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let matched = self.0.iter().any(|regex| regex.is_match(entry.relative));
+        (entry.is_file && !matched).then_some(SkipReason::Filtered)
+    }
+}
+
+/// Files whose path fuzzily matches none of these queries
+pub struct FuzzyFilter(pub Vec<FuzzyQuery>);
+
+impl Filter for FuzzyFilter {
+    fn name(&self) -> &str {
+        "fuzzy"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let matched = self.0.iter().any(|query| query.is_match(entry.relative));
+        (entry.is_file && !matched).then_some(SkipReason::Filtered)
+    }
+}
+
+/// Files matching none of these include globs
+pub struct IncludeFilter(PatternMatcher);
+
+impl IncludeFilter {
+    pub fn new(patterns: &[String]) -> Self {
+        Self(PatternMatcher::new(patterns.to_vec()))
+    }
+}
+
+impl Filter for IncludeFilter {
+    fn name(&self) -> &str {
+        "include"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let matched = self.0.matching_pattern(entry.relative, false).is_some();
+        (entry.is_file && !matched).then_some(SkipReason::Filtered)
+    }
+}
+
+/// Files with none of these extensions
+pub struct ExtensionFilter(pub Vec<String>);
+
+impl Filter for ExtensionFilter {
+    fn name(&self) -> &str {
+        "ext"
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let matched = matching_extension(entry.path, &self.0).is_some();
+        (entry.is_file && !matched).then_some(SkipReason::Filtered)
+    }
+}
+
+/// Files larger than the per-file limit, unless read whatever their size
+pub struct MaxSizeFilter(pub usize);
+
+impl Filter for MaxSizeFilter {
+    fn name(&self) -> &str {
+        "max-file-size"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Read
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let size = entry.size.filter(|size| *size > self.0 && !entry.any_size)?;
+        Some(SkipReason::TooLarge { size, limit: self.0 })
+    }
+}
+
+/// Files smaller than the minimum size
+pub struct MinSizeFilter(pub usize);
+
+impl Filter for MinSizeFilter {
+    fn name(&self) -> &str {
+        "min-file-size"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Read
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+        let size = entry.size.filter(|size| *size < self.0)?;
+        Some(SkipReason::TooSmall { size, limit: self.0 })
+    }
+}
+
+/// Files not modified since a time
+pub struct ModifiedFilter(pub SystemTime);
+
+impl Filter for ModifiedFilter {
+    fn name(&self) -> &str {
+        "since"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Read
+    }
+
+    fn applies_to_forced(&self) -> bool {
+        true
+    }
+
+    fn check(&self, entry: &Entry<'_>, context: &dyn WalkContext) -> Option<SkipReason> {
+        (!context.modified_after(entry, self.0)).then_some(SkipReason::Mtime)
+    }
+}
+
+/// Find the built-in default exclude matching a path, if any
+fn default_exclude(path: &Path, is_dir: bool) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+
+    if is_dir {
+        Config::DEFAULT_EXCLUDED_DIRS.iter().copied().find(|dir| *dir == name)
+    } else {
+        Config::DEFAULT_EXCLUDED_FILES
+            .iter()
+            .copied()
+            .find(|pattern| GlobMatcher::matches(name, pattern))
+    }
+}
+
+/// Find the sensitive file pattern matching a file's name, if any
+fn sensitive_pattern(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if Config::SENSITIVE_FILE_EXCEPTIONS.contains(&name.as_str()) {
+        return None;
+    }
+    Config::SENSITIVE_FILES
+        .iter()
+        .copied()
+        .find(|pattern| GlobMatcher::matches(&name, pattern))
+}
+
+/// Find the extension in `extensions` a file's name ends with, if any
+fn matching_extension<'a>(path: &Path, extensions: &'a [String]) -> Option<&'a str> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    extensions
+        .iter()
+        .map(|extension| extension.trim_start_matches('.'))
+        .find(|extension| {
+            // A name that is only the suffix, like `.toml`, has no extension
+            let suffix = format!(".{}", extension.to_lowercase());
+            name.len() > suffix.len() && name.ends_with(&suffix)
+        })
+}
+
+/// Check if a path's name starts with '.'
+pub(crate) fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A walk that has nothing ignored, generated or modified, all on one filesystem
+    struct Plain;
+
+    impl WalkContext for Plain {
+        fn ignored(&self, _entry: &Entry<'_>) -> Option<SkipReason> {
+            None
+        }
+
+        fn generated(&self, _entry: &Entry<'_>) -> Option<SkipReason> {
+            None
+        }
+
+        fn on_other_filesystem(&self, _entry: &Entry<'_>) -> bool {
+            false
+        }
+
+        fn modified_after(&self, _entry: &Entry<'_>, _since: SystemTime) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let options = WalkOptions {
+            exclude_patterns: vec!["*.tmp".to_string()],
+            extensions: vec!["rs".to_string()],
+            min_file_size: 1,
+            ..WalkOptions::default()
+        };
+        let pipeline = FilterPipeline::for_options(&options);
+        assert_eq!(
+            pipeline.names(),
+            [
+                "sensitive",
+                "exclude",
+                "processor",
+                "default-exclude",
+                "ignore",
+                "generated",
+                "hidden",
+                "ext",
+                "max-file-size",
+                "min-file-size"
+            ]
+        );
+
+        let decide = |path: &str, is_dir: bool, forced: bool| {
+            let entry = Entry::new(Path::new(path), path, is_dir, !is_dir);
+            pipeline
+                .decide(Stage::Path, &entry, &Plain, forced)
+                .map(|(filter, reason)| (filter.name().to_string(), reason))
+        };
+        assert_eq!(decide("src/main.rs", false, false), None);
+        // Include filters only narrow down files
+        assert_eq!(decide("src", true, false), None);
+        assert_eq!(decide("notes.md", false, false), Some(("ext".to_string(), SkipReason::Filtered)));
+        // The first filter to leave a path out decides
+        assert_eq!(decide(".cache.tmp", false, false).unwrap().0, "exclude");
+        assert_eq!(decide(".env", false, true).unwrap().0, "sensitive");
+        assert_eq!(decide("notes.md", false, true), None);
+
+        let read = Entry {
+            size: Some(0),
+            ..Entry::new(Path::new("empty.rs"), "empty.rs", false, true)
+        };
+        let (filter, reason) = pipeline.decide(Stage::Read, &read, &Plain, true).unwrap();
+        assert_eq!(filter.name(), "min-file-size");
+        assert_eq!(reason, SkipReason::TooSmall { size: 0, limit: 1 });
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 /// Utilities for formatting byte sizes
 pub struct ByteFormatter;
 
@@ -37,11 +39,11 @@ impl ByteFormatter {
         const MB: usize = 1024 * 1024;
         const KB: usize = 1024;
 
-        if bytes >= GB && bytes % GB == 0 {
+        if bytes >= GB && bytes.is_multiple_of(GB) {
             format!("{}GB", bytes / GB)
-        } else if bytes >= MB && bytes % MB == 0 {
+        } else if bytes >= MB && bytes.is_multiple_of(MB) {
             format!("{}MB", bytes / MB)
-        } else if bytes >= KB && bytes % KB == 0 {
+        } else if bytes >= KB && bytes.is_multiple_of(KB) {
             format!("{}KB", bytes / KB)
         } else {
             format!("{} bytes", bytes)
@@ -49,6 +51,27 @@ impl ByteFormatter {
     }
 }
 
+/// Format a point in time as a UTC date and time to the minute, like `2024-03-01 09:30 UTC`
+///
+/// Times before 1970 are shown as 1970-01-01.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (hour, minute) = (seconds % 86_400 / 3600, seconds % 3600 / 60);
+
+    // The reverse of `config::parse_since`, with years counted from March
+    let days = seconds / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, hour, minute)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,4 +94,17 @@ mod tests {
         assert_eq!(ByteFormatter::format_as_unit(1024 * 1024 * 1024), "1GB");
         assert_eq!(ByteFormatter::format_as_unit(5 * 1024 * 1024 * 1024), "5GB");
     }
+
+    #[test]
+    fn test_format_timestamp() {
+        use crate::config::parse_since;
+        use std::time::Duration;
+
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(1_000_000_000)), "2001-09-09 01:46 UTC");
+        for text in ["2000-02-29 23:59", "2024-03-01 09:30", "2099-12-31 00:01"] {
+            let time = parse_since(text, SystemTime::now()).unwrap();
+            assert_eq!(format_timestamp(time), format!("{} UTC", text));
+        }
+    }
 }
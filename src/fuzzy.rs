@@ -0,0 +1,188 @@
+use crate::error::Error;
+
+/// Score for each matched character
+const MATCH: i32 = 16;
+/// Extra for a character starting a word: after `/`, `_`, `-`, `.` or a space, or a camelCase hump
+const BOUNDARY: i32 = 8;
+/// Extra for a character right after the previous match
+const CONSECUTIVE: i32 = 4;
+/// Cost of each skipped character between the first and last match
+const GAP: i32 = 1;
+
+/// An fzf-style fuzzy query, matched against paths
+///
+/// A term matches if its characters appear in the text in order, not
+/// necessarily together (`wlkr` matches `walker.rs`). As in fzf, terms
+/// separated by spaces must all match and a lone `|` between terms makes
+/// them alternatives, so `walker | git rs` means (walker or git) and rs.
+/// Matching ignores case unless the term has an uppercase letter.
+#[derive(Clone, Debug)]
+pub struct FuzzyQuery {
+    // All groups must match, each through any one of its terms
+    groups: Vec<Vec<Term>>,
+}
+
+#[derive(Clone, Debug)]
+struct Term {
+    chars: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl FuzzyQuery {
+    /// Parse a query, which must have at least one term
+    pub fn new(query: &str) -> Result<Self, Error> {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut alternative = false;
+        for word in query.split_whitespace() {
+            if word == "|" {
+                if groups.is_empty() || alternative {
+                    return Err(Error::Pattern(format!("Misplaced '|' in fuzzy query: {}", query)));
+                }
+                alternative = true;
+                continue;
+            }
+            let term = Term {
+                chars: word.chars().collect(),
+                case_sensitive: word.chars().any(char::is_uppercase),
+            };
+            match groups.last_mut() {
+                Some(group) if alternative => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            alternative = false;
+        }
+
+        if alternative {
+            return Err(Error::Pattern(format!("Misplaced '|' in fuzzy query: {}", query)));
+        }
+        if groups.is_empty() {
+            return Err(Error::Pattern("Empty fuzzy query".to_string()));
+        }
+        Ok(Self { groups })
+    }
+
+    /// How well the text matches, higher being better, or `None` if it doesn't
+    pub fn score(&self, text: &str) -> Option<i32> {
+        let chars: Vec<char> = text.chars().collect();
+        self.groups
+            .iter()
+            .map(|group| group.iter().filter_map(|term| term.score(&chars)).max())
+            .sum()
+    }
+
+    /// Whether the text matches at all
+    pub fn is_match(&self, text: &str) -> bool {
+        self.score(text).is_some()
+    }
+}
+
+impl Term {
+    /// Score the shortest window of `text` holding the term's characters in order
+    ///
+    /// The window ending earliest is found by matching forward, then it is
+    /// narrowed by matching backward from its end.
+    fn score(&self, text: &[char]) -> Option<i32> {
+        let same = |a: char, b: char| {
+            if self.case_sensitive {
+                a == b
+            } else {
+                a.to_lowercase().eq(b.to_lowercase())
+            }
+        };
+
+        let mut next = 0;
+        let mut end = None;
+        for (index, &c) in text.iter().enumerate() {
+            if next < self.chars.len() && same(c, self.chars[next]) {
+                next += 1;
+                if next == self.chars.len() {
+                    end = Some(index);
+                    break;
+                }
+            }
+        }
+        let end = end?;
+
+        let mut positions = Vec::with_capacity(self.chars.len());
+        let mut remaining = self.chars.iter().rev().peekable();
+        for index in (0..=end).rev() {
+            if let Some(&&c) = remaining.peek()
+                && same(text[index], c)
+            {
+                positions.push(index);
+                remaining.next();
+            }
+        }
+        positions.reverse();
+
+        let mut score = 0;
+        for (matched, &position) in positions.iter().enumerate() {
+            score += MATCH;
+            if is_boundary(text, position) {
+                score += BOUNDARY;
+            }
+            if matched > 0 {
+                let previous = positions[matched - 1];
+                if position == previous + 1 {
+                    score += CONSECUTIVE;
+                } else {
+                    score -= GAP * (position - previous - 1) as i32;
+                }
+            }
+        }
+        Some(score)
+    }
+}
+
+/// Whether the character at `index` starts a word
+fn is_boundary(text: &[char], index: usize) -> bool {
+    let Some(previous) = index.checked_sub(1).map(|previous| text[previous]) else {
+        return true;
+    };
+    matches!(previous, '/' | '\\' | '_' | '-' | '.' | ' ') || (previous.is_lowercase() && text[index].is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching() {
+        let query = FuzzyQuery::new("wlkr").unwrap();
+        assert!(query.is_match("src/walker.rs"));
+        assert!(!query.is_match("src/worker.rs"));
+
+        // Smart case: lowercase terms ignore case, others don't
+        assert!(FuzzyQuery::new("readme").unwrap().is_match("README.md"));
+        assert!(!FuzzyQuery::new("Readme").unwrap().is_match("README.md"));
+
+        // Spaces separate terms that must all match, and '|' makes alternatives
+        let query = FuzzyQuery::new("walker | git rs").unwrap();
+        assert!(query.is_match("src/walker.rs"));
+        assert!(query.is_match("src/gitignore.rs"));
+        assert!(!query.is_match("src/git/README.md"));
+        assert!(!query.is_match("src/main.rs"));
+    }
+
+    #[test]
+    fn test_scoring() {
+        let query = FuzzyQuery::new("main").unwrap();
+        let exact = query.score("src/main.rs").unwrap();
+        let scattered = query.score("src/manifest_in.rs").unwrap();
+        assert!(exact > scattered);
+
+        // Word starts beat the middle of words
+        let query = FuzzyQuery::new("fp").unwrap();
+        assert!(query.score("src/file_processor.rs") > query.score("src/lifeplan.rs"));
+        assert!(query.score("src/FileProcessor.ts") > query.score("src/lifeplan.rs"));
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        assert!(FuzzyQuery::new("").is_err());
+        assert!(FuzzyQuery::new("  ").is_err());
+        assert!(FuzzyQuery::new("| walker").is_err());
+        assert!(FuzzyQuery::new("walker |").is_err());
+        assert!(FuzzyQuery::new("walker | | git").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The set of files git knows about in a repository, as absolute canonical paths
+///
+/// Built by shelling out to `git`, so it follows git's own ignore semantics
+/// exactly instead of re-implementing them.
+pub struct GitFiles {
+    files: HashSet<PathBuf>,
+    // Every directory containing at least one of the files
+    dirs: HashSet<PathBuf>,
+}
+
+impl GitFiles {
+    /// Files tracked in the repository containing `path`
+    ///
+    /// Returns `None` if `path` isn't inside a git repository or git isn't installed.
+    pub fn tracked(path: &Path) -> Option<Self> {
+        let top = toplevel(path)?;
+        let output = git(&top, &["ls-files", "-z"]).ok()?;
+        Some(Self::from_names(&top, &output))
+    }
+
+    /// Files that differ from `reference` in the working tree of the repository containing `path`
+    ///
+    /// Covers committed, staged and unstaged changes; new files git doesn't
+    /// ignore are included too unless `tracked_only` is set. Deleted files are
+    /// left out since there is nothing to read.
+    pub fn changed(path: &Path, reference: &str, tracked_only: bool) -> Result<Self, String> {
+        // Refs can't start with '-', and git would read one as an option
+        if reference.starts_with('-') {
+            return Err(format!("Invalid git ref: {}", reference));
+        }
+        let top = toplevel(path)
+            .ok_or_else(|| format!("{} is not inside a git repository", path.display()))?;
+
+        let mut names = git(&top, &["diff", "--name-only", "--diff-filter=d", "-z", reference, "--"])
+            .map_err(|error| format!("git diff against '{}' failed: {}", reference, error))?;
+        if !tracked_only {
+            names.push_str(&git(&top, &["ls-files", "--others", "--exclude-standard", "-z"])?);
+        }
+
+        Ok(Self::from_names(&top, &names))
+    }
+
+    /// Files committed after `since` in the repository containing `path`, plus uncommitted changes
+    ///
+    /// Untracked files count as changed, since they are newer than any commit.
+    /// Returns `None` if `path` isn't inside a git repository or git isn't installed.
+    pub fn modified_since(path: &Path, since: SystemTime) -> Option<Self> {
+        let top = toplevel(path)?;
+        let seconds = since.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let since = format!("--since=@{}", seconds);
+
+        // A repository without commits has no log, only uncommitted files
+        let mut names = git(&top, &["log", &since, "--name-only", "--diff-filter=d", "--format=", "-z"])
+            .unwrap_or_default();
+        names.push('\0');
+        names.push_str(&git(&top, &["diff", "--name-only", "--diff-filter=d", "-z"]).ok()?);
+        names.push_str(&git(&top, &["diff", "--cached", "--name-only", "--diff-filter=d", "-z"]).ok()?);
+        names.push_str(&git(&top, &["ls-files", "--others", "--exclude-standard", "-z"]).ok()?);
+        Some(Self::from_names(&top, &names))
+    }
+
+    /// Build the set from NUL-separated paths relative to the repository root
+    fn from_names(top: &Path, names: &str) -> Self {
+        let mut files = HashSet::new();
+        let mut dirs = HashSet::new();
+
+        for name in names.split('\0').filter(|name| !name.is_empty()) {
+            let file = top.join(name);
+            for dir in file.ancestors().skip(1) {
+                // Ancestors were already added by an earlier file in the same directory
+                if !dirs.insert(dir.to_path_buf()) {
+                    break;
+                }
+            }
+            files.insert(file);
+        }
+
+        Self { files, dirs }
+    }
+
+    /// Whether a canonical path is one of the files, or a directory containing one
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.dirs.contains(path)
+    }
+}
+
+/// The last commit that touched a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastCommit {
+    /// Abbreviated commit hash
+    pub hash: String,
+    pub author: String,
+    /// Author date, as `YYYY-MM-DD`
+    pub date: String,
+}
+
+impl fmt::Display for LastCommit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} by {} on {}", self.hash, self.author, self.date)
+    }
+}
+
+/// The last commit of every file in a repository's history
+pub struct LastCommits {
+    top: PathBuf,
+    // Keyed by absolute canonical path
+    commits: HashMap<PathBuf, LastCommit>,
+}
+
+impl LastCommits {
+    /// Read from a single `git log` of the repository containing `path`
+    ///
+    /// Returns `None` if `path` isn't inside a git repository or git isn't installed.
+    pub fn of_repository(path: &Path) -> Option<Self> {
+        let top = toplevel(path)?;
+        // A repository without commits has no log
+        let log = git(&top, &["log", "--name-only", "-z", "--format=%x1e%h%x1f%an%x1f%as"]).unwrap_or_default();
+        Some(Self::from_log(top, &log))
+    }
+
+    /// Parse `git log` output: each commit is a record separator, the
+    /// unit-separated hash, author and date, then NUL-terminated file names
+    fn from_log(top: PathBuf, log: &str) -> Self {
+        let mut commits = HashMap::new();
+        for record in log.split('\x1e').filter(|record| !record.is_empty()) {
+            let Some((header, names)) = record.split_once('\0') else {
+                continue;
+            };
+            let mut fields = header.split('\x1f');
+            let (Some(hash), Some(author), Some(date)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            let commit = LastCommit {
+                hash: hash.to_string(),
+                author: author.to_string(),
+                date: date.to_string(),
+            };
+            for name in names.split('\0').map(|name| name.trim_start_matches('\n')) {
+                // The log is newest first, so the first commit seen for a file is its last
+                if !name.is_empty() {
+                    commits.entry(top.join(name)).or_insert_with(|| commit.clone());
+                }
+            }
+        }
+        Self { top, commits }
+    }
+
+    /// The canonical root of the repository
+    pub fn top(&self) -> &Path {
+        &self.top
+    }
+
+    /// The last commit of a file, by its canonical path
+    pub fn get(&self, path: &Path) -> Option<&LastCommit> {
+        self.commits.get(path)
+    }
+}
+
+/// The git directory of a repository root: `.git` itself, or where a `.git` file points
+///
+/// Submodules and worktrees have a `.git` file reading `gitdir: <path>`.
+pub fn git_dir(repository: &Path) -> Option<PathBuf> {
+    let dot_git = repository.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let target = content.lines().next()?.strip_prefix("gitdir:")?.trim();
+    Some(repository.join(target)).filter(|dir| dir.is_dir())
+}
+
+/// The user's global ignore file, if it exists
+///
+/// Read from `git config core.excludesFile` as seen from `dir`, falling back
+/// to git's default of `$XDG_CONFIG_HOME/git/ignore` (or `~/.config/git/ignore`).
+pub fn global_excludes_file(dir: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let configured = git(dir, &["config", "--get", "core.excludesFile"])
+        .ok()
+        .map(|output| output.trim_end().to_string())
+        .filter(|value| !value.is_empty());
+
+    let path = match configured {
+        Some(value) => match value.strip_prefix("~/") {
+            Some(rest) => home?.join(rest),
+            None => PathBuf::from(value),
+        },
+        None => match std::env::var_os("XDG_CONFIG_HOME").filter(|value| !value.is_empty()) {
+            Some(config) => PathBuf::from(config).join("git/ignore"),
+            None => home?.join(".config/git/ignore"),
+        },
+    };
+    Some(path).filter(|path| path.is_file())
+}
+
+/// Canonical root of the repository containing `path`
+fn toplevel(path: &Path) -> Option<PathBuf> {
+    let canonical = path.canonicalize().ok()?;
+    let dir = if canonical.is_dir() {
+        canonical.as_path()
+    } else {
+        canonical.parent()?
+    };
+
+    let output = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+    PathBuf::from(output.trim_end()).canonicalize().ok()
+}
+
+/// Run git in `dir` and return its standard output, or its error message on failure
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|error| format!("Failed to run git: {}", error))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_names() {
+        let top = Path::new("/repo");
+        let files = GitFiles::from_names(top, "src/main.rs\0src/cli/args.rs\0README.md\0");
+
+        assert!(files.contains(Path::new("/repo/src/main.rs")));
+        assert!(files.contains(Path::new("/repo/src/cli")));
+        assert!(files.contains(Path::new("/repo/src")));
+        assert!(files.contains(Path::new("/repo")));
+        assert!(!files.contains(Path::new("/repo/target")));
+        assert!(!files.contains(Path::new("/repo/src/lib.rs")));
+    }
+
+    #[test]
+    fn test_last_commits() {
+        let log = "\x1ea1b2c3d\x1fAda Lovelace\x1f2024-03-01\0\nsrc/main.rs\0README.md\0\
+                   \x1ee4f5a6b\x1fCharles Babbage\x1f2024-02-01\0\nsrc/main.rs\0src/lib.rs\0\
+                   \x1ec7d8e9f\x1fCharles Babbage\x1f2024-01-01\0";
+        let commits = LastCommits::from_log(PathBuf::from("/repo"), log);
+
+        let main = commits.get(Path::new("/repo/src/main.rs")).unwrap();
+        assert_eq!(main.to_string(), "a1b2c3d by Ada Lovelace on 2024-03-01");
+        assert_eq!(commits.get(Path::new("/repo/src/lib.rs")).unwrap().hash, "e4f5a6b");
+        assert!(commits.get(Path::new("/repo/Cargo.toml")).is_none());
+        assert_eq!(commits.top(), Path::new("/repo"));
+    }
+}
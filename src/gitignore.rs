@@ -2,83 +2,332 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::error::{Error, Result};
+use crate::git;
 use crate::glob::GlobMatcher;
 
+/// Names of ignore files read in each directory, in order of increasing precedence
+///
+/// `.ignore` and `.rgignore` are the files ripgrep and fd read, so paths hidden
+/// from those tools are hidden from rcat too. `.rcatignore` uses gitignore
+/// syntax and lets users exclude files from rcat without touching the
+/// repository's `.gitignore`.
+pub const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".rgignore", ".rcatignore"];
+
+/// The ripgrep-style ignore files among `IGNORE_FILE_NAMES`, which can be turned off
+pub const DOT_IGNORE_FILE_NAMES: &[&str] = &[".ignore", ".rgignore"];
+
+/// `.gitattributes` attributes that mark files as not written by hand, as GitHub's linguist reads them
+pub const LINGUIST_ATTRIBUTES: &[&str] = &["linguist-generated", "linguist-vendored"];
+
+/// Read exclude patterns from a file in gitignore syntax, one per line
+///
+/// Blank lines and `#` comments are dropped and `\#` becomes a literal `#`;
+/// the rest, including `!` negations, are returned as written, to be used as
+/// exclude patterns.
+pub fn read_patterns(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path).map_err(|source| Error::Walk {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(pattern_lines(&content))
+}
+
+fn pattern_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            // `\#` stands for a literal `#`; `\!` is left for the exclude matcher
+            match line.strip_prefix('\\') {
+                Some(rest) if rest.starts_with('#') => rest.to_string(),
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
 /// Manages gitignore patterns hierarchically
+///
+/// `.gitignore` files only apply within their own git repository: not at all
+/// outside a repository, and not past the root of a nested one (a directory
+/// with a `.git` entry, such as a submodule). Each repository's
+/// `.git/info/exclude` and the user's global ignore file (`core.excludesFile`)
+/// apply the same way. `.ignore`, `.rgignore` and `.rcatignore` files apply to
+/// everything below them.
 pub struct GitignoreManager {
-    // Map from directory path to its gitignore matcher
-    matchers: HashMap<PathBuf, GitignoreMatcher>,
-    // Track which gitignore files we've found
+    // Map from directory path to the matchers of its ignore files
+    matchers: HashMap<PathBuf, Vec<GitignoreMatcher>>,
+    // Map from directory path to the matchers of its `.gitattributes`, one per linguist attribute
+    attributes: HashMap<PathBuf, Vec<(&'static str, GitignoreMatcher)>>,
+    // Track which ignore files we've found
     active_gitignores: Vec<PathBuf>,
     // The root path we started from
     root_path: PathBuf,
+    // Directories seen so far that are the root of a repository
+    repositories: Vec<PathBuf>,
+    // Whether the root path is inside a repository that starts above it
+    in_outer_repository: bool,
+    // The global ignore file and its content, once looked up
+    global_excludes: Option<Option<(PathBuf, String)>>,
+    // Whether `.ignore` and `.rgignore` files are read
+    dot_ignore: bool,
 }
 
 impl GitignoreManager {
     /// Create a new gitignore manager starting from the given root path
     pub fn new(root_path: &Path) -> Self {
+        Self::with_dot_ignore(root_path, true)
+    }
+
+    /// Create a gitignore manager, reading `.ignore` and `.rgignore` files only if `dot_ignore` is set
+    pub fn with_dot_ignore(root_path: &Path, dot_ignore: bool) -> Self {
+        let canonical = root_path.canonicalize().unwrap_or_else(|_| root_path.to_path_buf());
+        let outer_repository = canonical.ancestors().skip(1).find(|dir| is_repository(dir));
         let mut manager = Self {
             matchers: HashMap::new(),
+            attributes: HashMap::new(),
             active_gitignores: Vec::new(),
             root_path: root_path.to_path_buf(),
+            repositories: Vec::new(),
+            in_outer_repository: outer_repository.is_some(),
+            global_excludes: None,
+            dot_ignore,
         };
 
-        // Check for .gitignore in the root directory
-        let gitignore_path = root_path.join(".gitignore");
-        if gitignore_path.exists()
-            && let Ok(content) = fs::read_to_string(&gitignore_path)
-        {
-            let matcher = GitignoreMatcher::new(&content, root_path);
-            manager.matchers.insert(root_path.to_path_buf(), matcher);
-            manager.active_gitignores.push(gitignore_path);
+        // Check for ignore files in the root directory
+        manager.check_directory(root_path);
+
+        // A repository above the root still has its excludes, anchored at its own
+        // root, and the ignore files in the directories down to the root apply too
+        // (so `rcat packages/web` skips what the monorepo's `.gitignore` ignores)
+        if let Some(repository) = outer_repository {
+            let anchor = canonical.strip_prefix(repository).unwrap_or(Path::new(""));
+            let mut matchers = manager.repository_excludes(repository, root_path, anchor);
+            let mut attributes = Vec::new();
+            let mut ancestors: Vec<&Path> = canonical.ancestors().skip(1).take_while(|dir| dir.starts_with(repository)).collect();
+            ancestors.reverse();
+            for dir in ancestors {
+                let anchor = canonical.strip_prefix(dir).unwrap_or(Path::new(""));
+                for (attribute, mut matcher) in attribute_matchers(dir, root_path) {
+                    matcher.anchor = anchor.to_path_buf();
+                    attributes.push((attribute, matcher));
+                }
+                for name in manager.ignore_file_names() {
+                    let ignore_path = dir.join(name);
+                    if let Ok(content) = fs::read_to_string(&ignore_path) {
+                        let mut matcher = GitignoreMatcher::new(&content, root_path, &ignore_path);
+                        matcher.anchor = anchor.to_path_buf();
+                        matchers.push(matcher);
+                        manager.active_gitignores.push(ignore_path);
+                    }
+                }
+            }
+            // They rank below the root's own ignore files
+            let root_matchers = manager.matchers.entry(root_path.to_path_buf()).or_default();
+            matchers.append(root_matchers);
+            *root_matchers = matchers;
+            let root_attributes = manager.attributes.entry(root_path.to_path_buf()).or_default();
+            attributes.append(root_attributes);
+            *root_attributes = attributes;
         }
 
         manager
     }
 
-    /// Check and load gitignore for a directory if it exists
+    /// Check and load ignore files for a directory if they exist
     pub fn check_directory(&mut self, dir_path: &Path) {
-        let gitignore_path = dir_path.join(".gitignore");
-        if gitignore_path.exists() {
-            // Only load if we haven't already
-            if !self.matchers.contains_key(dir_path)
-                && let Ok(content) = fs::read_to_string(&gitignore_path)
+        // Only load if we haven't already
+        if self.matchers.contains_key(dir_path) || self.attributes.contains_key(dir_path) {
+            return;
+        }
+
+        let mut matchers = Vec::new();
+        if is_repository(dir_path) && !self.repositories.iter().any(|repository| repository == dir_path) {
+            self.repositories.push(dir_path.to_path_buf());
+            matchers = self.repository_excludes(dir_path, dir_path, Path::new(""));
+        }
+
+        for name in self.ignore_file_names() {
+            let ignore_path = dir_path.join(name);
+            if ignore_path.is_file()
+                && let Ok(content) = fs::read_to_string(&ignore_path)
             {
-                let matcher = GitignoreMatcher::new(&content, dir_path);
-                self.matchers.insert(dir_path.to_path_buf(), matcher);
-                self.active_gitignores.push(gitignore_path);
+                matchers.push(GitignoreMatcher::new(&content, dir_path, &ignore_path));
+                self.active_gitignores.push(ignore_path);
+            }
+        }
+
+        if !matchers.is_empty() {
+            self.matchers.insert(dir_path.to_path_buf(), matchers);
+        }
+
+        let attributes = attribute_matchers(dir_path, dir_path);
+        if !attributes.is_empty() {
+            self.attributes.insert(dir_path.to_path_buf(), attributes);
+        }
+    }
+
+    /// Names of the ignore files to read in each directory
+    fn ignore_file_names(&self) -> impl Iterator<Item = &'static str> + use<> {
+        let dot_ignore = self.dot_ignore;
+        IGNORE_FILE_NAMES
+            .iter()
+            .copied()
+            .filter(move |name| dot_ignore || !DOT_IGNORE_FILE_NAMES.contains(name))
+    }
+
+    /// Matchers for a repository's `info/exclude` file and the global ignore file
+    ///
+    /// Their patterns are relative to the repository root, which is `anchor`
+    /// above `base_path` (the directory they are looked up from).
+    fn repository_excludes(&mut self, repository: &Path, base_path: &Path, anchor: &Path) -> Vec<GitignoreMatcher> {
+        let global = self
+            .global_excludes
+            .get_or_insert_with(|| {
+                let path = git::global_excludes_file(repository)?;
+                let content = fs::read_to_string(&path).ok()?;
+                Some((path, content))
+            })
+            .clone();
+        let info_exclude = git::git_dir(repository)
+            .map(|dir| dir.join("info/exclude"))
+            .and_then(|path| fs::read_to_string(&path).ok().map(|content| (path, content)));
+
+        let mut matchers = Vec::new();
+        // Lowest precedence first, as later matchers override earlier ones
+        for (source, content) in global.into_iter().chain(info_exclude) {
+            let mut matcher = GitignoreMatcher::new(&content, base_path, &source);
+            matcher.repository_scoped = true;
+            matcher.anchor = anchor.to_path_buf();
+            if !matcher.patterns.is_empty() {
+                if !self.active_gitignores.contains(&source) {
+                    self.active_gitignores.push(source);
+                }
+                matchers.push(matcher);
             }
         }
+        matchers
     }
 
     /// Check if a path should be ignored based on all applicable gitignore files
     pub fn should_ignore(&self, path: &Path) -> bool {
-        // Check each gitignore from root down to the file's directory
-        // We need to check all parent directories
-        let mut current_path = self.root_path.clone();
+        self.ignored_by(path).is_some()
+    }
 
-        // First check the root
-        if let Some(matcher) = self.matchers.get(&current_path)
-            && matcher.should_ignore(path)
-        {
-            return true;
-        }
+    /// Find the ignore file that ignores a path, if any
+    ///
+    /// As in git, the last matching pattern decides: ignore files deeper in
+    /// the tree override those above them (so a child's `!pattern` re-includes
+    /// what a parent ignored), and later patterns in a file override earlier ones.
+    pub fn ignored_by(&self, path: &Path) -> Option<&Path> {
+        self.ignored_entry(path, path.is_dir())
+    }
+
+    /// Like `ignored_by`, for a path already known to be a directory or not
+    ///
+    /// Saves looking the path up on disk, which adds up in large trees.
+    pub fn ignored_entry(&self, path: &Path, is_dir: bool) -> Option<&Path> {
+        self.ignoring_rule(path, is_dir).map(|rule| rule.source)
+    }
+
+    /// The line of an ignore file that ignores a path, if any
+    pub fn ignoring_rule(&self, path: &Path, is_dir: bool) -> Option<IgnoreRule<'_>> {
+        self.last_decision(path, is_dir, |dir| self.matchers.get(dir).into_iter().flatten())
+    }
+
+    /// Find the linguist attribute (see `LINGUIST_ATTRIBUTES`) set on a path, and the `.gitattributes` setting it
+    ///
+    /// As with ignore files, the last matching line decides, so a deeper
+    /// `.gitattributes` can unset an attribute with `-linguist-generated`.
+    pub fn linguist_attribute(&self, path: &Path, is_dir: bool) -> Option<(&'static str, &Path)> {
+        LINGUIST_ATTRIBUTES.iter().find_map(|&attribute| {
+            let rule = self.last_decision(path, is_dir, |dir| {
+                self.attributes
+                    .get(dir)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |(name, _)| *name == attribute)
+                    .map(|(_, matcher)| matcher)
+            })?;
+            Some((attribute, rule.source))
+        })
+    }
+
+    /// The pattern of the last matcher that matches a path, if that match is positive
+    ///
+    /// `matchers_in` gives the matchers read in a directory.
+    fn last_decision<'a, I>(&'a self, path: &Path, is_dir: bool, matchers_in: impl Fn(&Path) -> I) -> Option<IgnoreRule<'a>>
+    where
+        I: Iterator<Item = &'a GitignoreMatcher>,
+    {
+        // Check each directory from root down to the file's directory
+        let mut current_path = self.root_path.clone();
+        let mut decision = self.decide_in(&current_path, matchers_in(&current_path), path, is_dir);
 
-        // Then check each subdirectory leading to the target
         if let Ok(relative) = path.strip_prefix(&self.root_path) {
             for component in relative.components() {
                 current_path.push(component);
 
-                // Only check directories that have gitignore files
-                if let Some(matcher) = self.matchers.get(&current_path)
-                    && matcher.should_ignore(path)
-                {
-                    return true;
+                // Only directories that have matchers can change the decision
+                if let Some(found) = self.decide_in(&current_path, matchers_in(&current_path), path, is_dir) {
+                    decision = Some(found);
                 }
             }
         }
 
-        false
+        match decision {
+            Some((pattern, source)) if !pattern.is_negation => Some(IgnoreRule {
+                source,
+                line: pattern.line,
+                pattern: &pattern.text,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The last pattern read in `dir_path` that matches `path`, and the file it is in
+    ///
+    /// Returns `None` if none of their patterns match.
+    fn decide_in<'a>(
+        &self,
+        dir_path: &Path,
+        matchers: impl Iterator<Item = &'a GitignoreMatcher>,
+        path: &Path,
+        is_dir: bool,
+    ) -> Option<(&'a Pattern, &'a Path)> {
+        // Only worked out when a `.gitignore` is there to be checked
+        let mut same_repository = None;
+        matchers
+            .filter(|matcher| {
+                !matcher.repository_scoped
+                    || *same_repository.get_or_insert_with(|| {
+                        let repository = self.repository_of(dir_path);
+                        repository.is_some() && repository == self.repository_of(path.parent().unwrap_or(path))
+                    })
+            })
+            .filter_map(|matcher| {
+                matcher
+                    .deciding_pattern(path, is_dir)
+                    .map(|pattern| (pattern, matcher.source.as_path()))
+            })
+            .last()
+    }
+
+    /// The repository a directory belongs to: the deepest repository root
+    /// containing it, or the root path if that is inside an outer repository
+    ///
+    /// A nested repository's own directory belongs to the nested repository,
+    /// while entries directly in it are checked from their parent directory,
+    /// so the outer repository can still ignore the nested one as a whole.
+    fn repository_of(&self, dir: &Path) -> Option<&Path> {
+        self.repositories
+            .iter()
+            .filter(|repository| dir.starts_with(repository))
+            .max_by_key(|repository| repository.components().count())
+            .map(PathBuf::as_path)
+            .or_else(|| self.in_outer_repository.then_some(self.root_path.as_path()))
     }
 
     /// Get the list of active gitignore files
@@ -92,10 +341,73 @@ impl GitignoreManager {
     }
 }
 
+/// Drop trailing spaces, except one escaped with a backslash (`foo\ ` keeps its space)
+///
+/// Leading whitespace is part of the pattern, as in git.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    let backslashes = trimmed.chars().rev().take_while(|&c| c == '\\').count();
+    if backslashes % 2 == 1 && trimmed.len() < line.len() {
+        // Keep the escaped space
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Matchers for the linguist attributes set in a directory's `.gitattributes`, if it has one
+fn attribute_matchers(dir_path: &Path, base_path: &Path) -> Vec<(&'static str, GitignoreMatcher)> {
+    let source = dir_path.join(".gitattributes");
+    let Ok(content) = fs::read_to_string(&source) else {
+        return Vec::new();
+    };
+    LINGUIST_ATTRIBUTES
+        .iter()
+        .map(|&attribute| (attribute, GitignoreMatcher::from_attributes(&content, base_path, &source, attribute)))
+        .filter(|(_, matcher)| !matcher.patterns.is_empty())
+        .collect()
+}
+
+/// Whether a `.gitattributes` field sets (`true`) or unsets (`false`) an attribute
+///
+/// `attr` and `attr=value` set it, while `-attr`, `!attr` and `attr=false` unset it.
+fn attribute_state(field: &str, attribute: &str) -> Option<bool> {
+    if field == attribute {
+        return Some(true);
+    }
+    if let Some(name) = field.strip_prefix(['-', '!']) {
+        return (name == attribute).then_some(false);
+    }
+    let (name, value) = field.split_once('=')?;
+    (name == attribute).then_some(value != "false")
+}
+
+/// Whether a directory is the root of a git repository (`.git` is a file in submodules and worktrees)
+fn is_repository(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// The line of an ignore file that ignores a path
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IgnoreRule<'a> {
+    pub source: &'a Path,
+    /// 1-based line number in `source`
+    pub line: usize,
+    /// The pattern as written on that line
+    pub pattern: &'a str,
+}
+
 /// A gitignore pattern matcher for a specific directory
 struct GitignoreMatcher {
     patterns: Vec<Pattern>,
     base_path: PathBuf,
+    // The ignore file these patterns were read from
+    source: PathBuf,
+    // Only applies within the same git repository (true for `.gitignore`)
+    repository_scoped: bool,
+    // Where `base_path` is relative to the directory patterns are anchored at,
+    // when that is above the walk's root
+    anchor: PathBuf,
 }
 
 struct Pattern {
@@ -103,165 +415,155 @@ struct Pattern {
     is_negation: bool,
     is_directory_only: bool,
     is_absolute: bool,
+    // The pattern as written, and its 1-based line in the file it was read from
+    text: String,
+    line: usize,
+}
+
+impl Pattern {
+    /// Parse one pattern, with any `!` and trailing `/` still in place
+    fn parse(line: &str) -> Self {
+        let text = line;
+        let is_negation = line.starts_with('!');
+        let line = if is_negation { &line[1..] } else { line };
+
+        let is_directory_only = line.ends_with('/');
+        let line = if is_directory_only {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+
+        let is_absolute = line.starts_with('/');
+        let pattern = if is_absolute {
+            line[1..].to_string()
+        } else {
+            line.to_string()
+        };
+
+        Self {
+            pattern,
+            is_negation,
+            is_directory_only,
+            is_absolute,
+            text: text.to_string(),
+            line: 0,
+        }
+    }
 }
 
 impl GitignoreMatcher {
     /// Create a new gitignore matcher from content and base path
-    fn new(content: &str, base_path: &Path) -> Self {
+    fn new(content: &str, base_path: &Path, source: &Path) -> Self {
         let patterns = Self::parse_gitignore(content);
         Self {
             patterns,
             base_path: base_path.to_path_buf(),
+            source: source.to_path_buf(),
+            repository_scoped: source.file_name().is_some_and(|name| name == ".gitignore" || name == ".gitattributes"),
+            anchor: PathBuf::new(),
         }
     }
 
-    /// Check if a path should be ignored by this specific gitignore
-    fn should_ignore(&self, path: &Path) -> bool {
+    /// Create a matcher for the paths a `.gitattributes` file sets `attribute` on
+    ///
+    /// Lines unsetting the attribute become negated patterns, so the last
+    /// matching line decides as in git.
+    fn from_attributes(content: &str, base_path: &Path, source: &Path, attribute: &str) -> Self {
+        let patterns = content
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next().filter(|pattern| !pattern.starts_with('#'))?;
+                // Negated patterns are not allowed, and a trailing `/` matches nothing
+                if pattern.starts_with('!') || pattern.ends_with('/') {
+                    return None;
+                }
+                let set = fields.rev().find_map(|field| attribute_state(field, attribute))?;
+                let mut pattern = Pattern::parse(pattern);
+                pattern.is_negation = !set;
+                pattern.line = index + 1;
+                Some(pattern)
+            })
+            .collect();
+        Self {
+            patterns,
+            ..Self::new("", base_path, source)
+        }
+    }
+
+    /// Whether the last pattern matching a path ignores it (`true`) or re-includes it (`false`)
+    ///
+    /// Returns `None` if no pattern matches.
+    #[cfg(test)]
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        self.deciding_pattern(path, is_dir).map(|pattern| !pattern.is_negation)
+    }
+
+    /// The last pattern matching a path, which decides whether it is ignored
+    fn deciding_pattern(&self, path: &Path, is_dir: bool) -> Option<&Pattern> {
         // Get the relative path from this gitignore's base
-        let relative_path = match path.strip_prefix(&self.base_path) {
-            Ok(rel) => rel,
-            Err(_) => return false,
-        };
+        let relative_path = path.strip_prefix(&self.base_path).ok()?;
 
         // Empty relative path means it's the base directory itself
         if relative_path.as_os_str().is_empty() {
-            return false;
+            return None;
         }
 
-        let path_str = relative_path.to_string_lossy();
-        let is_dir = path.is_dir();
-
-        let mut ignored = false;
-
-        for pattern in &self.patterns {
-            if pattern.is_directory_only && !is_dir {
-                continue;
-            }
-
-            if self.matches_pattern(&path_str, &pattern.pattern, pattern.is_absolute) {
-                ignored = !pattern.is_negation;
-            }
-        }
+        let anchored = self.anchor.join(relative_path);
+        let path_str = anchored.to_string_lossy();
 
-        ignored
+        self.patterns
+            .iter()
+            .rev()
+            .filter(|pattern| !pattern.is_directory_only || is_dir)
+            .find(|pattern| self.matches_pattern(&path_str, &pattern.pattern, pattern.is_absolute))
     }
 
     /// Parse gitignore content into patterns
     fn parse_gitignore(content: &str) -> Vec<Pattern> {
         content
             .lines()
-            .filter_map(|line| {
-                let line = line.trim();
-
-                // Skip empty lines and comments
-                if line.is_empty() || line.starts_with('#') {
+            .enumerate()
+            .filter_map(|(index, line)| {
+                // Comments start with an unescaped `#`; `\#` and `\!` stay
+                // escaped, which the glob matcher reads as literal characters
+                if line.starts_with('#') {
+                    return None;
+                }
+                let line = trim_trailing_spaces(line);
+                if line.is_empty() {
                     return None;
                 }
-
-                let is_negation = line.starts_with('!');
-                let line = if is_negation { &line[1..] } else { line };
-
-                let is_directory_only = line.ends_with('/');
-                let line = if is_directory_only {
-                    &line[..line.len() - 1]
-                } else {
-                    line
-                };
-
-                let is_absolute = line.starts_with('/');
-                let pattern = if is_absolute {
-                    line[1..].to_string()
-                } else {
-                    line.to_string()
-                };
-
                 Some(Pattern {
-                    pattern,
-                    is_negation,
-                    is_directory_only,
-                    is_absolute,
+                    line: index + 1,
+                    ..Pattern::parse(line)
                 })
             })
             .collect()
     }
-
     /// Check if a path matches a gitignore pattern
+    ///
+    /// A path inside a matching directory matches too, since git ignores
+    /// everything below an ignored directory.
     fn matches_pattern(&self, path: &str, pattern: &str, is_absolute: bool) -> bool {
         // Handle simple cases first
         if pattern == "*" {
             return true;
         }
 
-        // Convert pattern to a simple glob matcher
-        let pattern_parts: Vec<&str> = pattern.split('/').collect();
         let path_parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
 
-        if is_absolute {
-            // Pattern must match from the beginning
-            self.match_parts(&path_parts, &pattern_parts, 0)
+        if is_absolute || pattern.contains('/') {
+            // Patterns with a slash match the path from this gitignore's directory
+            let pattern_parts: Vec<&str> = pattern.split('/').collect();
+            (1..=path_parts.len()).any(|len| GlobMatcher::matches_segments(&path_parts[..len], &pattern_parts, true))
         } else {
-            // Pattern can match anywhere in the path
-            // But if pattern contains /, it should match the full path structure
-            if pattern.contains('/') {
-                // Match against full path
-                self.match_parts(&path_parts, &pattern_parts, 0)
-            } else {
-                // Match against any component
-                for part in &path_parts {
-                    if GlobMatcher::matches(part, pattern) {
-                        return true;
-                    }
-                }
-                false
-            }
-        }
-    }
-
-    /// Match path parts against pattern parts
-    fn match_parts(&self, path_parts: &[&str], pattern_parts: &[&str], start_idx: usize) -> bool {
-        if pattern_parts.is_empty() {
-            return true;
-        }
-
-        let mut path_idx = start_idx;
-        let mut pattern_idx = 0;
-
-        while pattern_idx < pattern_parts.len() && path_idx < path_parts.len() {
-            let pattern_part = pattern_parts[pattern_idx];
-            let path_part = path_parts[path_idx];
-
-            if pattern_part == "**" {
-                // Match zero or more directories
-                if pattern_idx == pattern_parts.len() - 1 {
-                    return true; // ** at end matches everything
-                }
-
-                // Try matching the rest of the pattern at different positions
-                pattern_idx += 1;
-                let next_pattern = pattern_parts[pattern_idx];
-
-                // Try to find where the next pattern matches
-                while path_idx < path_parts.len() {
-                    if GlobMatcher::matches(path_parts[path_idx], next_pattern) {
-                        // Found a match, continue matching from here
-                        if self.match_parts(path_parts, &pattern_parts[pattern_idx..], path_idx) {
-                            return true;
-                        }
-                    }
-                    path_idx += 1;
-                }
-                return false;
-            } else if GlobMatcher::matches(path_part, pattern_part) {
-                path_idx += 1;
-                pattern_idx += 1;
-            } else {
-                return false;
-            }
+            // Others match a file or directory name at any depth
+            path_parts.iter().any(|part| GlobMatcher::matches(part, pattern))
         }
-
-        pattern_idx == pattern_parts.len()
     }
-
 }
 
 #[cfg(test)]
@@ -306,4 +608,286 @@ node_modules/
         assert_eq!(patterns[2].pattern, "important.tmp");
         assert!(patterns[2].is_negation);
     }
+
+    #[test]
+    fn test_double_star() {
+        // Cases from git's t/t3070-wildmatch.sh, plus the gitignore documentation's examples
+        let cases = [
+            ("foo/bar/baz", "foo/**/baz", true),
+            ("foo/baz/bar", "foo/**/bar", true),
+            ("foo/b/a/z/bar", "foo/**/**/bar", true),
+            ("foo/bar", "foo/**/bar", true),
+            ("foo/bar", "foo/**/**/bar", true),
+            ("deep/foo/bar/baz", "**/bar/*", true),
+            ("deep/foo/bar/baz", "**/bar/**", true),
+            ("foo/bar/baz/x", "*/bar/**", true),
+            ("deep/foo/bar/baz/x", "*/bar/**", false),
+            ("deep/foo/bar/baz/x", "**/bar/*/*", true),
+            ("foo", "**/foo", true),
+            ("XXX/foo", "**/foo", true),
+            ("bar/baz/foo", "**/foo", true),
+            ("bar/baz/foo", "*/foo", false),
+            ("foo/bar/baz", "**/bar*", false),
+            ("deep/foo/bar/baz", "**/bar*/*", true),
+            ("logs/debug.log", "**/logs/debug.log", true),
+            ("build/logs/debug.log", "**/logs/debug.log", true),
+            ("logs/build/debug.log", "**/logs/debug.log", false),
+            ("a/b", "a/**/b", true),
+            ("a/x/y/b", "a/**/b", true),
+            ("a/bb", "a/**/b", false),
+            ("abc/x/y", "abc/**", true),
+            ("abc", "abc/**", false),
+            ("src/a**b.rs", "src/a**b.rs", true),
+            ("src/axyzb.rs", "src/a**b.rs", true),
+        ];
+        for (path, pattern, expected) in cases {
+            let path: Vec<&str> = path.split('/').collect();
+            let pattern_parts: Vec<&str> = pattern.split('/').collect();
+            assert_eq!(GlobMatcher::matches_segments(&path, &pattern_parts, true), expected, "{:?} against {}", path, pattern);
+        }
+
+        // Contents of a matching directory match as well
+        let matcher = GitignoreMatcher::new("", Path::new(""), Path::new(".gitignore"));
+        assert!(matcher.matches_pattern("foo/bar/baz", "**/bar*", false));
+        assert!(matcher.matches_pattern("a/x/b/c.txt", "a/**/b", false));
+
+        // `abc/**` leaves the directory itself alone, so a file in it can be re-included
+        let matcher = GitignoreMatcher::new("abc/**\n!abc/keep.txt\n", Path::new("base"), Path::new("base/.gitignore"));
+        assert_eq!(matcher.decide(Path::new("base/abc/drop.txt"), false), Some(true));
+        assert_eq!(matcher.decide(Path::new("base/abc/keep.txt"), false), Some(false));
+        assert_eq!(matcher.decide(Path::new("base/abc"), true), None);
+    }
+
+    #[test]
+    fn test_escapes() {
+        let content = "\\#notes.md\n\\!important\ntrailing   \nspace\\ \n  leading\n\\*.txt\n";
+        let patterns: Vec<String> = GitignoreMatcher::parse_gitignore(content)
+            .into_iter()
+            .map(|pattern| pattern.pattern)
+            .collect();
+        assert_eq!(patterns, vec!["\\#notes.md", "\\!important", "trailing", "space\\ ", "  leading", "\\*.txt"]);
+        assert!(!GitignoreMatcher::parse_gitignore("\\!important")[0].is_negation);
+
+        let matcher = GitignoreMatcher::new(content, Path::new("base"), Path::new("base/.gitignore"));
+        for name in ["#notes.md", "!important", "trailing", "space ", "  leading", "*.txt"] {
+            assert_eq!(matcher.decide(&Path::new("base").join(name), false), Some(true), "{}", name);
+        }
+        for name in ["space", "leading", "notes.txt"] {
+            assert_eq!(matcher.decide(&Path::new("base").join(name), false), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_pattern_lines() {
+        let content = "# shared excludes\n\n*.log\n  /dist/  \n\\#notes.md\n!keep.log\n";
+        assert_eq!(pattern_lines(content), vec!["*.log", "/dist/", "#notes.md", "!keep.log"]);
+    }
+
+    #[test]
+    fn test_rcatignore() {
+        let dir = PathBuf::from("test_rcatignore");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.join(".rcatignore"), "fixtures/\n").unwrap();
+        fs::create_dir(dir.join("fixtures")).unwrap();
+
+        let manager = GitignoreManager::new(&dir);
+        // Ignore files of the repository the test runs in may be active too
+        let active = manager.active_gitignores();
+        assert!(active.contains(&dir.join(".gitignore")) && active.contains(&dir.join(".rcatignore")));
+        assert!(manager.should_ignore(&dir.join("debug.log")));
+        assert_eq!(
+            manager.ignored_by(&dir.join("fixtures")),
+            Some(dir.join(".rcatignore").as_path())
+        );
+        assert!(!manager.should_ignore(&dir.join("main.rs")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dot_ignore() {
+        let dir = PathBuf::from("test_dot_ignore");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(".ignore"), "*.snap\n").unwrap();
+        fs::write(dir.join(".rgignore"), "*.bak\n").unwrap();
+        // `.rcatignore` takes precedence over the ripgrep files
+        fs::write(dir.join(".rcatignore"), "!keep.snap\n").unwrap();
+
+        let manager = GitignoreManager::new(&dir);
+        assert_eq!(
+            manager.ignored_by(&dir.join("ui.snap")),
+            Some(dir.join(".ignore").as_path())
+        );
+        assert!(manager.should_ignore(&dir.join("old.bak")));
+        assert!(!manager.should_ignore(&dir.join("keep.snap")));
+
+        let manager = GitignoreManager::with_dot_ignore(&dir, false);
+        assert!(!manager.should_ignore(&dir.join("ui.snap")));
+        assert!(!manager.should_ignore(&dir.join("old.bak")));
+        assert!(!manager.active_gitignores().contains(&dir.join(".ignore")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_linguist_attributes() {
+        let dir = PathBuf::from("test_linguist_attributes");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("sdk/custom")).unwrap();
+        fs::write(
+            dir.join(".gitattributes"),
+            "# generated code\n*.pb.go linguist-generated=true diff\nsdk/** linguist-vendored\n*.js text eol=lf\nkeep.pb.go -linguist-generated\n",
+        )
+        .unwrap();
+        fs::write(dir.join("sdk/custom/.gitattributes"), "* linguist-vendored=false\n").unwrap();
+
+        let mut manager = GitignoreManager::new(&dir);
+        manager.check_directory(&dir.join("sdk"));
+        manager.check_directory(&dir.join("sdk/custom"));
+        let source = dir.join(".gitattributes");
+        assert_eq!(
+            manager.linguist_attribute(&dir.join("api/user.pb.go"), false),
+            Some(("linguist-generated", source.as_path()))
+        );
+        assert_eq!(
+            manager.linguist_attribute(&dir.join("sdk/client.js"), false),
+            Some(("linguist-vendored", source.as_path()))
+        );
+        assert_eq!(manager.linguist_attribute(&dir.join("app.js"), false), None);
+        // Unset by a later line, or by a deeper `.gitattributes`
+        assert_eq!(manager.linguist_attribute(&dir.join("keep.pb.go"), false), None);
+        assert_eq!(manager.linguist_attribute(&dir.join("sdk/custom/patch.js"), false), None);
+        // Attributes don't make a path ignored
+        assert!(!manager.should_ignore(&dir.join("api/user.pb.go")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_negation_across_directories() {
+        let dir = PathBuf::from("test_negation_across");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("logs/keep")).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n!a.tmp\n").unwrap();
+        fs::write(dir.join("logs/.gitignore"), "!audit.log\n*.tmp\n").unwrap();
+        fs::write(dir.join("logs/keep/.gitignore"), "!*.log\n").unwrap();
+
+        let mut manager = GitignoreManager::new(&dir);
+        manager.check_directory(&dir.join("logs"));
+        manager.check_directory(&dir.join("logs/keep"));
+        assert!(manager.should_ignore(&dir.join("logs/debug.log")));
+        // A deeper `!pattern` re-includes what a parent ignored
+        assert!(!manager.should_ignore(&dir.join("logs/audit.log")));
+        assert!(!manager.should_ignore(&dir.join("logs/keep/debug.log")));
+        // ...but a parent's negation doesn't override a deeper ignore
+        assert_eq!(manager.ignored_by(&dir.join("logs/a.tmp")), Some(dir.join("logs/.gitignore").as_path()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ancestor_ignore_files() {
+        let dir = PathBuf::from("test_ancestor_ignore");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        let web = dir.join("packages/web");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(web.join("dist")).unwrap();
+        fs::create_dir_all(web.join(".cache")).unwrap();
+        fs::write(dir.join(".gitignore"), ".cache/\n/packages/web/dist/\n/dist/\n").unwrap();
+        fs::write(dir.join("packages/.rcatignore"), "*.snap\n").unwrap();
+
+        // Walking one package still applies the ignore files above it, anchored where they are
+        let manager = GitignoreManager::new(&web);
+        assert!(manager.should_ignore(&web.join(".cache")));
+        assert!(manager.should_ignore(&web.join("dist")));
+        assert!(manager.should_ignore(&web.join("app.snap")));
+        assert!(!manager.should_ignore(&web.join("index.ts")));
+        let active = manager.active_gitignores();
+        assert!(active.contains(&dir.canonicalize().unwrap().join(".gitignore")));
+        assert!(active.contains(&dir.canonicalize().unwrap().join("packages/.rcatignore")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_repository_scope() {
+        let dir = PathBuf::from("test_repository_scope");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        let nested = dir.join("vendor/lib");
+        fs::create_dir_all(nested.join(".git")).unwrap();
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.join(".rcatignore"), "*.tmp\n").unwrap();
+        fs::write(nested.join(".gitignore"), "*.out\n").unwrap();
+
+        let mut manager = GitignoreManager::new(&dir);
+        manager.check_directory(&dir.join("vendor"));
+        manager.check_directory(&nested);
+        assert!(manager.should_ignore(&dir.join("vendor/debug.log")));
+        assert!(manager.should_ignore(&nested.join("a.out")));
+        // The outer `.gitignore` stops at the nested repository; `.rcatignore` doesn't
+        assert!(!manager.should_ignore(&nested.join("debug.log")));
+        assert!(manager.should_ignore(&nested.join("scratch.tmp")));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Outside any repository, only `.rcatignore` applies
+        let outside = std::env::temp_dir().join(format!("rcat_no_repository_{}", std::process::id()));
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(outside.join(".rcatignore"), "*.tmp\n").unwrap();
+        let manager = GitignoreManager::new(&outside);
+        if !manager.in_outer_repository {
+            assert!(!manager.should_ignore(&outside.join("debug.log")));
+            assert!(manager.should_ignore(&outside.join("scratch.tmp")));
+        }
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn test_info_exclude() {
+        let dir = PathBuf::from("test_info_exclude");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join(".git/info")).unwrap();
+        fs::write(dir.join(".git/info/exclude"), "# local only\n*.bak\n/src/scratch.rs\n").unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        // A submodule's `.git` is a file pointing at its git directory
+        fs::create_dir_all(dir.join("modules/sub/info")).unwrap();
+        fs::write(dir.join("modules/sub/info/exclude"), "*.out\n").unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/.git"), "gitdir: ../modules/sub\n").unwrap();
+
+        let mut manager = GitignoreManager::new(&dir);
+        manager.check_directory(&dir.join("src"));
+        manager.check_directory(&dir.join("sub"));
+        assert!(manager.should_ignore(&dir.join("src/old.bak")));
+        assert!(manager.should_ignore(&dir.join("src/scratch.rs")));
+        assert!(manager.should_ignore(&dir.join("sub/a.out")));
+        assert!(!manager.should_ignore(&dir.join("sub/old.bak")));
+        assert!(manager.active_gitignores().contains(&dir.join(".git/info/exclude")));
+
+        // Walking only a subdirectory, anchored patterns still count from the repository root
+        let src = dir.join("src");
+        let manager = GitignoreManager::new(&src);
+        assert!(manager.should_ignore(&src.join("scratch.rs")));
+        assert!(!manager.should_ignore(&src.join("main.rs")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
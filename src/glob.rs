@@ -1,67 +1,281 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 /// Utilities for glob pattern matching
 pub struct GlobMatcher;
 
 impl GlobMatcher {
-    /// Simple glob matching for patterns supporting * and ? wildcards
+    /// Glob matching supporting `*`, `?`, `[abc]`/`[a-z]`/`[!x]` classes and `{a,b}` alternation
+    ///
+    /// A backslash makes the next character literal, so `\*` only matches `*`.
     pub fn matches(text: &str, pattern: &str) -> bool {
         if pattern == "*" {
             return true;
         }
 
-        if !pattern.contains('*') && !pattern.contains('?') {
+        if !pattern.contains(['*', '?', '[', '{', '\\']) {
             return text == pattern;
         }
 
-        // Simple glob matching implementation
+        let text: Vec<char> = text.chars().collect();
+        Self::expand_braces(pattern).iter().any(|alternative| {
+            let pattern: Vec<char> = alternative.chars().collect();
+            Self::matches_chars(&text, &pattern)
+        })
+    }
+
+    /// Match a `/`-separated path against a pattern segment by segment
+    ///
+    /// `*`, `?` and classes never cross a `/`; a `**` segment matches any number
+    /// of directories, including none. The whole path must match, so
+    /// `src/generated/*` matches `src/generated/api.rs` but not `lib/src/generated/api.rs`.
+    pub fn matches_path(path: &str, pattern: &str) -> bool {
+        let path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+
+        Self::expand_braces(pattern).iter().any(|alternative| {
+            let pattern: Vec<&str> = alternative.split('/').filter(|part| !part.is_empty()).collect();
+            Self::matches_segments(&path, &pattern, false)
+        })
+    }
+
+    /// Match path segments against pattern segments, where a `**` segment matches
+    /// any number of directories, including none
+    ///
+    /// With `contents_only`, a trailing `**` needs at least one segment, so as in
+    /// git `abc/**` matches everything inside `abc` but not `abc` itself. Within a
+    /// segment, `**` is just `*`.
+    pub(crate) fn matches_segments(path: &[&str], pattern: &[&str], contents_only: bool) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", [])) if contents_only => !path.is_empty(),
+            Some((&"**", rest)) => {
+                (0..=path.len()).any(|skip| Self::matches_segments(&path[skip..], rest, contents_only))
+            }
+            Some((segment, rest)) => path.split_first().is_some_and(|(part, tail)| {
+                Self::matches(part, segment) && Self::matches_segments(tail, rest, contents_only)
+            }),
+        }
+    }
+
+    /// Expand `{a,b}` alternation into every pattern it stands for
+    ///
+    /// Braces without a top-level comma, or without a closing brace, are literal.
+    fn expand_braces(pattern: &str) -> Vec<String> {
+        let chars: Vec<char> = pattern.chars().collect();
+
+        for open in 0..chars.len() {
+            if chars[open] != '{' || is_escaped(&chars, open) {
+                continue;
+            }
+
+            // Find the matching close brace and the top-level commas inside
+            let mut depth = 0;
+            let mut commas = Vec::new();
+            let mut close = None;
+            for (index, &c) in chars.iter().enumerate().skip(open + 1) {
+                if is_escaped(&chars, index) {
+                    continue;
+                }
+                match c {
+                    '{' => depth += 1,
+                    '}' if depth == 0 => {
+                        close = Some(index);
+                        break;
+                    }
+                    '}' => depth -= 1,
+                    ',' if depth == 0 => commas.push(index),
+                    _ => {}
+                }
+            }
+
+            let Some(close) = close else {
+                break;
+            };
+            if commas.is_empty() {
+                continue;
+            }
+
+            let prefix: String = chars[..open].iter().collect();
+            let suffix: String = chars[close + 1..].iter().collect();
+            let bounds = std::iter::once(open).chain(commas).chain(std::iter::once(close));
+            let bounds: Vec<usize> = bounds.collect();
+
+            return bounds
+                .windows(2)
+                .flat_map(|window| {
+                    let option: String = chars[window[0] + 1..window[1]].iter().collect();
+                    Self::expand_braces(&format!("{}{}{}", prefix, option, suffix))
+                })
+                .collect();
+        }
+
+        vec![pattern.to_string()]
+    }
+
+    /// Match text against a brace-free pattern, backtracking to the last `*`
+    fn matches_chars(text: &[char], pattern: &[char]) -> bool {
         let mut text_idx = 0;
         let mut pattern_idx = 0;
-        let text_bytes = text.as_bytes();
-        let pattern_bytes = pattern.as_bytes();
 
         let mut star_idx = None;
-        let mut star_match = None;
-
-        while text_idx < text_bytes.len() {
-            if pattern_idx < pattern_bytes.len() {
-                match pattern_bytes[pattern_idx] {
-                    b'*' => {
-                        star_idx = Some(pattern_idx);
-                        star_match = Some(text_idx);
-                        pattern_idx += 1;
-                    }
-                    b'?' => {
-                        text_idx += 1;
-                        pattern_idx += 1;
-                    }
-                    c if c == text_bytes[text_idx] => {
-                        text_idx += 1;
-                        pattern_idx += 1;
-                    }
-                    _ => {
-                        if let (Some(s_idx), Some(s_match)) = (star_idx, star_match) {
-                            pattern_idx = s_idx + 1;
-                            star_match = Some(s_match + 1);
-                            text_idx = s_match + 1;
-                        } else {
-                            return false;
-                        }
-                    }
+        let mut star_match = 0;
+
+        while text_idx < text.len() {
+            let step = match pattern.get(pattern_idx) {
+                Some('*') => {
+                    star_idx = Some(pattern_idx);
+                    star_match = text_idx;
+                    pattern_idx += 1;
+                    continue;
                 }
-            } else if let (Some(s_idx), Some(s_match)) = (star_idx, star_match) {
-                pattern_idx = s_idx + 1;
-                star_match = Some(s_match + 1);
-                text_idx = s_match + 1;
-            } else {
-                return false;
+                Some('?') => Some(1),
+                // An escaped character matches itself (a trailing backslash is literal)
+                Some('\\') if pattern_idx + 1 < pattern.len() => (pattern[pattern_idx + 1] == text[text_idx]).then_some(2),
+                Some('[') => match Self::match_class(&pattern[pattern_idx..], text[text_idx]) {
+                    Some((true, len)) => Some(len),
+                    Some((false, _)) => None,
+                    // No closing bracket: '[' is a literal
+                    None => (text[text_idx] == '[').then_some(1),
+                },
+                Some(&c) if c == text[text_idx] => Some(1),
+                _ => None,
+            };
+
+            match (step, star_idx) {
+                (Some(len), _) => {
+                    text_idx += 1;
+                    pattern_idx += len;
+                }
+                (None, Some(s_idx)) => {
+                    pattern_idx = s_idx + 1;
+                    star_match += 1;
+                    text_idx = star_match;
+                }
+                (None, None) => return false,
             }
         }
 
         // Check remaining pattern
-        while pattern_idx < pattern_bytes.len() && pattern_bytes[pattern_idx] == b'*' {
+        while pattern.get(pattern_idx) == Some(&'*') {
             pattern_idx += 1;
         }
 
-        pattern_idx == pattern_bytes.len()
+        pattern_idx == pattern.len()
+    }
+
+    /// Match one character against a `[...]` class at the start of the pattern
+    ///
+    /// Returns whether it matched and the length of the class, or `None` if the
+    /// class is never closed.
+    fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+        let mut idx = 1;
+        let negated = matches!(pattern.get(idx), Some('!' | '^'));
+        if negated {
+            idx += 1;
+        }
+
+        let mut matched = false;
+        let mut first = true;
+        loop {
+            let &start = pattern.get(idx)?;
+            // A ']' right after the opening bracket is a literal member
+            if start == ']' && !first {
+                return Some((matched != negated, idx + 1));
+            }
+            first = false;
+
+            if pattern.get(idx + 1) == Some(&'-')
+                && let Some(&end) = pattern.get(idx + 2)
+                && end != ']'
+            {
+                matched |= (start..=end).contains(&c);
+                idx += 3;
+            } else {
+                matched |= start == c;
+                idx += 1;
+            }
+        }
+    }
+}
+
+/// Whether the character at `index` follows an odd number of backslashes
+fn is_escaped(chars: &[char], index: usize) -> bool {
+    chars[..index].iter().rev().take_while(|&&c| c == '\\').count() % 2 == 1
+}
+
+/// Whether an argument contains glob syntax rather than naming a path
+pub fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?', '[', '{'])
+}
+
+/// Expand a glob pattern into the existing paths it matches, in sorted order
+///
+/// Segments are matched like `GlobMatcher::matches_path`, relative to the
+/// pattern's leading literal directories. Hidden entries only match segments
+/// that start with `.`, and `**` doesn't descend into symlinked directories.
+/// A match inside another match is dropped, so `tests/**` yields `tests`.
+pub fn expand(pattern: &str) -> Vec<PathBuf> {
+    let mut matches = Vec::new();
+    for alternative in GlobMatcher::expand_braces(pattern) {
+        let separators = |c: char| c == '/' || (cfg!(windows) && c == '\\');
+        let segments: Vec<&str> = alternative.split(separators).filter(|part| !part.is_empty()).collect();
+        let base = if alternative.starts_with(separators) { PathBuf::from("/") } else { PathBuf::new() };
+        expand_segments(&base, &segments, &mut matches);
+    }
+
+    matches.sort();
+    matches.dedup();
+    let mut outermost: Vec<PathBuf> = Vec::new();
+    for path in matches {
+        if !outermost.iter().any(|kept| path.starts_with(kept)) {
+            outermost.push(path);
+        }
+    }
+    outermost
+}
+
+fn expand_segments(dir: &Path, segments: &[&str], matches: &mut Vec<PathBuf>) {
+    let Some((&segment, rest)) = segments.split_first() else {
+        if !dir.as_os_str().is_empty() {
+            matches.push(dir.to_path_buf());
+        }
+        return;
+    };
+
+    if segment != "**" && !is_pattern(segment) {
+        let path = dir.join(segment);
+        if path.exists() {
+            expand_segments(&path, rest, matches);
+        }
+        return;
+    }
+
+    // An empty directory stands for the current one
+    let read_path = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(entries) = fs::read_dir(read_path) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    if segment == "**" {
+        expand_segments(dir, rest, matches);
+    }
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') && !segment.starts_with('.') {
+            continue;
+        }
+
+        let path = dir.join(&*name);
+        if segment == "**" {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                expand_segments(&path, segments, matches);
+            }
+        } else if GlobMatcher::matches(&name, segment) && (rest.is_empty() || path.is_dir()) {
+            expand_segments(&path, rest, matches);
+        }
     }
 }
 
@@ -81,4 +295,82 @@ mod tests {
         assert!(GlobMatcher::matches("test_file", "test_*"));
         assert!(GlobMatcher::matches("anything", "*"));
     }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(GlobMatcher::matches("main.o", "*.[oa]"));
+        assert!(GlobMatcher::matches("lib.a", "*.[oa]"));
+        assert!(!GlobMatcher::matches("lib.so", "*.[oa]"));
+        assert!(GlobMatcher::matches("file3.txt", "file[0-9].txt"));
+        assert!(!GlobMatcher::matches("fileX.txt", "file[0-9].txt"));
+        assert!(GlobMatcher::matches("fileX.txt", "file[!0-9].txt"));
+        assert!(!GlobMatcher::matches("file3.txt", "file[!0-9].txt"));
+        assert!(GlobMatcher::matches("a]", "a[]]"));
+        assert!(GlobMatcher::matches("a-", "a[x-]"));
+        assert!(GlobMatcher::matches("[abc", "[abc"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(GlobMatcher::matches("*.txt", "\\*.txt"));
+        assert!(!GlobMatcher::matches("notes.txt", "\\*.txt"));
+        assert!(GlobMatcher::matches("what?", "what\\?"));
+        assert!(GlobMatcher::matches("a b", "a\\ b"));
+        assert!(GlobMatcher::matches("{a,b}", "\\{a,b}"));
+        assert!(!GlobMatcher::matches("a", "\\{a,b}"));
+        assert!(GlobMatcher::matches("x\\", "x\\"));
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        assert!(GlobMatcher::matches("debug.log", "*.{log,tmp}"));
+        assert!(GlobMatcher::matches("scratch.tmp", "*.{log,tmp}"));
+        assert!(!GlobMatcher::matches("main.rs", "*.{log,tmp}"));
+        assert!(GlobMatcher::matches("Cargo.toml", "*.{rs,{to,ya}ml}"));
+        assert!(GlobMatcher::matches("config.yaml", "*.{rs,{to,ya}ml}"));
+        assert!(GlobMatcher::matches("{x}", "{x}"));
+        assert!(GlobMatcher::matches("a{b", "a{b"));
+    }
+
+    #[test]
+    fn test_path_matching() {
+        assert!(GlobMatcher::matches_path("src/generated/api.rs", "src/generated/*"));
+        assert!(GlobMatcher::matches_path("src/generated", "src/generated/"));
+        assert!(!GlobMatcher::matches_path("src/generated/v1/api.rs", "src/generated/*.rs"));
+        assert!(!GlobMatcher::matches_path("lib/src/generated/api.rs", "src/generated/*"));
+        assert!(GlobMatcher::matches_path("tests/fixtures", "**/fixtures/**"));
+        // Unlike in git, where `fixtures/**` leaves the directory itself alone
+        assert!(!GlobMatcher::matches_segments(&["tests", "fixtures"], &["**", "fixtures", "**"], true));
+        assert!(GlobMatcher::matches_path("a/b/fixtures/data.json", "**/fixtures/**"));
+        assert!(GlobMatcher::matches_path("docs/a/b/c.md", "docs/**/*.md"));
+        assert!(GlobMatcher::matches_path("docs/c.md", "docs/**/*.md"));
+        assert!(GlobMatcher::matches_path("web/dist/app.js", "{web,api}/dist/*"));
+        assert!(!GlobMatcher::matches_path("src/main.rs", "**/fixtures/**"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = PathBuf::from("test_glob_expand");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join("tests/.cache")).unwrap();
+        for file in ["src/lib.rs", "src/nested/mod.rs", "src/notes.md", "tests/it.rs", "tests/.cache/x.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let expand = |pattern: &str| expand(&format!("test_glob_expand/{}", pattern));
+        assert_eq!(expand("src/**/*.rs"), vec![dir.join("src/lib.rs"), dir.join("src/nested/mod.rs")]);
+        assert_eq!(expand("src/*.{rs,md}"), vec![dir.join("src/lib.rs"), dir.join("src/notes.md")]);
+        // Everything under tests/ is covered by tests itself
+        assert_eq!(expand("tests/**"), vec![dir.join("tests")]);
+        assert_eq!(expand("*/it.rs"), vec![dir.join("tests/it.rs")]);
+        assert_eq!(expand("tests/.*/*.rs"), vec![dir.join("tests/.cache/x.rs")]);
+        assert!(expand("**/*.py").is_empty());
+        assert!(is_pattern("src/*.rs"));
+        assert!(!is_pattern("src/main.rs"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
\ No newline at end of file
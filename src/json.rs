@@ -0,0 +1,234 @@
+use crate::serialize::Value;
+
+/// Parse a JSON document into a `Value`
+///
+/// Non-negative integers become `Value::Int` and every other number a
+/// `Value::Float`. Duplicate keys are kept, and `Value::get` finds the first.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(found) => Err(parser.error(format!("unexpected '{}' after value", found))),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(offset, c)| self.chars.get(self.pos + offset) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.bump();
+                Ok(())
+            }
+            Some(found) => Err(self.error(format!("expected '{}', found '{}'", c, found))),
+            None => Err(self.error(format!("expected '{}', found end of file", c))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+            self.bump();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            _ => {
+                for (word, value) in [("true", Value::Bool(true)), ("false", Value::Bool(false)), ("null", Value::Null)] {
+                    if self.starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                let found = self.peek().map_or("end of file".to_string(), |c| format!("'{}'", c));
+                Err(self.error(format!("expected a value, found {}", found)))
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(entries)),
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.escape()?),
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.bump() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('u') => {
+                let unit = self.code_unit()?;
+                // Characters outside the BMP are written as a surrogate pair
+                let code = if (0xD800..0xDC00).contains(&unit) && self.starts_with("\\u") {
+                    self.pos += 2;
+                    let low = self.code_unit()?;
+                    0x10000 + ((unit - 0xD800) << 10) + low.wrapping_sub(0xDC00)
+                } else {
+                    unit
+                };
+                char::from_u32(code).ok_or_else(|| self.error(format!("invalid unicode escape: \\u{:04x}", unit)))?
+            }
+            Some(other) => return Err(self.error(format!("invalid escape: \\{}", other))),
+            None => return Err(self.error("unterminated string")),
+        };
+        Ok(c)
+    }
+
+    /// The four hex digits of a `\u` escape
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+        u32::from_str_radix(&hex, 16).map_err(|_| self.error(format!("invalid unicode escape: \\u{}", hex)))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || "+-.eE".contains(c)) {
+            self.bump();
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        if let Ok(number) = raw.parse::<u64>() {
+            return Ok(Value::Int(number));
+        }
+        raw.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| self.error(format!("invalid number: {}", raw)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_document() {
+        let text = r#"{
+  "name": "demo",
+  "cells": [{"source": ["a\n", "b"], "count": 3}, null],
+  "ok": true,
+  "ratio": -0.5,
+  "empty": {}
+}"#;
+        let expected = Value::object([
+            ("name", Value::from("demo")),
+            (
+                "cells",
+                Value::Array(vec![
+                    Value::object([
+                        ("source", Value::Array(vec!["a\n".into(), "b".into()])),
+                        ("count", Value::Int(3)),
+                    ]),
+                    Value::Null,
+                ]),
+            ),
+            ("ok", Value::Bool(true)),
+            ("ratio", Value::Float(-0.5)),
+            ("empty", Value::Object(Vec::new())),
+        ]);
+        assert_eq!(parse(text).unwrap(), expected);
+        assert_eq!(parse(r#""tab\t \u00e9 \ud83d\ude00 \/""#).unwrap(), Value::from("tab\t é 😀 /"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("{\"a\": 1,\n}").unwrap_err(), "line 2: expected '\"', found '}'");
+        assert_eq!(parse("[1 2]").unwrap_err(), "line 1: expected ',' or ']' in array");
+        assert_eq!(parse("\"open").unwrap_err(), "line 1: unterminated string");
+        assert_eq!(parse("{} x").unwrap_err(), "line 1: unexpected 'x' after value");
+        assert!(parse("").is_err());
+        assert!(parse("nope").is_err());
+    }
+}
@@ -1,11 +1,49 @@
+pub mod archive;
+pub mod cache;
+pub mod chunk;
 pub mod clipboard;
+pub mod command;
+pub mod compression;
 pub mod config;
+pub mod config_file;
+#[cfg(unix)]
+pub mod daemon;
+pub mod encoding;
+pub mod error;
+pub mod fetch;
 pub mod file_processor;
+pub mod filter;
 pub mod format;
+pub mod fuzzy;
+pub mod git;
 pub mod gitignore;
 pub mod glob;
+pub mod json;
+pub mod log;
+pub mod output;
+pub mod preset;
+pub mod progress;
+pub mod redact;
+pub mod regex;
+pub mod serialize;
+pub mod skip;
+pub mod spool;
 pub mod stats;
+pub mod template;
+pub mod toml;
+pub mod tokens;
+pub mod transform;
+pub mod tree;
+#[cfg(feature = "tui")]
+pub mod tui;
 pub mod walker;
 
 pub use config::Config;
-pub use walker::{WalkOptions, WalkResult, walk_and_collect};
+pub use config_file::ConfigFile;
+pub use error::{Error, Result};
+pub use filter::{Filter, FilterDecision, FilterPipeline, FnFilter};
+pub use skip::{SkipReason, SkippedEntry, UnreadableCause};
+pub use walker::{
+    Explanation, FileEntry, FileKind, IncludedFile, Limit, PathDisplay, SortOrder, WalkIter, WalkOptions, WalkResult,
+    explain, walk_and_collect, walk_iter,
+};
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::error::{Error, Result};
+use crate::progress::{ProgressCallback, ProgressEvent};
+
+/// How much `--log-level` writes, each level including the ones before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// The start and end of a walk, and any limit that stopped it
+    Info,
+    /// Why each file was included or skipped, with sizes and timing
+    Debug,
+    /// Each file as it is started
+    Trace,
+}
+
+impl LogLevel {
+    /// Parse a level name, as given to `--log-level`
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(Error::Config(format!(
+                "Unknown log level: {} (expected info, debug or trace)",
+                name
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+/// Writes what a walk decides as logfmt lines, like
+/// `t=0.012s level=debug event=skip path=notes.txt category=gitignored reason="gitignored by .gitignore:3 ('*.txt')"`
+///
+/// Walk events arrive through `callback`, installed as `WalkOptions::progress`.
+pub struct Logger {
+    level: LogLevel,
+    start: Instant,
+    // When each file still being processed was started, to time it
+    started: Mutex<HashMap<PathBuf, Instant>>,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    /// A logger writing lines up to `level` to `output`
+    pub fn new(level: LogLevel, output: Box<dyn Write + Send>) -> Self {
+        Self {
+            level,
+            start: Instant::now(),
+            started: Mutex::new(HashMap::new()),
+            output: Mutex::new(output),
+        }
+    }
+
+    /// A logger writing to stderr
+    pub fn stderr(level: LogLevel) -> Self {
+        Self::new(level, Box::new(io::stderr()))
+    }
+
+    /// Whether lines at `level` are written
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level <= self.level
+    }
+
+    /// Write an event with its fields, if `level` is enabled
+    pub fn log(&self, level: LogLevel, event: &str, fields: &[(&str, &dyn Display)]) {
+        if !self.enabled(level) {
+            return;
+        }
+        let mut line = format!(
+            "t={:.3}s level={} event={}",
+            self.start.elapsed().as_secs_f64(),
+            level.name(),
+            event
+        );
+        for (key, value) in fields {
+            line.push_str(&format!(" {}={}", key, quote(&value.to_string())));
+        }
+        line.push('\n');
+        // Logging never fails the run
+        let _ = self.output.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    /// Log a walk event
+    pub fn record(&self, event: &ProgressEvent<'_>) {
+        match *event {
+            ProgressEvent::FileStarted { path } => {
+                if self.enabled(LogLevel::Debug) {
+                    self.started.lock().unwrap().insert(path.to_path_buf(), Instant::now());
+                }
+                self.log(LogLevel::Trace, "start", &[("path", &path.display())]);
+            }
+            ProgressEvent::FileIncluded { path, size, tokens } => {
+                let took = self.took(path);
+                self.log(
+                    LogLevel::Debug,
+                    "include",
+                    &[("path", &path.display()), ("size", &size), ("tokens", &tokens), ("took", &took)],
+                );
+            }
+            ProgressEvent::FileSkipped { path, reason } => {
+                let took = self.took(path);
+                self.log(
+                    LogLevel::Debug,
+                    "skip",
+                    &[
+                        ("path", &path.display()),
+                        ("category", &reason.category()),
+                        ("reason", reason),
+                        ("took", &took),
+                    ],
+                );
+            }
+            ProgressEvent::Truncated { limit } => self.log(LogLevel::Info, "truncate", &[("limit", &limit)]),
+        }
+    }
+
+    /// A callback logging each event, for `WalkOptions::progress`
+    pub fn callback(self: &Arc<Self>) -> ProgressCallback {
+        let logger = Arc::clone(self);
+        Arc::new(move |event| logger.record(&event))
+    }
+
+    /// How long ago a file was started, like `0.42ms`, or `-` for paths that weren't
+    /// (directories, and files skipped before being read)
+    fn took(&self, path: &Path) -> String {
+        match self.started.lock().unwrap().remove(path) {
+            Some(started) => format!("{:.2}ms", started.elapsed().as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// A logfmt value, quoted if it is empty or has spaces, quotes or `=`
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skip::SkipReason;
+    use crate::walker::Limit;
+
+    /// A buffer the test keeps a handle to while the logger writes into it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Logged lines without their timestamps
+    fn logged(buffer: &Shared) -> Vec<String> {
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        text.lines()
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_log_levels() {
+        assert_eq!(LogLevel::parse("DEBUG").unwrap(), LogLevel::Debug);
+        assert!(LogLevel::parse("verbose").is_err());
+
+        let buffer = Shared::default();
+        let logger = Logger::new(LogLevel::Debug, Box::new(buffer.clone()));
+        let path = Path::new("src/main.rs");
+        logger.record(&ProgressEvent::FileStarted { path });
+        logger.record(&ProgressEvent::FileIncluded { path, size: 120, tokens: 30 });
+        let reason = SkipReason::Excluded { pattern: "*.log".to_string() };
+        logger.record(&ProgressEvent::FileSkipped { path: Path::new("debug.log"), reason: &reason });
+        logger.record(&ProgressEvent::Truncated { limit: Limit::Files(1) });
+
+        let lines = logged(&buffer);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("level=debug event=include path=src/main.rs size=120 tokens=30 took="));
+        assert!(lines[0].ends_with("ms"));
+        assert_eq!(
+            lines[1],
+            "level=debug event=skip path=debug.log category=excluded reason=\"excluded by pattern '*.log'\" took=-"
+        );
+        assert_eq!(lines[2], "level=info event=truncate limit=\"1 file\"");
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("src/main.rs"), "src/main.rs");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("my file.rs"), "\"my file.rs\"");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+}
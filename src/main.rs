@@ -1,12 +1,25 @@
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use rcat::{
-    Config, WalkOptions, WalkResult, config::parse_size, format::ByteFormatter, walk_and_collect,
+    Config, Explanation, PathDisplay, SortOrder, WalkOptions, WalkResult, chunk::{ChunkLimit, Chunker}, clipboard::{self, Backend}, config::{parse_since, parse_size, parse_timeout}, config_file::ConfigFile, encoding::Decoder, fetch, file_processor::{LineRange, ProcessorRegistry, Strategy},
+    format::ByteFormatter, fuzzy::FuzzyQuery, gitignore, glob, log::{LogLevel, Logger}, output::{self, OutputFormat}, preset::Preset, progress::{ProgressCallback, ProgressEvent},
+    regex::Regex, spool::Spool, stats::{Phase, StatsCollector},
+    template::SectionTemplate, transform::Transforms,
+    tokens::{Model, TokenEstimator, Tokenizer, model_by_name, parse_token_count, tokenizer_by_name},
+    explain, walk_and_collect,
 };
 
-mod clipboard;
+#[cfg(feature = "tui")]
+use rcat::tui::{Candidate, Picker};
+
+mod doctor;
 
 /// Application metadata
 struct AppInfo;
@@ -17,14 +30,103 @@ impl AppInfo {
     const DESCRIPTION: &'static str = "Recursively concatenate files and copy to clipboard or output to stdout";
 }
 
+/// What the invocation should do
+#[derive(PartialEq)]
+enum Command {
+    /// Walk the paths and copy or print the result
+    Collect,
+    /// Keep a warm bundle of the paths and serve it over IPC
+    Daemon,
+    /// Fetch the latest bundle from a running daemon
+    FastCopy,
+    /// Diagnose the environment rcat runs in
+    Doctor,
+}
+
+/// Where a JSON copy of the run statistics goes
+enum StatsJson {
+    /// Only the human-readable summary on stderr
+    Off,
+    /// JSON on stderr in place of the human-readable summary
+    Stderr,
+    /// JSON written to this file, alongside the usual summary
+    File(PathBuf),
+}
+
 /// Command-line arguments
 struct Args {
+    command: Command,
     paths: Vec<PathBuf>,
-    include_all: bool,
+    line_ranges: Vec<(PathBuf, LineRange)>,
+    stdin: Option<String>,
+    urls: Vec<String>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    template: SectionTemplate,
+    line_numbers: bool,
+    transforms: Transforms,
+    redact: bool,
+    commands: Vec<String>,
+    include_hidden: bool,
+    include_binary: bool,
+    include_ignored: bool,
+    include_generated: bool,
+    git_tracked: bool,
+    diff_ref: Option<String>,
+    modified_since: Option<SystemTime>,
+    since_git: bool,
+    git_meta: bool,
     max_size: usize,
+    max_tokens: Option<usize>,
+    max_files: Option<usize>,
+    tokenizer: Arc<dyn Tokenizer>,
+    model: Option<Model>,
     max_file_size: usize,
+    min_file_size: usize,
+    skip_empty: bool,
+    fit: bool,
+    fail_if_truncated: bool,
+    cache: bool,
+    changed_only: bool,
+    list_unchanged: bool,
     exclude_patterns: Vec<String>,
+    default_excludes: bool,
+    dot_ignore: bool,
+    processors: ProcessorRegistry,
+    skip_sensitive: bool,
+    exclude_regexes: Vec<Regex>,
+    path_regexes: Vec<Regex>,
+    fuzzy_filters: Vec<FuzzyQuery>,
+    extensions: Vec<String>,
+    excluded_extensions: Vec<String>,
+    include_patterns: Vec<String>,
+    max_depth: Option<usize>,
+    max_files_per_dir: Option<usize>,
+    priority_patterns: Vec<String>,
+    dedup: bool,
+    sort: SortOrder,
+    reverse: bool,
+    follow_symlinks: bool,
+    one_file_system: bool,
+    path_display: PathDisplay,
+    tree: bool,
+    toc: bool,
+    low_memory: bool,
+    also: Vec<PathBuf>,
+    format: OutputFormat,
+    split: Option<ChunkLimit>,
+    split_dir: Option<PathBuf>,
     stdout: bool,
+    list: bool,
+    why: Option<PathBuf>,
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    interactive: bool,
+    stats_json: StatsJson,
+    verbose: bool,
+    quiet: bool,
+    log_level: Option<LogLevel>,
+    osc52: bool,
+    clipboard_timeout: Duration,
 }
 
 impl Args {
@@ -36,36 +138,217 @@ impl Args {
             return Err(ArgsError::InvalidCount);
         }
 
-        let mut include_all = false;
+        let mut include_hidden = false;
+        let mut include_binary = false;
+        let mut include_ignored = false;
+        let mut include_generated = false;
+        let mut git_tracked = false;
+        let mut diff_ref = None;
+        let mut modified_since = None;
+        let mut since_git = false;
         let mut paths = Vec::new();
+        let mut line_ranges = Vec::new();
+        let mut read_stdin = false;
+        let mut urls = Vec::new();
+        let mut prefix = None;
+        let mut suffix = None;
+        let mut header_template = None;
+        let mut line_numbers = false;
+        let mut transforms = Transforms::default();
+        let mut redact = None;
+        let mut footer_template = None;
+        let mut header_meta = false;
+        let mut git_meta = false;
+        let mut commands = Vec::new();
         let mut max_size = Config::DEFAULT_MAX_SIZE;
+        let mut max_tokens = None;
+        let mut max_files = None;
+        let mut tokenizer: Arc<dyn Tokenizer> = Arc::new(TokenEstimator);
+        let mut model = None;
         let mut max_file_size = Config::DEFAULT_MAX_FILE_SIZE;
+        let mut min_file_size = 0;
+        let mut skip_empty = false;
+        let mut fit = false;
+        let mut fail_if_truncated = false;
+        let mut cache = false;
+        let mut changed_only = false;
+        let mut list_unchanged = false;
         let mut exclude_patterns = Vec::new();
+        let mut default_excludes = true;
+        let mut dot_ignore = true;
+        let mut summarize_lockfiles = true;
+        let mut skip_sensitive = true;
+        let mut exclude_regexes = Vec::new();
+        let mut path_regexes = Vec::new();
+        let mut fuzzy_filters = Vec::new();
+        let mut presets = Vec::new();
+        let mut extensions = Vec::new();
+        let mut excluded_extensions = Vec::new();
+        let mut max_depth = None;
+        let mut max_files_per_dir = None;
+        let mut priority_patterns = Vec::new();
+        let mut dedup = true;
+        let mut sort = SortOrder::default();
+        let mut reverse = false;
+        let mut follow_symlinks = true;
+        let mut one_file_system = false;
+        let mut path_display = PathDisplay::AsGiven;
+        let mut tree = false;
+        let mut toc = false;
+        let mut low_memory = false;
+        let mut also = Vec::new();
+        let mut format = OutputFormat::default();
+        let mut split = None;
+        let mut split_dir = None;
         let mut stdout = false;
-        let mut skip_next = false;
+        let mut quiet = false;
+        let mut log_level = None;
+        let mut osc52 = false;
+        let mut clipboard_timeout = Config::DEFAULT_CLIPBOARD_TIMEOUT;
+        let mut list = false;
+        let mut why = None;
+        let mut interactive = false;
+        let mut stats_json = StatsJson::Off;
+        let mut verbose = false;
+        let mut fast = false;
 
-        let mut iter = args.iter().skip(1).peekable();
-        while let Some(arg) = iter.next() {
-            if skip_next {
-                skip_next = false;
-                continue;
+        let (mut options, operands) = split_args(&args[1..]);
+
+        // Subcommands are only recognized as the first argument
+        let mut command = Command::Collect;
+        match options.first().map_or("", String::as_str) {
+            "daemon" => {
+                command = Command::Daemon;
+                options.remove(0);
+            }
+            "copy" => {
+                options.remove(0);
             }
+            "doctor" => {
+                command = Command::Doctor;
+                options.remove(0);
+            }
+            _ => {}
+        }
+
+        let config = if matches!(command, Command::Collect | Command::Daemon) {
+            ConfigFile::discover()?.unwrap_or_default()
+        } else {
+            ConfigFile::default()
+        };
 
+        // A profile's options go first, so the ones given on the command line win
+        let profile = profile_name(&options);
+        let mut profile_len = 0;
+        if let Some(name) = &profile {
+            let profile = config.profile(name).ok_or_else(|| {
+                let defined: Vec<&str> = config.profiles.iter().map(|(name, _)| name.as_str()).collect();
+                ArgsError::InvalidValue(if defined.is_empty() {
+                    format!("Unknown profile: {} (none are defined in {})", name, ConfigFile::FILE_NAME)
+                } else {
+                    format!("Unknown profile: {} (expected {})", name, defined.join(", "))
+                })
+            })?;
+            profile_len = profile.args.len();
+            options.splice(0..0, profile.args.iter().cloned());
+        }
+        let mut iter = options.iter().peekable();
+
+        while let Some(arg) = iter.next() {
             match arg.as_str() {
                 "--help" | "-h" => return Err(ArgsError::HelpRequested),
-                "--all" | "-a" => include_all = true,
+                "--all" | "-a" => {
+                    include_hidden = true;
+                    include_binary = true;
+                    include_ignored = true;
+                    include_generated = true;
+                }
+                "--include-generated" => include_generated = true,
+                "--hidden" => include_hidden = true,
+                "--binary" => include_binary = true,
+                "--git" => git_tracked = true,
+                "--since-git" => since_git = true,
+                "--fit" => fit = true,
+                "--fail-if-truncated" => fail_if_truncated = true,
+                "--cache" => cache = true,
+                "--changed-only" => changed_only = true,
+                "--list-unchanged" => {
+                    changed_only = true;
+                    list_unchanged = true;
+                }
+                "--skip-empty" => skip_empty = true,
+                "--no-dedup" => dedup = false,
+                "--no-default-excludes" => default_excludes = false,
+                "--no-dotignore" => dot_ignore = false,
+                "--summarize-lockfiles" => summarize_lockfiles = true,
+                "--no-summarize-lockfiles" => summarize_lockfiles = false,
+                "--unsafe" => skip_sensitive = false,
                 "--stdout" | "-o" => stdout = true,
+                "--quiet" | "-q" => quiet = true,
+                "--log-level" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--log-level requires a level".to_string())
+                    })?;
+                    log_level = Some(LogLevel::parse(name)?);
+                }
+                "--osc52" => osc52 = true,
+                "--clipboard-timeout" => {
+                    let value = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--clipboard-timeout requires a duration".to_string())
+                    })?;
+                    clipboard_timeout = parse_timeout(value)?;
+                }
+                "--list" | "--dry-run" => list = true,
+                "--why" => {
+                    let path = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--why requires a path".to_string())
+                    })?;
+                    let path = PathBuf::from(path);
+                    if !path.exists() {
+                        return Err(ArgsError::PathNotFound(path));
+                    }
+                    why = Some(path);
+                }
+                "--interactive" | "-i" => interactive = true,
+                "--fast" => fast = true,
                 "--max-size" | "-m" => {
                     let size_str = iter.next().ok_or_else(|| {
                         ArgsError::InvalidSize("--max-size requires a value".to_string())
                     })?;
-                    max_size = parse_size(size_str).map_err(ArgsError::InvalidSize)?;
+                    max_size = parse_size(size_str)
+                        .map_err(|error| ArgsError::InvalidSize(error.to_string()))?;
+                }
+                "--max-tokens" | "-t" => {
+                    let count_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--max-tokens requires a value".to_string())
+                    })?;
+                    max_tokens = Some(parse_token_count(count_str)?);
+                }
+                "--tokenizer" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--tokenizer requires a name".to_string())
+                    })?;
+                    tokenizer = tokenizer_by_name(name)?;
+                }
+                "--model" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--model requires a name".to_string())
+                    })?;
+                    model = Some(model_by_name(name)?);
                 }
                 "--max-file-size" | "-f" => {
                     let size_str = iter.next().ok_or_else(|| {
                         ArgsError::InvalidSize("--max-file-size requires a value".to_string())
                     })?;
-                    max_file_size = parse_size(size_str).map_err(ArgsError::InvalidSize)?;
+                    max_file_size = parse_size(size_str)
+                        .map_err(|error| ArgsError::InvalidSize(error.to_string()))?;
+                }
+                "--min-file-size" => {
+                    let size_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidSize("--min-file-size requires a value".to_string())
+                    })?;
+                    min_file_size = parse_size(size_str)
+                        .map_err(|error| ArgsError::InvalidSize(error.to_string()))?;
                 }
                 "--exclude" | "-e" => {
                     let pattern = iter.next().ok_or_else(|| {
@@ -73,66 +356,760 @@ impl Args {
                     })?;
                     exclude_patterns.push(pattern.to_string());
                 }
-                path_str if path_str.starts_with('-') => {
-                    return Err(ArgsError::UnknownOption(path_str.to_string()));
+                "--exclude-from" => {
+                    let file = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--exclude-from requires a file".to_string())
+                    })?;
+                    exclude_patterns.extend(gitignore::read_patterns(Path::new(file))?);
+                }
+                "--priority" => {
+                    let pattern = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--priority requires a pattern".to_string())
+                    })?;
+                    priority_patterns.push(pattern.to_string());
+                }
+                "--sort" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--sort requires an order".to_string())
+                    })?;
+                    sort = SortOrder::parse(name)?;
+                }
+                "--reverse" => reverse = true,
+                "--follow-symlinks" => follow_symlinks = true,
+                "--no-follow-symlinks" => follow_symlinks = false,
+                "--one-file-system" => one_file_system = true,
+                "--canonical-paths" => path_display = PathDisplay::Canonical,
+                "--tree" => tree = true,
+                "--toc" => toc = true,
+                "--low-memory" => low_memory = true,
+                "--format" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--format requires a name".to_string())
+                    })?;
+                    format = OutputFormat::parse(name)?;
+                }
+                "--split" => {
+                    let limit = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--split requires a size or token count".to_string())
+                    })?;
+                    split = Some(ChunkLimit::parse(limit)?);
                 }
-                path_str => {
+                "--split-dir" => {
+                    let dir = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--split-dir requires a directory".to_string())
+                    })?;
+                    split_dir = Some(PathBuf::from(dir));
+                }
+                "--prefix" => {
+                    let value = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--prefix requires a file or text".to_string())
+                    })?;
+                    prefix = Some(value.clone());
+                }
+                "--suffix" => {
+                    let value = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--suffix requires a file or text".to_string())
+                    })?;
+                    suffix = Some(value.clone());
+                }
+                "--line-numbers" | "-n" => line_numbers = true,
+                "--strip-comments" => transforms.strip_comments = true,
+                "--strip-blank" => transforms.strip_blank = true,
+                "--minify" => transforms.minify = true,
+                "--dedent" => transforms.dedent = true,
+                "--redact" => redact = Some(true),
+                "--no-redact" => redact = Some(false),
+                "--header-template" => {
+                    let template = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--header-template requires a template".to_string())
+                    })?;
+                    header_template = Some(template.clone());
+                }
+                "--footer-template" => {
+                    let template = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--footer-template requires a template".to_string())
+                    })?;
+                    footer_template = Some(template.clone());
+                }
+                "--header-meta" => header_meta = true,
+                "--git-meta" => git_meta = true,
+                "--command" => {
+                    let command = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--command requires a command".to_string())
+                    })?;
+                    commands.push(command.clone());
+                }
+                "--also" => {
+                    let path_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--also requires a path".to_string())
+                    })?;
                     let path = PathBuf::from(path_str);
                     if !path.exists() {
                         return Err(ArgsError::PathNotFound(path));
                     }
-                    paths.push(path);
+                    also.push(path);
+                }
+                "--exclude-regex" => {
+                    let pattern = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--exclude-regex requires a pattern".to_string())
+                    })?;
+                    exclude_regexes.push(Regex::new(pattern)?);
                 }
+                "--diff" => {
+                    let reference = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--diff requires a git ref".to_string())
+                    })?;
+                    diff_ref = Some(reference.clone());
+                }
+                "--since" => {
+                    let time = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--since requires a time".to_string())
+                    })?;
+                    modified_since = Some(parse_since(time, SystemTime::now())?);
+                }
+                "--ext" => {
+                    let list = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--ext requires a list of extensions".to_string())
+                    })?;
+                    extensions.extend(parse_extensions(list));
+                }
+                "--not-ext" => {
+                    let list = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--not-ext requires a list of extensions".to_string())
+                    })?;
+                    excluded_extensions.extend(parse_extensions(list));
+                }
+                "--preset" => {
+                    let name = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--preset requires a name".to_string())
+                    })?;
+                    presets.push(name.clone());
+                }
+                "--path-regex" | "-r" => {
+                    let pattern = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--path-regex requires a pattern".to_string())
+                    })?;
+                    path_regexes.push(Regex::new(pattern)?);
+                }
+                "--profile" => {
+                    // Applied before the other options, by `profile_name`
+                    iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--profile requires a name".to_string())
+                    })?;
+                }
+                "--filter" => {
+                    let query = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--filter requires a query".to_string())
+                    })?;
+                    fuzzy_filters.push(FuzzyQuery::new(query)?);
+                }
+                "--max-depth" | "-d" => {
+                    let depth_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--max-depth requires a number".to_string())
+                    })?;
+                    let depth = depth_str.parse::<usize>().map_err(|_| {
+                        ArgsError::InvalidValue(format!("Invalid depth: {}", depth_str))
+                    })?;
+                    max_depth = Some(depth);
+                }
+                "--max-files" => {
+                    let count_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--max-files requires a number".to_string())
+                    })?;
+                    let count = count_str.parse::<usize>().map_err(|_| {
+                        ArgsError::InvalidValue(format!("Invalid file count: {}", count_str))
+                    })?;
+                    if count == 0 {
+                        return Err(ArgsError::InvalidValue(
+                            "--max-files must be greater than 0".to_string(),
+                        ));
+                    }
+                    max_files = Some(count);
+                }
+                "--max-files-per-dir" => {
+                    let count_str = iter.next().ok_or_else(|| {
+                        ArgsError::InvalidValue("--max-files-per-dir requires a number".to_string())
+                    })?;
+                    let count = count_str.parse::<usize>().map_err(|_| {
+                        ArgsError::InvalidValue(format!("Invalid file count: {}", count_str))
+                    })?;
+                    if count == 0 {
+                        return Err(ArgsError::InvalidValue(
+                            "--max-files-per-dir must be greater than 0".to_string(),
+                        ));
+                    }
+                    max_files_per_dir = Some(count);
+                }
+                "--stats-json" => stats_json = StatsJson::Stderr,
+                "--verbose" | "-v" => verbose = true,
+                option if option.starts_with("--stats-json=") => {
+                    let file = &option["--stats-json=".len()..];
+                    if file.is_empty() {
+                        return Err(ArgsError::InvalidValue(
+                            "--stats-json= requires a file name".to_string(),
+                        ));
+                    }
+                    stats_json = StatsJson::File(PathBuf::from(file));
+                }
+                "-" => read_stdin = true,
+                url if fetch::is_url(url) => urls.push(url.to_string()),
+                path_str if path_str.starts_with('-') => {
+                    if let Some(name) = &profile
+                        && options.len() - iter.len() <= profile_len
+                    {
+                        return Err(ArgsError::InvalidValue(format!(
+                            "Unknown option '{}' in profile '{}'",
+                            path_str, name
+                        )));
+                    }
+                    return Err(ArgsError::UnknownOption(path_str.to_string()));
+                }
+                path_str => push_path(path_str, &mut paths, &mut line_ranges)?,
+            }
+        }
+        // After `--`, even names starting with '-' are paths
+        for path_str in &operands {
+            push_path(path_str, &mut paths, &mut line_ranges)?;
+        }
+
+        if fast {
+            if command != Command::Collect {
+                return Err(ArgsError::UnknownOption("--fast".to_string()));
+            }
+            // The daemon did the walk, so only options about delivering the bundle apply
+            if let Some(option) = walk_option(&options[profile_len..]) {
+                return Err(ArgsError::InvalidValue(format!(
+                    "{} can't be used with 'copy --fast' (pass it to 'rcat daemon' instead)",
+                    option
+                )));
+            }
+            if !paths.is_empty() || !operands.is_empty() || !urls.is_empty() || read_stdin {
+                return Err(ArgsError::InvalidValue(
+                    "'copy --fast' takes no paths; it copies those the daemon was started with".to_string(),
+                ));
             }
+            command = Command::FastCopy;
         }
 
-        if paths.is_empty() {
+        if read_stdin && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("'-' (stdin) can't be used with this command".to_string()));
+        }
+        if since_git && modified_since.is_none() {
+            return Err(ArgsError::InvalidValue("--since-git requires --since".to_string()));
+        }
+        if interactive {
+            if !cfg!(feature = "tui") {
+                return Err(ArgsError::InvalidValue(
+                    "--interactive requires rcat to be built with the 'tui' feature".to_string(),
+                ));
+            }
+            if command != Command::Collect {
+                return Err(ArgsError::InvalidValue("--interactive can't be used with this command".to_string()));
+            }
+            // The listing run would record every file, leaving nothing changed for the real one
+            if changed_only {
+                return Err(ArgsError::InvalidValue("--interactive can't be combined with --changed-only".to_string()));
+            }
+        }
+        if split_dir.is_some() && split.is_none() {
+            return Err(ArgsError::InvalidValue("--split-dir requires --split".to_string()));
+        }
+        if split.is_some() && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("--split can't be used with this command".to_string()));
+        }
+        // The daemon serves the collected output, never a listing
+        if list && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("--list can't be used with this command".to_string()));
+        }
+        if split.is_some() && !matches!(format, OutputFormat::Plain | OutputFormat::Markdown) {
+            return Err(ArgsError::InvalidValue(
+                "--split only applies to --format plain and markdown".to_string(),
+            ));
+        }
+        // The spool is only read back when delivering the output as it is
+        if low_memory && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("--low-memory can't be used with this command".to_string()));
+        }
+        if low_memory && (split.is_some() || !matches!(format, OutputFormat::Plain | OutputFormat::Markdown)) {
+            return Err(ArgsError::InvalidValue(
+                "--low-memory only applies to --format plain and markdown, without --split".to_string(),
+            ));
+        }
+        if why.is_some() && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("--why can't be used with this command".to_string()));
+        }
+        // Explain a path as part of a walk of the current directory, unless told which paths to walk
+        if why.is_some() && paths.is_empty() {
+            paths.push(PathBuf::from("."));
+        }
+        if !urls.is_empty() && command != Command::Collect {
+            return Err(ArgsError::InvalidValue("URLs can't be used with this command".to_string()));
+        }
+
+        if paths.is_empty() && !read_stdin && urls.is_empty() && matches!(command, Command::Collect | Command::Daemon) {
             return Err(ArgsError::InvalidCount);
         }
 
+        if header_meta && header_template.is_some() {
+            return Err(ArgsError::InvalidValue(
+                "--header-meta can't be combined with --header-template (use {meta} in the template instead)"
+                    .to_string(),
+            ));
+        }
+        let default_header = if header_meta || git_meta {
+            SectionTemplate::META_HEADER
+        } else {
+            SectionTemplate::DEFAULT_HEADER
+        };
+        let template = SectionTemplate::new(
+            header_template.as_deref().unwrap_or(default_header),
+            footer_template.as_deref(),
+        )?;
+        if !template.is_default() && format != OutputFormat::Plain {
+            return Err(ArgsError::InvalidValue(
+                "--header-template, --footer-template, --header-meta and --git-meta only apply to --format plain"
+                    .to_string(),
+            ));
+        }
+
+        let stdin = if read_stdin { Some(read_stdin_text()?) } else { None };
+
+        let mut include_patterns = Vec::new();
+        for name in &presets {
+            let preset = config.preset(name).ok_or_else(|| {
+                ArgsError::InvalidValue(format!(
+                    "Unknown preset: {} (expected {} or one defined in {})",
+                    name,
+                    Preset::BUILTIN.join(", "),
+                    ConfigFile::FILE_NAME
+                ))
+            })?;
+            exclude_patterns.extend(preset.exclude);
+            include_patterns.extend(preset.include);
+        }
+
+        // Files named in the config file are relative to it, on the command line to the current directory
+        let config_dir = config.path.parent().unwrap_or(Path::new(""));
+        let prefix = match prefix {
+            Some(value) => prompt_text(&value, Path::new(""))?,
+            None => config.prefix.as_deref().map(|value| prompt_text(value, config_dir)).transpose()?.flatten(),
+        };
+        let suffix = match suffix {
+            Some(value) => prompt_text(&value, Path::new(""))?,
+            None => config.suffix.as_deref().map(|value| prompt_text(value, config_dir)).transpose()?.flatten(),
+        };
+
+        let redact = redact.unwrap_or(config.redact);
+
+        let mut processors = ProcessorRegistry::default();
+        if !summarize_lockfiles {
+            for (pattern, strategy) in ProcessorRegistry::BUILTIN {
+                if *strategy == Strategy::Lockfile {
+                    processors.register(*pattern, Strategy::Text);
+                }
+            }
+        }
+        for (pattern, strategy) in &config.processors {
+            processors.register(pattern.clone(), *strategy);
+        }
+
         Ok(Args {
+            command,
             paths,
-            include_all,
+            line_ranges,
+            stdin,
+            urls,
+            prefix,
+            suffix,
+            template,
+            line_numbers,
+            transforms,
+            redact,
+            commands,
+            include_hidden,
+            include_binary,
+            include_ignored,
+            include_generated,
+            git_tracked,
+            diff_ref,
+            modified_since,
+            since_git,
+            git_meta,
             max_size,
+            max_tokens,
+            max_files,
+            tokenizer,
+            model,
             max_file_size,
+            min_file_size,
+            skip_empty,
+            fit,
+            fail_if_truncated,
+            cache,
+            changed_only,
+            list_unchanged,
             exclude_patterns,
+            default_excludes,
+            dot_ignore,
+            processors,
+            skip_sensitive,
+            exclude_regexes,
+            path_regexes,
+            fuzzy_filters,
+            extensions,
+            excluded_extensions,
+            include_patterns,
+            max_depth,
+            max_files_per_dir,
+            priority_patterns,
+            dedup,
+            sort,
+            reverse,
+            follow_symlinks,
+            one_file_system,
+            path_display,
+            tree,
+            toc,
+            low_memory,
+            also,
+            format,
+            split,
+            split_dir,
             stdout,
+            list,
+            why,
+            interactive,
+            stats_json,
+            verbose,
+            quiet,
+            log_level,
+            osc52,
+            clipboard_timeout,
         })
     }
 }
 
+/// Add a path argument: an existing path, a `file:start-end` line range, or a glob pattern
+fn push_path(
+    path_str: &str,
+    paths: &mut Vec<PathBuf>,
+    line_ranges: &mut Vec<(PathBuf, LineRange)>,
+) -> Result<(), ArgsError> {
+    let path = PathBuf::from(path_str);
+    if path.exists() {
+        paths.push(path);
+    } else if let Some((file, range)) = parse_line_range(path_str)? {
+        paths.push(file.clone());
+        line_ranges.push((file, range));
+    } else if glob::is_pattern(path_str) {
+        // Quoted patterns, or a shell that doesn't expand them
+        let matches = glob::expand(path_str);
+        if matches.is_empty() {
+            return Err(ArgsError::InvalidValue(format!("No paths match '{}'", path_str)));
+        }
+        paths.extend(matches);
+    } else {
+        return Err(ArgsError::PathNotFound(path));
+    }
+    Ok(())
+}
+
+/// Options followed by a value, which may also be attached as `--name=value` or `-mVALUE`
+const VALUE_OPTIONS: &[&str] = &[
+    "--max-size", "-m", "--max-tokens", "-t", "--tokenizer", "--model", "--clipboard-timeout", "--max-file-size", "-f", "--min-file-size",
+    "--exclude", "-e", "--exclude-from", "--priority", "--sort", "--format", "--split", "--split-dir",
+    "--prefix", "--suffix", "--header-template", "--footer-template", "--command", "--also",
+    "--exclude-regex", "--diff", "--since", "--ext", "--not-ext", "--preset", "--path-regex", "-r",
+    "--filter", "--max-depth", "-d", "--max-files", "--max-files-per-dir", "--profile",
+    "--log-level", "--why",
+];
+
+/// Rewrite arguments POSIX-style, returning the options and the operands after `--`
+///
+/// `--name=value` becomes `--name value` for options taking a value, and
+/// combined short flags like `-ao` become `-a -o`. A short option taking a
+/// value uses the rest of its group, as in `-f500KB`, or else the next
+/// argument. Values are passed through untouched, even `--`.
+fn split_args(args: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut options = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            return (options, iter.cloned().collect());
+        }
+
+        let takes_value = if let Some((name, value)) = arg.split_once('=')
+            && name.starts_with("--")
+            && VALUE_OPTIONS.contains(&name)
+        {
+            options.push(name.to_string());
+            options.push(value.to_string());
+            false
+        } else if arg.len() > 2 && arg.starts_with('-') && !arg.starts_with("--") {
+            let mut takes_value = false;
+            for (index, flag) in arg.char_indices().skip(1) {
+                let option = format!("-{}", flag);
+                takes_value = VALUE_OPTIONS.contains(&option.as_str());
+                options.push(option);
+                if takes_value {
+                    let rest = &arg[index + flag.len_utf8()..];
+                    if !rest.is_empty() {
+                        options.push(rest.to_string());
+                        takes_value = false;
+                    }
+                    break;
+                }
+            }
+            takes_value
+        } else {
+            options.push(arg.clone());
+            VALUE_OPTIONS.contains(&arg.as_str())
+        };
+
+        // The value is taken as is, whatever it looks like
+        if takes_value && let Some(value) = iter.next() {
+            options.push(value.clone());
+        }
+    }
+    (options, Vec::new())
+}
+
+/// Options `copy --fast` accepts, none of which change what's collected
+const FAST_COPY_OPTIONS: &[&str] = &[
+    "--fast", "--stdout", "-o", "--quiet", "-q", "--osc52", "--clipboard-timeout", "--fail-if-truncated",
+];
+
+/// The first option among those from `split_args` that `copy --fast` doesn't accept
+fn walk_option(options: &[String]) -> Option<&str> {
+    let mut index = 0;
+    while index < options.len() {
+        let option = options[index].as_str();
+        if option.starts_with('-') && option != "-" && !FAST_COPY_OPTIONS.contains(&option) {
+            return Some(option);
+        }
+        index += if VALUE_OPTIONS.contains(&option) { 2 } else { 1 };
+    }
+    None
+}
+
+/// The profile named by the last `--profile` among options from `split_args`
+fn profile_name(options: &[String]) -> Option<String> {
+    let mut name = None;
+    let mut index = 0;
+    while index < options.len() {
+        if options[index] == "--profile" {
+            name = options.get(index + 1).cloned();
+        }
+        // Skip over values, which could read `--profile` themselves
+        index += if VALUE_OPTIONS.contains(&options[index].as_str()) { 2 } else { 1 };
+    }
+    name
+}
+
+/// Split a `path:start-end` (or open-ended `path:start-`) argument into the file and its lines
+///
+/// Returns `None` if the argument doesn't have that shape.
+fn parse_line_range(arg: &str) -> Result<Option<(PathBuf, LineRange)>, ArgsError> {
+    let Some((file, range)) = arg.rsplit_once(':') else {
+        return Ok(None);
+    };
+    let Some((start, end)) = range.split_once('-') else {
+        return Ok(None);
+    };
+    let is_number = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    if !is_number(start) || !(end.is_empty() || is_number(end)) {
+        return Ok(None);
+    }
+
+    let path = PathBuf::from(file);
+    if !path.is_file() {
+        return Err(ArgsError::PathNotFound(path));
+    }
+
+    let invalid = || {
+        ArgsError::InvalidValue(format!(
+            "Invalid line range in '{}' (lines start at 1 and the range can't end before it starts)",
+            arg
+        ))
+    };
+    let start: usize = start.parse().map_err(|_| invalid())?;
+    let end: Option<usize> = if end.is_empty() { None } else { Some(end.parse().map_err(|_| invalid())?) };
+    if start == 0 || end.is_some_and(|end| end < start) {
+        return Err(invalid());
+    }
+    Ok(Some((path, LineRange { start, end })))
+}
+
+/// Text for `--prefix` or `--suffix`: the content of the file `value` names, relative to `base`, or else `value` itself
+///
+/// Empty text is `None`, since there is nothing to add.
+fn prompt_text(value: &str, base: &Path) -> Result<Option<String>, ArgsError> {
+    let path = base.join(value);
+    let text = if path.is_file() {
+        std::fs::read_to_string(&path).map_err(|error| {
+            ArgsError::InvalidValue(format!("Failed to read {}: {}", path.display(), error))
+        })?
+    } else {
+        value.to_string()
+    };
+    Ok(Some(text).filter(|text| !text.is_empty()))
+}
+
+/// Read everything piped to stdin, decoded like a file's content
+fn read_stdin_text() -> Result<String, ArgsError> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Err(ArgsError::InvalidValue("'-' reads from stdin, but nothing is piped in".to_string()));
+    }
+
+    let mut bytes = Vec::new();
+    stdin
+        .read_to_end(&mut bytes)
+        .map_err(|error| ArgsError::InvalidValue(format!("Failed to read stdin: {}", error)))?;
+    Ok(Decoder::decode(&bytes)
+        .map(|(text, _)| text)
+        .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Split a comma-separated extension list like `rs,toml,.md`
+fn parse_extensions(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(|extension| extension.trim().trim_start_matches('.'))
+        .filter(|extension| !extension.is_empty())
+        .map(str::to_string)
+}
+
 /// Argument parsing errors
 enum ArgsError {
     InvalidCount,
     HelpRequested,
     PathNotFound(PathBuf),
     InvalidSize(String),
+    InvalidValue(String),
     UnknownOption(String),
 }
 
+impl From<rcat::Error> for ArgsError {
+    fn from(error: rcat::Error) -> Self {
+        ArgsError::InvalidValue(error.to_string())
+    }
+}
+
 /// Print help message
 fn print_help(program_name: &str) {
     eprintln!("{} v{}", AppInfo::NAME, AppInfo::VERSION);
     eprintln!("{}", AppInfo::DESCRIPTION);
     eprintln!();
     eprintln!("Usage: {} [OPTIONS] <path>...", program_name);
+    eprintln!("       {} daemon [OPTIONS] <path>...", program_name);
+    eprintln!("       {} copy --fast [--stdout]", program_name);
+    eprintln!("       {} doctor", program_name);
+    eprintln!();
+    eprintln!("Commands:");
+    eprintln!("  daemon                      Keep a warm bundle of the paths and serve it to 'copy --fast'");
+    eprintln!("  copy --fast                 Copy the latest bundle from a running daemon");
+    eprintln!("  doctor                      Check clipboard, terminal, git, config, cache and watch support");
     eprintln!();
     eprintln!("Options:");
-    eprintln!("  --all, -a                   Include hidden directories and binary files");
+    eprintln!("  --all, -a                   Include hidden, binary, ignored and generated files");
+    eprintln!("  --hidden                    Include hidden files and directories");
+    eprintln!("  --binary                    Include binary files as placeholders");
+    eprintln!("  --include-generated         Include generated code (.gitattributes linguist-generated/-vendored");
+    eprintln!("                              files, and files headed by '@generated' or 'DO NOT EDIT')");
+    eprintln!("  --git                       Only include files tracked by git (falls back outside a repo)");
+    eprintln!("  --diff <ref>                Only include files changed relative to a git ref (e.g., main, HEAD~3)");
+    eprintln!("  --since <time>              Only include files modified since a time (e.g., 2d, 3h, 2024-01-01)");
+    eprintln!("  --since-git                 Judge --since by git commits rather than modification times");
     eprintln!("  --max-size, -m <size>       Set maximum output size (e.g., 10MB, 1GB, 500KB)");
+    eprintln!("  --max-tokens, -t <count>    Set maximum estimated output tokens (e.g., 8000, 128k)");
+    eprintln!("  --max-files <n>             Stop after n files, whatever their size");
+    eprintln!("  --low-memory                Collect the output in a temporary file instead of in memory");
+    eprintln!("  --tokenizer <name>          Count tokens with estimate (default), cl100k or o200k");
+    eprintln!("  --model <name>              Show in stats how much of gpt-4o, gpt-4.1, claude, gemini or llama-70b's context the output fills");
     eprintln!("  --max-file-size, -f <size>  Skip files larger than this size (e.g., 500KB, 1MB)");
-    eprintln!("  --exclude, -e <pattern>     Exclude files matching pattern (can be used multiple times)");
+    eprintln!("  --min-file-size <size>      Skip files smaller than this size (e.g., 10B, 1KB)");
+    eprintln!("  --skip-empty                Skip files that are empty or only whitespace");
+    eprintln!("  --fit                       At the size, token or file limit, leave out the largest files instead of stopping");
+    eprintln!("  --fail-if-truncated         Exit with status 2 if a limit or Ctrl-C cut the output short");
+    eprintln!("  --cache                     Reuse what earlier runs read from files unchanged since (kept in ~/.cache/rcat)");
+    eprintln!("  --changed-only              Only include files that changed since the last --cache or --changed-only run");
+    eprintln!("  --list-unchanged            Like --changed-only, and list the unchanged files at the end");
+    eprintln!("  --no-dedup                  Repeat the content of identical files instead of pointing to the first");
+    eprintln!("  --exclude, -e <pattern>     Exclude files matching pattern (can be used multiple times, !pattern re-includes)");
+    eprintln!("                              A pattern with a '/' matches the path from the root, one without just the name");
+    eprintln!("  --exclude-from <file>       Read exclude patterns from a file, one per line (gitignore syntax)");
+    eprintln!("  --no-default-excludes       Don't skip node_modules, target, dist, lockfiles and similar");
+    eprintln!("  --no-dotignore              Don't read ripgrep-style .ignore and .rgignore files");
+    eprintln!("  --no-summarize-lockfiles    Include lockfiles as they are, not as package lists");
+    eprintln!("  --unsafe                    Include files that often hold credentials (.env, id_rsa, *.pem, ...)");
+    eprintln!("  --exclude-regex <regex>     Exclude paths matching regex (can be used multiple times)");
+    eprintln!("  --ext <list>                Only include files with these extensions (e.g., rs,toml,md)");
+    eprintln!("  --not-ext <list>            Exclude files with these extensions (e.g., png,lock)");
+    eprintln!("  --profile <name>            Use the options of a [profile.<name>] table in .rcat.toml");
+    eprintln!("  --preset <name>             Apply the rust, node, python or go excludes, or a preset from .rcat.toml");
+    eprintln!("  --path-regex, -r <regex>    Only include files whose path matches (can be used multiple times)");
+    eprintln!("  --filter <query>            Only include files whose path fuzzily matches, as in fzf (e.g., 'wlkr | gitig')");
+    eprintln!("  --max-depth, -d <n>         Only descend n directory levels below each path");
+    eprintln!("  --max-files-per-dir <n>     Include at most n files from any one directory");
+    eprintln!("  --priority <pattern>        Collect files matching pattern before all others (can be used multiple times)");
+    eprintln!("  --sort <order>              Order files in each directory by name (default), size, mtime or ext");
+    eprintln!("  --reverse                   Reverse the sort order, e.g. largest or newest files first");
+    eprintln!("  --follow-symlinks           Follow symbolic links to files and directories (default)");
+    eprintln!("  --no-follow-symlinks        Skip symbolic links found while walking");
+    eprintln!("  --one-file-system           Don't descend into other filesystems (mounts) below each path");
+    eprintln!("  --canonical-paths           Show resolved paths instead of the names typed");
+    eprintln!("  --command <cmd>             Append the output of a shell command after the files (can be used multiple times)");
+    eprintln!("  --also <path>               Include this path even if it would be ignored or excluded");
+    eprintln!("  --format <name>             Output as plain (default), markdown, json, yaml or xml");
+    eprintln!("  --prefix <file|text>        Put instructions before the output (default: prefix in .rcat.toml)");
+    eprintln!("  --suffix <file|text>        Put instructions after the output (default: suffix in .rcat.toml)");
+    eprintln!("  --line-numbers, -n          Prefix each line of content with its line number");
+    eprintln!("  --strip-comments            Remove comments from files in known languages to save space");
+    eprintln!("  --strip-blank               Remove blank lines from files");
+    eprintln!("  --minify                    Trim trailing whitespace and collapse runs of blank lines");
+    eprintln!("  --dedent                    Remove the indentation shared by all lines of a file");
+    eprintln!("  --redact                    Replace API keys, tokens, private keys and passwords with [REDACTED]");
+    eprintln!("  --no-redact                 Don't redact, even if redact = true in .rcat.toml");
+    eprintln!("  --header-template <text>    Header line for each file, with {{path}}, {{basename}}, {{size}}, {{lines}}, {{lang}},");
+    eprintln!("                              {{mtime}}, {{commit}} (with --git-meta) and {{meta}}");
+    eprintln!("  --header-meta               Add each file's size, line count and modification time to its header");
+    eprintln!("  --git-meta                  Also add each file's last commit (hash, author, date), from one git log");
+    eprintln!("  --footer-template <text>    Line after each file's content, with the same placeholders");
+    eprintln!("  --tree                      Prepend a directory tree of the included files");
+    eprintln!("  --toc                       Prepend an index of the included files with the line each starts on");
     eprintln!("  --stdout, -o                Output content to stdout instead of clipboard");
+    eprintln!("  --split <size|tokens>       Break output into numbered chunks (e.g., 500KB, or 100k tokens),");
+    eprintln!("                              copied one at a time (Enter for the next) or written with --stdout");
+    eprintln!("  --split-dir <dir>           Write the --split chunks to files in a directory instead");
+    eprintln!("  --list, --dry-run           Print the files that would be included, with sizes and tokens");
+    eprintln!("  --why <path>                Explain which rule includes or skips a path, then exit");
+    eprintln!("  --interactive, -i           Pick the files to include from a checkbox tree before copying");
+    eprintln!("  --stats-json[=<file>]       Write statistics as JSON to stderr, or to a file");
+    eprintln!("  --verbose, -v               List every skipped path and why after the summary");
+    eprintln!("  --log-level <level>         Log each file's include or skip decision (debug), file starts (trace)");
+    eprintln!("                              or just the walk's start and end (info) to stderr");
+    eprintln!("  --quiet, -q                 Only print errors: no progress bar, status messages or statistics");
+    eprintln!("  --osc52                     Copy via terminal escape sequences (default over SSH without a display)");
+    eprintln!("  --clipboard-timeout <time>  Kill the clipboard utility if it hangs this long (default: 10s)");
     eprintln!("  --help, -h                  Show this help message");
     eprintln!();
     eprintln!("Description:");
     eprintln!("  Recursively walks through directories, concatenates all file contents,");
     eprintln!("  and copies the result to the system clipboard (or outputs to stdout).");
     eprintln!();
-    eprintln!("  You can specify multiple paths to process them all together.");
+    eprintln!("  You can specify multiple paths to process them all together. A path of");
+    eprintln!("  '-' reads stdin, which is included first as a '<stdin>' section, an");
+    eprintln!("  http:// or https:// URL is fetched with curl and included next, and");
+    eprintln!("  'file:40-120' (or 'file:40-') includes only those lines of a file.");
     eprintln!();
-    eprintln!("  By default, hidden directories (starting with '.') and binary files");
-    eprintln!("  are skipped. Use --all to include them.");
+    eprintln!("  Short flags combine (-ao), values can be attached (--max-size=10MB,");
+    eprintln!("  -f500KB), and everything after '--' is a path even if it starts with '-'.");
+    eprintln!();
+    eprintln!("  By default, hidden directories (starting with '.'), binary files and");
+    eprintln!("  paths matched by .gitignore, .ignore, .rgignore or .rcatignore files are");
+    eprintln!("  skipped. Use --all to include them, or --hidden / --binary to include");
+    eprintln!("  just one kind.");
     eprintln!();
     eprintln!(
         "  The default size limit is {}. Use --max-size to change it.",
@@ -152,14 +1129,62 @@ fn print_help(program_name: &str) {
         "  {} --all src/ tests/     # Include all files from both directories",
         program_name
     );
+    eprintln!(
+        "  {} --hidden .            # Include dotfiles but still skip binaries",
+        program_name
+    );
+    eprintln!(
+        "  {} 'src/**/*.rs'         # Expand a quoted glob pattern",
+        program_name
+    );
+    eprintln!(
+        "  {} src/main.rs:40-120    # Only lines 40 to 120 of a file",
+        program_name
+    );
+    eprintln!(
+        "  {} --command 'git status' src/  # Add git status after the files",
+        program_name
+    );
+    eprintln!(
+        "  {} --header-template '```{{lang}} {{path}}' --footer-template '```' src/",
+        program_name
+    );
+    eprintln!(
+        "  git diff | {} - src/     # Include piped input as a <stdin> section",
+        program_name
+    );
+    eprintln!(
+        "  {} --git .               # Only files tracked by git",
+        program_name
+    );
+    eprintln!(
+        "  {} --diff main .         # Only files changed since main",
+        program_name
+    );
+    eprintln!(
+        "  {} --since 2d .          # Only files modified in the last two days",
+        program_name
+    );
     eprintln!(
         "  {} --max-size 10MB src/  # Limit output to 10MB",
         program_name
     );
+    eprintln!(
+        "  {} --max-tokens 128k src/  # Fit output into a 128k token context window",
+        program_name
+    );
+    eprintln!(
+        "  {} --max-files 200 .      # Probe an unfamiliar repo with the first 200 files",
+        program_name
+    );
     eprintln!(
         "  {} --max-file-size 1MB src/  # Skip files larger than 1MB",
         program_name
     );
+    eprintln!(
+        "  {} --skip-empty src/      # Leave out empty __init__.py files and placeholders",
+        program_name
+    );
     eprintln!(
         "  {} -e '*.log' -e '*.tmp' src/  # Exclude log and tmp files",
         program_name
@@ -168,6 +1193,70 @@ fn print_help(program_name: &str) {
         "  {} --exclude 'test_*' src/  # Exclude files starting with test_",
         program_name
     );
+    eprintln!(
+        "  {} -e '*.json' -e '!package.json' .  # Skip JSON files except package.json",
+        program_name
+    );
+    eprintln!(
+        "  {} -e 'src/generated/*' -e '**/fixtures/**' .  # Exclude whole subtrees",
+        program_name
+    );
+    eprintln!(
+        "  {} --exclude-from .rcat-excludes .  # Use a shared exclusion list",
+        program_name
+    );
+    eprintln!(
+        "  {} --also .env.example .  # Include one dotfile without --hidden",
+        program_name
+    );
+    eprintln!(
+        "  {} --exclude-regex '_(test|spec)\\.(js|ts)$' web/  # Skip JS/TS test files",
+        program_name
+    );
+    eprintln!(
+        "  {} -r '^src/(api|core)/.*\\.rs$' .  # Only Rust files under src/api and src/core",
+        program_name
+    );
+    eprintln!(
+        "  {} --ext rs,toml,md .     # Only Rust sources, manifests and docs",
+        program_name
+    );
+    eprintln!(
+        "  {} --preset node web/     # Skip lockfiles, .next, coverage and other Node output",
+        program_name
+    );
+    eprintln!(
+        "  {} --priority 'README*' --priority Cargo.toml -m 1MB .  # Key files survive truncation",
+        program_name
+    );
+    eprintln!(
+        "  {} --fit -t 128k .         # Drop the largest files until the rest fit in 128k tokens",
+        program_name
+    );
+    eprintln!(
+        "  {} --sort size -m 1MB .   # Smallest files first, so more of them fit",
+        program_name
+    );
+    eprintln!(
+        "  {} --max-depth 1 .        # Only the top two levels",
+        program_name
+    );
+    eprintln!(
+        "  {} daemon . &             # Serve the current directory in the background",
+        program_name
+    );
+    eprintln!(
+        "  {} copy --fast            # Copy the daemon's latest bundle instantly",
+        program_name
+    );
+    eprintln!(
+        "  {} doctor                 # Diagnose clipboard and environment problems",
+        program_name
+    );
+    eprintln!(
+        "  {} --list -e '*.lock' .  # Preview what would be collected",
+        program_name
+    );
     eprintln!(
         "  {} --stdout src/ | less    # Output to stdout and pipe to less",
         program_name
@@ -188,6 +1277,9 @@ fn print_error(program_name: &str, error: ArgsError) {
         ArgsError::InvalidSize(msg) => {
             eprintln!("Error: Invalid size - {}", msg);
         }
+        ArgsError::InvalidValue(msg) => {
+            eprintln!("Error: {}", msg);
+        }
         ArgsError::UnknownOption(opt) => {
             eprintln!("Error: Unknown option '{}'", opt);
             eprintln!("Try '{} --help' for more information", program_name);
@@ -217,12 +1309,16 @@ fn main() {
         },
     };
 
-    // Validate clipboard utility is available before processing (unless using stdout)
-    if !args.stdout {
-        if let Err(error) = clipboard::validate_clipboard() {
-            eprintln!("Error: {}", error);
-            process::exit(1);
-        }
+    // Validate clipboard utility is available before processing (unless using stdout or files)
+    if !args.stdout
+        && !args.list
+        && args.why.is_none()
+        && args.split_dir.is_none()
+        && matches!(args.command, Command::Collect | Command::FastCopy)
+        && let Err(error) = clipboard::validate_clipboard(Backend::detect(args.osc52))
+    {
+        eprintln!("Error: {}", error);
+        process::exit(1);
     }
 
     run(args);
@@ -230,16 +1326,338 @@ fn main() {
 
 /// Run the application
 fn run(args: Args) {
-    let options = WalkOptions {
-        include_all: args.include_all,
+    match args.command {
+        Command::Collect => match &args.why {
+            Some(target) => explain_path(&args, target),
+            None => collect(args),
+        },
+        Command::Daemon => serve(args),
+        Command::FastCopy => fast_copy(&args),
+        Command::Doctor => {
+            if !doctor::run() {
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Build walk options from the parsed arguments
+fn walk_options(args: &Args) -> WalkOptions {
+    WalkOptions {
+        include_hidden: args.include_hidden,
+        include_binary: args.include_binary,
+        include_ignored: args.include_ignored,
+        include_generated: args.include_generated,
+        git_tracked: args.git_tracked,
+        diff_ref: args.diff_ref.clone(),
+        modified_since: args.modified_since,
+        since_git: args.since_git,
+        git_meta: args.git_meta,
+        selected: None,
         max_size: args.max_size,
+        max_tokens: args.max_tokens,
+        max_files: args.max_files,
+        tokenizer: Arc::clone(&args.tokenizer),
+        model: args.model,
         max_file_size: args.max_file_size,
-        exclude_patterns: args.exclude_patterns,
+        min_file_size: args.min_file_size,
+        skip_empty: args.skip_empty,
+        fit: args.fit,
+        cache: args.cache,
+        changed_only: args.changed_only,
+        list_unchanged: args.list_unchanged,
+        exclude_patterns: args.exclude_patterns.clone(),
+        default_excludes: args.default_excludes,
+        dot_ignore: args.dot_ignore,
+        processors: args.processors.clone(),
+        skip_sensitive: args.skip_sensitive,
+        exclude_regexes: args.exclude_regexes.clone(),
+        path_regexes: args.path_regexes.clone(),
+        fuzzy_filters: args.fuzzy_filters.clone(),
+        extensions: args.extensions.clone(),
+        excluded_extensions: args.excluded_extensions.clone(),
+        include_patterns: args.include_patterns.clone(),
+        max_depth: args.max_depth,
+        max_files_per_dir: args.max_files_per_dir,
+        priority_patterns: args.priority_patterns.clone(),
+        dedup: args.dedup,
+        sort: args.sort,
+        reverse: args.reverse,
+        follow_symlinks: args.follow_symlinks,
+        one_file_system: args.one_file_system,
+        path_display: args.path_display,
+        prefix: args.prefix.clone(),
+        suffix: args.suffix.clone(),
+        line_ranges: args.line_ranges.clone(),
+        line_numbers: args.line_numbers,
+        transforms: args.transforms,
+        redact: args.redact,
+        formatter: args.format.formatter(args.template.clone()),
+        tree: args.tree,
+        toc: args.toc,
+        low_memory: args.low_memory,
+        stdin: args.stdin.clone(),
+        urls: args.urls.clone(),
+        commands: args.commands.clone(),
+        also: args.also.clone(),
+        filters: Vec::new(),
+        progress: None,
+        cancel: None,
+    }
+}
+
+#[cfg(unix)]
+const SIGINT: std::os::raw::c_int = 2;
+#[cfg(unix)]
+const SIG_DFL: usize = 0;
+
+#[cfg(unix)]
+unsafe extern "C" {
+    fn signal(signum: std::os::raw::c_int, handler: usize) -> usize;
+}
+
+/// Set `cancel` on the first Ctrl-C, so the walk stops and keeps what it has
+///
+/// A second Ctrl-C ends the process as usual, as does any Ctrl-C once
+/// `restore_interrupt_handler` is called.
+#[cfg(unix)]
+fn install_interrupt_handler(cancel: Arc<AtomicBool>) {
+    use std::os::raw::c_int;
+    use std::sync::OnceLock;
+
+    static CANCEL: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    extern "C" fn on_interrupt(_: c_int) {
+        if let Some(cancel) = CANCEL.get() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        // SAFETY: restoring the default disposition is async-signal-safe
+        unsafe {
+            signal(SIGINT, SIG_DFL);
+        }
+    }
+
+    if CANCEL.set(cancel).is_ok() {
+        // SAFETY: the handler only touches an atomic and calls signal()
+        unsafe {
+            signal(SIGINT, on_interrupt as extern "C" fn(c_int) as usize);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler(_cancel: Arc<AtomicBool>) {}
+
+/// Let Ctrl-C end the process again once the walk is over, so it isn't
+/// swallowed while copying or waiting at a prompt
+#[cfg(unix)]
+fn restore_interrupt_handler() {
+    // SAFETY: restoring the default disposition has no preconditions
+    unsafe {
+        signal(SIGINT, SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+fn restore_interrupt_handler() {}
+
+/// Log the paths a walk starts from, for `--log-level`
+fn log_walk_start(logger: &Logger, paths: &[PathBuf]) {
+    let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    logger.log(LogLevel::Info, "walk", &[("paths", &paths.join(","))]);
+}
+
+/// Log what a walk collected, for `--log-level`
+fn log_walk_end(logger: &Logger, result: &WalkResult) {
+    let elapsed = format!("{:.3}s", result.stats.elapsed().as_secs_f64());
+    logger.log(
+        LogLevel::Info,
+        "done",
+        &[
+            ("files", &result.files.len()),
+            ("skipped", &result.stats.skipped().len()),
+            ("bytes", &result.content.len()),
+            ("tokens", &result.stats.total_tokens()),
+            ("truncated", &result.truncated),
+            ("elapsed", &elapsed),
+        ],
+    );
+}
+
+/// Progress bar on stderr for runs taking over a second, when stderr is a terminal
+///
+/// The bar fills toward whichever of the size, token and file limits is
+/// closest to being reached, and shows that limit with how long until the
+/// current rate would reach it; a walk that runs out of files ends sooner.
+/// Hidden by `--quiet`.
+fn progress_reporter(args: &Args) -> Option<ProgressCallback> {
+    const BAR_WIDTH: usize = 20;
+
+    #[derive(Default)]
+    struct Progress {
+        processed: usize,
+        included: usize,
+        bytes: usize,
+        tokens: usize,
+        // When the bar was last drawn
+        drawn: Duration,
+    }
+
+    if args.quiet || !io::stderr().is_terminal() {
+        return None;
+    }
+
+    let (max_size, max_tokens, max_files) = (args.max_size, args.max_tokens, args.max_files);
+    let start = Instant::now();
+    let state = Mutex::new(Progress::default());
+
+    Some(Arc::new(move |event| {
+        let mut state = state.lock().unwrap();
+        match event {
+            ProgressEvent::FileStarted { .. } => state.processed += 1,
+            ProgressEvent::FileIncluded { size, tokens, .. } => {
+                state.included += 1;
+                state.bytes += size;
+                state.tokens += tokens;
+            }
+            _ => return,
+        }
+
+        // Stay quiet for quick runs, and redraw at most ten times a second
+        let elapsed = start.elapsed();
+        if elapsed < Duration::from_secs(1) || elapsed - state.drawn < Duration::from_millis(100) {
+            return;
+        }
+        state.drawn = elapsed;
+
+        // The bar follows whichever limit is closest to being reached
+        let share = |done: usize, limit: usize| (done as f64 / limit.max(1) as f64).min(1.0);
+        let size = (
+            share(state.bytes, max_size),
+            "size",
+            format!("{} of {}", ByteFormatter::format(state.bytes), ByteFormatter::format(max_size)),
+        );
+        let tokens = max_tokens.map(|limit| {
+            let text = format!("{} of {} tokens", TokenEstimator::format(state.tokens), TokenEstimator::format(limit));
+            (share(state.tokens, limit), "token", text)
+        });
+        let files = max_files.map(|limit| {
+            (share(state.included, limit), "file", format!("{} of {} files included", state.included, limit))
+        });
+        let (done, limit_name, limit_text) = [tokens, files]
+            .into_iter()
+            .flatten()
+            .fold(size, |nearest, limit| if limit.0 > nearest.0 { limit } else { nearest });
+
+        let filled = (done * BAR_WIDTH as f64) as usize;
+        let eta = if done > 0.0 && done < 1.0 {
+            format!(", {} limit in ~{}", limit_name, format_duration(elapsed.mul_f64((1.0 - done) / done)))
+        } else {
+            String::new()
+        };
+        eprint!(
+            "\r\x1b[K[{}{}] {:>3}% {} files, {}{}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            (done * 100.0) as usize,
+            state.processed,
+            limit_text,
+            eta
+        );
+    }))
+}
+
+/// A duration for the progress bar, like `45s` or `3m 20s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Walk the paths and copy or print the result
+fn collect(args: Args) {
+    let cancel = Arc::new(AtomicBool::new(false));
+    install_interrupt_handler(Arc::clone(&cancel));
+
+    let logger = args.log_level.map(|level| Arc::new(Logger::stderr(level)));
+    let mut options = walk_options(&args);
+    // Log lines take the place of the progress bar, which would draw over them
+    options.progress = match &logger {
+        Some(logger) => Some(logger.callback()),
+        None => progress_reporter(&args),
     };
+    let show_progress = logger.is_none() && options.progress.is_some();
+    options.cancel = Some(cancel);
+    #[cfg(feature = "tui")]
+    if args.interactive {
+        options.selected = Some(pick_files(&args).into_iter().collect());
+    }
+    let formatter = Arc::clone(&options.formatter);
+
+    if let Some(logger) = &logger {
+        log_walk_start(logger, &args.paths);
+    }
+    let result = walk_and_collect(&args.paths, options);
+    restore_interrupt_handler();
+    if show_progress {
+        // Clear the progress line before anything else is printed
+        eprint!("\r\x1b[K");
+    }
+    if let (Some(logger), Ok(result)) = (&logger, &result) {
+        log_walk_end(logger, result);
+    }
 
-    match walk_and_collect(&args.paths, options) {
-        Ok(result) => {
-            handle_result(result, args.max_size, args.stdout);
+    match result {
+        Ok(result) if args.list => {
+            print!("{}", output::listing(&result));
+            print_stats(stats_report(&result.stats, &args).as_deref(), args.quiet);
+            exit_partial(
+                result.interrupted,
+                result.truncated && args.fail_if_truncated,
+                result.stats.unreadable_files(),
+            );
+        }
+        Ok(mut result) => {
+            let notice = if result.interrupted {
+                Some("Interrupted - keeping the files collected so far".to_string())
+            } else {
+                result.truncated_by.map(truncation_notice)
+            };
+            if let Some(limit) = args.split {
+                // Plain and markdown output is the content as collected, so file spans still apply
+                let chunks = Chunker::split_numbered(&result.content, &result.files, limit, args.tokenizer.as_ref());
+                handle_chunks(&chunks, notice.as_deref(), stats_report(&result.stats, &args).as_deref(), &args);
+            } else {
+                let mut spool = result.spool.take();
+                let rendered;
+                let content = match &mut spool {
+                    Some(spool) => Output::Spool(spool),
+                    None => {
+                        rendered = result.stats.time(Phase::Format, || formatter.render(&result));
+                        Output::Text(&rendered)
+                    }
+                };
+                handle_result(
+                    content,
+                    notice.as_deref(),
+                    |clipboard| {
+                        result.stats.record_phase(Phase::Clipboard, clipboard);
+                        stats_report(&result.stats, &args)
+                    },
+                    args.stdout,
+                    Backend::detect(args.osc52),
+                    args.clipboard_timeout,
+                    args.quiet,
+                );
+            }
+            exit_partial(
+                result.interrupted,
+                result.truncated && args.fail_if_truncated,
+                result.stats.unreadable_files(),
+            );
         }
         Err(error) => {
             eprintln!("Error: Failed to process directories - {}", error);
@@ -248,60 +1666,506 @@ fn run(args: Args) {
     }
 }
 
+/// Print whether a walk would collect a path, and the rule that decides (`--why`)
+fn explain_path(args: &Args, target: &Path) {
+    let explanation = match explain(&args.paths, target, walk_options(args)) {
+        Ok(explanation) => explanation,
+        Err(error) => {
+            eprintln!("Error: Failed to explain {} - {}", target.display(), error);
+            process::exit(1);
+        }
+    };
+
+    let shown = target.display();
+    match explanation {
+        Explanation::Included => println!("{}: included", shown),
+        Explanation::Forced { also } => println!("{}: included, forced by --also {}", shown, also.display()),
+        Explanation::Skipped { at, reason } if same_file(&at, target) => println!("{}: skipped, {}", shown, reason),
+        Explanation::Skipped { at, reason } => {
+            println!("{}: skipped, inside {}/ which is skipped: {}", shown, at.display(), reason)
+        }
+        Explanation::NotReached => {
+            let paths: Vec<String> = args.paths.iter().map(|path| path.display().to_string()).collect();
+            println!("{}: not below any of the paths walked ({})", shown, paths.join(", "));
+        }
+    }
+}
+
+/// Whether two paths lead to the same file
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// List what a walk would include and let the user pick among it (`--interactive`)
+///
+/// Exits if the picker is cancelled or can't be shown.
+#[cfg(feature = "tui")]
+fn pick_files(args: &Args) -> Vec<PathBuf> {
+    let result = match walk_and_collect(&args.paths, walk_options(args)) {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("Error: Failed to process directories - {}", error);
+            process::exit(1);
+        }
+    };
+    // Nothing to pick from: the real walk reports that no files were found
+    if result.files.is_empty() {
+        return Vec::new();
+    }
+
+    let candidates = result
+        .files
+        .iter()
+        .map(|file| Candidate {
+            path: file.path.clone(),
+            size: file.size,
+            tokens: file.tokens,
+        })
+        .collect();
+    match Picker::new(candidates).run() {
+        Ok(Some(selected)) => selected,
+        Ok(None) => {
+            eprintln!("Cancelled, nothing was copied");
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("Error: Failed to show the file picker - {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Run the daemon in the foreground
+#[cfg(unix)]
+fn serve(args: Args) {
+    use rcat::daemon::{self, Daemon};
+
+    let socket = match daemon::socket_path() {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("Error: Failed to start daemon - {}", error);
+            process::exit(1);
+        }
+    };
+    let mut options = walk_options(&args);
+    if let Some(level) = args.log_level {
+        options.progress = Some(Arc::new(Logger::stderr(level)).callback());
+    }
+
+    match Daemon::bind(&socket, args.paths, options) {
+        Ok(daemon) => {
+            if !args.quiet {
+                eprintln!("Serving bundle on {}", daemon.socket().display());
+            }
+            if let Err(error) = daemon.run(Duration::from_secs(1)) {
+                eprintln!("Error: Daemon stopped - {}", error);
+                process::exit(1);
+            }
+        }
+        Err(error) => {
+            eprintln!("Error: Failed to start daemon - {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn serve(_args: Args) {
+    eprintln!("Error: The daemon is only supported on Unix platforms");
+    process::exit(1);
+}
+
+/// Statistics summary for stderr, writing the JSON file first if one was requested
+///
+/// `--quiet` leaves out the summary, but not JSON asked for on stderr.
+fn stats_report(stats: &StatsCollector, args: &Args) -> Option<String> {
+    let summary = match &args.stats_json {
+        StatsJson::Off => stats.format_stats(),
+        // The JSON already lists every skipped path
+        StatsJson::Stderr => return Some(stats.to_json()),
+        StatsJson::File(path) => {
+            if let Err(error) = std::fs::write(path, stats.to_json()) {
+                eprintln!("Error: Failed to write stats to {} - {}", path.display(), error);
+                process::exit(1);
+            }
+            stats.format_stats()
+        }
+    };
+    if args.quiet {
+        return None;
+    }
+
+    let summary = match stats.sensitive_skipped() {
+        0 => summary,
+        count => format!(
+            "{}\nWarning: Skipped {} sensitive file{} (.env, keys, credentials); use --unsafe to include them",
+            summary,
+            count,
+            if count == 1 { "" } else { "s" }
+        ),
+    };
+
+    let summary = match stats.generated_skipped() {
+        0 => summary,
+        count => format!(
+            "{}\nNote: Skipped {} generated file{}; use --include-generated to include them",
+            summary,
+            count,
+            if count == 1 { "" } else { "s" }
+        ),
+    };
+
+    if args.verbose && !stats.skipped().is_empty() {
+        Some(format!("{}\n\n{}", summary, stats.format_skipped()))
+    } else {
+        Some(summary)
+    }
+}
+
+/// Status line for output cut short by a size or token limit
+fn truncation_notice(limit: impl std::fmt::Display) -> String {
+    format!("Content truncated at {} limit", limit)
+}
+
+/// Exit code when `--fail-if-truncated` is given and the output was cut short
+const EXIT_TRUNCATED: i32 = 2;
+/// Exit code when some files couldn't be read, and were left out of otherwise complete output
+const EXIT_UNREADABLE: i32 = 3;
+/// Exit code when Ctrl-C stopped the walk, as for a shell command killed by SIGINT
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Exit with a nonzero status if the output that was delivered is incomplete
+///
+/// Truncation only counts when `--fail-if-truncated` asked for it, since a
+/// limit usually cuts the output on purpose; it takes precedence over
+/// unreadable files. An interrupted walk always counts, and takes precedence
+/// over both.
+fn exit_partial(interrupted: bool, truncated: bool, unreadable_files: usize) {
+    if interrupted {
+        process::exit(EXIT_INTERRUPTED);
+    }
+    if truncated {
+        process::exit(EXIT_TRUNCATED);
+    }
+    if unreadable_files > 0 {
+        process::exit(EXIT_UNREADABLE);
+    }
+}
+
+/// Copy the latest bundle from a running daemon
+#[cfg(unix)]
+fn fast_copy(args: &Args) {
+    use rcat::daemon;
+
+    match daemon::socket_path().and_then(|socket| daemon::fetch(&socket)) {
+        Ok(bundle) => {
+            handle_result(
+                Output::Text(&bundle.content),
+                bundle.truncated_by.as_deref().map(truncation_notice).as_deref(),
+                |_| Some(bundle.stats.clone()).filter(|_| !args.quiet),
+                args.stdout,
+                Backend::detect(args.osc52),
+                args.clipboard_timeout,
+                args.quiet,
+            );
+            // The bundle only carries its stats as text, so unreadable files can't be told apart
+            exit_partial(false, bundle.truncated_by.is_some() && args.fail_if_truncated, 0);
+        }
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn fast_copy(_args: &Args) {
+    eprintln!("Error: The daemon is only supported on Unix platforms");
+    process::exit(1);
+}
+
+/// Deliver the chunks of `--split`: into files, to stdout, or to the clipboard one at a time
+fn handle_chunks(chunks: &[String], notice: Option<&str>, stats: Option<&str>, args: &Args) {
+    if chunks.is_empty() {
+        if !args.quiet {
+            eprintln!("No files found to output");
+        }
+        return;
+    }
+    if let Some(notice) = notice.filter(|_| !args.quiet) {
+        eprintln!("{}", notice);
+    }
+
+    let total = chunks.len();
+    if let Some(dir) = &args.split_dir {
+        let extension = if args.format == OutputFormat::Markdown { "md" } else { "txt" };
+        if let Err(error) = write_chunks(dir, chunks, extension) {
+            eprintln!("Error: Failed to write chunks to {} - {}", dir.display(), error);
+            process::exit(1);
+        }
+        if !args.quiet {
+            eprintln!("Successfully wrote {} chunks to {}", total, dir.display());
+        }
+    } else if args.stdout {
+        print!("{}", chunks.join("\n"));
+        if !args.quiet {
+            eprintln!("Successfully output {} chunks to stdout", total);
+        }
+    } else if !copy_chunks(chunks, Backend::detect(args.osc52), args.clipboard_timeout) {
+        return;
+    }
+    print_stats(stats, args.quiet);
+}
+
+/// Write chunks to `chunk-1.txt`, `chunk-2.txt`, ... in `dir`, zero-padded so they sort in order
+fn write_chunks(dir: &Path, chunks: &[String], extension: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let width = chunks.len().to_string().len();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let name = format!("chunk-{:0width$}.{}", index + 1, extension, width = width);
+        fs::write(dir.join(name), chunk)?;
+    }
+    Ok(())
+}
+
+/// Copy chunks to the clipboard one by one, waiting for Enter in between
+///
+/// Input comes from the terminal even when stdin was read as a path. Returns
+/// whether every chunk was delivered; end of input stops early. A chunk that
+/// can't be copied falls back as in `handle_result`, to OSC 52 and then, if
+/// the user agrees, to printing it and the rest on stdout.
+fn copy_chunks(chunks: &[String], mut backend: Backend, timeout: Duration) -> bool {
+    let mut input: Box<dyn BufRead> = match fs::File::open("/dev/tty") {
+        Ok(terminal) => Box::new(BufReader::new(terminal)),
+        Err(_) => Box::new(io::stdin().lock()),
+    };
+
+    let total = chunks.len();
+    for (index, chunk) in chunks.iter().enumerate() {
+        match copy_with_fallback(&mut Output::Text(chunk), backend, timeout) {
+            // Once OSC 52 stands in for the clipboard utility, the rest go the same way
+            Ok(copied) => backend = copied,
+            Err(error) => {
+                eprintln!("Error: Failed to copy to clipboard - {}", error);
+                if !confirm("Print the remaining chunks to stdout instead?") {
+                    process::exit(1);
+                }
+                print!("{}", chunks[index..].join("\n"));
+                eprintln!("Successfully output {} chunks to stdout", total - index);
+                return true;
+            }
+        }
+        let via = match backend {
+            Backend::System => "",
+            Backend::Osc52 => " via OSC 52",
+        };
+        eprint!(
+            "Copied chunk {}/{} ({}) to clipboard{}",
+            index + 1,
+            total,
+            ByteFormatter::format(chunk.len()),
+            via
+        );
+        if index + 1 == total {
+            eprintln!();
+            break;
+        }
+
+        eprint!(" - press Enter for the next chunk");
+        let mut line = String::new();
+        if !matches!(input.read_line(&mut line), Ok(read) if read > 0) {
+            eprintln!("\nStopped after chunk {}/{}", index + 1, total);
+            return false;
+        }
+    }
+    true
+}
+
+/// Collected output to deliver: in memory, or in the temporary file `--low-memory` wrote it to
+enum Output<'a> {
+    Text(&'a str),
+    Spool(&'a mut Spool),
+}
+
+impl Output<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Output::Text(text) => text.len(),
+            Output::Spool(spool) => spool.len(),
+        }
+    }
+
+    /// Read the output, from the file a bit at a time when it was spooled
+    fn reader(&mut self) -> io::Result<Box<dyn BufRead + '_>> {
+        Ok(match self {
+            Output::Text(text) => Box::new(text.as_bytes()),
+            Output::Spool(spool) => Box::new(spool.reader()?),
+        })
+    }
+}
+
 /// Handle the collected result
-fn handle_result(result: WalkResult, max_size: usize, stdout: bool) {
-    let size = result.content.len();
+///
+/// With `quiet`, only errors are printed besides the statistics, which are
+/// `None` unless asked for on stderr. They're produced once the content is
+/// delivered, given how long the clipboard took, so they can include it.
+fn handle_result(
+    mut content: Output<'_>,
+    notice: Option<&str>,
+    stats: impl FnOnce(Duration) -> Option<String>,
+    stdout: bool,
+    backend: Backend,
+    timeout: Duration,
+    quiet: bool,
+) {
+    let size = content.len();
 
     if size == 0 {
-        if stdout {
-            eprintln!("No files found to output");
-        } else {
-            eprintln!("No files found to copy");
+        if !quiet {
+            let action = if stdout { "output" } else { "copy" };
+            eprintln!("No files found to {}", action);
         }
         return;
     }
 
+    let mut clipboard_time = Duration::ZERO;
+    let mut stdout = stdout;
+    if !stdout {
+        let started = Instant::now();
+        let copied = copy_with_fallback(&mut content, backend, timeout);
+        clipboard_time = started.elapsed();
+        match copied {
+            Ok(backend) => {
+                if !quiet {
+                    if let Some(notice) = notice {
+                        eprintln!("{}", notice);
+                    }
+                    let via = match backend {
+                        Backend::System => "",
+                        Backend::Osc52 => " via OSC 52",
+                    };
+                    eprintln!("Successfully copied {} to clipboard{}", ByteFormatter::format(size), via);
+                }
+            }
+            Err(error) => {
+                eprintln!("Error: Failed to copy to clipboard - {}", error);
+                if !confirm("Print the output to stdout instead?") {
+                    process::exit(1);
+                }
+                stdout = true;
+            }
+        }
+    }
     if stdout {
         // Output content to stdout
-        print!("{}", result.content);
+        let mut stdout = io::stdout().lock();
+        let written = content
+            .reader()
+            .and_then(|mut reader| io::copy(&mut reader, &mut stdout))
+            .and_then(|_| stdout.flush());
+        if let Err(error) = written {
+            eprintln!("Error: Failed to write to stdout - {}", error);
+            process::exit(1);
+        }
         
         // Status messages to stderr
-        if result.truncated {
+        if !quiet {
+            if let Some(notice) = notice {
+                eprintln!("{}", notice);
+            }
             eprintln!(
-                "Content truncated at {} limit",
-                ByteFormatter::format_as_unit(max_size)
+                "Successfully output {} to stdout",
+                ByteFormatter::format(size)
             );
         }
-        eprintln!(
-            "Successfully output {} to stdout",
-            ByteFormatter::format(size)
-        );
-        eprintln!("\n{}", result.stats.format_stats());
-    } else {
-        // Copy to clipboard (existing behavior)
-        match clipboard::copy_to_clipboard(&result.content) {
-            Ok(_) => {
-                if result.truncated {
-                    eprintln!(
-                        "Content truncated at {} limit",
-                        ByteFormatter::format_as_unit(max_size)
-                    );
-                    eprintln!(
-                        "Successfully copied {} to clipboard",
-                        ByteFormatter::format(size)
-                    );
-                } else {
-                    eprintln!(
-                        "Successfully copied {} to clipboard",
-                        ByteFormatter::format(size)
-                    );
-                }
-                eprintln!("\n{}", result.stats.format_stats());
-            }
-            Err(error) => {
-                eprintln!("Error: Failed to copy to clipboard - {}", error);
-                process::exit(1);
-            }
+    }
+    print_stats(stats(clipboard_time).as_deref(), quiet);
+}
+
+/// Copy the output to the clipboard, returning the backend that took it
+///
+/// When the clipboard utility fails, OSC 52 is tried next if there's a
+/// terminal to send it to, since it needs no display.
+fn copy_with_fallback(content: &mut Output<'_>, backend: Backend, timeout: Duration) -> Result<Backend, rcat::Error> {
+    let mut copy = |backend| match content.reader() {
+        Ok(reader) => clipboard::copy_to_clipboard(reader, backend, timeout),
+        Err(error) => Err(rcat::Error::Clipboard(format!("can't read the collected output back ({})", error))),
+    };
+    match copy(backend) {
+        Err(error) if backend == Backend::System && clipboard::validate_clipboard(Backend::Osc52).is_ok() => {
+            eprintln!("Warning: Failed to copy to clipboard - {}; trying OSC 52", error);
+            copy(Backend::Osc52).map(|()| Backend::Osc52)
         }
+        copied => copied.map(|()| backend),
+    }
+}
+
+/// Ask a yes or no question on the terminal, taking anything but yes as no
+///
+/// Without a terminal to ask on, the answer is no.
+fn confirm(question: &str) -> bool {
+    let Ok(terminal) = fs::File::open("/dev/tty") else {
+        return false;
+    };
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    BufReader::new(terminal).read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Print the statistics after the status messages, set apart from them unless `quiet` left those out
+fn print_stats(stats: Option<&str>, quiet: bool) {
+    match stats {
+        Some(stats) if quiet => eprintln!("{}", stats),
+        Some(stats) => eprintln!("\n{}", stats),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_args() {
+        let split = |args: &[&str]| split_args(&strings(args));
+
+        assert_eq!(split(&["-ao", "src"]), (strings(&["-a", "-o", "src"]), Vec::new()));
+        // A short option taking a value takes the rest of the group, or the next argument
+        assert_eq!(split(&["-f500KB"]), (strings(&["-f", "500KB"]), Vec::new()));
+        assert_eq!(split(&["-qf500KB"]), (strings(&["-q", "-f", "500KB"]), Vec::new()));
+        assert_eq!(split(&["-e", "*.log"]), (strings(&["-e", "*.log"]), Vec::new()));
+        assert_eq!(split(&["--max-size=1MB"]), (strings(&["--max-size", "1MB"]), Vec::new()));
+        // Only options taking a value are split at '='
+        assert_eq!(split(&["--stdout=x"]), (strings(&["--stdout=x"]), Vec::new()));
+
+        // A value is taken as is, even `--`
+        assert_eq!(split(&["--exclude", "--", "src"]), (strings(&["--exclude", "--", "src"]), Vec::new()));
+        assert_eq!(split(&["-o", "--", "-weird"]), (strings(&["-o"]), strings(&["-weird"])));
+    }
+
+    #[test]
+    fn test_walk_option() {
+        let option = |args: &[&str]| walk_option(&strings(args)).map(str::to_string);
+
+        assert_eq!(option(&["--fast", "-q", "--clipboard-timeout", "5s"]), None);
+        assert_eq!(option(&["--fast", "--max-size", "1MB"]).as_deref(), Some("--max-size"));
+        // The daemon does the logging, so it's rejected along with the walk options
+        assert_eq!(option(&["--fast", "--log-level", "debug"]).as_deref(), Some("--log-level"));
+    }
+
+    #[test]
+    fn test_profile_name() {
+        let name = |args: &[&str]| profile_name(&strings(args));
+
+        assert_eq!(name(&["-o", "src"]), None);
+        assert_eq!(name(&["--profile", "a", "-o", "--profile", "b"]).as_deref(), Some("b"));
+        // `--profile` as another option's value doesn't name a profile
+        assert_eq!(name(&["--profile", "a", "--prefix", "--profile", "b"]).as_deref(), Some("a"));
+        assert_eq!(name(&["--exclude", "--profile"]), None);
     }
 }
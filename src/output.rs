@@ -0,0 +1,378 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::format::ByteFormatter;
+use crate::serialize::Value;
+use crate::template::{self, FileMeta, Section, SectionTemplate};
+use crate::tokens::TokenEstimator;
+use crate::walker::WalkResult;
+
+/// Layout of the final output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Concatenated files with `--- path ---` headers
+    #[default]
+    Plain,
+    /// Each file as a heading and a fenced code block
+    Markdown,
+    /// File entries plus stats as JSON
+    Json,
+    /// The same structure as `Json`, written as YAML
+    Yaml,
+    /// An index of the files, then each file in a `<document>` element
+    Xml,
+}
+
+/// Lays out the output: each file's section as it is collected, then the whole result
+///
+/// Each `OutputFormat` has one; implement it and set `WalkOptions::formatter`
+/// to lay out results another way. Sections are what size and token limits
+/// count, and are joined with newlines into `WalkResult::content`.
+pub trait OutputFormatter: Send + Sync {
+    /// Wrap one file's content, by default in a `--- path ---` header
+    fn section(&self, path: &Path, body: &str) -> Section {
+        SectionTemplate::default().render(path, body)
+    }
+
+    /// Wrap a file's content given what else is known about the file, by default ignoring it
+    fn file_section(&self, path: &Path, body: &str, _meta: &FileMeta) -> Section {
+        self.section(path, body)
+    }
+
+    /// Turn a finished walk into the final output
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str>;
+}
+
+/// `OutputFormat::Plain`: sections from a header and footer template, concatenated
+#[derive(Clone, Debug, Default)]
+pub struct PlainFormatter {
+    pub template: SectionTemplate,
+}
+
+/// `OutputFormat::Markdown`: a heading with the path, then the content in a code fence
+///
+/// The fence is longer than any run of backticks in the content, so it can't
+/// be closed early.
+pub struct MarkdownFormatter;
+
+/// `OutputFormat::Json`: the `document` structure as JSON
+pub struct JsonFormatter;
+
+/// `OutputFormat::Yaml`: the `document` structure as YAML
+pub struct YamlFormatter;
+
+/// `OutputFormat::Xml`: files wrapped in `<document>` elements, as suits long-context prompts
+///
+/// An `<index>` listing every file comes first, then a `<documents>` element
+/// with each file's content, escaped. The prefix and suffix are written as
+/// is, before and after.
+pub struct XmlFormatter;
+
+impl OutputFormatter for PlainFormatter {
+    fn section(&self, path: &Path, body: &str) -> Section {
+        self.template.render(path, body)
+    }
+
+    fn file_section(&self, path: &Path, body: &str, meta: &FileMeta) -> Section {
+        self.template.render_file(path, body, meta)
+    }
+
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str> {
+        Cow::Borrowed(&result.content)
+    }
+}
+
+impl OutputFormatter for MarkdownFormatter {
+    fn section(&self, path: &Path, body: &str) -> Section {
+        let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+
+        let mut text = format!(
+            "## {}\n\n{}{}\n",
+            path.display(),
+            fence,
+            template::language(path).unwrap_or_default()
+        );
+        let start = text.len();
+        text.push_str(body);
+        let body_range = start..text.len();
+        if !body.is_empty() && !body.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&fence);
+        text.push('\n');
+
+        Section { text, body: body_range }
+    }
+
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str> {
+        Cow::Borrowed(&result.content)
+    }
+}
+
+impl OutputFormatter for JsonFormatter {
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str> {
+        Cow::Owned(document(result).to_json())
+    }
+}
+
+impl OutputFormatter for YamlFormatter {
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str> {
+        Cow::Owned(document(result).to_yaml())
+    }
+}
+
+impl OutputFormatter for XmlFormatter {
+    fn render<'a>(&self, result: &'a WalkResult) -> Cow<'a, str> {
+        let mut output = String::new();
+        if let Some(prefix) = &result.prefix {
+            output.push_str(prefix);
+            output.push_str("\n\n");
+        }
+
+        output.push_str("<index>\n");
+        for (index, file) in result.files.iter().enumerate() {
+            output.push_str(&format!(
+                "<file index=\"{}\" path=\"{}\" size=\"{}\" tokens=\"{}\"/>\n",
+                index + 1,
+                xml_escape(&file.path.display().to_string()),
+                file.size,
+                file.tokens
+            ));
+        }
+        output.push_str("</index>\n<documents>\n");
+
+        for (index, file) in result.files.iter().enumerate() {
+            let path = xml_escape(&file.path.display().to_string());
+            if let Some(original) = &file.identical_to {
+                output.push_str(&format!(
+                    "<document index=\"{}\" path=\"{}\" identical_to=\"{}\"/>\n",
+                    index + 1,
+                    path,
+                    xml_escape(&original.display().to_string())
+                ));
+                continue;
+            }
+
+            let body = result.file_body(file);
+            output.push_str(&format!("<document index=\"{}\" path=\"{}\">\n", index + 1, path));
+            output.push_str(&xml_escape(body));
+            if !body.is_empty() && !body.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str("</document>\n");
+        }
+        output.push_str("</documents>\n");
+
+        if let Some(limit) = result.truncated_by {
+            output.push_str(&format!("<truncated limit=\"{}\"/>\n", limit));
+        }
+        if let Some(suffix) = &result.suffix {
+            output.push('\n');
+            output.push_str(suffix);
+        }
+        Cow::Owned(output)
+    }
+}
+
+/// Prefix each line with its number, starting at `first_line` and right-aligned to the widest, e.g. ` 9 | ` and `10 | `
+///
+/// A trailing newline is kept, and empty lines get no trailing space.
+pub fn number_lines(text: &str, first_line: usize) -> String {
+    let width = (first_line + text.lines().count().saturating_sub(1)).to_string().len();
+    let mut numbered = String::with_capacity(text.len() + text.len() / 8);
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let (content, newline) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
+        };
+        if content.is_empty() {
+            numbered.push_str(&format!("{:>width$} |{}", first_line + index, newline));
+        } else {
+            numbered.push_str(&format!("{:>width$} | {}{}", first_line + index, content, newline));
+        }
+    }
+    numbered
+}
+
+/// Escape text for XML content and double-quoted attribute values
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl OutputFormat {
+    /// Parse a format name as given to `--format`
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        match name.to_lowercase().as_str() {
+            "plain" | "text" => Ok(OutputFormat::Plain),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            "xml" => Ok(OutputFormat::Xml),
+            _ => Err(Error::Config(format!(
+                "Unknown format: {} (expected plain, markdown, json, yaml or xml)",
+                name
+            ))),
+        }
+    }
+
+    /// The formatter that lays out this format; `template` only applies to `Plain`
+    pub fn formatter(self, template: SectionTemplate) -> Arc<dyn OutputFormatter> {
+        match self {
+            OutputFormat::Plain => Arc::new(PlainFormatter { template }),
+            OutputFormat::Markdown => Arc::new(MarkdownFormatter),
+            OutputFormat::Json => Arc::new(JsonFormatter),
+            OutputFormat::Yaml => Arc::new(YamlFormatter),
+            OutputFormat::Xml => Arc::new(XmlFormatter),
+        }
+    }
+
+    /// Render a walk result in this format
+    ///
+    /// The sections in `WalkResult::content` come from `WalkOptions::formatter`,
+    /// so for `Plain` and `Markdown` this is the content as collected.
+    pub fn render(self, result: &WalkResult) -> Cow<'_, str> {
+        self.formatter(SectionTemplate::default()).render(result)
+    }
+}
+
+/// Structured form of a walk result: prefix, file entries, truncation, suffix and stats
+pub fn document(result: &WalkResult) -> Value {
+    let files = result
+        .files
+        .iter()
+        .map(|file| {
+            Value::object([
+                ("path", file.path.display().to_string().into()),
+                ("size", file.size.into()),
+                ("tokens", file.tokens.into()),
+                ("forced", file.forced.into()),
+                (
+                    "identical_to",
+                    file.identical_to.as_ref().map(|path| path.display().to_string()).into(),
+                ),
+                ("content", result.file_body(file).into()),
+            ])
+        })
+        .collect();
+
+    Value::object([
+        ("prefix", result.prefix.clone().into()),
+        ("files", Value::Array(files)),
+        (
+            "truncated_by",
+            result.truncated_by.map(|limit| limit.to_string()).into(),
+        ),
+        ("suffix", result.suffix.clone().into()),
+        ("stats", result.stats.to_value()),
+    ])
+}
+
+/// One line per included file with its size and tokens, followed by a total
+///
+/// Used by `--list` to preview what a run would collect.
+pub fn listing(result: &WalkResult) -> String {
+    let mut output = String::new();
+
+    for file in &result.files {
+        output.push_str(&format!(
+            "{:>10}  {:>7} tokens  {}{}\n",
+            ByteFormatter::format(file.size),
+            TokenEstimator::format(file.tokens),
+            file.path.display(),
+            if file.forced { " (forced)" } else { "" }
+        ));
+    }
+
+    let size: usize = result.files.iter().map(|file| file.size).sum();
+    let tokens: usize = result.files.iter().map(|file| file.tokens).sum();
+    output.push_str(&format!(
+        "{} {}, {}, {} tokens\n",
+        result.files.len(),
+        if result.files.len() == 1 { "file" } else { "files" },
+        ByteFormatter::format(size),
+        TokenEstimator::format(tokens)
+    ));
+    if let Some(limit) = result.truncated_by {
+        output.push_str(&format!("Stopped at the {} limit\n", limit));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::walker::{WalkOptions, walk_and_collect};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_structured_formats() {
+        let dir = PathBuf::from("test_output_formats");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "first line\nsecond line\n").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+
+        let json = OutputFormat::Json.render(&result);
+        assert!(json.contains("\"path\": \"test_output_formats/notes.txt\""));
+        assert!(json.contains("\"content\": \"first line\\nsecond line\\n\""));
+        assert!(json.contains("\"truncated_by\": null"));
+        assert!(json.contains("\"files_processed\": 1"));
+
+        let yaml = OutputFormat::Yaml.render(&result);
+        assert!(yaml.contains("- path: test_output_formats/notes.txt\n"));
+        assert!(yaml.contains("    content: |\n      first line\n      second line\n"));
+        assert!(yaml.contains("  files_processed: 1\n"));
+
+        assert_eq!(OutputFormat::Plain.render(&result), result.content);
+        assert_eq!(OutputFormat::parse("YAML").unwrap(), OutputFormat::Yaml);
+        assert!(OutputFormat::parse("toml").is_err());
+
+        let xml = OutputFormat::Xml.render(&result);
+        assert!(xml.starts_with(
+            "<index>\n<file index=\"1\" path=\"test_output_formats/notes.txt\" size=\"61\" tokens=\"19\"/>\n</index>\n"
+        ));
+        assert!(xml.contains(
+            "<document index=\"1\" path=\"test_output_formats/notes.txt\">\nfirst line\nsecond line\n</document>\n"
+        ));
+        assert_eq!(xml_escape("a < b && \"c\" > d"), "a &lt; b &amp;&amp; &quot;c&quot; &gt; d");
+
+        let markdown = MarkdownFormatter.section(Path::new("src/lib.rs"), "/// ```\n/// f();\n/// ```\npub fn f() {}");
+        assert_eq!(
+            markdown.text,
+            "## src/lib.rs\n\n````rust\n/// ```\n/// f();\n/// ```\npub fn f() {}\n````\n"
+        );
+        assert_eq!(&markdown.text[markdown.body], "/// ```\n/// f();\n/// ```\npub fn f() {}");
+        assert_eq!(OutputFormat::parse("md").unwrap(), OutputFormat::Markdown);
+
+        assert_eq!(number_lines("", 1), "");
+        let text: String = (1..=10).map(|i| if i == 2 { "\n".to_string() } else { format!("line {}\n", i) }).collect();
+        let numbered = number_lines(&text, 1);
+        assert!(numbered.starts_with(" 1 | line 1\n 2 |\n 3 | line 3\n"));
+        assert!(numbered.ends_with("10 | line 10\n"));
+        assert_eq!(number_lines("a\nb", 1), "1 | a\n2 | b");
+        assert_eq!(number_lines("x\ny\n", 99), " 99 | x\n100 | y\n");
+
+        let listing = listing(&result);
+        assert!(listing.contains("tokens  test_output_formats/notes.txt\n"));
+        assert!(listing.ends_with("1 file, 61 B, 19 tokens\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
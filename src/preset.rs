@@ -0,0 +1,75 @@
+/// A named set of exclude and include patterns for a kind of project
+///
+/// Patterns follow the same rules as `WalkOptions::exclude_patterns`: a
+/// pattern with a `/` matches the root-relative path, others the name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preset {
+    /// Files and directories to leave out
+    pub exclude: Vec<String>,
+    /// If non-empty, only files matching one of these are collected
+    pub include: Vec<String>,
+}
+
+impl Preset {
+    /// Names of the presets built into rcat
+    pub const BUILTIN: &'static [&'static str] = &["rust", "node", "python", "go"];
+
+    /// One of the built-in presets, by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let exclude: &[&str] = match name {
+            "rust" => &["target", "Cargo.lock", "*.rs.bk", "*.profraw"],
+            "node" => &[
+                "node_modules",
+                "package-lock.json",
+                "yarn.lock",
+                "pnpm-lock.yaml",
+                ".next",
+                ".nuxt",
+                ".turbo",
+                ".parcel-cache",
+                "coverage",
+                "dist",
+                "build",
+                "*.min.js",
+                "*.map",
+                "*.tsbuildinfo",
+            ],
+            "python" => &[
+                "__pycache__",
+                "*.pyc",
+                ".venv",
+                "venv",
+                ".tox",
+                ".mypy_cache",
+                ".pytest_cache",
+                ".ruff_cache",
+                "*.egg-info",
+                "htmlcov",
+                ".coverage",
+                "poetry.lock",
+                "Pipfile.lock",
+            ],
+            "go" => &["vendor", "bin", "go.sum", "*.test", "coverage.out"],
+            _ => return None,
+        };
+
+        Some(Self {
+            exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+            include: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_presets() {
+        for name in Preset::BUILTIN {
+            assert!(!Preset::builtin(name).unwrap().exclude.is_empty(), "{} is empty", name);
+        }
+        assert!(Preset::builtin("node").unwrap().exclude.contains(&".next".to_string()));
+        assert!(Preset::builtin("cobol").is_none());
+    }
+}
@@ -0,0 +1,28 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::skip::SkipReason;
+use crate::walker::Limit;
+
+/// Something that happened during a walk, reported to `WalkOptions::progress`
+#[derive(Debug)]
+pub enum ProgressEvent<'a> {
+    /// A file is about to be read
+    FileStarted { path: &'a Path },
+    /// A file's content made it into the output
+    FileIncluded {
+        path: &'a Path,
+        size: usize,
+        tokens: usize,
+    },
+    /// A file or directory was left out
+    FileSkipped {
+        path: &'a Path,
+        reason: &'a SkipReason,
+    },
+    /// A limit was reached and the walk stops
+    Truncated { limit: Limit },
+}
+
+/// Callback receiving progress events as the walk goes
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent<'_>) + Send + Sync>;
@@ -0,0 +1,413 @@
+use crate::error::Error;
+
+/// A small backtracking regular expression engine
+///
+/// Supports literals, `.`, character classes (`[a-z]`, `[^x]`, `\d`, `\w`, `\s`
+/// and their negations), anchors (`^`, `$`), groups (`(...)`, `(?:...)`),
+/// alternation (`|`) and quantifiers (`*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`,
+/// each optionally lazy with a trailing `?`). Matching is unanchored unless
+/// the pattern uses `^`/`$`.
+#[derive(Clone, Debug)]
+pub struct Regex {
+    pattern: String,
+    node: Node,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        found != self.negated
+    }
+
+    fn digit(negated: bool) -> Self {
+        Self {
+            ranges: vec![('0', '9')],
+            negated,
+        }
+    }
+
+    fn word(negated: bool) -> Self {
+        Self {
+            ranges: vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            negated,
+        }
+    }
+
+    fn space(negated: bool) -> Self {
+        Self {
+            ranges: vec![(' ', ' '), ('\t', '\r')],
+            negated,
+        }
+    }
+}
+
+impl Regex {
+    /// Compile a pattern, returning a descriptive error if it is malformed
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.parse_alt().map_err(Error::Pattern)?;
+        if parser.pos < parser.chars.len() {
+            return Err(Error::Pattern(format!("Unmatched ')' in regex: {}", pattern)));
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            node,
+        })
+    }
+
+    /// The source pattern
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Check if the pattern matches anywhere in the text
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        (0..=chars.len()).any(|start| Matcher { text: &chars }.matches(&self.node, start, &mut |_| true))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alt(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("Unexpected end of regex")?;
+        self.pos += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                // Non-capturing groups behave the same as plain groups here
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if !self.eat(':') {
+                        return Err("Only (?:...) group modifiers are supported".to_string());
+                    }
+                }
+                let inner = self.parse_alt()?;
+                if !self.eat(')') {
+                    return Err("Unclosed '(' in regex".to_string());
+                }
+                Ok(inner)
+            }
+            '[' => self.parse_class().map(Node::Class),
+            '\\' => self.parse_escape(),
+            '*' | '+' | '?' => Err(format!("Nothing to repeat before '{}'", c)),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("Trailing backslash in regex")?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => Node::Class(Class::digit(false)),
+            'D' => Node::Class(Class::digit(true)),
+            'w' => Node::Class(Class::word(false)),
+            'W' => Node::Class(Class::word(true)),
+            's' => Node::Class(Class::space(false)),
+            'S' => Node::Class(Class::space(true)),
+            'n' => Node::Char('\n'),
+            't' => Node::Char('\t'),
+            c => Node::Char(c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Class, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.peek().ok_or("Unclosed '[' in regex")?;
+            self.pos += 1;
+
+            // A ']' right after '[' or '[^' is a literal
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if c == '\\' {
+                let escaped = self.peek().ok_or("Trailing backslash in regex")?;
+                self.pos += 1;
+                match escaped {
+                    'd' | 'w' | 's' => {
+                        let class = match escaped {
+                            'd' => Class::digit(false),
+                            'w' => Class::word(false),
+                            _ => Class::space(false),
+                        };
+                        ranges.extend(class.ranges);
+                        continue;
+                    }
+                    'n' => '\n',
+                    't' => '\t',
+                    other => other,
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let hi = self.peek().ok_or("Unclosed '[' in regex")?;
+                self.pos += 1;
+                if hi < lo {
+                    return Err(format!("Invalid range {}-{} in regex", lo, hi));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+
+        Ok(Class { ranges, negated })
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                (0, None)
+            }
+            Some('+') => {
+                self.pos += 1;
+                (1, None)
+            }
+            Some('?') => {
+                self.pos += 1;
+                (0, Some(1))
+            }
+            Some('{') => match self.parse_braces()? {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        let greedy = !self.eat('?');
+
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Parse `{n}`, `{n,}` or `{n,m}`; a '{' not followed by that shape is a literal
+    fn parse_braces(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let Some(close) = rest.find('}') else {
+            return Ok(None);
+        };
+        let body = &rest[1..close];
+        let (min_str, max_str) = match body.split_once(',') {
+            Some((min, max)) => (min, Some(max)),
+            None => (body, None),
+        };
+
+        let Ok(min) = min_str.parse::<usize>() else {
+            return Ok(None);
+        };
+        let max = match max_str {
+            None => Some(min),
+            Some("") => None,
+            Some(max) => match max.parse::<usize>() {
+                Ok(max) if max >= min => Some(max),
+                Ok(_) => return Err(format!("Invalid repetition {{{}}} in regex", body)),
+                Err(_) => return Ok(None),
+            },
+        };
+
+        self.pos += rest[..=close].chars().count();
+        Ok(Some((min, max)))
+    }
+}
+
+struct Matcher<'a> {
+    text: &'a [char],
+}
+
+impl Matcher<'_> {
+    /// Match `node` at `pos`, calling `next` with each possible end position until it accepts
+    fn matches(&self, node: &Node, pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        match node {
+            Node::Char(c) => self.text.get(pos) == Some(c) && next(pos + 1),
+            Node::Any => pos < self.text.len() && self.text[pos] != '\n' && next(pos + 1),
+            Node::Class(class) => self.text.get(pos).is_some_and(|&c| class.matches(c)) && next(pos + 1),
+            Node::Start => pos == 0 && next(pos),
+            Node::End => pos == self.text.len() && next(pos),
+            Node::Concat(nodes) => self.matches_sequence(nodes, pos, next),
+            Node::Alt(branches) => branches.iter().any(|branch| self.matches(branch, pos, next)),
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => self.matches_repeat(node, *min, *max, *greedy, 0, pos, next),
+        }
+    }
+
+    fn matches_sequence(&self, nodes: &[Node], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        match nodes.split_first() {
+            None => next(pos),
+            Some((first, rest)) => self.matches(first, pos, &mut |end| self.matches_sequence(rest, end, next)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn matches_repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        count: usize,
+        pos: usize,
+        next: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        if count < min {
+            return self.matches(node, pos, &mut |end| {
+                self.matches_repeat(node, min, max, greedy, count + 1, end, next)
+            });
+        }
+
+        let can_repeat = max.is_none_or(|max| count < max);
+        // Empty iterations can't make progress, so they don't count as another repetition
+        let repeat = |next: &mut dyn FnMut(usize) -> bool| {
+            can_repeat
+                && self.matches(node, pos, &mut |end| {
+                    end != pos && self.matches_repeat(node, min, max, greedy, count + 1, end, next)
+                })
+        };
+
+        // Greedy repetition tries another iteration first, lazy tries the continuation first
+        match greedy {
+            true => repeat(next) || next(pos),
+            false => next(pos) || repeat(next),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_basic_matching() {
+        assert!(is_match("abc", "xxabcxx"));
+        assert!(!is_match("abd", "xxabcxx"));
+        assert!(is_match("^abc$", "abc"));
+        assert!(!is_match("^abc$", "abcd"));
+        assert!(is_match("a.c", "abc"));
+        assert!(is_match(r"\.rs$", "src/main.rs"));
+        assert!(!is_match(r"\.rs$", "src/main_rs"));
+    }
+
+    #[test]
+    fn test_quantifiers_and_groups() {
+        assert!(is_match("^ab*c$", "ac"));
+        assert!(is_match("^ab*c$", "abbbc"));
+        assert!(!is_match("^ab+c$", "ac"));
+        assert!(is_match("^colou?r$", "color"));
+        assert!(is_match(r"^\d{3}-\d{2,}$", "123-45678"));
+        assert!(!is_match(r"^\d{3}$", "1234"));
+        assert!(is_match("^(ab)+$", "ababab"));
+        assert!(is_match("^(?:a|b)*c$", "abbac"));
+        assert!(is_match("^a.*?b$", "axxb"));
+        assert!(is_match("^(a*)*$", "aaa"));
+    }
+
+    #[test]
+    fn test_classes_and_alternation() {
+        assert!(is_match("^[a-c]+$", "abcabc"));
+        assert!(!is_match("^[a-c]+$", "abd"));
+        assert!(is_match("^[^x]$", "y"));
+        assert!(!is_match("^[^x]$", "x"));
+        assert!(is_match(r"^\w+\s\w+$", "hello world"));
+        assert!(is_match(r"src/(api|core)/.*\.rs$", "src/core/lib.rs"));
+        assert!(!is_match(r"src/(api|core)/.*\.rs$", "src/cli/main.rs"));
+        assert!(is_match(r".*_(test|spec)\.(js|ts)$", "widget_spec.ts"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("[abc").is_err());
+        assert!(Regex::new("*abc").is_err());
+        assert!(Regex::new("[z-a]").is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Write;
+
+/// A structured value that can be written as JSON or YAML
+///
+/// Structured output formats build one of these and pick a writer, so every
+/// format shares the same shape.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Key/value pairs, written in insertion order
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Build an object from key/value pairs
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Value)>) -> Self {
+        Value::Object(entries.into_iter().map(|(key, value)| (key.into(), value)).collect())
+    }
+
+    /// The value under `key`, if this is an object that has one
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Write as pretty-printed JSON with two-space indentation
+    pub fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(&mut output, 0);
+        output.push('\n');
+        output
+    }
+
+    /// Write as a block-style YAML document
+    pub fn to_yaml(&self) -> String {
+        let mut output = String::new();
+        match self {
+            Value::Object(entries) if !entries.is_empty() => write_yaml_entries(entries, &mut output, 0),
+            Value::Array(items) if !items.is_empty() => write_yaml_items(items, &mut output, 0),
+            scalar => {
+                write_yaml_scalar(scalar, &mut output, 0);
+                output.push('\n');
+            }
+        }
+        output
+    }
+
+    fn write_json(&self, output: &mut String, indent: usize) {
+        match self {
+            Value::Array(items) if !items.is_empty() => {
+                output.push_str("[\n");
+                for (index, item) in items.iter().enumerate() {
+                    push_indent(output, indent + 2);
+                    item.write_json(output, indent + 2);
+                    output.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                push_indent(output, indent);
+                output.push(']');
+            }
+            Value::Object(entries) if !entries.is_empty() => {
+                output.push_str("{\n");
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    push_indent(output, indent + 2);
+                    write_json_string(key, output);
+                    output.push_str(": ");
+                    value.write_json(output, indent + 2);
+                    output.push_str(if index + 1 < entries.len() { ",\n" } else { "\n" });
+                }
+                push_indent(output, indent);
+                output.push('}');
+            }
+            Value::Array(_) => output.push_str("[]"),
+            Value::Object(_) => output.push_str("{}"),
+            Value::String(text) => write_json_string(text, output),
+            scalar => output.push_str(&scalar_text(scalar, "null")),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::String(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as u64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+fn push_indent(output: &mut String, indent: usize) {
+    output.extend(std::iter::repeat_n(' ', indent));
+}
+
+/// Text of a non-string scalar, with `null` spelled the way the format wants
+fn scalar_text(value: &Value, null: &str) -> String {
+    match value {
+        Value::Bool(flag) => flag.to_string(),
+        Value::Int(number) => number.to_string(),
+        Value::Float(number) if number.is_finite() => {
+            let text = number.to_string();
+            if text.contains(['.', 'e']) { text } else { format!("{}.0", text) }
+        }
+        _ => null.to_string(),
+    }
+}
+
+fn write_json_string(text: &str, output: &mut String) {
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+fn write_yaml_entries(entries: &[(String, Value)], output: &mut String, indent: usize) {
+    for (key, value) in entries {
+        push_indent(output, indent);
+        write_yaml_key(key, output);
+        output.push(':');
+        write_yaml_nested(value, output, indent);
+    }
+}
+
+fn write_yaml_items(items: &[Value], output: &mut String, indent: usize) {
+    for item in items {
+        push_indent(output, indent);
+        output.push('-');
+        match item {
+            // The first entry shares the dash's line; the rest line up under it
+            Value::Object(entries) if !entries.is_empty() => {
+                let mut nested = String::new();
+                write_yaml_entries(entries, &mut nested, indent + 2);
+                output.push(' ');
+                output.push_str(&nested[indent + 2..]);
+            }
+            other => write_yaml_nested(other, output, indent),
+        }
+    }
+}
+
+/// Write a value that follows a `key:` or `-` at `indent`
+fn write_yaml_nested(value: &Value, output: &mut String, indent: usize) {
+    match value {
+        Value::Object(entries) if !entries.is_empty() => {
+            output.push('\n');
+            write_yaml_entries(entries, output, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            output.push('\n');
+            write_yaml_items(items, output, indent + 2);
+        }
+        scalar => {
+            output.push(' ');
+            write_yaml_scalar(scalar, output, indent + 2);
+            output.push('\n');
+        }
+    }
+}
+
+fn write_yaml_key(key: &str, output: &mut String) {
+    if is_plain_yaml(key) {
+        output.push_str(key);
+    } else {
+        write_json_string(key, output);
+    }
+}
+
+/// Write a scalar; multi-line strings become literal blocks indented to `indent`
+fn write_yaml_scalar(value: &Value, output: &mut String, indent: usize) {
+    match value {
+        Value::Array(_) => output.push_str("[]"),
+        Value::Object(_) => output.push_str("{}"),
+        Value::String(text) if is_literal_block(text) => write_yaml_block(text, output, indent),
+        Value::String(text) if is_plain_yaml(text) => output.push_str(text),
+        // YAML double-quoted strings accept JSON escapes
+        Value::String(text) => write_json_string(text, output),
+        Value::Float(number) if number.is_nan() => output.push_str(".nan"),
+        scalar => output.push_str(&scalar_text(scalar, "null")),
+    }
+}
+
+fn write_yaml_block(text: &str, output: &mut String, indent: usize) {
+    output.push('|');
+    // Leading spaces on the first line would be mistaken for indentation
+    if text.starts_with(' ') {
+        output.push('2');
+    }
+    // Chomping: strip a missing final newline, keep extra trailing blank lines
+    if !text.ends_with('\n') {
+        output.push('-');
+    } else if text.ends_with("\n\n") {
+        output.push('+');
+    }
+
+    for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+        output.push('\n');
+        if !line.is_empty() {
+            push_indent(output, indent);
+            output.push_str(line);
+        }
+    }
+}
+
+/// Multi-line strings without characters a literal block can't hold
+fn is_literal_block(text: &str) -> bool {
+    text.contains('\n')
+        && !text.starts_with('\n')
+        && !text
+            .chars()
+            .any(|c| (c.is_control() && c != '\n' && c != '\t') || c == '\u{feff}')
+}
+
+/// Strings that read back as the same string without quoting
+fn is_plain_yaml(text: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "true", "false", "yes", "no", "on", "off", "null", "~", "y", "n",
+    ];
+
+    let Some(first) = text.chars().next() else {
+        return false;
+    };
+
+    (first.is_alphanumeric() || "/._".contains(first))
+        && !text.ends_with(' ')
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with(':')
+        && !text.chars().any(|c| c.is_control() || "\"'`{}[],&*!|>%@".contains(c))
+        && !RESERVED.contains(&text.to_lowercase().as_str())
+        && !looks_numeric(text)
+}
+
+/// Whether YAML would read the text as a number (or a date/time)
+fn looks_numeric(text: &str) -> bool {
+    let first = text.chars().next().unwrap_or_default();
+    (first.is_ascii_digit() || first == '.')
+        && text.chars().all(|c| c.is_ascii_hexdigit() || "._:+-xXoO".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        Value::object([
+            ("name", Value::from("src/main.rs")),
+            ("size", Value::from(42usize)),
+            ("ratio", Value::from(0.5)),
+            ("missing", Value::Null),
+            (
+                "files",
+                Value::Array(vec![
+                    Value::object([("path", Value::from("a.txt")), ("content", Value::from("one\ntwo\n"))]),
+                    Value::object([("path", Value::from("yes")), ("content", Value::from("tab\there"))]),
+                ]),
+            ),
+            ("empty", Value::Array(Vec::new())),
+        ])
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            sample().to_json(),
+            r#"{
+  "name": "src/main.rs",
+  "size": 42,
+  "ratio": 0.5,
+  "missing": null,
+  "files": [
+    {
+      "path": "a.txt",
+      "content": "one\ntwo\n"
+    },
+    {
+      "path": "yes",
+      "content": "tab\there"
+    }
+  ],
+  "empty": []
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_yaml() {
+        assert_eq!(
+            sample().to_yaml(),
+            "name: src/main.rs\n\
+             size: 42\n\
+             ratio: 0.5\n\
+             missing: null\n\
+             files:\n  \
+               - path: a.txt\n    \
+                 content: |\n      \
+                   one\n      \
+                   two\n  \
+               - path: \"yes\"\n    \
+                 content: \"tab\\there\"\n\
+             empty: []\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_block_chomping() {
+        let block = |text: &str| {
+            let mut output = String::new();
+            write_yaml_block(text, &mut output, 2);
+            output
+        };
+
+        assert_eq!(block("a\nb"), "|-\n  a\n  b");
+        assert_eq!(block("a\n\nb\n"), "|\n  a\n\n  b");
+        assert_eq!(block("a\n\n"), "|+\n  a\n");
+        assert_eq!(block("  indented\nline\n"), "|2\n    indented\n  line");
+    }
+
+    #[test]
+    fn test_yaml_quoting() {
+        assert!(is_plain_yaml("src/lib.rs"));
+        assert!(is_plain_yaml(".gitignore"));
+        assert!(!is_plain_yaml("123"));
+        assert!(!is_plain_yaml("1.5"));
+        assert!(!is_plain_yaml("No"));
+        assert!(!is_plain_yaml("key: value"));
+        assert!(!is_plain_yaml("*.log"));
+        assert!(!is_plain_yaml(""));
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::format::ByteFormatter;
+
+/// Why a file or directory was left out of the output
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// Name starts with '.'
+    Hidden,
+    /// Matched by a line of an ignore file (`.gitignore`, `.rcatignore`, ...)
+    Gitignored { source: PathBuf, line: usize, pattern: String },
+    /// Marked `linguist-generated` or `linguist-vendored` in a `.gitattributes` file
+    Generated { attribute: String, source: PathBuf },
+    /// Starts with a comment marking it as generated, like `@generated` or `DO NOT EDIT`
+    GeneratedMarker { marker: String },
+    /// Matched by an `--exclude` pattern or `--exclude-regex`
+    Excluded { pattern: String },
+    /// Matched by the built-in default excludes (dependency and build directories, lockfiles)
+    DefaultExcluded { pattern: String },
+    /// Named like a file that holds credentials (see `Config::SENSITIVE_FILES`)
+    Sensitive { pattern: String },
+    /// Not tracked by git (with `--git`)
+    Untracked,
+    /// Unchanged relative to a git ref (with `--diff`)
+    Unchanged { reference: String },
+    /// Same content as in the last cached run (with `--changed-only`)
+    UnchangedSinceLastRun,
+    /// Left unchecked in the file picker (with `--interactive`)
+    NotSelected,
+    /// Didn't match any of the include filters (e.g. `--path-regex`)
+    Filtered,
+    /// Larger than the per-file size limit
+    TooLarge { size: usize, limit: usize },
+    /// Smaller than the minimum file size
+    TooSmall { size: usize, limit: usize },
+    /// Empty or only whitespace (with `--skip-empty`)
+    Empty,
+    /// Binary content, skipped unless binaries are included
+    Binary,
+    /// Couldn't be read as text
+    Unreadable { cause: UnreadableCause },
+    /// Same file already reached through another path
+    Duplicate { of: PathBuf },
+    /// A symbolic link, skipped when links aren't followed
+    Symlink,
+    /// On a different filesystem than its root (with `--one-file-system`)
+    OtherFilesystem,
+    /// Below the maximum directory depth
+    Depth,
+    /// Past the per-directory file limit
+    DirectoryLimit { limit: usize },
+    /// Not modified recently enough
+    Mtime,
+    /// Would have exceeded the output size or token budget
+    Budget,
+    /// Left out by a filter from `WalkOptions::filters`, for the reason it gives
+    Custom { filter: String, reason: String },
+}
+
+impl SkipReason {
+    /// Short category name used when summarizing skips
+    pub fn category(&self) -> &'static str {
+        match self {
+            SkipReason::Hidden => "hidden",
+            SkipReason::Gitignored { .. } => "gitignored",
+            SkipReason::Generated { .. } | SkipReason::GeneratedMarker { .. } => "generated",
+            SkipReason::Excluded { .. } => "excluded",
+            SkipReason::DefaultExcluded { .. } => "excluded by default",
+            SkipReason::Sensitive { .. } => "sensitive",
+            SkipReason::Untracked => "untracked",
+            SkipReason::Unchanged { .. } => "unchanged",
+            SkipReason::UnchangedSinceLastRun => "unchanged since last run",
+            SkipReason::NotSelected => "not selected",
+            SkipReason::Filtered => "not matching filters",
+            SkipReason::TooLarge { .. } => "too large",
+            SkipReason::TooSmall { .. } => "too small",
+            SkipReason::Empty => "empty",
+            SkipReason::Binary => "binary",
+            SkipReason::Unreadable { .. } => "unreadable",
+            SkipReason::Duplicate { .. } => "duplicate",
+            SkipReason::Symlink => "symlink",
+            SkipReason::OtherFilesystem => "on another filesystem",
+            SkipReason::Depth => "beyond max depth",
+            SkipReason::DirectoryLimit { .. } => "over per-directory limit",
+            SkipReason::Mtime => "not modified recently",
+            SkipReason::Budget => "over budget",
+            SkipReason::Custom { .. } => "custom filter",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Gitignored { source, line, pattern } => {
+                write!(f, "gitignored by {}:{} ('{}')", source.display(), line, pattern)
+            }
+            SkipReason::Generated { attribute, source } => {
+                write!(f, "marked {} by {}", attribute, source.display())
+            }
+            SkipReason::GeneratedMarker { marker } => write!(f, "generated (marked '{}')", marker),
+            SkipReason::Excluded { pattern } => write!(f, "excluded by pattern '{}'", pattern),
+            SkipReason::DefaultExcluded { pattern } => {
+                write!(f, "excluded by default pattern '{}'", pattern)
+            }
+            SkipReason::TooLarge { size, limit } => write!(
+                f,
+                "too large ({} > {})",
+                ByteFormatter::format(*size),
+                ByteFormatter::format_as_unit(*limit)
+            ),
+            SkipReason::TooSmall { size, limit } => write!(
+                f,
+                "too small ({} < {})",
+                ByteFormatter::format(*size),
+                ByteFormatter::format(*limit)
+            ),
+            SkipReason::Sensitive { pattern } => write!(f, "sensitive file matching '{}'", pattern),
+            SkipReason::Unchanged { reference } => write!(f, "unchanged since {}", reference),
+            SkipReason::Unreadable { cause } => write!(f, "unreadable ({})", cause),
+            SkipReason::Duplicate { of } => write!(f, "duplicate of {}", of.display()),
+            SkipReason::Custom { filter, reason } => write!(f, "{} (filter '{}')", reason, filter),
+            SkipReason::DirectoryLimit { limit } => {
+                write!(f, "past the first {} files in its directory", limit)
+            }
+            other => f.write_str(other.category()),
+        }
+    }
+}
+
+/// Why a file couldn't be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnreadableCause {
+    /// Not readable by the current user
+    PermissionDenied,
+    /// Removed or renamed between being listed and being read
+    Vanished,
+    /// Not valid text in the encoding it was detected as, such as UTF-16 with unpaired surrogates
+    InvalidEncoding,
+    /// An entry of an archive root that couldn't be extracted
+    CorruptArchiveEntry,
+    /// Any other I/O error, with its message
+    Io(String),
+}
+
+impl UnreadableCause {
+    /// The cause of a failed read
+    pub fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => UnreadableCause::PermissionDenied,
+            io::ErrorKind::NotFound => UnreadableCause::Vanished,
+            _ => UnreadableCause::Io(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for UnreadableCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnreadableCause::PermissionDenied => f.write_str("permission denied"),
+            UnreadableCause::Vanished => f.write_str("vanished during walk"),
+            UnreadableCause::InvalidEncoding => f.write_str("invalid encoding"),
+            UnreadableCause::CorruptArchiveEntry => f.write_str("corrupt archive entry"),
+            UnreadableCause::Io(message) => f.write_str(message),
+        }
+    }
+}
+
+/// A path that was skipped, and why
+#[derive(Clone, Debug)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub reason: SkipReason,
+}
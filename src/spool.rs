@@ -0,0 +1,134 @@
+use std::env;
+#[cfg(unix)]
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Output written to a temporary file as it is collected instead of held in
+/// memory, for `WalkOptions::low_memory`
+///
+/// The file is deleted as soon as it's created on Unix, and when it's closed
+/// on Windows, so it doesn't outlive the process however that ends.
+pub struct Spool {
+    path: PathBuf,
+    file: BufWriter<File>,
+    len: usize,
+    // Whether the file position was left at the end by the last write, rather than moved by a read
+    at_end: bool,
+}
+
+impl Spool {
+    /// Create an empty spool in the system's temporary directory
+    pub fn create() -> io::Result<Self> {
+        // Several walks in one process each get a file of their own
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("rcat-{}-{}.spool", std::process::id(), id));
+
+        let mut options = File::options();
+        options.read(true).write(true).create_new(true);
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::OpenOptionsExt;
+
+            const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+            options.custom_flags(FILE_FLAG_DELETE_ON_CLOSE);
+        }
+        let file = options.open(&path)?;
+        #[cfg(unix)]
+        fs::remove_file(&path)?;
+
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            len: 0,
+            at_end: true,
+        })
+    }
+
+    /// Append text to the end
+    pub fn write(&mut self, text: &str) -> io::Result<()> {
+        if !self.at_end {
+            self.file.seek(SeekFrom::End(0))?;
+            self.at_end = true;
+        }
+        self.file.write_all(text.as_bytes())?;
+        self.len += text.len();
+        Ok(())
+    }
+
+    /// Insert text at a byte offset, moving what follows it along
+    ///
+    /// This rewrites everything after `at`, so it's meant for the odd short
+    /// section that goes before the rest, such as the directory tree.
+    pub fn insert(&mut self, at: usize, text: &str) -> io::Result<()> {
+        let mut rest = Vec::new();
+        self.reader_from(at)?.read_to_end(&mut rest)?;
+        self.file.seek(SeekFrom::Start(at as u64))?;
+        self.file.write_all(text.as_bytes())?;
+        self.file.write_all(&rest)?;
+        self.len += text.len();
+        self.at_end = true;
+        Ok(())
+    }
+
+    /// Length of the spooled text in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing was spooled
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Where the temporary file was created, for error messages
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the spooled text from the start
+    pub fn reader(&mut self) -> io::Result<impl BufRead + '_> {
+        self.reader_from(0)
+    }
+
+    /// Read the spooled text from a byte offset, after writing out what's buffered
+    fn reader_from(&mut self, offset: usize) -> io::Result<BufReader<&mut File>> {
+        self.file.seek(SeekFrom::Start(offset as u64))?;
+        self.at_end = false;
+        Ok(BufReader::new(self.file.get_mut()))
+    }
+
+    /// The whole spooled text
+    pub fn read_to_string(&mut self) -> io::Result<String> {
+        let mut text = String::with_capacity(self.len);
+        self.reader()?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool() {
+        let mut spool = Spool::create().unwrap();
+        #[cfg(unix)]
+        assert!(!spool.path().exists());
+        assert!(spool.is_empty());
+
+        spool.write("prefix\n").unwrap();
+        spool.write("--- a.txt ---\na").unwrap();
+        spool.insert(7, "tree\n").unwrap();
+        spool.write("\n--- b.txt ---").unwrap();
+        assert_eq!(spool.read_to_string().unwrap(), "prefix\ntree\n--- a.txt ---\na\n--- b.txt ---");
+
+        // Writing after a read appends rather than overwriting
+        spool.write("\nb").unwrap();
+        assert_eq!(spool.len(), 43);
+        assert_eq!(spool.read_to_string().unwrap(), "prefix\ntree\n--- a.txt ---\na\n--- b.txt ---\nb");
+    }
+}
@@ -1,7 +1,46 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
+use crate::encoding::{Encoding, TextInfo};
+use crate::format::ByteFormatter;
+use crate::serialize::Value;
+use crate::skip::{SkipReason, SkippedEntry};
+use crate::template;
+use crate::tokens::{Model, TokenEstimator};
+
+/// A part of a run timed on its own, to tell which one a slow run spends its time in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Listing the entries of directories
+    Enumerate,
+    /// Loading ignore files and matching paths against them
+    Ignore,
+    /// Reading, decompressing and extracting file content
+    Read,
+    /// Rendering sections and the final output
+    Format,
+    /// Handing the output to the clipboard
+    Clipboard,
+}
+
+impl Phase {
+    /// Every phase, in the order a run goes through them
+    pub const ALL: [Phase; 5] = [Phase::Enumerate, Phase::Ignore, Phase::Read, Phase::Format, Phase::Clipboard];
+
+    /// Name of the phase in stats
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Enumerate => "enumerate",
+            Phase::Ignore => "ignore",
+            Phase::Read => "read",
+            Phase::Format => "format",
+            Phase::Clipboard => "clipboard",
+        }
+    }
+}
+
 /// Statistics collector for tracking processing metrics
 pub struct StatsCollector {
     files_processed: usize,
@@ -9,14 +48,28 @@ pub struct StatsCollector {
     binary_files: usize,
     text_files: usize,
     unreadable_files: usize,
-    skipped_files: usize,
-    skipped_directories: usize,
-    skipped_large_files: usize,
-    gitignored_files: usize,
-    gitignored_directories: usize,
+    skipped: Vec<SkippedEntry>,
+    sensitive_skipped: usize,
+    generated_skipped: usize,
     gitignore_files: Vec<PathBuf>,
     extensions: HashMap<String, usize>,
+    // Tokens of text per extension, `""` for files without one
+    extension_tokens: HashMap<String, usize>,
+    // Files and lines of text per language (see `template::language`), `None` for unknown ones
+    languages: HashMap<Option<&'static str>, (usize, usize)>,
     total_bytes: usize,
+    total_tokens: usize,
+    tokens_exact: bool,
+    model: Option<Model>,
+    transcoded: BTreeMap<Encoding, usize>,
+    bom_files: usize,
+    mixed_line_ending_files: usize,
+    redactions: usize,
+    // Most collected output held in memory at once, in bytes
+    peak_buffer: usize,
+    // Nanoseconds spent in each phase, indexed like `Phase::ALL`; atomic so
+    // phases can be timed through shared references
+    phases: [AtomicU64; 5],
     start_time: Instant,
 }
 
@@ -36,32 +89,47 @@ impl StatsCollector {
             binary_files: 0,
             text_files: 0,
             unreadable_files: 0,
-            skipped_files: 0,
-            skipped_directories: 0,
-            skipped_large_files: 0,
-            gitignored_files: 0,
-            gitignored_directories: 0,
+            skipped: Vec::new(),
+            sensitive_skipped: 0,
+            generated_skipped: 0,
             gitignore_files: Vec::new(),
             extensions: HashMap::new(),
+            extension_tokens: HashMap::new(),
+            languages: HashMap::new(),
             total_bytes: 0,
+            total_tokens: 0,
+            tokens_exact: false,
+            model: None,
+            transcoded: BTreeMap::new(),
+            bom_files: 0,
+            mixed_line_ending_files: 0,
+            redactions: 0,
+            peak_buffer: 0,
+            phases: Default::default(),
             start_time: Instant::now(),
         }
     }
 
-    /// Record a processed text file
-    pub fn record_text_file(&mut self, path: &std::path::Path, size: usize) {
+    /// Record a processed text file, with the number of lines of its content
+    pub fn record_text_file(&mut self, path: &Path, size: usize, tokens: usize, lines: usize) {
         self.files_processed += 1;
         self.text_files += 1;
         self.total_bytes += size;
+        self.total_tokens += tokens;
 
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
+        let language = self.languages.entry(template::language(path)).or_insert((0, 0));
+        language.0 += 1;
+        language.1 += lines;
+
+        let ext_str = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        *self.extension_tokens.entry(ext_str.clone().unwrap_or_default()).or_insert(0) += tokens;
+        if let Some(ext_str) = ext_str {
             *self.extensions.entry(ext_str).or_insert(0) += 1;
         }
     }
 
     /// Record a processed binary file
-    pub fn record_binary_file(&mut self, path: &std::path::Path) {
+    pub fn record_binary_file(&mut self, path: &Path) {
         self.files_processed += 1;
         self.binary_files += 1;
 
@@ -82,29 +150,59 @@ impl StatsCollector {
         self.directories_processed += 1;
     }
 
-    /// Record a skipped file
-    pub fn record_skipped_file(&mut self) {
-        self.skipped_files += 1;
+    /// Record a skipped file or directory and the reason it was skipped
+    ///
+    /// Binary and unreadable files are also counted by `record_binary_file`
+    /// and `record_unreadable_file`; this only notes that they were left out.
+    pub fn record_skip(&mut self, path: &Path, is_dir: bool, reason: SkipReason) {
+        match reason {
+            SkipReason::Sensitive { .. } => self.sensitive_skipped += 1,
+            SkipReason::Generated { .. } | SkipReason::GeneratedMarker { .. } => self.generated_skipped += 1,
+            _ => {}
+        }
+        self.skipped.push(SkippedEntry {
+            path: path.to_path_buf(),
+            is_dir,
+            reason,
+        });
+    }
+
+    /// Get every skipped path with its reason, in walk order
+    pub fn skipped(&self) -> &[SkippedEntry] {
+        &self.skipped
     }
 
-    /// Record a skipped directory
-    pub fn record_skipped_directory(&mut self) {
-        self.skipped_directories += 1;
+    /// Number of files that couldn't be read
+    pub fn unreadable_files(&self) -> usize {
+        self.unreadable_files
     }
 
-    /// Record a gitignored file
-    pub fn record_gitignored_file(&mut self) {
-        self.gitignored_files += 1;
+    /// How many files were unreadable for each cause, in the order the causes were first seen
+    ///
+    /// `--verbose` lists the files themselves among the skipped paths.
+    pub fn unreadable_causes(&self) -> Vec<(String, usize)> {
+        let mut causes: Vec<(String, usize)> = Vec::new();
+        for entry in &self.skipped {
+            let SkipReason::Unreadable { cause } = &entry.reason else {
+                continue;
+            };
+            let cause = cause.to_string();
+            match causes.iter_mut().find(|(seen, _)| *seen == cause) {
+                Some((_, count)) => *count += 1,
+                None => causes.push((cause, 1)),
+            }
+        }
+        causes
     }
 
-    /// Record a gitignored directory
-    pub fn record_gitignored_directory(&mut self) {
-        self.gitignored_directories += 1;
+    /// Number of files skipped for looking like they hold credentials
+    pub fn sensitive_skipped(&self) -> usize {
+        self.sensitive_skipped
     }
 
-    /// Record a large file that was skipped
-    pub fn record_skipped_large_file(&mut self) {
-        self.skipped_large_files += 1;
+    /// Number of paths skipped as generated or vendored code
+    pub fn generated_skipped(&self) -> usize {
+        self.generated_skipped
     }
 
     /// Set gitignore files being used
@@ -112,11 +210,240 @@ impl StatsCollector {
         self.gitignore_files = gitignore_files;
     }
 
+    /// Record encoding and line-ending facts about a text file
+    pub fn record_text_info(&mut self, info: &TextInfo) {
+        if info.transcoded() {
+            *self.transcoded.entry(info.encoding).or_insert(0) += 1;
+        }
+        if info.bom {
+            self.bom_files += 1;
+        }
+        if info.mixed_line_endings {
+            self.mixed_line_ending_files += 1;
+        }
+    }
+
+    /// Record secrets replaced with `[REDACTED]`
+    pub fn record_redactions(&mut self, count: usize) {
+        self.redactions += count;
+    }
+
+    /// Note how much collected output is held in memory, keeping the most seen
+    pub fn record_buffer(&mut self, bytes: usize) {
+        self.peak_buffer = self.peak_buffer.max(bytes);
+    }
+
+    /// Most collected output held in memory at once, in bytes
+    pub fn peak_buffer(&self) -> usize {
+        self.peak_buffer
+    }
+
+    /// Mark token counts as exact rather than estimated
+    pub fn set_tokens_exact(&mut self, exact: bool) {
+        self.tokens_exact = exact;
+    }
+
+    /// Set the model whose context window the tokens are measured against
+    pub fn set_model(&mut self, model: Option<Model>) {
+        self.model = model;
+    }
+
+    /// Add time spent in a phase
+    pub fn record_phase(&self, phase: Phase, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.phases[phase as usize].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Run `f`, adding the time it takes to `phase`
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let value = f();
+        self.record_phase(phase, started.elapsed());
+        value
+    }
+
+    /// Time spent in each phase, in the order of `Phase::ALL`
+    pub fn phase_times(&self) -> Vec<(Phase, Duration)> {
+        Phase::ALL
+            .iter()
+            .map(|phase| (*phase, Duration::from_nanos(self.phases[*phase as usize].load(Ordering::Relaxed))))
+            .collect()
+    }
+
     /// Get elapsed time
     pub fn elapsed(&self) -> Duration {
         self.start_time.elapsed()
     }
 
+    /// Files and lines of text per language, most lines first
+    ///
+    /// Languages are named as in `template::language`, and files without a
+    /// known one are counted as `other`.
+    pub fn languages(&self) -> Vec<(&'static str, usize, usize)> {
+        let mut languages: Vec<_> = self
+            .languages
+            .iter()
+            .map(|(language, (files, lines))| (language.unwrap_or("other"), *files, *lines))
+            .collect();
+        languages.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)));
+        languages
+    }
+
+    /// Tokens of text per extension, most first, with `""` for files without one
+    pub fn extension_tokens(&self) -> Vec<(&str, usize)> {
+        let mut tokens: Vec<_> = self
+            .extension_tokens
+            .iter()
+            .map(|(ext, tokens)| (ext.as_str(), *tokens))
+            .collect();
+        tokens.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        tokens
+    }
+
+    /// Share of the model's context window the tokens fill, if a model was set
+    pub fn context_share(&self) -> Option<f64> {
+        self.model
+            .map(|model| self.total_tokens as f64 / model.context as f64)
+    }
+
+    /// Get the number of tokens collected
+    pub fn total_tokens(&self) -> usize {
+        self.total_tokens
+    }
+
+    /// The statistics as a JSON document, for `--stats-json`
+    pub fn to_json(&self) -> String {
+        self.to_value().to_json()
+    }
+
+    /// Structured form of the statistics, for JSON and YAML output
+    pub fn to_value(&self) -> Value {
+        let mut extensions: Vec<_> = self.extensions.iter().collect();
+        extensions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        Value::object([
+            ("files_processed", self.files_processed.into()),
+            ("directories_processed", self.directories_processed.into()),
+            ("text_files", self.text_files.into()),
+            ("binary_files", self.binary_files.into()),
+            ("unreadable_files", self.unreadable_files.into()),
+            (
+                "unreadable_causes",
+                Value::object(
+                    self.unreadable_causes()
+                        .into_iter()
+                        .map(|(cause, count)| (cause, count.into())),
+                ),
+            ),
+            ("total_bytes", self.total_bytes.into()),
+            ("peak_buffer_bytes", self.peak_buffer.into()),
+            ("total_tokens", self.total_tokens.into()),
+            ("tokens_exact", self.tokens_exact.into()),
+            (
+                "extension_tokens",
+                Value::object(
+                    self.extension_tokens()
+                        .into_iter()
+                        .map(|(ext, tokens)| (ext.to_string(), tokens.into())),
+                ),
+            ),
+            (
+                "model",
+                match self.model {
+                    Some(model) => Value::object([
+                        ("name", model.name.into()),
+                        ("context", model.context.into()),
+                        ("context_share", self.context_share().unwrap_or_default().into()),
+                    ]),
+                    None => Value::Null,
+                },
+            ),
+            ("redactions", self.redactions.into()),
+            ("sensitive_skipped", self.sensitive_skipped.into()),
+            ("generated_skipped", self.generated_skipped.into()),
+            ("elapsed_secs", self.elapsed().as_secs_f64().into()),
+            (
+                "phase_secs",
+                Value::object(
+                    self.phase_times()
+                        .into_iter()
+                        .map(|(phase, elapsed)| (phase.name().to_string(), elapsed.as_secs_f64().into())),
+                ),
+            ),
+            (
+                "ignore_files",
+                Value::Array(
+                    self.gitignore_files
+                        .iter()
+                        .map(|path| path.display().to_string().into())
+                        .collect(),
+                ),
+            ),
+            (
+                "extensions",
+                Value::object(
+                    extensions
+                        .into_iter()
+                        .map(|(ext, count)| (ext.clone(), (*count).into())),
+                ),
+            ),
+            (
+                "languages",
+                Value::object(self.languages().into_iter().map(|(language, files, lines)| {
+                    let counts = Value::object([("files", files.into()), ("lines", lines.into())]);
+                    (language.to_string(), counts)
+                })),
+            ),
+            (
+                "encoding",
+                Value::object([
+                    (
+                        "transcoded",
+                        Value::object(
+                            self.transcoded
+                                .iter()
+                                .map(|(encoding, count)| (encoding.to_string(), (*count).into())),
+                        ),
+                    ),
+                    ("bom_files", self.bom_files.into()),
+                    ("mixed_line_ending_files", self.mixed_line_ending_files.into()),
+                ]),
+            ),
+            (
+                "skipped",
+                Value::Array(
+                    self.skipped
+                        .iter()
+                        .map(|entry| {
+                            Value::object([
+                                ("path", entry.path.display().to_string().into()),
+                                ("is_dir", entry.is_dir.into()),
+                                ("category", entry.reason.category().into()),
+                                ("reason", entry.reason.to_string().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    /// Every skipped path with its reason, one per line, for `--verbose`
+    ///
+    /// Directories end with a `/`; their contents weren't visited, so aren't listed.
+    pub fn format_skipped(&self) -> String {
+        let mut output = vec![format!("Skipped paths ({}):", self.skipped.len())];
+        output.extend(self.skipped.iter().map(|entry| {
+            format!(
+                "  {}{} - {}",
+                entry.path.display(),
+                if entry.is_dir { "/" } else { "" },
+                entry.reason
+            )
+        }));
+        output.join("\n")
+    }
+
     /// Format statistics for display
     pub fn format_stats(&self) -> String {
         let elapsed = self.elapsed();
@@ -131,6 +458,20 @@ impl StatsCollector {
             elapsed.as_secs_f64()
         ));
 
+        // Where the time went, leaving out phases the run didn't go through
+        let phases: Vec<String> = self
+            .phase_times()
+            .into_iter()
+            .filter(|(_, elapsed)| !elapsed.is_zero())
+            .map(|(phase, elapsed)| match elapsed.as_millis() {
+                millis @ 0..1000 => format!("{} {}ms", phase.name(), millis),
+                _ => format!("{} {:.2}s", phase.name(), elapsed.as_secs_f64()),
+            })
+            .collect();
+        if !phases.is_empty() {
+            output.push(format!("Time: {}", phases.join(", ")));
+        }
+
         // Gitignore info
         if !self.gitignore_files.is_empty() {
             let gitignore_names: Vec<String> = self
@@ -138,47 +479,112 @@ impl StatsCollector {
                 .iter()
                 .map(|p| p.display().to_string())
                 .collect();
-            output.push(format!("Using .gitignore: {}", gitignore_names.join(", ")));
+            output.push(format!("Using ignore files: {}", gitignore_names.join(", ")));
         }
 
         // File type breakdown
         if self.files_processed > 0 {
+            let causes: Vec<String> = self
+                .unreadable_causes()
+                .into_iter()
+                .map(|(cause, count)| format!("{} {}", count, cause))
+                .collect();
+            let causes = if causes.is_empty() { String::new() } else { format!(" ({})", causes.join(", ")) };
             output.push(format!(
-                "Files: {} text, {} binary, {} unreadable",
-                self.text_files, self.binary_files, self.unreadable_files
+                "Files: {} text, {} binary, {} unreadable{}",
+                self.text_files, self.binary_files, self.unreadable_files, causes
             ));
         }
 
-        // Skipped items
-        let total_skipped_files = self.skipped_files + self.binary_files + self.gitignored_files + self.skipped_large_files;
-        let total_skipped_dirs = self.skipped_directories + self.gitignored_directories;
+        if self.total_tokens > 0 {
+            let formatted = TokenEstimator::format(self.total_tokens);
+            if self.tokens_exact {
+                output.push(format!("Tokens: {}", formatted));
+            } else {
+                output.push(format!("Estimated tokens: ~{}", formatted));
+            }
+
+            // Which extensions the tokens go to, as bytes and file counts don't say
+            let mark = if self.tokens_exact { "" } else { "~" };
+            let by_extension = self.extension_tokens();
+            let breakdown: Vec<String> = by_extension
+                .iter()
+                .filter(|(_, tokens)| *tokens > 0)
+                .take(6)
+                .map(|(ext, tokens)| {
+                    let ext = if ext.is_empty() { "(none)".to_string() } else { format!(".{}", ext) };
+                    let percent = *tokens as f64 * 100.0 / self.total_tokens as f64;
+                    format!("{} {}{} ({:.0}%)", ext, mark, TokenEstimator::format(*tokens), percent)
+                })
+                .collect();
+            let more = by_extension.iter().filter(|(_, tokens)| *tokens > 0).count() - breakdown.len();
+            let more = if more > 0 { format!(" and {} more", more) } else { String::new() };
+            output.push(format!("Tokens by extension: {}{}", breakdown.join(", "), more));
+        }
+
+        if let (Some(model), Some(share)) = (self.model, self.context_share()) {
+            output.push(format!(
+                "Context: {:.1}% of {}'s {} tokens{}",
+                share * 100.0,
+                model.name,
+                TokenEstimator::format(model.context),
+                if share > 1.0 { " (doesn't fit)" } else { "" }
+            ));
+        }
 
-        if total_skipped_files > 0 || total_skipped_dirs > 0 {
-            let mut skip_reasons = Vec::new();
+        if self.redactions > 0 {
+            output.push(format!(
+                "Redacted {} secret{}",
+                self.redactions,
+                if self.redactions == 1 { "" } else { "s" }
+            ));
+        }
 
-            if self.skipped_files + self.binary_files > 0 {
-                skip_reasons.push(format!(
-                    "{} hidden/binary",
-                    self.skipped_files + self.binary_files
-                ));
-            }
-            if self.skipped_large_files > 0 {
-                skip_reasons.push(format!(
-                    "{} too large",
-                    self.skipped_large_files
-                ));
-            }
-            if self.gitignored_files + self.gitignored_directories > 0 {
-                skip_reasons.push(format!(
-                    "{} gitignored",
-                    self.gitignored_files + self.gitignored_directories
-                ));
+        // Encoding issues that may trip up downstream tools
+        let mut issues = Vec::new();
+        if !self.transcoded.is_empty() {
+            let total: usize = self.transcoded.values().sum();
+            let encodings: Vec<String> = self
+                .transcoded
+                .iter()
+                .map(|(encoding, count)| format!("{} {}", count, encoding))
+                .collect();
+            issues.push(format!("{} non-UTF-8 transcoded ({})", total, encodings.join(", ")));
+        }
+        if self.bom_files > 0 {
+            issues.push(format!("{} with BOM", self.bom_files));
+        }
+        if self.mixed_line_ending_files > 0 {
+            issues.push(format!("{} with mixed line endings", self.mixed_line_ending_files));
+        }
+        if !issues.is_empty() {
+            output.push(format!("Encoding warnings: {}", issues.join(", ")));
+        }
+
+        // Skipped items
+        if !self.skipped.is_empty() {
+            let skipped_dirs = self.skipped.iter().filter(|entry| entry.is_dir).count();
+            let skipped_files = self.skipped.len() - skipped_dirs;
+
+            // Count per category, in order of first appearance
+            let mut categories: Vec<(&str, usize)> = Vec::new();
+            for entry in &self.skipped {
+                let category = entry.reason.category();
+                match categories.iter_mut().find(|(name, _)| *name == category) {
+                    Some((_, count)) => *count += 1,
+                    None => categories.push((category, 1)),
+                }
             }
 
+            let skip_reasons: Vec<String> = categories
+                .iter()
+                .map(|(category, count)| format!("{} {}", count, category))
+                .collect();
+
             output.push(format!(
                 "Skipped: {} files, {} directories ({})",
-                total_skipped_files,
-                total_skipped_dirs,
+                skipped_files,
+                skipped_dirs,
                 skip_reasons.join(", ")
             ));
         }
@@ -199,6 +605,31 @@ impl StatsCollector {
             }
         }
 
+        // Where the lines of text are, which file counts per extension don't show
+        let languages = self.languages();
+        if !languages.is_empty() {
+            let breakdown: Vec<String> = languages
+                .iter()
+                .take(6)
+                .map(|(language, _, lines)| {
+                    format!(
+                        "{} {} line{}",
+                        language_name(language),
+                        TokenEstimator::format(*lines),
+                        if *lines == 1 { "" } else { "s" }
+                    )
+                })
+                .collect();
+            let more = languages.len().saturating_sub(breakdown.len());
+            let more = if more > 0 { format!(" and {} more", more) } else { String::new() };
+            output.push(format!("Languages: {}{}", breakdown.join(", "), more));
+        }
+
+        // What holding the output cost, which `--low-memory` keeps to one section
+        if self.peak_buffer > 0 {
+            output.push(format!("Peak buffer: {}", ByteFormatter::format(self.peak_buffer)));
+        }
+
         // Processing speed
         if elapsed.as_secs_f64() > 0.0 {
             let files_per_sec = self.files_processed as f64 / elapsed.as_secs_f64();
@@ -212,3 +643,23 @@ impl StatsCollector {
         output.join("\n")
     }
 }
+
+/// How a language from `template::language` is written for people, e.g. `TypeScript` for `typescript`
+fn language_name(language: &str) -> String {
+    match language {
+        "javascript" => "JavaScript".to_string(),
+        "typescript" => "TypeScript".to_string(),
+        "cpp" => "C++".to_string(),
+        "csharp" => "C#".to_string(),
+        "jsx" | "tsx" | "php" | "html" | "css" | "scss" | "json" | "yaml" | "toml" | "xml" | "sql" => {
+            language.to_uppercase()
+        }
+        _ => {
+            let mut chars = language.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Range;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::error::{Error, Result};
+use crate::format::{self, ByteFormatter};
+use crate::git::LastCommit;
+
+/// Lines written before and after each file's content
+///
+/// Both are templates with placeholders: `{path}`, `{basename}`, `{size}`
+/// (content bytes), `{lines}` (content lines), `{lang}` (a language name
+/// guessed from the extension, empty if unknown), `{mtime}` (when the file
+/// was last modified, empty if unknown), `{commit}` (the last commit to
+/// touch the file, like `a1b2c3d by Ada on 2024-02-28`, empty if unknown)
+/// and `{meta}` (all of those that are known, like
+/// `1.50 KB, 40 lines, modified 2024-03-01 09:30 UTC, last commit a1b2c3d by Ada on 2024-02-28`).
+/// `{{` and `}}` stand for literal braces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionTemplate {
+    header: String,
+    footer: Option<String>,
+}
+
+/// What is known about a file besides its content, for headers that show it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileMeta {
+    /// When the file was last modified
+    pub modified: Option<SystemTime>,
+    /// The last commit that touched it (see `WalkOptions::git_meta`)
+    pub commit: Option<LastCommit>,
+}
+
+/// A file's formatted section: header, content and footer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub text: String,
+    /// Where the content sits within `text`
+    pub body: Range<usize>,
+}
+
+impl Section {
+    /// The number of lines of the content, without header or footer
+    pub fn line_count(&self) -> usize {
+        self.text[self.body.clone()].lines().count()
+    }
+}
+
+impl SectionTemplate {
+    /// The header rcat writes unless told otherwise
+    pub const DEFAULT_HEADER: &'static str = "--- {path} ---";
+
+    /// The header used with `--header-meta`
+    pub const META_HEADER: &'static str = "--- {path} ({meta}) ---";
+
+    const PLACEHOLDERS: &'static [&'static str] = &["path", "basename", "size", "lines", "lang", "mtime", "commit", "meta"];
+
+    /// Check both templates for unknown placeholders and unbalanced braces
+    pub fn new(header: &str, footer: Option<&str>) -> Result<Self> {
+        for template in std::iter::once(header).chain(footer) {
+            expand(template, |name| Self::PLACEHOLDERS.contains(&name).then(String::new))?;
+        }
+        Ok(Self {
+            header: header.to_string(),
+            footer: footer.map(str::to_string),
+        })
+    }
+
+    /// Whether this is the plain `--- {path} ---` header without a footer
+    pub fn is_default(&self) -> bool {
+        self.header == Self::DEFAULT_HEADER && self.footer.is_none()
+    }
+
+    /// Wrap a file's content in the header and footer, each on a line of its own
+    pub fn render(&self, path: &Path, body: &str) -> Section {
+        self.render_file(path, body, &FileMeta::default())
+    }
+
+    /// Wrap a file's content as `render` does, with what else is known about the file
+    pub fn render_file(&self, path: &Path, body: &str, meta: &FileMeta) -> Section {
+        let value = |name: &str| {
+            Some(match name {
+                "path" => path.display().to_string(),
+                "basename" => path.file_name().map_or_else(
+                    || path.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                ),
+                "size" => body.len().to_string(),
+                "lines" => body.lines().count().to_string(),
+                "lang" => language(path).unwrap_or_default().to_string(),
+                "mtime" => meta.modified.map(format::format_timestamp).unwrap_or_default(),
+                "commit" => meta.commit.as_ref().map(LastCommit::to_string).unwrap_or_default(),
+                "meta" => {
+                    let lines = body.lines().count();
+                    let mut text = format!(
+                        "{}, {} line{}",
+                        ByteFormatter::format(body.len()),
+                        lines,
+                        if lines == 1 { "" } else { "s" }
+                    );
+                    if let Some(modified) = meta.modified {
+                        text.push_str(&format!(", modified {}", format::format_timestamp(modified)));
+                    }
+                    if let Some(commit) = &meta.commit {
+                        text.push_str(&format!(", last commit {}", commit));
+                    }
+                    text
+                }
+                _ => return None,
+            })
+        };
+
+        // Validated in `new`, so expansion can't fail
+        let mut text = expand(&self.header, value).unwrap_or_default();
+        text.push('\n');
+        let start = text.len();
+        text.push_str(body);
+        let body_range = start..text.len();
+
+        if let Some(footer) = &self.footer {
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&expand(footer, value).unwrap_or_default());
+        }
+
+        Section { text, body: body_range }
+    }
+}
+
+impl Default for SectionTemplate {
+    fn default() -> Self {
+        Self {
+            header: Self::DEFAULT_HEADER.to_string(),
+            footer: None,
+        }
+    }
+}
+
+/// Replace each `{name}` in a template with its value, or fail on an unknown name
+fn expand(template: &str, value: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(index) = rest.find(['{', '}']) {
+        output.push_str(&rest[..index]);
+        let brace = rest.as_bytes()[index];
+        rest = &rest[index + 1..];
+
+        if rest.as_bytes().first() == Some(&brace) {
+            output.push(brace as char);
+            rest = &rest[1..];
+            continue;
+        }
+        if brace == b'}' {
+            return Err(Error::Config(format!("Unmatched '}}' in template: {}", template)));
+        }
+
+        let end = rest
+            .find('}')
+            .ok_or_else(|| Error::Config(format!("Unclosed '{{' in template: {}", template)))?;
+        let name = &rest[..end];
+        let text = value(name).ok_or_else(|| {
+            Error::Config(format!(
+                "Unknown placeholder {{{}}} in template (expected {{path}}, {{basename}}, {{size}}, {{lines}}, {{lang}}, {{mtime}}, {{commit}} or {{meta}})",
+                name
+            ))
+        })?;
+        output.push_str(&text);
+        rest = &rest[end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Language name for a file, as used to tag fenced code blocks
+pub fn language(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let language = match extension.as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "sh" | "bash" | "zsh" => "bash",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "sql" => "sql",
+        "md" | "markdown" => "markdown",
+        "lua" => "lua",
+        "dart" => "dart",
+        "scala" => "scala",
+        "ex" | "exs" => "elixir",
+        "hs" => "haskell",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        _ => return None,
+    };
+    Some(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let template = SectionTemplate::new("```{lang} {path} ({lines} lines, {size} B)", Some("```")).unwrap();
+        let section = template.render(Path::new("src/main.rs"), "fn main() {}\n");
+        assert_eq!(section.text, "```rust src/main.rs (1 lines, 13 B)\nfn main() {}\n```");
+        assert_eq!(&section.text[section.body.clone()], "fn main() {}\n");
+
+        let template = SectionTemplate::new("<file name=\"{basename}\">", Some("</file>")).unwrap();
+        let section = template.render(Path::new("docs/notes"), "no newline");
+        assert_eq!(section.text, "<file name=\"notes\">\nno newline\n</file>");
+
+        let section = SectionTemplate::default().render(Path::new("a.txt"), "text");
+        assert_eq!(section.text, "--- a.txt ---\ntext");
+        assert_eq!(section.body, 14..18);
+    }
+
+    #[test]
+    fn test_render_meta() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let template = SectionTemplate::new(SectionTemplate::META_HEADER, None).unwrap();
+        let mut meta = FileMeta {
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000)),
+            commit: None,
+        };
+        let section = template.render_file(Path::new("src/lib.rs"), &"x\n".repeat(768), &meta);
+        assert_eq!(
+            section.text.lines().next(),
+            Some("--- src/lib.rs (1.50 KB, 768 lines, modified 2001-09-09 01:46 UTC) ---")
+        );
+
+        meta.commit = Some(LastCommit {
+            hash: "a1b2c3d".to_string(),
+            author: "Ada".to_string(),
+            date: "2001-09-08".to_string(),
+        });
+        let section = template.render_file(Path::new("a.txt"), "", &meta);
+        assert_eq!(
+            section.text,
+            "--- a.txt (0 B, 0 lines, modified 2001-09-09 01:46 UTC, last commit a1b2c3d by Ada on 2001-09-08) ---\n"
+        );
+
+        // Without a modification time, as for standard input
+        let section = template.render(Path::new("<stdin>"), "one line");
+        assert_eq!(section.text, "--- <stdin> (8 B, 1 line) ---\none line");
+
+        let template = SectionTemplate::new("{path} @ {commit}", None).unwrap();
+        assert_eq!(template.render_file(Path::new("a.txt"), "", &meta).text, "a.txt @ a1b2c3d by Ada on 2001-09-08\n");
+        let template = SectionTemplate::new("{path} [{mtime}{commit}]", None).unwrap();
+        assert_eq!(template.render(Path::new("a.txt"), "").text, "a.txt []\n");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(SectionTemplate::new("{{literal}} {path}", None).is_ok());
+        assert!(matches!(SectionTemplate::new("{name}", None), Err(Error::Config(_))));
+        assert!(SectionTemplate::new("{path", None).is_err());
+        assert!(SectionTemplate::new("{path}", Some("}")).is_err());
+    }
+}
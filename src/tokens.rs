@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// Counts the tokens a model would see for a piece of text
+///
+/// The built-in `TokenEstimator` needs no vocabulary and is fast but approximate.
+/// Exact BPE tokenizers for specific model families are available behind cargo
+/// features (`tiktoken`), so library users can trade binary size for accuracy.
+pub trait Tokenizer: Send + Sync {
+    /// Count the tokens in the given text
+    fn count(&self, text: &str) -> usize;
+
+    /// Whether counts are exact for the target model rather than estimates
+    fn is_exact(&self) -> bool {
+        false
+    }
+}
+
+/// Names accepted by `tokenizer_by_name`, in the order they're listed in help
+pub const TOKENIZER_NAMES: &[&str] = &["estimate", "cl100k", "o200k"];
+
+/// Look up a tokenizer by name
+///
+/// `estimate` is always available; `cl100k` and `o200k` need the `tiktoken` feature.
+pub fn tokenizer_by_name(name: &str) -> Result<Arc<dyn Tokenizer>, Error> {
+    match name {
+        "estimate" => Ok(Arc::new(TokenEstimator)),
+        #[cfg(feature = "tiktoken")]
+        "cl100k" => Ok(Arc::new(BpeTokenizer::cl100k())),
+        #[cfg(feature = "tiktoken")]
+        "o200k" => Ok(Arc::new(BpeTokenizer::o200k())),
+        #[cfg(not(feature = "tiktoken"))]
+        "cl100k" | "o200k" => Err(Error::Config(format!(
+            "Tokenizer '{}' requires rcat to be built with the 'tiktoken' feature",
+            name
+        ))),
+        _ => Err(Error::Config(format!(
+            "Unknown tokenizer: {} (expected one of: {})",
+            name,
+            TOKENIZER_NAMES.join(", ")
+        ))),
+    }
+}
+
+/// A model whose context window output can be measured against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Model {
+    /// Name accepted by `model_by_name`
+    pub name: &'static str,
+    /// How many tokens fit in one request
+    pub context: usize,
+}
+
+/// Models accepted by `model_by_name`, in the order they're listed in help
+pub const MODELS: &[Model] = &[
+    Model { name: "gpt-4o", context: 128_000 },
+    Model { name: "gpt-4.1", context: 1_047_576 },
+    Model { name: "claude", context: 200_000 },
+    Model { name: "gemini", context: 1_048_576 },
+    Model { name: "llama-70b", context: 128_000 },
+];
+
+/// Look up a model by name, ignoring case
+pub fn model_by_name(name: &str) -> Result<Model, Error> {
+    MODELS
+        .iter()
+        .find(|model| model.name.eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<&str> = MODELS.iter().map(|model| model.name).collect();
+            Error::Config(format!("Unknown model: {} (expected one of: {})", name, names.join(", ")))
+        })
+}
+
+/// Heuristic token estimator approximating cl100k-style BPE tokenizers
+pub struct TokenEstimator;
+
+impl Tokenizer for TokenEstimator {
+    fn count(&self, text: &str) -> usize {
+        Self::estimate(text)
+    }
+}
+
+/// Exact BPE tokenizer using OpenAI's published vocabularies
+#[cfg(feature = "tiktoken")]
+pub struct BpeTokenizer {
+    bpe: &'static tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl BpeTokenizer {
+    /// The cl100k_base vocabulary (GPT-4, GPT-3.5)
+    pub fn cl100k() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base_singleton(),
+        }
+    }
+
+    /// The o200k_base vocabulary (GPT-4o and later)
+    pub fn o200k() -> Self {
+        Self {
+            bpe: tiktoken_rs::o200k_base_singleton(),
+        }
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for BpeTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+
+    fn is_exact(&self) -> bool {
+        true
+    }
+}
+
+impl TokenEstimator {
+    /// Common words fit in one token; longer identifiers split into pieces of about this size
+    const CHARS_PER_WORD_TOKEN: usize = 6;
+
+    /// BPE tokenizers split digit runs into groups of at most this many digits
+    const DIGITS_PER_TOKEN: usize = 3;
+
+    /// Estimate the number of tokens in the given text
+    pub fn estimate(text: &str) -> usize {
+        let mut tokens = 0;
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c.is_ascii_alphabetic() || c == '_' {
+                // Words: a leading space merges into the word, long words split into pieces
+                let mut len: usize = 1;
+                while chars
+                    .next_if(|next| next.is_ascii_alphabetic() || *next == '_')
+                    .is_some()
+                {
+                    len += 1;
+                }
+                tokens += len.div_ceil(Self::CHARS_PER_WORD_TOKEN);
+            } else if c.is_ascii_digit() {
+                let mut len: usize = 1;
+                while chars.next_if(|next| next.is_ascii_digit()).is_some() {
+                    len += 1;
+                }
+                tokens += len.div_ceil(Self::DIGITS_PER_TOKEN);
+            } else if c == '\n' {
+                // Runs of newlines are typically a single token
+                while chars.next_if_eq(&'\n').is_some() {}
+                tokens += 1;
+            } else if c.is_whitespace() {
+                // Single spaces merge with the following word; indentation runs are one token
+                let mut len: usize = 1;
+                while chars.next_if(|next| *next != '\n' && next.is_whitespace()).is_some() {
+                    len += 1;
+                }
+                if len > 1 {
+                    tokens += 1;
+                }
+            } else if c.is_ascii_punctuation() {
+                // Common operator pairs like `::`, `->`, `==` usually merge
+                chars.next_if(|next| next.is_ascii_punctuation());
+                tokens += 1;
+            } else {
+                // Non-ASCII characters cost roughly one token each
+                tokens += 1;
+            }
+        }
+
+        tokens
+    }
+
+    /// Format a token count compactly (e.g. "950", "12.3k", "1.2M")
+    pub fn format(tokens: usize) -> String {
+        if tokens < 1_000 {
+            tokens.to_string()
+        } else if tokens < 1_000_000 {
+            format!("{:.1}k", tokens as f64 / 1_000.0)
+        } else {
+            format!("{:.1}M", tokens as f64 / 1_000_000.0)
+        }
+    }
+}
+
+/// Parse a token count with an optional k/M suffix (e.g. "8000", "128k", "1M")
+pub fn parse_token_count(count_str: &str) -> Result<usize, Error> {
+    let count_str = count_str.trim();
+
+    let (number_part, multiplier) = match count_str.chars().last() {
+        Some('k' | 'K') => (&count_str[..count_str.len() - 1], 1_000.0),
+        Some('m' | 'M') => (&count_str[..count_str.len() - 1], 1_000_000.0),
+        _ => (count_str, 1.0),
+    };
+
+    let number: f64 = number_part
+        .trim()
+        .parse()
+        .map_err(|_| Error::Config(format!("Invalid token count: {}", count_str)))?;
+
+    let count = (number * multiplier) as usize;
+    if number < 0.0 || count == 0 {
+        return Err(Error::Config("Token count must be greater than 0".to_string()));
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(TokenEstimator::estimate(""), 0);
+        assert_eq!(TokenEstimator::estimate("hello world"), 2);
+        assert_eq!(TokenEstimator::estimate("fn main() {}"), 4);
+        assert_eq!(TokenEstimator::estimate("1234567"), 3);
+
+        // Close to real tokenizer counts on ordinary prose (10 words + period per sentence)
+        let prose = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let estimate = TokenEstimator::estimate(&prose);
+        assert!((1000..=1200).contains(&estimate), "estimate was {}", estimate);
+    }
+
+    #[test]
+    fn test_format_and_parse() {
+        assert_eq!(TokenEstimator::format(950), "950");
+        assert_eq!(TokenEstimator::format(12_345), "12.3k");
+        assert_eq!(TokenEstimator::format(1_200_000), "1.2M");
+
+        assert_eq!(parse_token_count("8000").unwrap(), 8000);
+        assert_eq!(parse_token_count("128k").unwrap(), 128_000);
+        assert_eq!(parse_token_count("1.5M").unwrap(), 1_500_000);
+        assert!(parse_token_count("lots").is_err());
+        assert!(parse_token_count("0").is_err());
+    }
+
+    #[test]
+    fn test_tokenizer_by_name() {
+        let estimate = tokenizer_by_name("estimate").unwrap();
+        assert_eq!(estimate.count("hello world"), 2);
+        assert!(!estimate.is_exact());
+
+        assert!(tokenizer_by_name("nope").is_err());
+        #[cfg(not(feature = "tiktoken"))]
+        assert!(tokenizer_by_name("cl100k").is_err());
+        #[cfg(feature = "tiktoken")]
+        {
+            let exact = tokenizer_by_name("cl100k").unwrap();
+            assert_eq!(exact.count("hello world"), 2);
+            assert!(exact.is_exact());
+        }
+    }
+
+    #[test]
+    fn test_model_by_name() {
+        assert_eq!(model_by_name("Claude").unwrap().context, 200_000);
+        assert_eq!(model_by_name("gpt-4o").unwrap().name, "gpt-4o");
+        let error = model_by_name("gpt-2").unwrap_err().to_string();
+        assert!(error.contains("gpt-4o, gpt-4.1, claude"), "{}", error);
+    }
+}
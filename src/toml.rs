@@ -0,0 +1,462 @@
+use crate::serialize::Value;
+
+/// Parse a TOML document into a `Value::Object`
+///
+/// Covers the subset config files need: tables and dotted keys, basic and
+/// literal strings (single- and multi-line), integers, floats, booleans,
+/// arrays and inline tables. Arrays of tables, dates and negative integers
+/// are rejected with an error rather than misread.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(offset, c)| self.chars.get(self.pos + offset) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.bump();
+                Ok(())
+            }
+            Some(found) => Err(self.error(format!("expected '{}', found '{}'", c, found))),
+            None => Err(self.error(format!("expected '{}', found end of file", c))),
+        }
+    }
+
+    /// Skip spaces and tabs on the current line
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skip whitespace, newlines and comments, as allowed between array items
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), None | Some('\n')) {
+            self.bump();
+        }
+    }
+
+    /// Require the rest of the line to be blank or a comment
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        if self.starts_with("\r\n") {
+            self.bump();
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(found) => Err(self.error(format!("unexpected '{}' after value", found))),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, String> {
+        let mut root = Value::Object(Vec::new());
+        // Path of the table the following keys belong to
+        let mut table: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') => {
+                    self.bump();
+                    if self.peek() == Some('[') {
+                        return Err(self.error("arrays of tables are not supported"));
+                    }
+                    self.skip_spaces();
+                    table = self.key()?;
+                    self.skip_spaces();
+                    self.expect(']')?;
+                    let line = self.line;
+                    lookup(&mut root, &table).map_err(|message| format!("line {}: {}", line, message))?;
+                }
+                Some(_) => {
+                    let mut path = table.clone();
+                    path.extend(self.key()?);
+                    self.skip_spaces();
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    let line = self.line;
+                    insert(&mut root, &path, value).map_err(|message| format!("line {}: {}", line, message))?;
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    /// A possibly dotted key such as `preset.node` or `"odd key".name`
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.bump();
+                    }
+                    if self.pos == start {
+                        return Err(match self.peek() {
+                            Some(found) => self.error(format!("expected a key, found '{}'", found)),
+                            None => self.error("expected a key, found end of file"),
+                        });
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.bump();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => self.multiline_string("\"\"\"").map(Value::String),
+            Some('\'') if self.starts_with("'''") => self.multiline_string("'''").map(Value::String),
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(_) if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.number(),
+            None => Err(self.error("expected a value, found end of file")),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            // Peeked rather than consumed so the error names the string's own line
+            let Some(c) = self.peek().filter(|&c| c != '\n') else {
+                return Err(self.error("unterminated string"));
+            };
+            self.bump();
+            match c {
+                '"' => return Ok(text),
+                '\\' => text.push(self.escape()?),
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.peek().filter(|&c| c != '\n') else {
+                return Err(self.error("unterminated string"));
+            };
+            self.bump();
+            if c == '\'' {
+                return Ok(text);
+            }
+            text.push(c);
+        }
+    }
+
+    /// A `"""` or `'''` string; escapes only apply to the former
+    fn multiline_string(&mut self, quotes: &str) -> Result<String, String> {
+        self.pos += 3;
+        // A newline right after the opening quotes isn't part of the string
+        if self.starts_with("\r\n") {
+            self.bump();
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+        }
+
+        let mut text = String::new();
+        loop {
+            if self.starts_with(quotes) {
+                self.pos += 3;
+                return Ok(text);
+            }
+            match self.bump() {
+                Some('\\') if quotes == "\"\"\"" => {
+                    // A backslash at the end of a line trims the following whitespace
+                    if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.skip_blank_in_string();
+                    } else {
+                        text.push(self.escape()?);
+                    }
+                }
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn skip_blank_in_string(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+            self.bump();
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.bump() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some(kind @ ('u' | 'U')) => {
+                let digits = if kind == 'u' { 4 } else { 8 };
+                let hex: String = (0..digits).filter_map(|_| self.bump()).collect();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error(format!("invalid unicode escape: \\{}{}", kind, hex)))?
+            }
+            Some(other) => return Err(self.error(format!("invalid escape: \\{}", other))),
+            None => return Err(self.error("unterminated string")),
+        };
+        Ok(c)
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {}
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut table = Value::Object(Vec::new());
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(table);
+        }
+        loop {
+            let path = self.key()?;
+            self.skip_spaces();
+            self.expect('=')?;
+            self.skip_spaces();
+            let value = self.value()?;
+            insert(&mut table, &path, value).map_err(|message| self.error(message))?;
+            self.skip_spaces();
+            match self.bump() {
+                Some(',') => self.skip_spaces(),
+                Some('}') => return Ok(table),
+                _ => return Err(self.error("expected ',' or '}' in inline table")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-._".contains(c)) {
+            self.bump();
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        let digits = raw.replace('_', "");
+
+        if let Ok(number) = digits.strip_prefix('+').unwrap_or(&digits).parse::<u64>() {
+            return Ok(Value::Int(number));
+        }
+        if digits.contains(['.', 'e', 'E'])
+            && let Ok(number) = digits.parse::<f64>()
+        {
+            return Ok(Value::Float(number));
+        }
+        if raw.is_empty() {
+            let found = match self.peek() {
+                None => "end of file".to_string(),
+                Some('\r' | '\n') => "end of line".to_string(),
+                Some(c) => format!("'{}'", c),
+            };
+            return Err(self.error(format!("expected a value, found {}", found)));
+        }
+        Err(self.error(format!("unsupported value: {}", raw)))
+    }
+}
+
+/// The table at `path`, creating missing tables along the way
+fn lookup<'a>(root: &'a mut Value, path: &[String]) -> Result<&'a mut Vec<(String, Value)>, String> {
+    let Value::Object(entries) = root else {
+        unreachable!("lookup starts from a table");
+    };
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(entries);
+    };
+
+    let index = match entries.iter().position(|(key, _)| key == first) {
+        Some(index) => index,
+        None => {
+            entries.push((first.clone(), Value::Object(Vec::new())));
+            entries.len() - 1
+        }
+    };
+    match &mut entries[index].1 {
+        table @ Value::Object(_) => lookup(table, rest),
+        _ => Err(format!("'{}' is already defined as a value", first)),
+    }
+}
+
+fn insert(root: &mut Value, path: &[String], value: Value) -> Result<(), String> {
+    let (key, tables) = path.split_last().expect("keys have at least one part");
+    let entries = lookup(root, tables)?;
+    if entries.iter().any(|(existing, _)| existing == key) {
+        return Err(format!("duplicate key '{}'", path.join(".")));
+    }
+    entries.push((key.clone(), value));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_document() {
+        let text = r#"
+# Project settings
+max-size = "2MB"
+depth = 3
+ratio = 0.5
+tree = true
+
+[preset.web]
+exclude = [
+    ".next",   # build output
+    'coverage',
+]
+limits = { files = 10, tokens = 1_000 }
+
+[preset."my app"]
+prefix = """
+Review this:
+  carefully"""
+"#;
+        let value = parse(text).unwrap();
+        let expected = Value::object([
+            ("max-size", Value::from("2MB")),
+            ("depth", Value::Int(3)),
+            ("ratio", Value::Float(0.5)),
+            ("tree", Value::Bool(true)),
+            (
+                "preset",
+                Value::object([
+                    (
+                        "web",
+                        Value::object([
+                            ("exclude", Value::Array(vec![".next".into(), "coverage".into()])),
+                            (
+                                "limits",
+                                Value::object([("files", Value::Int(10)), ("tokens", Value::Int(1000))]),
+                            ),
+                        ]),
+                    ),
+                    ("my app", Value::object([("prefix", Value::from("Review this:\n  carefully"))])),
+                ]),
+            ),
+        ]);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_strings() {
+        let value = parse(r#"a = "tab\there \"quoted\" \u00e9"
+b = 'C:\path'"#)
+        .unwrap();
+        assert_eq!(
+            value,
+            Value::object([
+                ("a", Value::from("tab\there \"quoted\" é")),
+                ("b", Value::from("C:\\path")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("a = 1\na = 2").unwrap_err(), "line 2: duplicate key 'a'");
+        assert_eq!(parse("a = \"open\nb = 1").unwrap_err(), "line 1: unterminated string");
+        assert!(parse("[[item]]").is_err());
+        assert!(parse("a = 1 b = 2").is_err());
+        assert!(parse("a = 1\n[a]").is_err());
+        assert_eq!(parse("a = \nb = 1").unwrap_err(), "line 1: expected a value, found end of line");
+    }
+}
@@ -0,0 +1,344 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::template;
+
+/// Rewrites applied to each text file's content before it is formatted
+///
+/// Used to squeeze more code into the size and token limits. All are off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transforms {
+    /// Remove comments, for languages whose comment syntax is known (see `strip_comments`)
+    pub strip_comments: bool,
+    /// Remove lines that are empty or only whitespace
+    pub strip_blank: bool,
+    /// Trim trailing whitespace and collapse runs of blank lines into one
+    pub minify: bool,
+    /// Remove the indentation common to all non-blank lines
+    pub dedent: bool,
+}
+
+impl Transforms {
+    /// Whether no transform is enabled
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the enabled transforms to the content of the file at `path`
+    pub fn apply<'a>(&self, path: &Path, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if self.strip_comments
+            && let Some(syntax) = CommentSyntax::for_path(path)
+        {
+            text = Cow::Owned(strip_comments(&text, &syntax));
+        }
+        if self.strip_blank {
+            text = Cow::Owned(strip_blank_lines(&text));
+        }
+        if self.minify {
+            text = Cow::Owned(minify(&text));
+        }
+        if self.dedent {
+            text = Cow::Owned(dedent(&text));
+        }
+        text
+    }
+}
+
+/// How comments and strings are written in a language
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommentSyntax {
+    /// Markers that comment out the rest of the line
+    pub line: &'static [&'static str],
+    /// Opening and closing markers of a block comment
+    pub block: Option<(&'static str, &'static str)>,
+    /// Characters that delimit strings, inside which comment markers mean nothing
+    pub quotes: &'static [char],
+    /// Line comments only start at the start of a word, as `#` does in shells
+    pub line_at_word_start: bool,
+    /// `'x'` is a character literal, but a lone `'` (like a Rust lifetime) isn't a quote
+    pub char_literals: bool,
+}
+
+impl CommentSyntax {
+    const C_LIKE: Self = Self {
+        line: &["//"],
+        block: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        line_at_word_start: false,
+        char_literals: false,
+    };
+
+    const HASH: Self = Self {
+        line: &["#"],
+        block: None,
+        quotes: &['"', '\''],
+        line_at_word_start: true,
+        char_literals: false,
+    };
+
+    const MARKUP: Self = Self {
+        line: &[],
+        block: Some(("<!--", "-->")),
+        quotes: &[],
+        line_at_word_start: false,
+        char_literals: false,
+    };
+
+    /// The comment syntax of a file's language, guessed from its extension
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let syntax = match template::language(path)? {
+            "rust" => Self {
+                quotes: &['"'],
+                char_literals: true,
+                ..Self::C_LIKE
+            },
+            "javascript" | "jsx" | "typescript" | "tsx" | "go" | "java" | "kotlin" | "swift" | "c"
+            | "cpp" | "csharp" | "dart" | "scala" | "scss" => Self::C_LIKE,
+            "php" => Self {
+                line: &["//", "#"],
+                ..Self::C_LIKE
+            },
+            "css" => Self {
+                line: &[],
+                ..Self::C_LIKE
+            },
+            "python" | "ruby" | "bash" | "yaml" | "toml" | "elixir" => Self::HASH,
+            "sql" | "lua" => Self {
+                line: &["--"],
+                line_at_word_start: false,
+                ..Self::HASH
+            },
+            "haskell" => Self {
+                line: &["--"],
+                block: Some(("{-", "-}")),
+                line_at_word_start: false,
+                ..Self::HASH
+            },
+            "html" | "xml" | "vue" | "svelte" => Self::MARKUP,
+            _ => return None,
+        };
+        Some(syntax)
+    }
+}
+
+/// Remove comments, dropping lines that held nothing but a comment
+///
+/// Markers inside strings are left alone. Strings end at the end of a line
+/// unless delimited by backticks, so an apostrophe in unquoted text can't
+/// hide the rest of the file. A `#!` line at the top is kept.
+pub fn strip_comments(text: &str, syntax: &CommentSyntax) -> String {
+    let mut output = String::with_capacity(text.len());
+    // Output lines a comment was removed from
+    let mut stripped = HashSet::new();
+    let mut line = 0;
+    let mut quote = None;
+
+    let mut rest = text;
+    if text.starts_with("#!") {
+        let end = text.find('\n').map_or(text.len(), |end| end + 1);
+        output.push_str(&text[..end]);
+        line += usize::from(end < text.len() || text.ends_with('\n'));
+        rest = &text[end..];
+    }
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(open) = quote {
+            let mut len = c.len_utf8();
+            if c == '\\' {
+                len += rest[len..].chars().next().map_or(0, char::len_utf8);
+            } else if c == open || (c == '\n' && open != '`') {
+                quote = None;
+            }
+            line += rest[..len].matches('\n').count();
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        if syntax.quotes.contains(&c) {
+            quote = Some(c);
+        } else if syntax.char_literals
+            && c == '\''
+            && let Some(len) = char_literal_len(rest)
+        {
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        } else if let Some((open, close)) = syntax.block
+            && rest.starts_with(open)
+        {
+            let end = rest[open.len()..].find(close).map_or(rest.len(), |end| open.len() + end + close.len());
+            // Keep the line breaks so the code around the comment stays on its lines
+            stripped.insert(line);
+            for _ in rest[..end].matches('\n') {
+                output.push('\n');
+                line += 1;
+                stripped.insert(line);
+            }
+            rest = &rest[end..];
+            continue;
+        } else if syntax.line.iter().any(|marker| rest.starts_with(marker))
+            && (!syntax.line_at_word_start || output.is_empty() || output.ends_with(char::is_whitespace))
+        {
+            stripped.insert(line);
+            let end = rest.find('\n').unwrap_or(rest.len());
+            // Keep a `\r` so the line ending is left as it was
+            rest = &rest[rest[..end].strip_suffix('\r').map_or(end, str::len)..];
+            continue;
+        }
+
+        if c == '\n' {
+            line += 1;
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    let mut result = String::with_capacity(output.len());
+    for (index, text_line) in output.split_inclusive('\n').enumerate() {
+        if !stripped.contains(&index) {
+            result.push_str(text_line);
+            continue;
+        }
+        let content = text_line.trim_end();
+        if content.is_empty() {
+            continue;
+        }
+        result.push_str(content);
+        result.push_str(line_ending(text_line));
+    }
+    result
+}
+
+/// Length of the character literal at the start of `text`, like `'a'` or `'\n'`
+fn char_literal_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        // Escapes are short: `'\n'`, `'\''`, `'\x7f'`, `'\u{1F600}'`
+        return text.get(3..)?.find('\'').filter(|&end| end <= 8).map(|end| end + 4);
+    }
+    match chars.next() {
+        Some((index, '\'')) => Some(index + 1),
+        _ => None,
+    }
+}
+
+/// Remove lines that are empty or only whitespace
+pub fn strip_blank_lines(text: &str) -> String {
+    text.split_inclusive('\n')
+        .filter(|line| !line.trim().is_empty())
+        .collect()
+}
+
+/// Trim trailing whitespace from each line and collapse runs of blank lines into one
+pub fn minify(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut blank_run = false;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end();
+        if content.is_empty() && blank_run {
+            continue;
+        }
+        blank_run = content.is_empty();
+        output.push_str(content);
+        output.push_str(line_ending(line));
+    }
+    output
+}
+
+/// Remove the leading whitespace shared by every non-blank line
+///
+/// Only an identical prefix is removed, so tabs and spaces aren't mixed up.
+/// Blank lines don't count toward the shared prefix.
+pub fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(|common, indent| {
+            let shared = common.bytes().zip(indent.bytes()).take_while(|(a, b)| a == b).count();
+            &common[..shared]
+        })
+        .unwrap_or_default();
+    if indent.is_empty() {
+        return text.to_string();
+    }
+
+    text.split_inclusive('\n')
+        .map(|line| line.strip_prefix(indent).unwrap_or_else(|| line.trim_start_matches([' ', '\t'])))
+        .collect()
+}
+
+/// The `\r\n` or `\n` a line ends with, if any
+fn line_ending(line: &str) -> &str {
+    if line.ends_with("\r\n") {
+        "\r\n"
+    } else if line.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(path: &str, text: &str) -> String {
+        strip_comments(text, &CommentSyntax::for_path(Path::new(path)).unwrap())
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let rust = "/// Docs\nfn main() { // trailing\n    let url = \"http://x\"; /* inline */ let c = '\"';\n    /* multi\n       line */\n    f::<'a>(); // lifetime\n}\n";
+        assert_eq!(
+            strip("main.rs", rust),
+            "fn main() {\n    let url = \"http://x\";  let c = '\"';\n    f::<'a>();\n}\n"
+        );
+
+        let python = "#!/usr/bin/env python\n# comment\nx = \"# not a comment\"  # trailing\nprint(f'{x}#')\n";
+        assert_eq!(
+            strip("tool.py", python),
+            "#!/usr/bin/env python\nx = \"# not a comment\"\nprint(f'{x}#')\n"
+        );
+
+        assert_eq!(strip("quote.rs", "let q = '\\''; // quote\nlet n = '\\n';\n"), "let q = '\\'';\nlet n = '\\n';\n");
+        assert_eq!(strip("run.sh", "echo ${#args[@]} # count\n"), "echo ${#args[@]}\n");
+        assert_eq!(strip("page.html", "<p>a</p>\n<!-- note -->\n<p>b</p>"), "<p>a</p>\n<p>b</p>");
+        assert_eq!(strip("app.ts", "const s = `// ${a}\n// b`; // c\r\nf();\r\n"), "const s = `// ${a}\n// b`;\r\nf();\r\n");
+        assert!(CommentSyntax::for_path(Path::new("notes.txt")).is_none());
+    }
+
+    #[test]
+    fn test_dedent() {
+        assert_eq!(dedent("\t\ta\n\t  b\n"), "\ta\n  b\n");
+        assert_eq!(dedent("  a\n \n    b"), "a\n\n  b");
+        // Different kinds of indentation share no prefix
+        assert_eq!(dedent("\ta\n  b\n"), "\ta\n  b\n");
+    }
+
+    #[test]
+    fn test_transforms() {
+        let transforms = Transforms {
+            strip_comments: true,
+            strip_blank: true,
+            ..Transforms::default()
+        };
+        assert_eq!(transforms.apply(Path::new("lib.rs"), "// a\n\nfn f() {}\n  \n\nfn g() {}\n"), "fn f() {}\nfn g() {}\n");
+        // Files of unknown languages only lose their blank lines
+        assert_eq!(transforms.apply(Path::new("notes.txt"), "# title\n\ntext"), "# title\ntext");
+        assert!(Transforms::default().is_empty());
+
+        let transforms = Transforms {
+            minify: true,
+            dedent: true,
+            ..Transforms::default()
+        };
+        let text = "    fn f() {  \n\n\n        g();\t\n    }\n\n";
+        assert_eq!(transforms.apply(Path::new("lib.rs"), text), "fn f() {\n\n    g();\n}\n\n");
+        assert!(matches!(Transforms::default().apply(Path::new("lib.rs"), "// a"), Cow::Borrowed("// a")));
+    }
+}
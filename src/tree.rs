@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+/// Renders a set of file paths as an ASCII tree, like the `tree` command
+pub struct TreeRenderer;
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+}
+
+impl TreeRenderer {
+    /// Render the given file paths as a tree, one top-level entry per distinct first component
+    pub fn render<P: AsRef<Path>>(paths: &[P]) -> String {
+        let mut root = Node::default();
+
+        for path in paths {
+            let mut node = &mut root;
+            for component in path.as_ref().components() {
+                let name = match component {
+                    Component::CurDir => continue,
+                    Component::RootDir => "/".to_string(),
+                    other => other.as_os_str().to_string_lossy().into_owned(),
+                };
+                node = node.children.entry(name).or_default();
+            }
+        }
+
+        let mut output = String::new();
+        for (name, node) in &root.children {
+            output.push_str(name);
+            output.push('\n');
+            Self::render_children(node, "", &mut output);
+        }
+        output
+    }
+
+    /// Render the children of a node with box-drawing connectors
+    fn render_children(node: &Node, prefix: &str, output: &mut String) {
+        let count = node.children.len();
+        for (index, (name, child)) in node.children.iter().enumerate() {
+            let is_last = index + 1 == count;
+            let (connector, extension) = if is_last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            output.push_str(prefix);
+            output.push_str(connector);
+            output.push_str(name);
+            output.push('\n');
+
+            Self::render_children(child, &format!("{}{}", prefix, extension), output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_tree() {
+        let paths = ["src/main.rs", "src/walk/mod.rs", "src/lib.rs", "README.md"];
+        let tree = TreeRenderer::render(&paths);
+
+        assert_eq!(
+            tree,
+            "README.md\n\
+             src\n\
+             ├── lib.rs\n\
+             ├── main.rs\n\
+             └── walk\n    \
+                 └── mod.rs\n"
+        );
+    }
+
+    #[test]
+    fn test_render_skips_current_dir() {
+        let tree = TreeRenderer::render(&["./a.txt", "./dir/b.txt"]);
+        assert_eq!(tree, "a.txt\ndir\n└── b.txt\n");
+    }
+}
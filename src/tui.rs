@@ -0,0 +1,393 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::format::ByteFormatter;
+use crate::tokens::TokenEstimator;
+
+/// A file offered by the picker, as listed by a dry run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub size: usize,
+    pub tokens: usize,
+}
+
+/// One line of the tree: a directory covering a run of files, or a file
+#[derive(Debug, PartialEq, Eq)]
+enum Row {
+    // Positions in `Picker::order` of the files below the directory
+    Dir { depth: usize, name: String, files: Range<usize> },
+    File { depth: usize, name: String, position: usize },
+}
+
+/// What a key press asks the picker to do
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Toggle,
+    ToggleAll,
+    Confirm,
+    Cancel,
+    Other,
+}
+
+/// A checkbox tree of candidate files, all checked to start with
+///
+/// Checking a directory checks every file below it. The terminal is drawn
+/// on `/dev/tty`, so the picker works while stdout is redirected.
+pub struct Picker {
+    candidates: Vec<Candidate>,
+    // Candidate indices in tree order
+    order: Vec<usize>,
+    // Whether each candidate is checked, by candidate index
+    checked: Vec<bool>,
+    rows: Vec<Row>,
+    cursor: usize,
+    // First row shown, once the tree is taller than the screen
+    scroll: usize,
+}
+
+impl Picker {
+    pub fn new(candidates: Vec<Candidate>) -> Self {
+        let components: Vec<Vec<String>> = candidates.iter().map(|candidate| components(&candidate.path)).collect();
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        // Sorting by component keeps each directory's files together
+        order.sort_by(|&a, &b| components[a].cmp(&components[b]));
+
+        let mut rows = Vec::new();
+        // Row indices of the directories containing the previous file
+        let mut open: Vec<usize> = Vec::new();
+        for (position, &index) in order.iter().enumerate() {
+            let (name, dirs) = components[index].split_last().expect("paths have a component");
+            let shared = open
+                .iter()
+                .zip(dirs)
+                .take_while(|(row, dir)| matches!(&rows[**row], Row::Dir { name, .. } if name == *dir))
+                .count();
+            for row in open.drain(shared..) {
+                close_dir(&mut rows, row, position);
+            }
+            for dir in &dirs[shared..] {
+                open.push(rows.len());
+                rows.push(Row::Dir {
+                    depth: open.len() - 1,
+                    name: dir.clone(),
+                    files: position..position,
+                });
+            }
+            rows.push(Row::File {
+                depth: dirs.len(),
+                name: name.clone(),
+                position,
+            });
+        }
+        for row in open {
+            close_dir(&mut rows, row, order.len());
+        }
+
+        Self {
+            checked: vec![true; candidates.len()],
+            candidates,
+            order,
+            rows,
+            cursor: 0,
+            scroll: 0,
+        }
+    }
+
+    /// The checked files, in their original order
+    pub fn selected(&self) -> Vec<PathBuf> {
+        self.candidates
+            .iter()
+            .zip(&self.checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(candidate, _)| candidate.path.clone())
+            .collect()
+    }
+
+    /// Show the picker until the choice is confirmed, returning the checked files
+    ///
+    /// Returns `None` if the picker is cancelled.
+    pub fn run(mut self) -> io::Result<Option<Vec<PathBuf>>> {
+        let mut terminal = Terminal::open()?;
+        let result = loop {
+            let (height, width) = terminal.size();
+            terminal.draw(&self.render(height, width))?;
+            match terminal.read_key()? {
+                Key::Up => self.cursor = self.cursor.saturating_sub(1),
+                Key::Down => self.cursor = (self.cursor + 1).min(self.rows.len().saturating_sub(1)),
+                Key::Toggle => self.toggle(),
+                Key::ToggleAll => self.toggle_all(),
+                Key::Confirm => break Some(self.selected()),
+                Key::Cancel => break None,
+                Key::Other => {}
+            }
+        };
+        terminal.restore()?;
+        Ok(result)
+    }
+
+    /// Flip the row under the cursor; a directory is checked unless all its files already are
+    fn toggle(&mut self) {
+        let positions = match self.rows.get(self.cursor) {
+            Some(Row::Dir { files, .. }) => files.clone(),
+            Some(Row::File { position, .. }) => *position..*position + 1,
+            None => return,
+        };
+        let check = !self.all_checked(positions.clone());
+        for position in positions {
+            self.checked[self.order[position]] = check;
+        }
+    }
+
+    /// Check every file, or uncheck them all if they already are
+    fn toggle_all(&mut self) {
+        let check = !self.all_checked(0..self.order.len());
+        self.checked.fill(check);
+    }
+
+    fn all_checked(&self, positions: Range<usize>) -> bool {
+        self.order[positions].iter().all(|&index| self.checked[index])
+    }
+
+    /// Lines of the screen: a help line, the visible rows and the selection's totals
+    fn render(&mut self, height: usize, width: usize) -> Vec<String> {
+        let visible = height.saturating_sub(2).max(1);
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + visible {
+            self.scroll = self.cursor + 1 - visible;
+        }
+
+        let mut lines = vec!["Up/Down move, Space toggle, a toggle all, Enter confirm, q cancel".to_string()];
+        for (index, row) in self.rows.iter().enumerate().skip(self.scroll).take(visible) {
+            let pointer = if index == self.cursor { '>' } else { ' ' };
+            let line = match row {
+                Row::Dir { depth, name, files } => {
+                    let checked = self.order[files.clone()].iter().filter(|&&index| self.checked[index]).count();
+                    let mark = match checked {
+                        0 => ' ',
+                        count if count == files.len() => 'x',
+                        _ => '-',
+                    };
+                    format!("{} {}[{}] {}/", pointer, "  ".repeat(*depth), mark, name)
+                }
+                Row::File { depth, name, position } => {
+                    let candidate = &self.candidates[self.order[*position]];
+                    let mark = if self.checked[self.order[*position]] { 'x' } else { ' ' };
+                    format!(
+                        "{} {}[{}] {}  ({}, {} tokens)",
+                        pointer,
+                        "  ".repeat(*depth),
+                        mark,
+                        name,
+                        ByteFormatter::format(candidate.size),
+                        TokenEstimator::format(candidate.tokens)
+                    )
+                }
+            };
+            lines.push(line);
+        }
+
+        let chosen: Vec<&Candidate> = self
+            .candidates
+            .iter()
+            .zip(&self.checked)
+            .filter_map(|(candidate, checked)| checked.then_some(candidate))
+            .collect();
+        lines.push(format!(
+            "{} of {} files selected, {}, {} tokens",
+            chosen.len(),
+            self.candidates.len(),
+            ByteFormatter::format(chosen.iter().map(|candidate| candidate.size).sum()),
+            TokenEstimator::format(chosen.iter().map(|candidate| candidate.tokens).sum())
+        ));
+
+        for line in &mut lines {
+            if let Some((cut, _)) = line.char_indices().nth(width) {
+                line.truncate(cut);
+            }
+        }
+        lines
+    }
+}
+
+/// Set where the files below a directory row end
+fn close_dir(rows: &mut [Row], row: usize, end: usize) {
+    if let Row::Dir { files, .. } = &mut rows[row] {
+        files.end = end;
+    }
+}
+
+/// The names making up a path, without `.` components
+fn components(path: &Path) -> Vec<String> {
+    let names: Vec<String> = path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if names.is_empty() {
+        vec![path.display().to_string()]
+    } else {
+        names
+    }
+}
+
+/// Decode the bytes of one key press
+fn parse_key(bytes: &[u8]) -> Key {
+    match bytes {
+        b"\x1b[A" | b"\x1bOA" | b"k" => Key::Up,
+        b"\x1b[B" | b"\x1bOB" | b"j" => Key::Down,
+        b" " | b"x" => Key::Toggle,
+        b"a" => Key::ToggleAll,
+        b"\r" | b"\n" => Key::Confirm,
+        // Escape on its own, q, or Ctrl-C (signals are off while picking)
+        b"\x1b" | b"q" | b"\x03" => Key::Cancel,
+        _ => Key::Other,
+    }
+}
+
+/// The controlling terminal, switched to unbuffered input on an alternate screen
+///
+/// Terminal modes are changed with `stty`, as a shell script would.
+struct Terminal {
+    tty: File,
+    // `stty -g` settings to restore
+    saved: String,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Self> {
+        let tty = File::options().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&tty, &["-g"])?.trim().to_string();
+        stty(&tty, &["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+        let mut terminal = Self { tty, saved };
+        // Alternate screen, cursor hidden
+        terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+
+    /// Rows and columns, assuming 24x80 if `stty` can't tell
+    fn size(&self) -> (usize, usize) {
+        stty(&self.tty, &["size"])
+            .ok()
+            .and_then(|output| {
+                let (rows, columns) = output.trim().split_once(' ')?;
+                Some((rows.parse().ok()?, columns.parse().ok()?))
+            })
+            .filter(|&(rows, columns)| rows > 0 && columns > 0)
+            .unwrap_or((24, 80))
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&lines.join("\r\n"));
+        self.tty.write_all(screen.as_bytes())?;
+        self.tty.flush()
+    }
+
+    fn read_key(&mut self) -> io::Result<Key> {
+        // An arrow key's escape sequence arrives in one read
+        let mut buffer = [0u8; 8];
+        let read = self.tty.read(&mut buffer)?;
+        Ok(parse_key(&buffer[..read]))
+    }
+
+    fn restore(mut self) -> io::Result<()> {
+        self.tty.write_all(b"\x1b[?25h\x1b[?1049l")?;
+        stty(&self.tty, &[self.saved.as_str()]).map(|_| ())
+    }
+}
+
+/// Run `stty` on the terminal, returning its output
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty.try_clone()?))
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed to set up the terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(path: &str, size: usize) -> Candidate {
+        Candidate {
+            path: PathBuf::from(path),
+            size,
+            tokens: size / 4,
+        }
+    }
+
+    #[test]
+    fn test_tree_rows() {
+        let picker = Picker::new(vec![
+            candidate("./README.md", 100),
+            candidate("./src/main.rs", 400),
+            candidate("./src/cli/args.rs", 200),
+            candidate("./src/lib.rs", 300),
+        ]);
+
+        let names: Vec<(usize, &str)> = picker
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Dir { depth, name, .. } | Row::File { depth, name, .. } => (*depth, name.as_str()),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![(0, "README.md"), (0, "src"), (1, "cli"), (2, "args.rs"), (1, "lib.rs"), (1, "main.rs")]
+        );
+        assert!(matches!(&picker.rows[1], Row::Dir { files, .. } if *files == (1..4)));
+        assert!(matches!(&picker.rows[2], Row::Dir { files, .. } if *files == (1..2)));
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mut picker = Picker::new(vec![
+            candidate("src/main.rs", 400),
+            candidate("src/lib.rs", 300),
+            candidate("README.md", 100),
+        ]);
+        assert_eq!(picker.selected().len(), 3);
+
+        // Unchecking the directory unchecks its files, in their original order
+        picker.cursor = 1;
+        picker.toggle();
+        assert_eq!(picker.selected(), vec![PathBuf::from("README.md")]);
+
+        // One file back makes the directory partly checked, and toggling it checks the rest
+        picker.cursor = 2;
+        picker.toggle();
+        let screen = picker.render(10, 80);
+        assert_eq!(screen[2], "  [-] src/");
+        assert_eq!(screen[3], ">   [x] lib.rs  (300 B, 75 tokens)");
+        picker.cursor = 1;
+        picker.toggle();
+        assert_eq!(picker.selected().len(), 3);
+
+        picker.toggle_all();
+        assert!(picker.selected().is_empty());
+        assert_eq!(picker.render(10, 80).last().unwrap(), "0 of 3 files selected, 0 B, 0 tokens");
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key(b"\x1b[A"), Key::Up);
+        assert_eq!(parse_key(b"j"), Key::Down);
+        assert_eq!(parse_key(b" "), Key::Toggle);
+        assert_eq!(parse_key(b"\r"), Key::Confirm);
+        assert_eq!(parse_key(b"\x03"), Key::Cancel);
+        assert_eq!(parse_key(b"\x1b[C"), Key::Other);
+    }
+}
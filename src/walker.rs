@@ -1,121 +1,744 @@
-use std::collections::{HashSet, VecDeque};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs;
-use std::io;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime};
 
+use crate::archive::{Archive, ArchiveFormat};
+use crate::cache::FileCache;
+use crate::command::{self, Limited};
+use crate::compression::Compression;
 use crate::config::Config;
-use crate::file_processor::FileProcessor;
+use crate::error::{Error, Result};
+use crate::fetch;
+use crate::file_processor::{FileContent, FileProcessor, LineRange, ProcessorRegistry};
+use crate::filter::{self, Entry, Filter, FilterPipeline, Stage, WalkContext};
 use crate::format::ByteFormatter;
+use crate::fuzzy::FuzzyQuery;
+use crate::git::{GitFiles, LastCommits};
 use crate::gitignore::GitignoreManager;
+use crate::output::{self, OutputFormatter, PlainFormatter};
 use crate::glob::GlobMatcher;
-use crate::stats::StatsCollector;
+use crate::progress::{ProgressCallback, ProgressEvent};
+use crate::redact;
+use crate::regex::Regex;
+use crate::skip::{SkipReason, UnreadableCause};
+use crate::spool::Spool;
+use crate::stats::{Phase, StatsCollector};
+use crate::template::{FileMeta, Section};
+use crate::transform::Transforms;
+use crate::tokens::{Model, TokenEstimator, Tokenizer};
+use crate::tree::TreeRenderer;
 
-/// Simple pattern matcher for exclude patterns using glob-style matching
-struct ExcludeMatcher {
+/// Simple pattern matcher for exclude and include patterns using glob-style matching
+pub(crate) struct PatternMatcher {
     patterns: Vec<String>,
 }
 
-impl ExcludeMatcher {
-    /// Create a new exclude matcher with the given patterns
-    fn new(patterns: Vec<String>) -> Self {
+impl PatternMatcher {
+    /// Create a new matcher with the given patterns
+    pub(crate) fn new(patterns: Vec<String>) -> Self {
         Self { patterns }
     }
 
-    /// Check if a path matches any of the exclude patterns
-    fn should_exclude(&self, path: &Path) -> bool {
-        if self.patterns.is_empty() {
-            return false;
+    /// Find the pattern that decides a root-relative, `/`-separated path
+    ///
+    /// As in gitignore files, the last matching pattern wins: a `!pattern`
+    /// after it cancels an earlier match (`\!` stands for a literal `!`), and a
+    /// trailing `/` limits a pattern to directories.
+    pub(crate) fn matching_pattern(&self, relative: &str, is_dir: bool) -> Option<&str> {
+        let mut matched = None;
+        for pattern in &self.patterns {
+            let (negated, body) = if let Some(body) = pattern.strip_prefix('!') {
+                (true, body)
+            } else if pattern.starts_with("\\!") {
+                (false, &pattern[1..])
+            } else {
+                (false, pattern.as_str())
+            };
+            if Self::matches(body, relative, is_dir) {
+                matched = (!negated).then_some(pattern.as_str());
+            }
+        }
+        matched
+    }
+
+    fn matches(pattern: &str, relative: &str, is_dir: bool) -> bool {
+        let pattern = match pattern.strip_suffix('/') {
+            Some(_) if !is_dir => return false,
+            Some(dir_pattern) => dir_pattern,
+            None => pattern,
+        };
+        // Patterns with a slash match against the path relative to the root, others just the filename
+        if pattern.contains('/') {
+            GlobMatcher::matches_path(relative, pattern)
+        } else {
+            let file_name = relative.rsplit('/').next().unwrap_or(relative);
+            GlobMatcher::matches(file_name, pattern)
         }
+    }
+}
 
-        let path_str = path.to_string_lossy();
-        let file_name = path.file_name()
-            .map(|name| name.to_string_lossy())
-            .unwrap_or_default();
+/// How file paths are displayed in output headers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathDisplay {
+    /// Paths as reached from the root the user typed, even if that root is a symlink
+    #[default]
+    AsGiven,
+    /// Fully resolved canonical paths
+    Canonical,
+}
 
-        for pattern in &self.patterns {
-            // Match against full path or just filename
-            if GlobMatcher::matches(&path_str, pattern) || GlobMatcher::matches(&file_name, pattern) {
-                return true;
-            }
+/// Order in which the files of each directory are collected
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Alphabetically by file name
+    #[default]
+    Name,
+    /// Smallest first, so more files fit before a size limit is reached
+    Size,
+    /// Least recently modified first
+    Mtime,
+    /// Grouped by extension, files without one first
+    Extension,
+}
+
+impl SortOrder {
+    /// Parse an order name as given to `--sort`
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "name" | "path" => Ok(SortOrder::Name),
+            "size" => Ok(SortOrder::Size),
+            "mtime" | "modified" => Ok(SortOrder::Mtime),
+            "ext" | "extension" => Ok(SortOrder::Extension),
+            _ => Err(Error::Config(format!(
+                "Unknown sort order: {} (expected name, size, mtime or ext)",
+                name
+            ))),
         }
-        false
     }
 
+    /// Sort name-ordered files into this order; ties keep their name order
+    fn sort(self, files: &mut [PathBuf], reverse: bool) {
+        match self {
+            SortOrder::Name => {}
+            SortOrder::Size => files.sort_by_cached_key(|file| fs::metadata(file).map_or(0, |metadata| metadata.len())),
+            SortOrder::Mtime => {
+                files.sort_by_cached_key(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+            }
+            SortOrder::Extension => files.sort_by_cached_key(|file| file.extension().map(|ext| ext.to_ascii_lowercase())),
+        }
+        if reverse {
+            files.reverse();
+        }
+    }
 }
 
 /// Options for walking the directory tree
 #[derive(Clone)]
 pub struct WalkOptions {
-    pub include_all: bool,
+    /// Include hidden files and directories (names starting with '.')
+    pub include_hidden: bool,
+    /// Include binary files as `<BINARY_FILE: ...>` placeholders (see `FileProcessor::binary_info`)
+    pub include_binary: bool,
+    /// Include paths matched by ignore files
+    pub include_ignored: bool,
+    /// Include generated code: files marked `linguist-generated` or `linguist-vendored`
+    /// in `.gitattributes`, and files starting with a comment like `@generated` or `DO NOT EDIT`
+    ///
+    /// Also applies under `git_tracked`, since generated files are often committed.
+    pub include_generated: bool,
+    /// Only include files tracked by git, in place of ignore-file rules
+    ///
+    /// Roots outside a git repository are walked normally.
+    pub git_tracked: bool,
+    /// Only include files changed relative to this git ref (e.g. `main`, `HEAD~3`)
+    ///
+    /// Unlike `git_tracked`, every root must be inside a git repository.
+    pub diff_ref: Option<String>,
     pub max_size: usize,
+    /// Maximum tokens of output, as counted by `tokenizer` (`None` for no token budget)
+    pub max_tokens: Option<usize>,
+    /// Maximum number of files to include, regardless of their size (`None` for unlimited)
+    pub max_files: Option<usize>,
     pub max_file_size: usize,
+    /// Skip files smaller than this many bytes
+    pub min_file_size: usize,
+    /// Skip text files that are empty or only whitespace
+    pub skip_empty: bool,
+    /// When the size, token or file limit would be exceeded, leave out the largest files instead of stopping
+    ///
+    /// Every file is read before anything is emitted, then the largest are
+    /// dropped until the rest fit, which keeps as many files as possible.
+    pub fit: bool,
     pub exclude_patterns: Vec<String>,
+    /// Skip common dependency and build directories and lockfiles (see `Config::DEFAULT_EXCLUDED_DIRS`)
+    pub default_excludes: bool,
+    /// Read ripgrep-style `.ignore` and `.rgignore` files along with `.gitignore` and `.rcatignore`
+    pub dot_ignore: bool,
+    /// How files are processed, by name (notebooks have their cells extracted by default)
+    pub processors: ProcessorRegistry,
+    /// Skip files that often hold credentials, like `.env` and `id_rsa` (see `Config::SENSITIVE_FILES`)
+    ///
+    /// Unlike other filters this also applies below `also` paths; only files
+    /// given directly in `paths` are exempt.
+    pub skip_sensitive: bool,
+    /// Exclude files and directories whose root-relative path matches one of these
+    pub exclude_regexes: Vec<Regex>,
+    /// Only include files whose root-relative path matches one of these (if any are given)
+    ///
+    /// Exclusions (patterns, gitignore, hidden files) are applied first and always win.
+    pub path_regexes: Vec<Regex>,
+    /// Only include files whose root-relative path fuzzily matches one of these queries (if any are given)
+    ///
+    /// Applied like `path_regexes`.
+    pub fuzzy_filters: Vec<FuzzyQuery>,
+    /// Only include files with one of these extensions (if any are given), without the dot and case-insensitive
+    ///
+    /// Multi-part extensions like `d.ts` match the end of the name.
+    pub extensions: Vec<String>,
+    /// Exclude files with any of these extensions, matched like `extensions`
+    pub excluded_extensions: Vec<String>,
+    /// Only include files matching one of these globs (if any are given), with the same rules as `exclude_patterns`
+    pub include_patterns: Vec<String>,
+    /// Maximum directory depth to descend into (`None` for unlimited)
+    pub max_depth: Option<usize>,
+    /// Include at most this many files from any one directory, in `sort` order (`None` for unlimited)
+    pub max_files_per_dir: Option<usize>,
+    /// Files matching one of these globs are collected before all others, so they survive truncation
+    ///
+    /// The tree is walked once: other files are set aside and collected after
+    /// every priority file, in the order they would have had. Files given
+    /// directly as roots keep their place.
+    pub priority_patterns: Vec<String>,
+    /// Emit the content of identical text files once, with a short stub for later copies
+    ///
+    /// Only affects collected output; `walk_iter` always yields full contents.
+    pub dedup: bool,
+    /// Order of the files within each directory; subdirectories are always walked by name
+    pub sort: SortOrder,
+    /// Reverse `sort`, e.g. largest or newest files first
+    pub reverse: bool,
+    /// Follow symbolic links to files and directories; when off, links met during the walk are skipped
+    ///
+    /// Roots are always followed. A followed link to something already walked
+    /// is still only included once.
+    pub follow_symlinks: bool,
+    /// Don't descend into directories on a different filesystem than their root (Unix only)
+    pub one_file_system: bool,
+    /// How paths are shown in headers (deduplication always uses canonical paths)
+    pub path_display: PathDisplay,
+    /// Text put before everything else in the output, such as instructions for an LLM
+    ///
+    /// Counts toward the size and token limits, along with `suffix`.
+    pub prefix: Option<String>,
+    /// Text put after everything else in the output, including any truncation marker
+    pub suffix: Option<String>,
+    /// Only include these lines of files given directly in `paths`, keyed by the path as given
+    ///
+    /// The range is added to the path in the header, e.g. `src/main.rs:40-120`,
+    /// and such files are read even if larger than `max_file_size`.
+    pub line_ranges: Vec<(PathBuf, LineRange)>,
+    /// Prefix each line of text content with its number, like ` 12 | `
+    pub line_numbers: bool,
+    /// Rewrites such as comment stripping applied to each file's text before it is formatted
+    ///
+    /// Applied before `line_numbers`, so numbers then count the rewritten lines.
+    pub transforms: Transforms,
+    /// Replace secrets such as API keys and passwords with `[REDACTED]` (see `redact::redact`)
+    ///
+    /// Covers standard input and command output as well as files.
+    pub redact: bool,
+    /// Formats each file's section, e.g. with `--- path ---` headers (see `OutputFormatter`)
+    pub formatter: Arc<dyn OutputFormatter>,
+    /// Prepend a directory tree of the included files to the output
+    pub tree: bool,
+    /// Prepend an index of the included files, each with the line its section starts on in the output
+    ///
+    /// Comes before the tree, if there is one. Line numbers count from 1 in
+    /// `WalkResult::content`, so they hold for plain and markdown output.
+    pub toc: bool,
+    /// Write the output to a temporary file as it is collected instead of holding it in memory
+    ///
+    /// `WalkResult::content` is then empty; the output is in `WalkResult::spool`,
+    /// which file spans are offsets into. `walk_iter` ignores this.
+    pub low_memory: bool,
+    /// Counts tokens for `max_tokens` and stats (defaults to the built-in estimator)
+    pub tokenizer: Arc<dyn Tokenizer>,
+    /// Model whose context window stats measure the tokens against
+    pub model: Option<Model>,
+    /// Text read from standard input, included as a `<stdin>` section before any walked file
+    pub stdin: Option<String>,
+    /// Shell commands run once the files are collected, each adding a `$ <command>` section with its stdout
+    ///
+    /// A command that exits with an error still adds its output, followed by
+    /// its exit status.
+    pub commands: Vec<String>,
+    /// HTTP(S) URLs fetched and included after stdin, before any walked file, each as a section headed by the URL
+    ///
+    /// Bodies are subject to `max_file_size`; one that can't be fetched fails the walk.
+    pub urls: Vec<String>,
+    /// Keep processed file content in the cache directory between runs, and reuse it for files unchanged since
+    pub cache: bool,
+    /// Only include files whose content differs from what the last cached run recorded (implies `cache`)
+    ///
+    /// Files the last run didn't see are included. Whatever a run reads is
+    /// recorded, even if a limit then leaves it out.
+    pub changed_only: bool,
+    /// With `changed_only`, end the output with a section listing the files left out as unchanged
+    pub list_unchanged: bool,
+    /// Only include files modified after this time (see `config::parse_since`)
+    pub modified_since: Option<SystemTime>,
+    /// Judge `modified_since` by git history instead of modification times
+    ///
+    /// Files committed since then count as modified, as do uncommitted and
+    /// untracked ones. Roots outside a git repository fall back to modification times.
+    pub since_git: bool,
+    /// Look up the last commit of each file, for headers that show it (`{commit}` and `{meta}`, see `SectionTemplate`)
+    ///
+    /// Read with a single `git log` per repository. Files outside a
+    /// repository, or never committed, have no commit to show.
+    pub git_meta: bool,
+    /// Only include files shown under one of these paths (`IncludedFile::path`), as picked from an earlier walk
+    ///
+    /// Checked as each file is appended, after everything else, so the same
+    /// options give the same paths.
+    pub selected: Option<HashSet<PathBuf>>,
+    /// Paths to include even if ignore files, excludes, hidden-file rules or path filters would drop them
+    ///
+    /// Each is walked as an extra root; binary, size and budget limits still apply.
+    pub also: Vec<PathBuf>,
+    /// Filters applied after the built-in ones, leaving out more paths (see `Filter`)
+    pub filters: Vec<Arc<dyn Filter>>,
+    /// Called with each file started, included or skipped, and when a limit is reached
+    pub progress: Option<ProgressCallback>,
+    /// Stop early, keeping what was collected so far, once this is set (e.g. on Ctrl-C)
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
 impl Default for WalkOptions {
     fn default() -> Self {
         Self {
-            include_all: false,
+            include_hidden: false,
+            include_binary: false,
+            include_ignored: false,
+            include_generated: false,
+            git_tracked: false,
+            diff_ref: None,
             max_size: Config::DEFAULT_MAX_SIZE,
+            max_tokens: None,
+            max_files: None,
             max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
+            min_file_size: 0,
+            skip_empty: false,
+            fit: false,
             exclude_patterns: Vec::new(),
+            default_excludes: true,
+            dot_ignore: true,
+            processors: ProcessorRegistry::default(),
+            skip_sensitive: true,
+            exclude_regexes: Vec::new(),
+            path_regexes: Vec::new(),
+            fuzzy_filters: Vec::new(),
+            extensions: Vec::new(),
+            excluded_extensions: Vec::new(),
+            include_patterns: Vec::new(),
+            max_depth: None,
+            max_files_per_dir: None,
+            priority_patterns: Vec::new(),
+            dedup: true,
+            sort: SortOrder::default(),
+            reverse: false,
+            follow_symlinks: true,
+            one_file_system: false,
+            path_display: PathDisplay::default(),
+            prefix: None,
+            suffix: None,
+            line_ranges: Vec::new(),
+            line_numbers: false,
+            transforms: Transforms::default(),
+            redact: false,
+            formatter: Arc::new(PlainFormatter::default()),
+            tree: false,
+            toc: false,
+            low_memory: false,
+            tokenizer: Arc::new(TokenEstimator),
+            model: None,
+            stdin: None,
+            commands: Vec::new(),
+            urls: Vec::new(),
+            cache: false,
+            changed_only: false,
+            list_unchanged: false,
+            modified_since: None,
+            since_git: false,
+            git_meta: false,
+            selected: None,
+            also: Vec::new(),
+            filters: Vec::new(),
+            progress: None,
+            cancel: None,
+        }
+    }
+}
+
+/// The limit that caused output to be truncated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Output size in bytes
+    Size(usize),
+    /// Estimated output tokens
+    Tokens(usize),
+    /// Number of files included
+    Files(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Size(bytes) => write!(f, "{}", ByteFormatter::format_as_unit(*bytes)),
+            Limit::Tokens(tokens) => write!(f, "{} token", TokenEstimator::format(*tokens)),
+            Limit::Files(files) => write!(f, "{} file", files),
         }
     }
 }
 
+/// A file whose content made it into the output
+#[derive(Clone, Debug)]
+pub struct IncludedFile {
+    /// Path as shown in the output header
+    pub path: PathBuf,
+    /// Size of the formatted section in bytes
+    pub size: usize,
+    /// Estimated tokens of the formatted section
+    pub tokens: usize,
+    /// Byte range of the section (header included) within `WalkResult::content`
+    pub span: Range<usize>,
+    /// Byte range of the file's content within `WalkResult::content`, without header or footer
+    pub body: Range<usize>,
+    /// Included because of `WalkOptions::also`, bypassing the usual filters
+    pub forced: bool,
+    /// Shown as a stub because its content is identical to this earlier file (see `WalkOptions::dedup`)
+    pub identical_to: Option<PathBuf>,
+}
+
 /// Result of walking a directory tree
 pub struct WalkResult {
+    /// The output, unless it was written to `spool` instead
     pub content: String,
+    /// The output, when collected with `WalkOptions::low_memory`
+    pub spool: Option<Spool>,
+    /// Files included in the output, in output order
+    pub files: Vec<IncludedFile>,
     pub stats: StatsCollector,
     pub truncated: bool,
+    /// Which limit truncated the output, if any
+    pub truncated_by: Option<Limit>,
+    /// Stopped early through `WalkOptions::cancel`; `truncated` is set too
+    pub interrupted: bool,
+    /// `WalkOptions::prefix`, which starts `content`
+    pub prefix: Option<String>,
+    /// `WalkOptions::suffix`, which ends `content`
+    pub suffix: Option<String>,
+    /// Directories walked and files read, as paths on disk, in walk order
+    ///
+    /// Together with the skipped files, these are what the output depends on,
+    /// so watching them tells when it may have changed.
+    pub seen: Vec<PathBuf>,
+}
+
+impl WalkResult {
+    /// The content of an included file, without its header and footer
+    ///
+    /// Empty for a deduplicated copy; the content is under `identical_to`.
+    /// Only for output held in `content`, not in a spool.
+    pub fn file_body(&self, file: &IncludedFile) -> &str {
+        &self.content[file.body.clone()]
+    }
+}
+
+/// What kind of content a streamed file has
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    /// Text, decoded to UTF-8
+    Text,
+    /// Binary content, only yielded when binaries are included
+    Binary,
+}
+
+/// One file yielded by `walk_iter`
+#[derive(Clone, Debug)]
+pub struct FileEntry {
+    /// Path as it would be shown in the output header
+    pub path: PathBuf,
+    /// The file's text, without a header (empty for binary files)
+    pub content: String,
+    pub kind: FileKind,
+    /// Size of the file on disk in bytes
+    pub size: usize,
+}
+
+/// Iterator over the files a walk would include, returned by `walk_iter`
+pub struct WalkIter {
+    walker: DirectoryWalker,
+    done: bool,
+}
+
+impl WalkIter {
+    /// Statistics gathered so far; complete once the iterator is exhausted
+    pub fn stats(&self) -> &StatsCollector {
+        &self.walker.stats
+    }
+
+    /// Which limit stopped the walk, if any
+    pub fn truncated_by(&self) -> Option<Limit> {
+        self.walker.truncated_by
+    }
+
+    /// Whether the walk was stopped through `WalkOptions::cancel`
+    pub fn interrupted(&self) -> bool {
+        self.walker.interrupted
+    }
+}
+
+impl Iterator for WalkIter {
+    type Item = Result<FileEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.walker.streamed.as_mut().and_then(VecDeque::pop_front) {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+            match self.walker.step() {
+                Ok(more) => self.done = !more,
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+/// Walk the paths lazily, yielding each included file as it is read
+///
+/// Applies the same filters and limits as `walk_and_collect`, but nothing is
+/// concatenated: truncation markers, notes and the tree are left out, and the
+/// iterator simply ends when a size or token limit is reached.
+pub fn walk_iter(paths: &[PathBuf], options: WalkOptions) -> Result<WalkIter> {
+    let mut walker = DirectoryWalker::new(options);
+    walker.streamed = Some(VecDeque::new());
+
+    for path in paths {
+        walker.add_root(path)?;
+    }
+    walker.start();
+
+    Ok(WalkIter { walker, done: false })
 }
 
 /// Main entry point for walking directory tree and collecting contents
-pub fn walk_and_collect(paths: &[PathBuf], options: WalkOptions) -> io::Result<WalkResult> {
+pub fn walk_and_collect(paths: &[PathBuf], options: WalkOptions) -> Result<WalkResult> {
     let mut walker = DirectoryWalker::new(options);
 
     for path in paths {
-        walker.add_root(path);
+        walker.add_root(path)?;
     }
 
     walker.walk()
 }
 
+/// Whether a walk would collect a path, and why, as worked out by `explain`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Explanation {
+    /// Collected, having passed every filter
+    Included,
+    /// Collected from a path in `WalkOptions::also`, which bypasses most filters
+    Forced { also: PathBuf },
+    /// Left out for `reason`, which applies to `at`: the path itself or a directory above it
+    Skipped { at: PathBuf, reason: SkipReason },
+    /// Not at or below any of the paths walked
+    NotReached,
+}
+
+/// Work out whether a walk of `paths` would collect `target`, and which rule decides
+///
+/// Each directory from the walked path down to `target` goes through the
+/// same filters as in a walk, so a directory that is left out explains
+/// everything below it. A file that passes them is then read as in a walk,
+/// for the size limits and the binary, empty and generated checks. Limits
+/// that depend on the other files (`max_files`, `max_files_per_dir`, the
+/// size and token budgets they use up) aren't taken into account.
+pub fn explain(paths: &[PathBuf], target: &Path, options: WalkOptions) -> Result<Explanation> {
+    let mut walker = DirectoryWalker::new(options);
+
+    for path in paths {
+        walker.add_root(path)?;
+    }
+    walker.start();
+
+    walker.explain(target)
+}
+
 /// Handles directory traversal using breadth-first search
 struct DirectoryWalker {
     contents: Vec<String>,
+    // Length of `contents` once joined with newlines
+    content_len: usize,
+    // Where sections go instead of `contents` with `low_memory`, and the first error writing to it
+    spool: Option<Spool>,
+    spool_error: Option<std::io::Error>,
+    files: Vec<IncludedFile>,
+    // Directories walked and files read, for `WalkResult::seen`
+    seen: Vec<PathBuf>,
     total_size: usize,
+    total_tokens: usize,
+    truncated_by: Option<Limit>,
     truncated: bool,
+    interrupted: bool,
     stats: StatsCollector,
     options: WalkOptions,
     gitignore_managers: Vec<GitignoreManager>,
-    exclude_matcher: ExcludeMatcher,
+    // Per root: its canonical path and the git files to keep, when filtering by git
+    git_files: Vec<Option<(PathBuf, GitFiles)>>,
+    // Per root: the files git saw modified since `modified_since`, when judging by git history
+    recent_git_files: Vec<Option<GitFiles>>,
+    // The last commit of each file in the repositories of the roots, with `git_meta`
+    last_commits: Vec<LastCommits>,
+    filters: FilterPipeline,
+    priority_matcher: PatternMatcher,
     root_paths: Vec<PathBuf>,
-    visited_paths: HashSet<PathBuf>,
+    // Device ID of each root, when staying on one filesystem
+    root_devices: Vec<Option<u64>>,
+    current_root: usize,
+    // Roots from this index on come from `WalkOptions::also`
+    first_also_root: usize,
+    // Canonical `also` paths, left for their own roots when met during the walk
+    also_targets: HashSet<PathBuf>,
+    // Canonical paths already walked, mapped to the path they were first reached by
+    visited_paths: HashMap<PathBuf, PathBuf>,
+    // Device and inode of each file read, so hard links to it are only read once
+    visited_files: HashMap<(u64, u64), PathBuf>,
+    // Hash and length of each included text body, mapped to the path it was shown under
+    content_hashes: HashMap<(u64, usize), PathBuf>,
+    // Paths still to walk, with their depth and the root they came from
+    queue: VecDeque<(PathBuf, usize, usize)>,
+    // Work left over from the directory being walked, done before the next queued path
+    pending: VecDeque<Pending>,
+    // Work held back until every priority file is collected, with the root it came from
+    deferred: VecDeque<(usize, Pending)>,
+    // Commands whose output is still to be added, after every file
+    commands: VecDeque<String>,
+    // Sections held back until the walk is done, when fitting files to the limits
+    held: Option<Vec<Held>>,
+    // Files handed out one at a time by `walk_iter` instead of collected into `contents`
+    streamed: Option<VecDeque<FileEntry>>,
+    // Processed content from earlier runs, when caching
+    cache: Option<FileCache>,
+}
+
+/// Output held back by `WalkOptions::fit` until every file has been read
+enum Held {
+    /// A non-file section, such as a per-directory limit note
+    Note(String),
+    File {
+        path: PathBuf,
+        display_path: PathBuf,
+        section: Section,
+        kind: FileKind,
+        tokens: usize,
+        // The root the file was reached from
+        root: usize,
+    },
+}
+
+/// What the filters decide about an entry met while walking (see `DirectoryWalker::verdict`)
+enum Verdict {
+    Walk,
+    Skip(SkipReason),
+    /// Left for its own root, being a path from `WalkOptions::also`
+    AlsoRoot,
+}
+
+/// Work deferred while walking one directory
+enum Pending {
+    File(PathBuf),
+    /// Text piped in through standard input
+    Stdin(String),
+    /// A URL to fetch
+    Url(String),
+    /// Files left out by the per-directory limit, summarized after the rest
+    Omitted {
+        dir: PathBuf,
+        files: Vec<PathBuf>,
+        limit: usize,
+    },
+    /// Subdirectories to queue once the directory's files are done
+    Subdirs { dirs: Vec<PathBuf>, depth: usize },
 }
 
 impl DirectoryWalker {
     /// Create a new directory walker
     fn new(options: WalkOptions) -> Self {
-        let exclude_matcher = ExcludeMatcher::new(options.exclude_patterns.clone());
+        let filters = FilterPipeline::for_options(&options);
+        let priority_matcher = PatternMatcher::new(options.priority_patterns.clone());
+        let fit = options.fit;
+        let commands = options.commands.iter().cloned().collect();
+        let mut stats = StatsCollector::new();
+        stats.set_tokens_exact(options.tokenizer.is_exact());
+        stats.set_model(options.model);
         Self {
             contents: Vec::new(),
+            content_len: 0,
+            spool: None,
+            spool_error: None,
+            files: Vec::new(),
+            seen: Vec::new(),
             total_size: 0,
+            total_tokens: 0,
+            truncated_by: None,
             truncated: false,
-            stats: StatsCollector::new(),
+            interrupted: false,
+            stats,
             options,
             gitignore_managers: Vec::new(),
-            exclude_matcher,
+            git_files: Vec::new(),
+            recent_git_files: Vec::new(),
+            last_commits: Vec::new(),
+            filters,
+            priority_matcher,
             root_paths: Vec::new(),
-            visited_paths: HashSet::new(),
+            root_devices: Vec::new(),
+            current_root: 0,
+            first_also_root: 0,
+            also_targets: HashSet::new(),
+            visited_paths: HashMap::new(),
+            visited_files: HashMap::new(),
+            content_hashes: HashMap::new(),
+            queue: VecDeque::new(),
+            pending: VecDeque::new(),
+            deferred: VecDeque::new(),
+            commands,
+            held: fit.then(Vec::new),
+            streamed: None,
+            cache: None,
         }
     }
 
     /// Add a root path to process
-    fn add_root(&mut self, path: &Path) {
+    fn add_root(&mut self, path: &Path) -> Result<()> {
         self.root_paths.push(path.to_path_buf());
 
-        let gitignore = GitignoreManager::new(path);
+        let gitignore = GitignoreManager::with_dot_ignore(path, self.options.dot_ignore);
 
         // Record if gitignore is active
         if gitignore.has_active_gitignores() {
@@ -124,42 +747,282 @@ impl DirectoryWalker {
         }
 
         self.gitignore_managers.push(gitignore);
-    }
 
-    /// Walk the directory tree using breadth-first search
-    fn walk(mut self) -> io::Result<WalkResult> {
-        // Use a queue for BFS - process all files at each level before subdirectories
-        let mut queue = VecDeque::new();
+        let git_files = if let Some(reference) = &self.options.diff_ref {
+            let changed = GitFiles::changed(path, reference, self.options.git_tracked)
+                .map_err(Error::Git)?;
+            path.canonicalize().ok().zip(Some(changed))
+        } else if self.options.git_tracked {
+            path.canonicalize().ok().zip(GitFiles::tracked(path))
+        } else {
+            None
+        };
+        self.git_files.push(git_files);
+
+        let recent_git_files = match self.options.modified_since {
+            Some(since) if self.options.since_git => GitFiles::modified_since(path, since),
+            _ => None,
+        };
+        self.recent_git_files.push(recent_git_files);
+        Ok(())
+    }
 
-        // Add all root paths to the queue
-        for path in self.root_paths.clone() {
-            queue.push_back(path);
+    /// Add the force-included paths as extra roots after the regular ones
+    fn add_also_roots(&mut self) {
+        self.first_also_root = self.root_paths.len();
+        for path in self.options.also.clone() {
+            if let Ok(canonical) = path.canonicalize() {
+                self.also_targets.insert(canonical);
+            }
+            self.root_paths.push(path);
         }
+    }
 
-        // Process queue in BFS order
-        while let Some(path) = queue.pop_front() {
-            if self.truncated {
-                break;
+    /// Read the history of each repository a root is in, once even if several roots share it
+    fn load_last_commits(&mut self) {
+        for root in &self.root_paths {
+            let Ok(canonical) = root.canonicalize() else {
+                continue;
+            };
+            if self.last_commits.iter().any(|commits| canonical.starts_with(commits.top())) {
+                continue;
             }
+            if let Some(commits) = LastCommits::of_repository(root) {
+                self.last_commits.push(commits);
+            }
+        }
+    }
 
-            // Process this path and collect subdirectories
-            let subdirs = self.process_path_bfs(&path)?;
+    /// What is known about a file besides its content, for its header
+    fn file_meta(&self, path: &Path) -> FileMeta {
+        // Entries of archives have no modification time of their own on disk
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let commit = if self.last_commits.is_empty() {
+            None
+        } else {
+            path.canonicalize().ok().and_then(|canonical| {
+                self.last_commits
+                    .iter()
+                    .find_map(|commits| commits.get(&canonical))
+                    .cloned()
+            })
+        };
+        FileMeta { modified, commit }
+    }
 
-            // Add subdirectories to the end of the queue (BFS)
-            for subdir in subdirs {
-                queue.push_back(subdir);
-            }
+    /// Whether the current root bypasses filters because it came from `also`
+    fn forcing(&self) -> bool {
+        (self.first_also_root..self.root_paths.len()).contains(&self.current_root)
+    }
+
+    /// Walk the directory tree using breadth-first search
+    fn walk(mut self) -> Result<WalkResult> {
+        if self.options.low_memory {
+            let spool = Spool::create().map_err(|source| Error::Walk {
+                path: std::env::temp_dir(),
+                source,
+            })?;
+            self.spool = Some(spool);
+        }
+        self.start();
+        while self.step()? {}
+
+        if let Some(suffix) = self.options.suffix.clone() {
+            self.push_content(suffix);
         }
 
+        // The tree is rendered last since it lists exactly what was included
+        if self.options.tree && !self.files.is_empty() {
+            let paths: Vec<&PathBuf> = self.files.iter().map(|file| &file.path).collect();
+            let tree = format!("--- Directory tree ---\n{}", TreeRenderer::render(&paths));
+            self.insert_front(tree);
+        }
+        // The index goes in front of the tree, so its line numbers count the tree's lines
+        if self.options.toc && !self.files.is_empty() {
+            let toc = self.table_of_contents();
+            self.insert_front(toc);
+        }
+
+        if let (Some(spool), Some(source)) = (&self.spool, self.spool_error.take()) {
+            let path = spool.path().to_path_buf();
+            return Err(Error::Walk { path, source });
+        }
         Ok(WalkResult {
             content: self.contents.join("\n"),
+            spool: self.spool,
+            files: self.files,
             stats: self.stats,
             truncated: self.truncated,
+            truncated_by: self.truncated_by,
+            interrupted: self.interrupted,
+            prefix: self.options.prefix,
+            suffix: self.options.suffix,
+            seen: self.seen,
         })
     }
 
+    /// Queue the roots, including the force-included ones, at depth 0
+    fn start(&mut self) {
+        self.add_also_roots();
+
+        if self.options.cache || self.options.changed_only {
+            self.cache = FileCache::for_roots(&self.root_paths);
+        }
+
+        if self.options.one_file_system {
+            self.root_devices = self.root_paths.iter().map(|root| device_id(root)).collect();
+        }
+        if self.options.git_meta {
+            self.load_last_commits();
+        }
+
+        if let Some(text) = self.options.stdin.take() {
+            self.pending.push_back(Pending::Stdin(text));
+        }
+        for url in self.options.urls.clone() {
+            self.pending.push_back(Pending::Url(url));
+        }
+
+        // The text around the files is set aside from the limits up front
+        for text in [&self.options.prefix, &self.options.suffix].into_iter().flatten() {
+            self.total_size += text.len() + 1;
+            self.total_tokens += self.options.tokenizer.count(text);
+        }
+        if let Some(prefix) = self.options.prefix.clone() {
+            self.push_content(prefix);
+        }
+
+        // Remember which root each entry came from
+        for (root, path) in self.root_paths.clone().into_iter().enumerate() {
+            self.queue.push_back((path, 0, root));
+        }
+    }
+
+    /// Do the next piece of work, returning whether there is any left
+    ///
+    /// A directory's files are processed before the next queued path, so paths
+    /// are still walked level by level (BFS), each directory's files first.
+    fn step(&mut self) -> Result<bool> {
+        if !self.truncated
+            && self.options.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            self.push_content(format!(
+                "\n--- INTERRUPTED: {} files collected before the walk was stopped ---",
+                self.files.len()
+            ));
+            self.truncated = true;
+            self.interrupted = true;
+        }
+
+        if self.truncated {
+            self.finish();
+            // Files left out by a per-directory limit are still reported as such
+            let deferred = std::mem::take(&mut self.deferred).into_iter().map(|(_, pending)| pending);
+            for pending in std::mem::take(&mut self.pending).into_iter().chain(deferred) {
+                if let Pending::Omitted { files, limit, .. } = pending {
+                    for file in files {
+                        self.skip(&file, SkipReason::DirectoryLimit { limit });
+                    }
+                }
+            }
+            return Ok(false);
+        }
+
+        if let Some(pending) = self.pending.pop_front() {
+            match pending {
+                Pending::File(path) => self.process_file(&path)?,
+                Pending::Stdin(text) => self.process_stdin(text),
+                Pending::Url(url) => self.process_url(&url)?,
+                Pending::Omitted { dir, files, limit } => self.note_omitted(&dir, files, limit),
+                Pending::Subdirs { dirs, depth } => self.queue_subdirs(dirs, depth),
+            }
+            return Ok(true);
+        }
+
+        let Some((path, depth, root)) = self.queue.pop_front() else {
+            // The whole tree has been walked; collect what was held back
+            let Some((root, pending)) = self.deferred.pop_front() else {
+                if let Some(command) = self.commands.pop_front() {
+                    self.process_command(&command)?;
+                    return Ok(true);
+                }
+                self.finish();
+                return Ok(false);
+            };
+            self.current_root = root;
+            self.pending.push_back(pending);
+            return Ok(true);
+        };
+
+        // Process this path and collect subdirectories
+        self.current_root = root;
+        let subdirs = self.process_path_bfs(&path)?;
+        self.pending.push_back(Pending::Subdirs {
+            dirs: subdirs,
+            depth: depth + 1,
+        });
+        Ok(true)
+    }
+
+    /// Wrap up once there is no work left: append what was held back and the unchanged files, and save the cache
+    fn finish(&mut self) {
+        self.release_held();
+        if self.options.changed_only && self.options.list_unchanged {
+            let unchanged: Vec<String> = self
+                .stats
+                .skipped()
+                .iter()
+                .filter(|entry| entry.reason == SkipReason::UnchangedSinceLastRun)
+                .map(|entry| self.display_path(&entry.path).display().to_string())
+                .collect();
+            if !unchanged.is_empty() {
+                self.push_content(format!(
+                    "--- Unchanged since the last run: {} files ---\n{}",
+                    unchanged.len(),
+                    unchanged.join("\n")
+                ));
+            }
+        }
+        // The cache only saves time, so failing to write it doesn't fail the walk
+        if let Some(cache) = self.cache.take() {
+            let _ = cache.save();
+        }
+    }
+
+    /// Add subdirectories to the end of the queue (BFS), respecting max depth
+    fn queue_subdirs(&mut self, dirs: Vec<PathBuf>, depth: usize) {
+        for subdir in dirs {
+            if self.exceeds_max_depth(depth) {
+                self.skip(&subdir, SkipReason::Depth);
+                continue;
+            }
+            self.queue.push_back((subdir, depth, self.current_root));
+        }
+    }
+
+    /// Follow a directory's files with a note saying how many were left out
+    fn note_omitted(&mut self, dir: &Path, files: Vec<PathBuf>, limit: usize) {
+        self.push_content(format!(
+            "--- {}: {} more files omitted (limit of {} per directory) ---",
+            self.display_path(dir).display(),
+            files.len(),
+            limit
+        ));
+        for file in files {
+            self.skip(&file, SkipReason::DirectoryLimit { limit });
+        }
+    }
+
+    /// Check if a directory at the given depth is too deep to descend into
+    ///
+    /// Roots are at depth 0, so `max_depth: Some(0)` only collects files
+    /// directly inside each root and `Some(1)` also those one level down.
+    fn exceeds_max_depth(&self, depth: usize) -> bool {
+        self.options.max_depth.is_some_and(|max_depth| depth > max_depth)
+    }
+
     /// Process a path and return any subdirectories to be queued
-    fn process_path_bfs(&mut self, path: &Path) -> io::Result<Vec<PathBuf>> {
+    fn process_path_bfs(&mut self, path: &Path) -> Result<Vec<PathBuf>> {
         if self.truncated {
             return Ok(Vec::new());
         }
@@ -174,63 +1037,131 @@ impl DirectoryWalker {
         };
 
         // Check if we've already visited this path
-        if !self.visited_paths.insert(canonical_path.clone()) {
-            // Path was already in the set, skip it
+        if let Some(first) = self.visited_paths.get(&canonical_path) {
+            let reason = SkipReason::Duplicate { of: first.clone() };
+            self.skip(path, reason);
             return Ok(Vec::new());
         }
+        self.visited_paths.insert(canonical_path, path.to_path_buf());
 
-        // Check gitignore first (unless --all is specified)
-        if !self.options.include_all {
-            for gitignore in &self.gitignore_managers {
-                if gitignore.should_ignore(path) {
-                    if path.is_file() {
-                        self.stats.record_gitignored_file();
-                    } else if path.is_dir() {
-                        self.stats.record_gitignored_directory();
-                    }
-                    return Ok(Vec::new());
-                }
-            }
+        if let Some(reason) = self.queued_skip_reason(path) {
+            self.skip(path, reason);
+            return Ok(Vec::new());
         }
 
         if path.is_file() {
-            // Skip hidden files (starting with '.') unless --all is specified
-            if !self.options.include_all
-                && let Some(file_name) = path.file_name()
-                && let Some(name_str) = file_name.to_str()
-                && name_str.starts_with('.')
-            {
-                self.stats.record_skipped_file();
-                return Ok(Vec::new());
+            // Archives named as roots are walked through their entries
+            match ArchiveFormat::from_path(path).filter(|_| self.root_paths[self.current_root] == path) {
+                Some(format) => self.process_archive(path, format)?,
+                None => self.process_file(path)?,
             }
-            self.process_file(path)?;
             Ok(Vec::new())
         } else if path.is_dir() {
-            // Skip hidden directories (starting with '.') unless --all is specified
-            if !self.options.include_all
-                && let Some(dir_name) = path.file_name()
-                && let Some(name_str) = dir_name.to_str()
-                && name_str.starts_with('.')
-            {
-                self.stats.record_skipped_directory();
-                return Ok(Vec::new());
-            }
             self.process_directory_bfs(path)
         } else {
             Ok(Vec::new())
         }
     }
 
+    /// See `explain`: try each root the target is at or below, in walk order
+    fn explain(&mut self, target: &Path) -> Result<Explanation> {
+        let canonical = target.canonicalize().map_err(|source| Error::Walk {
+            path: target.to_path_buf(),
+            source,
+        })?;
+
+        for root in 0..self.root_paths.len() {
+            let root_path = self.root_paths[root].clone();
+            let Some(relative) = root_path
+                .canonicalize()
+                .ok()
+                .and_then(|canonical_root| canonical.strip_prefix(canonical_root).ok().map(Path::to_path_buf))
+            else {
+                continue;
+            };
+            self.current_root = root;
+            if let Some(explanation) = self.explain_below(&root_path, &relative)? {
+                return Ok(explanation);
+            }
+        }
+        Ok(Explanation::NotReached)
+    }
+
+    /// Explain the path at `relative` below a root, or `None` if it is left for an `also` root
+    fn explain_below(&mut self, root_path: &Path, relative: &Path) -> Result<Option<Explanation>> {
+        let skipped = |at: &Path, reason| {
+            Some(Explanation::Skipped {
+                at: at.to_path_buf(),
+                reason,
+            })
+        };
+        if let Some(reason) = self.queued_skip_reason(root_path) {
+            return Ok(skipped(root_path, reason));
+        }
+
+        let mut path = root_path.to_path_buf();
+        let components: Vec<_> = relative.components().collect();
+        for (depth, component) in components.iter().enumerate() {
+            // Ignore files in each directory apply below it, as they are read when walking
+            for gitignore in &mut self.gitignore_managers {
+                gitignore.check_directory(&path);
+            }
+            path.push(component);
+            let is_dir = path.is_dir();
+            match self.verdict(&path, is_dir, path.is_file()) {
+                Verdict::Walk => {}
+                Verdict::Skip(reason) => return Ok(skipped(&path, reason)),
+                Verdict::AlsoRoot => return Ok(None),
+            }
+            if is_dir && self.exceeds_max_depth(depth + 1) {
+                return Ok(skipped(&path, SkipReason::Depth));
+            }
+        }
+
+        if path.is_file() {
+            self.process_file(&path)?;
+            if let Some(entry) = self.stats.skipped().iter().rev().find(|entry| entry.path == path) {
+                return Ok(skipped(&path, entry.reason.clone()));
+            }
+        }
+        Ok(Some(if self.forcing() {
+            Explanation::Forced {
+                also: root_path.to_path_buf(),
+            }
+        } else {
+            Explanation::Included
+        }))
+    }
+
+    /// Why a queued path is left out before it is entered, if it is
+    ///
+    /// Entries were already filtered when their directory was read, so this
+    /// decides for roots: ignore files and hidden names apply to them too,
+    /// except to roots from `also`.
+    fn queued_skip_reason(&self, path: &Path) -> Option<SkipReason> {
+        if self.forcing() {
+            return None;
+        }
+        // Check git or ignore files first (unless ignored paths are included)
+        if let Some(reason) = self.ignore_reason(path, path.is_dir()) {
+            return Some(reason);
+        }
+        // Skip hidden files and directories (starting with '.') unless included
+        (!self.options.include_hidden && filter::is_hidden(path)).then_some(SkipReason::Hidden)
+    }
+
     /// Process a directory in BFS manner - process files first, then return subdirs
-    fn process_directory_bfs(&mut self, path: &Path) -> io::Result<Vec<PathBuf>> {
+    fn process_directory_bfs(&mut self, path: &Path) -> Result<Vec<PathBuf>> {
         if self.truncated {
             return Ok(Vec::new());
         }
+        self.seen.push(path.to_path_buf());
 
         // Record this directory in statistics
         self.stats.record_directory();
 
         // Check for .gitignore in this directory for all managers
+        let started = Instant::now();
         for gitignore in &mut self.gitignore_managers {
             gitignore.check_directory(path);
 
@@ -240,165 +1171,941 @@ impl DirectoryWalker {
                 self.stats.set_gitignore_active(gitignore_files);
             }
         }
+        self.stats.record_phase(Phase::Ignore, started.elapsed());
 
-        // Read all entries
-        let mut all_entries: Vec<PathBuf> = fs::read_dir(path)?
+        // Read all entries, with their types so each isn't looked up again per check
+        let started = Instant::now();
+        let mut all_entries: Vec<(PathBuf, Option<fs::FileType>)> = fs::read_dir(path)
+            .map_err(|source| Error::Walk {
+                path: path.to_path_buf(),
+                source,
+            })?
             .filter_map(|e| e.ok())
-            .map(|e| e.path())
+            .map(|e| {
+                let entry = e.path();
+                // A link counts as what it points to (`None` if broken)
+                let file_type = match e.file_type() {
+                    Ok(file_type) if file_type.is_symlink() => fs::metadata(&entry).ok().map(|m| m.file_type()),
+                    file_type => file_type.ok(),
+                };
+                (entry, file_type)
+            })
             .collect();
+        self.stats.record_phase(Phase::Enumerate, started.elapsed());
 
         // Sort for deterministic ordering
-        all_entries.sort();
+        all_entries.sort_by(|a, b| a.0.cmp(&b.0));
 
-        // Separate files and directories  
+        // Separate files and directories. Ignored directories are dropped
+        // here, so their contents are never read.
         let mut files = Vec::new();
         let mut subdirs = Vec::new();
 
-        for entry in all_entries {
+        for (entry, file_type) in all_entries {
+            let is_dir = file_type.is_some_and(|file_type| file_type.is_dir());
+            let is_file = file_type.is_some_and(|file_type| file_type.is_file());
+
             // Check if we should skip this entry
-            if !self.should_process(&entry) {
+            if !self.should_process(&entry, is_dir, is_file) {
                 continue;
             }
 
-            if entry.is_file() {
+            if is_file {
                 files.push(entry);
-            } else if entry.is_dir() {
+            } else if is_dir {
                 subdirs.push(entry);
             }
         }
 
-        // Process all files first (breadth-first within this directory)
+        self.options.sort.sort(&mut files, self.options.reverse);
+
+        // Leave the rest of a crowded directory out
+        let omitted = match self.options.max_files_per_dir {
+            Some(limit) if files.len() > limit => files.split_off(limit),
+            _ => Vec::new(),
+        };
+
+        // Files are processed before the subdirectories (breadth-first within this directory)
+        let prioritizing = !self.options.priority_patterns.is_empty();
         for file in files {
-            if self.truncated {
-                break;
+            if prioritizing && self.priority_matcher.matching_pattern(&self.relative_path(&file), false).is_none() {
+                self.deferred.push_back((self.current_root, Pending::File(file)));
+            } else {
+                self.pending.push_back(Pending::File(file));
+            }
+        }
+        if let Some(limit) = self.options.max_files_per_dir
+            && !omitted.is_empty()
+        {
+            let note = Pending::Omitted {
+                dir: path.to_path_buf(),
+                files: omitted,
+                limit,
+            };
+            if prioritizing {
+                self.deferred.push_back((self.current_root, note));
+            } else {
+                self.pending.push_back(note);
             }
-            self.process_file(&file)?;
         }
 
         // Return subdirectories to be processed later
         Ok(subdirs)
     }
 
-    /// Check if a path should be processed
-    fn should_process(&mut self, path: &Path) -> bool {
-        // Check exclude patterns first
-        if self.exclude_matcher.should_exclude(path) {
-            if path.is_file() {
-                self.stats.record_skipped_file();
-            } else if path.is_dir() {
-                self.stats.record_skipped_directory();
+    /// Check if a path should be processed, recording why if not
+    fn should_process(&mut self, path: &Path, is_dir: bool, is_file: bool) -> bool {
+        match self.verdict(path, is_dir, is_file) {
+            Verdict::Walk => true,
+            Verdict::Skip(reason) => {
+                self.skip(path, reason);
+                false
             }
-            return false;
+            Verdict::AlsoRoot => false,
         }
+    }
 
-        // Check gitignore
-        if !self.options.include_all {
-            for gitignore in &self.gitignore_managers {
-                if gitignore.should_ignore(path) {
-                    if path.is_file() {
-                        self.stats.record_gitignored_file();
-                    } else if path.is_dir() {
-                        self.stats.record_gitignored_directory();
-                    }
-                    return false;
-                }
-            }
+    /// What the filters decide about an entry met while walking a directory
+    ///
+    /// The first filter of the pipeline to leave the path out decides;
+    /// nothing is recorded, so this can also explain a decision.
+    fn verdict(&self, path: &Path, is_dir: bool, is_file: bool) -> Verdict {
+        // Force-included paths are walked as their own roots, and everything under them passes
+        if !self.also_targets.is_empty()
+            && path.canonicalize().is_ok_and(|canonical| self.also_targets.contains(&canonical))
+        {
+            return Verdict::AlsoRoot;
+        }
 
-            // Check for hidden files/directories
-            if let Some(name) = path.file_name()
-                && let Some(name_str) = name.to_str()
-                && name_str.starts_with('.')
-            {
-                if path.is_file() {
-                    self.stats.record_skipped_file();
-                } else if path.is_dir() {
-                    self.stats.record_skipped_directory();
+        let relative = self.relative_path(path);
+        let entry = Entry::new(path, &relative, is_dir, is_file);
+        match self.filters.decide(Stage::Path, &entry, self, self.forcing()) {
+            Some((_, reason)) => Verdict::Skip(reason),
+            None => Verdict::Walk,
+        }
+    }
+
+    /// Why a path is left out by git or ignore files, if it is
+    ///
+    /// Under a root filtered by git, git decides alone and ignore files are not consulted.
+    fn ignore_reason(&self, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        match self.git_files.get(self.current_root) {
+            Some(Some((canonical_root, git_files))) => {
+                let root = &self.root_paths[self.current_root];
+                let relative = path.strip_prefix(root).ok()?;
+                if git_files.contains(&canonical_root.join(relative)) {
+                    None
+                } else if let Some(reference) = &self.options.diff_ref {
+                    Some(SkipReason::Unchanged {
+                        reference: reference.clone(),
+                    })
+                } else {
+                    Some(SkipReason::Untracked)
                 }
-                return false;
             }
+            _ => self.gitignore_reason(path, is_dir),
+        }
+    }
+
+    /// Find the ignore file that ignores a path (never when ignored paths are included)
+    fn gitignore_reason(&self, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        if self.options.include_ignored {
+            return None;
+        }
+
+        self.gitignore_managers
+            .iter()
+            .find_map(|gitignore| gitignore.ignoring_rule(path, is_dir))
+            .map(|rule| SkipReason::Gitignored {
+                source: rule.source.to_path_buf(),
+                line: rule.line,
+                pattern: rule.pattern.to_string(),
+            })
+    }
+
+    /// Find the `.gitattributes` marking a path as generated or vendored (never when those are included)
+    fn generated_reason(&self, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        if self.options.include_generated {
+            return None;
+        }
+
+        self.gitignore_managers
+            .iter()
+            .find_map(|gitignore| gitignore.linguist_attribute(path, is_dir))
+            .map(|(attribute, source)| SkipReason::Generated {
+                attribute: attribute.to_string(),
+                source: source.to_path_buf(),
+            })
+    }
+
+    /// Record a skipped path in the statistics
+    fn skip(&mut self, path: &Path, reason: SkipReason) {
+        self.report(ProgressEvent::FileSkipped { path, reason: &reason });
+        self.stats.record_skip(path, path.is_dir(), reason);
+    }
+
+    /// Pass an event to the progress callback, if there is one
+    fn report(&self, event: ProgressEvent<'_>) {
+        if let Some(progress) = &self.options.progress {
+            progress(event);
         }
+    }
 
-        true
+    /// Get a path relative to the root it was reached from, with '/' separators
+    fn relative_path(&self, path: &Path) -> String {
+        let root = &self.root_paths[self.current_root];
+        let relative = path
+            .strip_prefix(root)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .unwrap_or(path);
+
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Get the path to show in headers for a walked path
+    fn display_path(&self, path: &Path) -> PathBuf {
+        match self.options.path_display {
+            PathDisplay::AsGiven => path.to_path_buf(),
+            PathDisplay::Canonical => path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+        }
     }
 
     /// Process a file
-    fn process_file(&mut self, path: &Path) -> io::Result<()> {
-        use crate::file_processor::FileContent;
+    fn process_file(&mut self, path: &Path) -> Result<()> {
+        self.report(ProgressEvent::FileStarted { path });
+        self.seen.push(path.to_path_buf());
+
+        // Hard links share a canonical path only with themselves, so compare inodes
+        if let Some(id) = file_id(path) {
+            if let Some(first) = self.visited_files.get(&id) {
+                let reason = SkipReason::Duplicate { of: first.clone() };
+                self.skip(path, reason);
+                return Ok(());
+            }
+            self.visited_files.insert(id, path.to_path_buf());
+        }
+
+        let line_range = self.line_range(path);
+        // Compressed files are processed as the file they decompress to
+        let compression = Compression::from_path(path);
+        let inner_path = compression.map(|_| Compression::inner_path(path));
+        // Files with a processor are limited by the size of what it extracts
+        let strategy = self.options.processors.strategy(inner_path.as_deref().unwrap_or(path));
+        let has_processor = strategy.processor().is_some() || compression.is_some();
 
         // Check file size before processing
         if let Ok(metadata) = path.metadata() {
-            let file_size = metadata.len() as usize;
-            if file_size > self.options.max_file_size {
-                self.stats.record_skipped_large_file();
+            let relative = self.relative_path(path);
+            let entry = Entry {
+                size: Some(metadata.len() as usize),
+                modified: metadata.modified().ok(),
+                any_size: line_range.is_some() || has_processor,
+                ..Entry::new(path, &relative, false, true)
+            };
+            if let Some((_, reason)) = self.filters.decide(Stage::Read, &entry, self, self.forcing()) {
+                self.skip(path, reason);
                 return Ok(());
             }
         }
 
-        let content = FileProcessor::process(path);
-
-        match &content {
-            FileContent::Text(_) => {
-                if let Some(formatted) = FileProcessor::format_content(path, content) {
-                    let size = formatted.len();
-
-                    // Check if adding this would exceed the limit
-                    if self.total_size + size > self.options.max_size {
-                        self.contents.push(format!(
-                            "\n--- TRUNCATED: Size limit of {} reached ---\n--- {} collected, {} would exceed limit ---",
-                            ByteFormatter::format_as_unit(self.options.max_size),
-                            ByteFormatter::format(self.total_size),
-                            ByteFormatter::format(self.total_size + size)
-                        ));
-                        self.truncated = true;
+        // A file that fails to decompress is left to binary detection
+        let limit = if line_range.is_some() { usize::MAX } else { self.options.max_file_size };
+        // Content from the cache is by definition unchanged since the last run
+        let started = Instant::now();
+        let (content, changed) = match self.cache.as_mut().and_then(|cache| cache.get(path, strategy)) {
+            Some(content) => (content, false),
+            None => {
+                let decompressed = compression.and_then(|compression| compression.decompress(path, limit));
+                let content = match (&decompressed, &inner_path) {
+                    (Some(Limited::TooLarge(size)), _) => {
+                        let reason = SkipReason::TooLarge {
+                            size: *size,
+                            limit: self.options.max_file_size,
+                        };
+                        self.skip(path, reason);
                         return Ok(());
                     }
+                    (Some(Limited::Complete(bytes)), Some(inner_path)) => {
+                        FileProcessor::process_bytes(inner_path, bytes, strategy)
+                    }
+                    _ => FileProcessor::process(path, strategy),
+                };
+                let changed = self
+                    .cache
+                    .as_mut()
+                    .is_none_or(|cache| cache.insert(path, strategy, &content));
+                (content, changed)
+            }
+        };
+        self.stats.record_phase(Phase::Read, started.elapsed());
+        if self.options.changed_only && !changed {
+            self.skip(path, SkipReason::UnchangedSinceLastRun);
+            return Ok(());
+        }
+        let display_path = self.display_path(path);
+        // Compressed files are otherwise binary, so text content was decompressed
+        let note = compression.map(|compression| format!("decompressed from {}", compression));
+        self.include_content(path, display_path, content, has_processor, note, || FileProcessor::binary_info(path));
+        Ok(())
+    }
+
+    /// Add a file's processed content to the output, or record why it was left out
+    ///
+    /// `size_limited` says the text still has to be checked against the
+    /// per-file size limit, and `note` is added to the header of text content.
+    fn include_content(
+        &mut self,
+        path: &Path,
+        display_path: PathBuf,
+        content: FileContent,
+        size_limited: bool,
+        note: Option<String>,
+        binary_info: impl FnOnce() -> String,
+    ) {
+        let line_range = self.line_range(path);
+
+        // Checked before the content counts toward any budget; files named on
+        // the command line or reached through `also` are kept
+        if !self.options.include_generated
+            && !self.forcing()
+            && self.root_paths.get(self.current_root).is_none_or(|root| root != path)
+            && let FileContent::Text(text, _) = &content
+            && let Some(marker) = FileProcessor::generated_marker(text)
+        {
+            let reason = SkipReason::GeneratedMarker {
+                marker: marker.to_string(),
+            };
+            self.skip(path, reason);
+            return;
+        }
 
-                    self.total_size += size;
-                    self.stats.record_text_file(path, size);
-                    self.contents.push(formatted);
+        match &content {
+            FileContent::Text(text, _) if self.options.skip_empty && text.trim().is_empty() => {
+                self.skip(path, SkipReason::Empty);
+            }
+            FileContent::Text(text, _)
+                if size_limited && line_range.is_none() && text.len() > self.options.max_file_size =>
+            {
+                let reason = SkipReason::TooLarge {
+                    size: text.len(),
+                    limit: self.options.max_file_size,
+                };
+                self.skip(path, reason);
+            }
+            FileContent::Text(text, info) => {
+                self.stats.record_text_info(info);
+                let (text, display_path, first_line) = match line_range {
+                    Some(range) => {
+                        let (slice, last) = FileProcessor::slice_lines(text, range);
+                        let shown = format!("{}:{}-{}", display_path.display(), range.start, last.max(range.start));
+                        (slice, PathBuf::from(shown), range.start)
+                    }
+                    None => (text.as_str(), display_path, 1),
+                };
+                // Note the original encoding of a file that had to be transcoded
+                let notes: Vec<String> = [
+                    note,
+                    info.transcoded().then(|| info.encoding.to_string()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let display_path = if notes.is_empty() {
+                    display_path
+                } else {
+                    PathBuf::from(format!("{} ({})", display_path.display(), notes.join(", ")))
+                };
+                let text = self.options.transforms.apply(path, text);
+                let meta = self.file_meta(path);
+                let section = self.text_section(&display_path, &text, first_line, &meta);
+                let (size, lines) = (section.text.len(), section.line_count());
+                if let Some(tokens) = self.append(path, display_path, section, FileKind::Text) {
+                    self.stats.record_text_file(path, size, tokens, lines);
                 }
             }
             FileContent::Binary => {
                 self.stats.record_binary_file(path);
-                // Skip binary files unless --all is specified
-                if self.options.include_all
-                    && let Some(formatted) = FileProcessor::format_content(path, content)
-                {
-                    let size = formatted.len();
-
-                    if self.total_size + size > self.options.max_size {
-                        self.contents.push(format!(
-                            "\n--- TRUNCATED: Size limit of {} reached ---\n--- {} collected, {} would exceed limit ---",
-                            ByteFormatter::format_as_unit(self.options.max_size),
-                            ByteFormatter::format(self.total_size),
-                            ByteFormatter::format(self.total_size + size)
-                        ));
-                        self.truncated = true;
-                        return Ok(());
-                    }
-
-                    self.total_size += size;
-                    self.contents.push(formatted);
+                // Skip binary files unless included
+                if !self.options.include_binary {
+                    self.skip(path, SkipReason::Binary);
+                } else {
+                    let section = self.options.formatter.section(&display_path, &binary_info());
+                    self.append(path, display_path, section, FileKind::Binary);
                 }
             }
-            FileContent::Unreadable => {
+            FileContent::Unreadable(cause) => {
                 self.stats.record_unreadable_file();
+                self.skip(path, SkipReason::Unreadable { cause: cause.clone() });
             }
         }
-
-        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::io::Write;
+    /// Process the files in an archive given as a root, as `archive.zip!/inner/path`
+    ///
+    /// Entries are filtered like files in a directory, by their path within
+    /// the archive. A file that can't be read as an archive is processed like
+    /// any other.
+    fn process_archive(&mut self, path: &Path, format: ArchiveFormat) -> Result<()> {
+        let archive = match Archive::open(path, format) {
+            Ok(archive) => archive,
+            Err(_) => return self.process_file(path),
+        };
+        self.seen.push(path.to_path_buf());
+        let display_root = self.display_path(path);
+        // Whether each directory within the archive is walked, decided once
+        let mut dirs: HashMap<PathBuf, bool> = HashMap::new();
 
-    fn setup_test_dir(name: &str) -> PathBuf {
-        let dir = PathBuf::from(format!("test_{}", name));
-        if dir.exists() {
+        for entry in archive.entries() {
+            if self.truncated {
+                break;
+            }
+            let entry_path = path.join(&entry.name);
+            let included_dirs = entry_path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| *dir != path)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .all(|dir| match dirs.get(dir) {
+                    Some(&included) => included,
+                    None => {
+                        let included = self.should_process(dir, true, false);
+                        dirs.insert(dir.to_path_buf(), included);
+                        included
+                    }
+                });
+            if !included_dirs || !self.should_process(&entry_path, false, true) {
+                continue;
+            }
+
+            self.report(ProgressEvent::FileStarted { path: &entry_path });
+            let strategy = self.options.processors.strategy(&entry_path);
+            let has_processor = strategy.processor().is_some();
+            let relative = self.relative_path(&entry_path);
+            let read = Entry {
+                size: Some(entry.size),
+                any_size: has_processor,
+                ..Entry::new(&entry_path, &relative, false, true)
+            };
+            if let Some((_, reason)) = self.filters.decide(Stage::Read, &read, self, self.forcing()) {
+                self.skip(&entry_path, reason);
+                continue;
+            }
+
+            let Some(bytes) = self.stats.time(Phase::Read, || archive.read(entry)) else {
+                self.stats.record_unreadable_file();
+                let cause = UnreadableCause::CorruptArchiveEntry;
+                self.skip(&entry_path, SkipReason::Unreadable { cause });
+                continue;
+            };
+            let content = self.stats.time(Phase::Read, || FileProcessor::process_bytes(&entry_path, &bytes, strategy));
+            let display_path = PathBuf::from(format!("{}!/{}", display_root.display(), entry.name));
+            self.include_content(&entry_path, display_path, content, has_processor, None, || {
+                FileProcessor::describe_binary(&bytes, bytes.len())
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a path is an entry inside an archive root rather than on disk
+    fn in_archive(&self, path: &Path) -> bool {
+        let root = &self.root_paths[self.current_root];
+        path != root && path.starts_with(root) && ArchiveFormat::from_path(root).is_some() && root.is_file()
+    }
+
+    /// The lines to include of a file given as a root, if only some are wanted
+    fn line_range(&self, path: &Path) -> Option<LineRange> {
+        // The same file reached through a directory root is included whole
+        if self.root_paths.get(self.current_root).is_none_or(|root| root != path) {
+            return None;
+        }
+        self.options
+            .line_ranges
+            .iter()
+            .find(|(file, _)| file == path)
+            .map(|(_, range)| *range)
+    }
+
+    /// Format text content as a section, redacting secrets and numbering its lines from `first_line` if asked to
+    ///
+    /// `meta` is what else is known about the file, for headers that show it.
+    fn text_section(&mut self, display_path: &Path, text: &str, first_line: usize, meta: &FileMeta) -> Section {
+        let started = Instant::now();
+        let text = if self.options.redact {
+            let (text, count) = redact::redact(text);
+            self.stats.record_redactions(count);
+            text
+        } else {
+            Cow::Borrowed(text)
+        };
+        let formatter = &self.options.formatter;
+        let section = if self.options.line_numbers {
+            formatter.file_section(display_path, &output::number_lines(&text, first_line), meta)
+        } else {
+            formatter.file_section(display_path, &text, meta)
+        };
+        self.stats.record_phase(Phase::Format, started.elapsed());
+        section
+    }
+
+    /// Add the text from standard input as a section of its own
+    fn process_stdin(&mut self, text: String) {
+        let path = Path::new(Config::STDIN_PATH);
+        // Stdin isn't reached from any root
+        self.current_root = self.root_paths.len();
+        self.report(ProgressEvent::FileStarted { path });
+
+        if self.options.skip_empty && text.trim().is_empty() {
+            self.skip(path, SkipReason::Empty);
+            return;
+        }
+
+        let section = self.text_section(path, &text, 1, &FileMeta::default());
+        let (size, lines) = (section.text.len(), section.line_count());
+        if let Some(tokens) = self.append(path, path.to_path_buf(), section, FileKind::Text) {
+            self.stats.record_text_file(path, size, tokens, lines);
+        }
+    }
+
+    /// Fetch a URL and add its body like a file's content, headed by the URL
+    fn process_url(&mut self, url: &str) -> Result<()> {
+        let path = PathBuf::from(url);
+        // URLs aren't reached from any root
+        self.current_root = self.root_paths.len();
+        self.report(ProgressEvent::FileStarted { path: &path });
+
+        let body = match fetch::fetch(url, self.options.max_file_size)? {
+            Limited::Complete(body) => body,
+            Limited::TooLarge(size) => {
+                let reason = SkipReason::TooLarge {
+                    size,
+                    limit: self.options.max_file_size,
+                };
+                self.skip(&path, reason);
+                return Ok(());
+            }
+        };
+        // The last segment of the URL's path, like `openapi.yaml`, tells what kind of file it is
+        let kind = Path::new(url.split(['?', '#']).next().unwrap_or(url));
+        let strategy = self.options.processors.strategy(kind);
+        let content = FileProcessor::process_bytes(kind, &body, strategy);
+        self.include_content(&path, path.clone(), content, true, None, || {
+            FileProcessor::describe_binary(&body, body.len())
+        });
+        Ok(())
+    }
+
+    /// Run a command and add what it printed as a section of its own
+    fn process_command(&mut self, command_line: &str) -> Result<()> {
+        let path = PathBuf::from(format!("$ {}", command_line));
+        // Commands aren't reached from any root
+        self.current_root = self.root_paths.len();
+        self.report(ProgressEvent::FileStarted { path: &path });
+
+        let mut output = command::run(command_line)?;
+        if !output.success() {
+            if !output.stdout.is_empty() && !output.stdout.ends_with('\n') {
+                output.stdout.push('\n');
+            }
+            match output.code {
+                Some(code) => output.stdout.push_str(&format!("[exit status {}]", code)),
+                None => output.stdout.push_str("[killed by a signal]"),
+            }
+        }
+
+        let section = self.text_section(&path, &output.stdout, 1, &FileMeta::default());
+        let (size, lines) = (section.text.len(), section.line_count());
+        if let Some(tokens) = self.append(&path, path.clone(), section, FileKind::Text) {
+            self.stats.record_text_file(&path, size, tokens, lines);
+        }
+        Ok(())
+    }
+
+    /// Append a file's formatted section, returning its estimated tokens
+    ///
+    /// If the section would exceed the size or token limit, a truncation
+    /// marker is appended instead, the file is recorded as skipped and
+    /// `None` is returned.
+    ///
+    /// When fitting, the section is held back and `None` is returned; it is
+    /// appended for real by `release_held` once the walk is done. A file not
+    /// in `WalkOptions::selected` is skipped before either.
+    fn append(
+        &mut self,
+        path: &Path,
+        display_path: PathBuf,
+        section: Section,
+        kind: FileKind,
+    ) -> Option<usize> {
+        if let Some(selected) = &self.options.selected
+            && !selected.contains(&display_path)
+        {
+            self.skip(path, SkipReason::NotSelected);
+            return None;
+        }
+        let tokens = self.options.tokenizer.count(&section.text);
+        if let Some(held) = &mut self.held {
+            held.push(Held::File {
+                path: path.to_path_buf(),
+                display_path,
+                section,
+                kind,
+                tokens,
+                root: self.current_root,
+            });
+            return None;
+        }
+        self.append_counted(path, display_path, section, kind, tokens)
+    }
+
+    /// `append` for a section whose tokens have already been counted
+    fn append_counted(
+        &mut self,
+        path: &Path,
+        display_path: PathBuf,
+        section: Section,
+        kind: FileKind,
+        tokens: usize,
+    ) -> Option<usize> {
+        // A copy of an earlier file is replaced by a stub pointing at it
+        let body_key = (self.options.dedup && kind == FileKind::Text && self.streamed.is_none())
+            .then(|| &section.text[section.body.clone()])
+            .filter(|body| !body.is_empty())
+            .map(|body| {
+                let mut hasher = DefaultHasher::new();
+                body.hash(&mut hasher);
+                (hasher.finish(), body.len())
+            });
+        let identical_to = body_key.and_then(|key| self.content_hashes.get(&key)).cloned();
+        let (section, tokens) = match &identical_to {
+            Some(original) => {
+                let stub = FileProcessor::format_identical_header(&display_path, original);
+                let tokens = self.options.tokenizer.count(&stub);
+                let body = stub.len()..stub.len();
+                (Section { text: stub, body }, tokens)
+            }
+            None => (section, tokens),
+        };
+        let size = section.text.len();
+
+        // Check if adding this would exceed the limit
+        if self.total_size + size > self.options.max_size {
+            self.push_content(format!(
+                "\n--- TRUNCATED: Size limit of {} reached ---\n--- {} collected, {} would exceed limit ---",
+                ByteFormatter::format_as_unit(self.options.max_size),
+                ByteFormatter::format(self.total_size),
+                ByteFormatter::format(self.total_size + size)
+            ));
+            self.truncated = true;
+            self.truncated_by = Some(Limit::Size(self.options.max_size));
+            self.report(ProgressEvent::Truncated { limit: Limit::Size(self.options.max_size) });
+            self.skip(path, SkipReason::Budget);
+            return None;
+        }
+
+        if let Some(max_tokens) = self.options.max_tokens
+            && self.total_tokens + tokens > max_tokens
+        {
+            self.push_content(format!(
+                "\n--- TRUNCATED: Token limit of {} reached ---\n--- ~{} tokens collected, ~{} would exceed limit ---",
+                TokenEstimator::format(max_tokens),
+                TokenEstimator::format(self.total_tokens),
+                TokenEstimator::format(self.total_tokens + tokens)
+            ));
+            self.truncated = true;
+            self.truncated_by = Some(Limit::Tokens(max_tokens));
+            self.report(ProgressEvent::Truncated { limit: Limit::Tokens(max_tokens) });
+            self.skip(path, SkipReason::Budget);
+            return None;
+        }
+
+        if let Some(max_files) = self.options.max_files
+            && self.files.len() >= max_files
+        {
+            self.push_content(format!("\n--- TRUNCATED: File limit of {} reached ---", max_files));
+            self.truncated = true;
+            self.truncated_by = Some(Limit::Files(max_files));
+            self.report(ProgressEvent::Truncated { limit: Limit::Files(max_files) });
+            self.skip(path, SkipReason::Budget);
+            return None;
+        }
+
+        self.total_size += size;
+        self.total_tokens += tokens;
+        if let Some(streamed) = &mut self.streamed {
+            streamed.push_back(FileEntry {
+                path: display_path.clone(),
+                content: match kind {
+                    FileKind::Text => section.text[section.body.clone()].to_string(),
+                    FileKind::Binary => String::new(),
+                },
+                kind,
+                size: path.metadata().map_or(0, |metadata| metadata.len() as usize),
+            });
+        }
+        if let Some(key) = body_key {
+            self.content_hashes.entry(key).or_insert_with(|| display_path.clone());
+        }
+        self.report(ProgressEvent::FileIncluded { path, size, tokens });
+        let start = self.push_content(section.text);
+        self.files.push(IncludedFile {
+            path: display_path,
+            size,
+            tokens,
+            span: start..start + size,
+            body: start + section.body.start..start + section.body.end,
+            forced: self.forcing(),
+            identical_to,
+        });
+        Some(tokens)
+    }
+
+    /// Append the sections held back for `WalkOptions::fit`, leaving out the largest files until the rest fit
+    fn release_held(&mut self) {
+        let Some(held) = self.held.take() else {
+            return;
+        };
+
+        let mut sizes: Vec<(usize, usize, usize)> = held
+            .iter()
+            .enumerate()
+            .filter_map(|(index, section)| match section {
+                Held::File { section, tokens, .. } => Some((index, section.text.len(), *tokens)),
+                Held::Note(_) => None,
+            })
+            .collect();
+        // Anything counted already, such as the prefix and suffix, stays
+        let mut total_size = self.total_size + sizes.iter().map(|(_, size, _)| size).sum::<usize>();
+        let mut total_tokens = self.total_tokens + sizes.iter().map(|(_, _, tokens)| tokens).sum::<usize>();
+        let mut total_files = sizes.len();
+        let over_tokens = |tokens: usize| self.options.max_tokens.is_some_and(|max_tokens| tokens > max_tokens);
+        let over_files = |files: usize| self.options.max_files.is_some_and(|max_files| files > max_files);
+        let limit = if total_size > self.options.max_size {
+            Some(Limit::Size(self.options.max_size))
+        } else if over_tokens(total_tokens) {
+            self.options.max_tokens.map(Limit::Tokens)
+        } else if over_files(total_files) {
+            self.options.max_files.map(Limit::Files)
+        } else {
+            None
+        };
+
+        // Dropping the largest files first keeps the most files
+        sizes.sort_by_key(|&(_, size, tokens)| std::cmp::Reverse((size, tokens)));
+        let mut dropped = HashSet::new();
+        for (index, size, tokens) in sizes {
+            if total_size <= self.options.max_size && !over_tokens(total_tokens) && !over_files(total_files) {
+                break;
+            }
+            dropped.insert(index);
+            total_size -= size;
+            total_tokens -= tokens;
+            total_files -= 1;
+        }
+
+        for (index, entry) in held.into_iter().enumerate() {
+            match entry {
+                Held::Note(note) => {
+                    self.push_content(note);
+                }
+                Held::File { path, .. } if dropped.contains(&index) => self.skip(&path, SkipReason::Budget),
+                Held::File { path, display_path, section, kind, tokens, root } => {
+                    self.current_root = root;
+                    let (size, lines) = (section.text.len(), section.line_count());
+                    if self.append_counted(&path, display_path, section, kind, tokens).is_some()
+                        && kind == FileKind::Text
+                    {
+                        self.stats.record_text_file(&path, size, tokens, lines);
+                    }
+                }
+            }
+        }
+
+        if let Some(limit) = limit {
+            let left_out = match dropped.len() {
+                1 => "the largest file".to_string(),
+                count => format!("the {} largest files", count),
+            };
+            self.push_content(format!(
+                "\n--- TRUNCATED: {} limit reached, left out {} to fit ---",
+                limit, left_out
+            ));
+            self.truncated = true;
+            self.truncated_by = Some(limit);
+            self.report(ProgressEvent::Truncated { limit });
+        }
+    }
+
+    /// Insert a section at the start of the output, right after the prefix if there is one
+    fn insert_front(&mut self, section: String) {
+        // Everything after it moves down by its length plus the separator
+        let shift = section.len() + 1;
+        for file in &mut self.files {
+            file.span = file.span.start + shift..file.span.end + shift;
+            file.body = file.body.start + shift..file.body.end + shift;
+        }
+        match &mut self.spool {
+            Some(spool) => {
+                let at = self.options.prefix.as_ref().map_or(0, |prefix| prefix.len() + 1);
+                if let Err(error) = spool.insert(at, &format!("{}\n", section)) {
+                    self.spool_error.get_or_insert(error);
+                }
+            }
+            None => self.contents.insert(usize::from(self.options.prefix.is_some()), section),
+        }
+    }
+
+    /// List the included files with the line each one's section will start on
+    /// once the list itself is inserted with `insert_front`
+    fn table_of_contents(&mut self) -> String {
+        // Files are in output order, so newlines are counted in one pass
+        let offsets: Vec<usize> = self.files.iter().map(|file| file.span.start).collect();
+        let starts = match &mut self.spool {
+            Some(spool) => spool
+                .reader()
+                .and_then(|content| line_numbers(content, &offsets)),
+            None => line_numbers(self.contents.join("\n").as_bytes(), &offsets),
+        };
+        let starts = starts.unwrap_or_else(|error| {
+            self.spool_error.get_or_insert(error);
+            vec![0; offsets.len()]
+        });
+
+        // The header and one line per file push every section down
+        let shift = 1 + self.files.len();
+        let width = (starts.last().copied().unwrap_or(0) + shift).to_string().len();
+        let mut toc = "--- Table of contents (line: file) ---".to_string();
+        for (file, start) in self.files.iter().zip(starts) {
+            toc.push_str(&format!("\n{:>width$}: {}", start + shift, file.path.display()));
+        }
+        toc
+    }
+
+    /// Push a section onto the output, returning its starting byte offset
+    ///
+    /// When streaming, only the offsets are tracked and the section is dropped.
+    /// While fitting, sections are held back instead (see `release_held`).
+    fn push_content(&mut self, section: String) -> usize {
+        if let Some(held) = &mut self.held {
+            held.push(Held::Note(section));
+            return 0;
+        }
+        // Sections are never empty, so a zero length means nothing was pushed yet
+        let start = if self.content_len == 0 {
+            0
+        } else {
+            self.content_len + 1
+        };
+        self.content_len = start + section.len();
+        if let Some(spool) = &mut self.spool {
+            self.stats.record_buffer(section.len());
+            let separator = if start == 0 { "" } else { "\n" };
+            if let Err(error) = spool.write(separator).and_then(|()| spool.write(&section)) {
+                self.spool_error.get_or_insert(error);
+            }
+        } else if self.streamed.is_none() {
+            self.stats.record_buffer(self.content_len);
+            self.contents.push(section);
+        }
+        start
+    }
+}
+
+/// The line, counting from 1, that each of the ascending byte `offsets` falls on in `content`
+fn line_numbers(mut content: impl BufRead, offsets: &[usize]) -> std::io::Result<Vec<usize>> {
+    let mut lines = Vec::with_capacity(offsets.len());
+    let (mut line, mut position) = (1, 0);
+    for &offset in offsets {
+        while position < offset {
+            let buffer = content.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            let read = buffer.len().min(offset - position);
+            line += buffer[..read].iter().filter(|&&byte| byte == b'\n').count();
+            content.consume(read);
+            position += read;
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+impl WalkContext for DirectoryWalker {
+    // Git and ignore files don't apply inside archives
+    fn ignored(&self, entry: &Entry<'_>) -> Option<SkipReason> {
+        self.stats.time(Phase::Ignore, || {
+            self.ignore_reason(entry.path, entry.is_dir).filter(|_| !self.in_archive(entry.path))
+        })
+    }
+
+    fn generated(&self, entry: &Entry<'_>) -> Option<SkipReason> {
+        self.generated_reason(entry.path, entry.is_dir).filter(|_| !self.in_archive(entry.path))
+    }
+
+    fn on_other_filesystem(&self, entry: &Entry<'_>) -> bool {
+        match self.root_devices.get(self.current_root) {
+            Some(Some(root_device)) => device_id(entry.path).is_some_and(|device| device != *root_device),
+            _ => false,
+        }
+    }
+
+    fn modified_after(&self, entry: &Entry<'_>, since: SystemTime) -> bool {
+        if self.in_archive(entry.path) {
+            return true;
+        }
+        if let Some(Some(recent)) = self.recent_git_files.get(self.current_root) {
+            return entry.path.canonicalize().is_ok_and(|canonical| recent.contains(&canonical));
+        }
+        // Files without a modification time are kept rather than guessed at
+        entry.modified.is_none_or(|modified| modified > since)
+    }
+}
+
+/// Device and inode number of a file, shared by all hard links to it
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// ID of the device (filesystem) a path is on
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::Strategy;
+    use crate::serialize::Value;
+    use crate::template::SectionTemplate;
+    use std::fs;
+    use std::io::Write;
+
+    fn setup_test_dir(name: &str) -> PathBuf {
+        let dir = PathBuf::from(format!("test_{}", name));
+        if dir.exists() {
             fs::remove_dir_all(&dir).unwrap();
         }
         fs::create_dir(&dir).unwrap();
@@ -435,25 +2142,187 @@ mod tests {
 
         // Binary files should be skipped by default
         let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
-        assert!(!result.content.contains("<BINARY_FILE>"));
+        assert!(!result.content.contains("<BINARY_FILE"));
 
-        // But included with include_all option
+        // But included with include_binary option
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: true,
-                max_size: Config::DEFAULT_MAX_SIZE,
-                max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
-                exclude_patterns: Vec::new(),
+                include_binary: true,
+                ..WalkOptions::default()
             },
         )
         .unwrap();
-        assert!(result.content.contains("<BINARY_FILE>"));
+        assert!(result.content.contains("<BINARY_FILE: 100 B>"));
         assert!(result.content.contains("binary.dat"));
 
         cleanup_test_dir(&dir);
     }
 
+    #[test]
+    fn test_notebook() {
+        let dir = setup_test_dir("notebook");
+
+        let notebook = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "Load the data"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "df = load()\ndf.shape",
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["loading\n"]},
+    {"output_type": "execute_result", "data": {"text/plain": ["(3, 2)"]}, "metadata": {}},
+    {"output_type": "display_data", "data": {"image/png": "iVBORw0KGgoAAAANSUhEUg=="}, "metadata": {}}
+   ]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+        fs::write(dir.join("analysis.ipynb"), notebook).unwrap();
+        // Not valid notebook JSON, so included as it is
+        fs::write(dir.join("broken.ipynb"), "{\"cells\": [").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        let expected = "# %% [markdown]\n# Analysis\nLoad the data\n\n# %%\ndf = load()\ndf.shape\n# Output:\n# loading\n# (3, 2)\n# [image/png output]\n";
+        assert!(result.content.contains(expected));
+        assert!(!result.content.contains("iVBORw0KGgo"));
+        assert!(result.content.contains("{\"cells\": ["));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_lockfiles() {
+        let dir = setup_test_dir("lockfiles");
+
+        let cargo = "# This file is automatically @generated by Cargo.\nversion = 4\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [\n \"serde\",\n]\n";
+        fs::write(dir.join("Cargo.lock"), cargo).unwrap();
+        let npm = r#"{"name": "web", "lockfileVersion": 3, "packages": {
+  "": {"name": "web", "version": "1.0.0"},
+  "node_modules/react": {"version": "18.2.0"},
+  "node_modules/@types/node": {"version": "20.1.0"},
+  "node_modules/a/node_modules/react": {"version": "17.0.2"}
+}}"#;
+        fs::create_dir(dir.join("npm")).unwrap();
+        fs::write(dir.join("npm/package-lock.json"), npm).unwrap();
+        let yarn = "# yarn lockfile v1\n\n\"@babel/core@^7.0.0\", \"@babel/core@^7.1.0\":\n  version \"7.1.0\"\n  resolved \"https://registry.yarnpkg.com/x\"\n\nlodash@^4.17.0:\n  version \"4.17.21\"\n";
+        fs::create_dir(dir.join("yarn")).unwrap();
+        fs::write(dir.join("yarn/yarn.lock"), yarn).unwrap();
+
+        let roots = [dir.join("Cargo.lock"), dir.join("npm/package-lock.json"), dir.join("yarn/yarn.lock")];
+        let result = walk_and_collect(&roots, WalkOptions::default()).unwrap();
+        assert!(result.content.contains("2 locked packages:\napp 0.1.0\nserde 1.0.200\n"));
+        assert!(result.content.contains("3 locked packages:\n@types/node 20.1.0\nreact 17.0.2\nreact 18.2.0\n"));
+        assert!(result.content.contains("2 locked packages:\n@babel/core 7.1.0\nlodash 4.17.21\n"));
+        assert!(!result.content.contains("registry+https"));
+
+        // Without summaries, or when the file isn't a lockfile after all, the raw text is included
+        let mut processors = ProcessorRegistry::default();
+        processors.register("Cargo.lock", Strategy::Text);
+        fs::write(dir.join("yarn/yarn.lock"), "not a lockfile").unwrap();
+        let options = WalkOptions {
+            processors,
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(&roots, options).unwrap();
+        assert!(result.content.contains("registry+https"));
+        assert!(result.content.contains("not a lockfile"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_processor_registry() {
+        let dir = setup_test_dir("processor_registry");
+
+        fs::write(dir.join("notes.ipynb"), r#"{"cells": [{"cell_type": "code", "source": "x = 1"}]}"#).unwrap();
+        fs::write(dir.join("view.snap"), "snapshot").unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let mut processors = ProcessorRegistry::default();
+        processors.register("*.snap", Strategy::Skip);
+        processors.register("*.ipynb", Strategy::Text);
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                processors,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        // The later registration overrides the built-in notebook processor
+        assert!(result.content.contains(r#"{"cells": ["#));
+        assert!(!result.content.contains("snapshot"));
+        assert!(result.content.contains("fn main() {}"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::Excluded {
+                    pattern: "*.snap".to_string(),
+                }
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_binary_info() {
+        let dir = setup_test_dir("binary_info");
+
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        fs::write(dir.join("image.png"), &png).unwrap();
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend(32u16.to_le_bytes());
+        gif.extend(16u16.to_le_bytes());
+        fs::write(dir.join("icon.gif"), &gif).unwrap();
+        // A JPEG with an APP0 segment before its frame header
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x04JF\xFF\xC0\x00\x11\x08\x01\x2C\x01\x90";
+        fs::write(dir.join("photo.jpg"), jpeg).unwrap();
+        fs::write(dir.join("archive.zip"), b"PK\x03\x04rest").unwrap();
+
+        assert_eq!(FileProcessor::binary_info(&dir.join("image.png")), "<BINARY_FILE: PNG image, 640x480, 24 B>");
+        assert_eq!(FileProcessor::binary_info(&dir.join("icon.gif")), "<BINARY_FILE: GIF image, 32x16, 10 B>");
+        assert_eq!(FileProcessor::binary_info(&dir.join("photo.jpg")), "<BINARY_FILE: JPEG image, 400x300, 17 B>");
+        assert_eq!(FileProcessor::binary_info(&dir.join("archive.zip")), "<BINARY_FILE: ZIP archive, 8 B>");
+        assert_eq!(FileProcessor::binary_info(&dir.join("missing.bin")), "<BINARY_FILE>");
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_binary_detection() {
+        let dir = setup_test_dir("binary_detection");
+
+        // Binary by extension, whatever the content
+        fs::write(dir.join("logo.png"), "not really a png").unwrap();
+        // No null bytes, but full of control characters
+        let noisy: Vec<u8> = (1..=255u8).cycle().take(1024).collect();
+        fs::write(dir.join("bitmap.dat"), noisy).unwrap();
+        // Control characters in a file of a known language don't count
+        fs::write(dir.join("escapes.py"), "print('\x01\x02\x03\x04')").unwrap();
+        fs::write(dir.join("accents.dat"), b"d\xE9j\xE0 vu, na\xEFve caf\xE9\n").unwrap();
+        let utf16: Vec<u8> = "wide text".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(dir.join("wide.dat"), utf16).unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(!result.content.contains("not really a png"));
+        assert!(!result.content.contains("bitmap.dat"));
+        assert!(result.content.contains("print("));
+        assert!(result.content.contains("déjà vu"));
+        assert!(result.content.contains("wide text"));
+        let binary: Vec<_> = result
+            .stats
+            .skipped()
+            .iter()
+            .filter(|entry| entry.reason == SkipReason::Binary)
+            .map(|entry| entry.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(binary.len(), 2);
+        assert!(binary.contains(&"logo.png") && binary.contains(&"bitmap.dat"));
+
+        cleanup_test_dir(&dir);
+    }
+
     #[test]
     fn test_walk_and_collect_nested_directories() {
         let dir = setup_test_dir("nested");
@@ -490,7 +2359,8 @@ mod tests {
         // Create files that together exceed DEFAULT_MAX_SIZE (5MB)
         // Files will be processed in alphabetical order
         for i in 0..20 {
-            let content = "x".repeat(300_000); // 300KB per file = 6MB total
+            // 300KB per file = 6MB total, each distinct so none are deduplicated
+            let content = format!("{:02}{}", i, "x".repeat(300_000));
             fs::write(dir.join(format!("file_{:02}.txt", i)), content).unwrap();
         }
 
@@ -525,14 +2395,14 @@ mod tests {
         assert!(!result.content.contains("git config"));
         assert!(result.content.contains("visible content"));
 
-        // With include_all: include hidden files and directories
+        // With include_hidden: include hidden files and directories
+        // (`.env` is also a sensitive file, so that check is turned off too)
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: true,
-                max_size: Config::DEFAULT_MAX_SIZE,
-                max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
-                exclude_patterns: Vec::new(),
+                include_hidden: true,
+                skip_sensitive: false,
+                ..WalkOptions::default()
             },
         )
         .unwrap();
@@ -544,6 +2414,72 @@ mod tests {
         cleanup_test_dir(&dir);
     }
 
+    #[test]
+    fn test_also_force_includes() {
+        let dir = setup_test_dir("also");
+
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(dir.join("target/doc")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::write(dir.join("target/doc/index.html"), "docs").unwrap();
+        fs::write(dir.join("target/debug/build.log"), "build log").unwrap();
+        fs::write(dir.join(".env.example"), "KEY=").unwrap();
+        fs::write(dir.join(".env"), "KEY=secret").unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                also: vec![dir.join("target/doc"), dir.join(".env.example")],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.contains("docs"));
+        assert!(result.content.contains("KEY="));
+        assert!(!result.content.contains("secret"));
+        assert!(!result.content.contains("build log"));
+
+        let forced: Vec<&Path> = result
+            .files
+            .iter()
+            .filter(|file| file.forced)
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(
+            forced,
+            [dir.join("target/doc/index.html"), dir.join(".env.example")]
+        );
+
+        // The forced file isn't also reported as skipped
+        let env_example = dir.join(".env.example");
+        assert!(!result.stats.skipped().iter().any(|entry| entry.path == env_example));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_hidden_without_binary() {
+        let dir = setup_test_dir("hidden_without_binary");
+
+        fs::write(dir.join(".env.example"), "KEY=").unwrap();
+        fs::write(dir.join("image.bin"), [0u8; 16]).unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                include_hidden: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("KEY="));
+        assert!(!result.content.contains("<BINARY_FILE"));
+
+        cleanup_test_dir(&dir);
+    }
+
     #[test]
     fn test_breadth_first_order() {
         let dir = setup_test_dir("bfs");
@@ -682,9 +2618,8 @@ mod tests {
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: false,
-                max_size: Config::DEFAULT_MAX_SIZE,
                 max_file_size: 1024 * 1024, // 1MB
+                ..WalkOptions::default()
             },
         )
         .unwrap();
@@ -709,10 +2644,8 @@ mod tests {
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: false,
-                max_size: Config::DEFAULT_MAX_SIZE,
-                max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
                 exclude_patterns: vec!["*.rs".to_string()],
+                ..WalkOptions::default()
             },
         )
         .unwrap();
@@ -725,10 +2658,8 @@ mod tests {
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: false,
-                max_size: Config::DEFAULT_MAX_SIZE,
-                max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
                 exclude_patterns: vec!["*.rs".to_string(), "*.py".to_string(), "test_*".to_string()],
+                ..WalkOptions::default()
             },
         )
         .unwrap();
@@ -743,10 +2674,8 @@ mod tests {
         let result = walk_and_collect(
             std::slice::from_ref(&dir),
             WalkOptions {
-                include_all: false,
-                max_size: Config::DEFAULT_MAX_SIZE,
-                max_file_size: Config::DEFAULT_MAX_FILE_SIZE,
                 exclude_patterns: vec!["config.yaml".to_string()],
+                ..WalkOptions::default()
             },
         )
         .unwrap();
@@ -759,15 +2688,1898 @@ mod tests {
 
     #[test]
     fn test_exclude_matcher_glob_patterns() {
-        let matcher = ExcludeMatcher::new(vec!["*.rs".to_string(), "test_*".to_string()]);
+        let matcher = PatternMatcher::new(vec!["*.rs".to_string(), "test_*".to_string()]);
 
-        assert!(matcher.should_exclude(Path::new("main.rs")));
-        assert!(matcher.should_exclude(Path::new("src/lib.rs")));
-        assert!(matcher.should_exclude(Path::new("test_file.txt")));
-        assert!(matcher.should_exclude(Path::new("test_123")));
+        assert_eq!(matcher.matching_pattern("main.rs", false), Some("*.rs"));
+        assert!(matcher.matching_pattern("src/lib.rs", false).is_some());
+        assert!(matcher.matching_pattern("test_file.txt", false).is_some());
+        assert!(matcher.matching_pattern("test_123", false).is_some());
         
-        assert!(!matcher.should_exclude(Path::new("main.py")));
-        assert!(!matcher.should_exclude(Path::new("config.yaml")));
-        assert!(!matcher.should_exclude(Path::new("file_test.txt")));
+        assert!(matcher.matching_pattern("main.py", false).is_none());
+        assert!(matcher.matching_pattern("config.yaml", false).is_none());
+        assert!(matcher.matching_pattern("file_test.txt", false).is_none());
+
+        let matcher = PatternMatcher::new(vec!["logs/".to_string(), "/out/".to_string()]);
+        assert_eq!(matcher.matching_pattern("app/logs", true), Some("logs/"));
+        assert!(matcher.matching_pattern("app/logs", false).is_none());
+        assert!(matcher.matching_pattern("out", true).is_some());
+        assert!(matcher.matching_pattern("app/out", true).is_none());
+
+        let matcher = PatternMatcher::new(vec![
+            "*.json".to_string(),
+            "!package.json".to_string(),
+            "\\!important".to_string(),
+        ]);
+        assert_eq!(matcher.matching_pattern("data/fixtures.json", false), Some("*.json"));
+        assert!(matcher.matching_pattern("web/package.json", false).is_none());
+        assert!(matcher.matching_pattern("!important", false).is_some());
+
+        // A later match takes precedence over the negation again
+        let matcher = PatternMatcher::new(vec!["!keep.txt".to_string(), "*.txt".to_string()]);
+        assert_eq!(matcher.matching_pattern("keep.txt", false), Some("*.txt"));
+    }
+
+    #[test]
+    fn test_exclude_matcher_name_or_path() {
+        // Without a slash, only the name counts, never the rest of the path
+        let matcher = PatternMatcher::new(vec!["src*".to_string(), "generated".to_string()]);
+        assert!(matcher.matching_pattern("src", true).is_some());
+        assert!(matcher.matching_pattern("lib/src.rs", false).is_some());
+        assert!(matcher.matching_pattern("src/lib.rs", false).is_none());
+        assert_eq!(matcher.matching_pattern("app/generated", true), Some("generated"));
+        assert!(matcher.matching_pattern("generated/api.rs", false).is_none());
+
+        // With a slash, the whole path from the root counts, never just the name
+        let matcher = PatternMatcher::new(vec!["src/*.rs".to_string()]);
+        assert!(matcher.matching_pattern("src/main.rs", false).is_some());
+        assert!(matcher.matching_pattern("lib/src/main.rs", false).is_none());
+        assert!(matcher.matching_pattern("main.rs", false).is_none());
+    }
+
+    #[test]
+    fn test_exclude_path_patterns() {
+        let dir = setup_test_dir("exclude_path_patterns");
+
+        fs::create_dir_all(dir.join("src/generated/v1")).unwrap();
+        fs::create_dir_all(dir.join("tests/fixtures/deep")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("src/generated/api.rs"), "generated api").unwrap();
+        fs::write(dir.join("src/generated/v1/old.rs"), "generated v1").unwrap();
+        fs::write(dir.join("tests/fixtures/deep/data.json"), "fixture data").unwrap();
+        fs::write(dir.join("tests/it.rs"), "integration test").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                exclude_patterns: vec!["src/generated/*".to_string(), "**/fixtures/**".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.contains("fn main() {}"));
+        assert!(result.content.contains("integration test"));
+        assert!(!result.content.contains("generated"));
+        assert!(!result.content.contains("fixture data"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_max_files_per_dir() {
+        let dir = setup_test_dir("max_files_per_dir");
+
+        fs::create_dir(dir.join("migrations")).unwrap();
+        for i in 0..5 {
+            fs::write(dir.join(format!("migrations/{:03}.sql", i)), format!("migration {}", i)).unwrap();
+        }
+        fs::write(dir.join("schema.sql"), "schema").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_files_per_dir: Some(2),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.contains("schema"));
+        assert!(result.content.contains("migration 0"));
+        assert!(result.content.contains("migration 1"));
+        assert!(!result.content.contains("migration 2"));
+        assert!(result.content.contains("3 more files omitted (limit of 2 per directory)"));
+
+        let limited = result
+            .stats
+            .skipped()
+            .iter()
+            .filter(|entry| entry.reason == SkipReason::DirectoryLimit { limit: 2 })
+            .count();
+        assert_eq!(limited, 3);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let dir = setup_test_dir("sort_order");
+
+        fs::write(dir.join("a.txt"), "medium body").unwrap();
+        fs::write(dir.join("b.rs"), "largest body of all").unwrap();
+        fs::write(dir.join("c.md"), "tiny").unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/d.txt"), "nested").unwrap();
+
+        let names = |sort: SortOrder, reverse: bool| -> Vec<String> {
+            let result = walk_and_collect(
+                std::slice::from_ref(&dir),
+                WalkOptions {
+                    sort,
+                    reverse,
+                    ..WalkOptions::default()
+                },
+            )
+            .unwrap();
+            result
+                .files
+                .iter()
+                .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        assert_eq!(names(SortOrder::Name, false), ["a.txt", "b.rs", "c.md", "d.txt"]);
+        assert_eq!(names(SortOrder::Size, false), ["c.md", "a.txt", "b.rs", "d.txt"]);
+        assert_eq!(names(SortOrder::Size, true), ["b.rs", "a.txt", "c.md", "d.txt"]);
+        assert_eq!(names(SortOrder::Extension, false), ["c.md", "b.rs", "a.txt", "d.txt"]);
+        assert_eq!(SortOrder::parse("EXT").unwrap(), SortOrder::Extension);
+        assert!(SortOrder::parse("random").is_err());
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_priority_patterns() {
+        let dir = setup_test_dir("priority_patterns");
+
+        fs::create_dir_all(dir.join("src/util")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "manifest").unwrap();
+        fs::write(dir.join("README.md"), "readme").unwrap();
+        fs::write(dir.join("src/lib.rs"), "library").unwrap();
+        fs::write(dir.join("src/main.rs"), "entry point").unwrap();
+        fs::write(dir.join("src/util/helpers.rs"), "helpers").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                priority_patterns: vec!["README*".to_string(), "src/main.rs".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        let names: Vec<String> = result
+            .files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["README.md", "main.rs", "Cargo.toml", "lib.rs", "helpers.rs"]);
+
+        // The priority files are the ones that survive a tight limit
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                priority_patterns: vec!["src/main.rs".to_string()],
+                max_size: 60,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("entry point"));
+        assert!(!result.content.contains("manifest"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_no_follow_symlinks() {
+        let dir = setup_test_dir("no_follow_symlinks");
+
+        fs::create_dir(dir.join("real")).unwrap();
+        fs::write(dir.join("real/data.txt"), "real data").unwrap();
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+        std::os::unix::fs::symlink("real", dir.join("linked_dir")).unwrap();
+        std::os::unix::fs::symlink("notes.txt", dir.join("linked_notes.txt")).unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                follow_symlinks: false,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.content.matches("real data").count(), 1);
+        assert!(result.files.iter().all(|file| !file.path.starts_with(dir.join("linked_dir"))));
+        assert!(result.files.iter().all(|file| file.path != dir.join("linked_notes.txt")));
+        let symlinks = result
+            .stats
+            .skipped()
+            .iter()
+            .filter(|entry| entry.reason == SkipReason::Symlink)
+            .count();
+        assert_eq!(symlinks, 2);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_one_file_system() {
+        let dir = setup_test_dir("one_file_system");
+
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/local.txt"), "same filesystem").unwrap();
+        // /proc is its own filesystem; walking into it would never finish
+        let other = Path::new("/proc");
+        let on_other = device_id(other).is_some_and(|device| device_id(&dir) != Some(device));
+        if on_other {
+            std::os::unix::fs::symlink(other, dir.join("proc")).unwrap();
+        }
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                one_file_system: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("same filesystem"));
+        assert_eq!(result.files.len(), 1);
+        if on_other {
+            assert!(result.stats.skipped().iter().any(|entry| {
+                entry.path.ends_with("proc") && entry.reason == SkipReason::OtherFilesystem
+            }));
+        }
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links() {
+        let dir = setup_test_dir("hard_links");
+
+        fs::write(dir.join("a.txt"), "linked content").unwrap();
+        fs::hard_link(dir.join("a.txt"), dir.join("b.txt")).unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert_eq!(result.files.len(), 1);
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.path.ends_with("b.txt") && entry.reason == SkipReason::Duplicate { of: dir.join("a.txt") }
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_extensions() {
+        let dir = setup_test_dir("extensions");
+
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(dir.join("README.MD"), "# readme").unwrap();
+        fs::write(dir.join("types.d.ts"), "declare").unwrap();
+        fs::write(dir.join("app.ts"), "app").unwrap();
+
+        let included = |options: WalkOptions| -> Vec<String> {
+            let result = walk_and_collect(std::slice::from_ref(&dir), options).unwrap();
+            result
+                .files
+                .iter()
+                .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        let extensions = |list: &[&str]| list.iter().map(|ext| ext.to_string()).collect::<Vec<_>>();
+
+        let allowed = WalkOptions {
+            extensions: extensions(&["rs", ".md"]),
+            ..WalkOptions::default()
+        };
+        assert_eq!(included(allowed), ["README.MD", "main.rs"]);
+
+        let denied = WalkOptions {
+            excluded_extensions: extensions(&["toml", "d.ts"]),
+            ..WalkOptions::default()
+        };
+        assert_eq!(included(denied), ["README.MD", "app.ts", "main.rs"]);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_min_file_size_and_empty() {
+        let dir = setup_test_dir("min_file_size");
+
+        fs::write(dir.join("__init__.py"), "\n").unwrap();
+        fs::write(dir.join("placeholder.txt"), "").unwrap();
+        fs::write(dir.join("tiny.txt"), "ok").unwrap();
+        fs::write(dir.join("main.py"), "print('hello world')\n").unwrap();
+
+        let included = |options: WalkOptions| -> Vec<String> {
+            let result = walk_and_collect(std::slice::from_ref(&dir), options).unwrap();
+            result
+                .files
+                .iter()
+                .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let skip_empty = WalkOptions {
+            skip_empty: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(included(skip_empty), ["main.py", "tiny.txt"]);
+
+        let min_size = WalkOptions {
+            min_file_size: 10,
+            ..WalkOptions::default()
+        };
+        assert_eq!(included(min_size), ["main.py"]);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_max_files() {
+        let dir = setup_test_dir("max_files");
+
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.join(name), format!("content of {}", name)).unwrap();
+        }
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_files: Some(2),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files.len(), 2);
+        assert!(!result.content.contains("content of c.txt"));
+        assert!(result.content.ends_with("--- TRUNCATED: File limit of 2 reached ---"));
+        assert_eq!(result.truncated_by, Some(Limit::Files(2)));
+        assert_eq!(Limit::Files(2).to_string(), "2 file");
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_stdin() {
+        let dir = setup_test_dir("stdin");
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                stdin: Some("diff --git a/main.rs b/main.rs\n".to_string()),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files.len(), 2);
+        assert_eq!(result.files[0].path, PathBuf::from("<stdin>"));
+        assert_eq!(result.file_body(&result.files[0]), "diff --git a/main.rs b/main.rs\n");
+        assert!(result.content.starts_with("--- <stdin> ---\n"));
+
+        // Nothing to walk but stdin
+        let result = walk_and_collect(
+            &[],
+            WalkOptions {
+                stdin: Some("piped".to_string()),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.content, "--- <stdin> ---\npiped");
+        assert!(!result.files[0].forced);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_section_template() {
+        let dir = setup_test_dir("section_template");
+        fs::write(dir.join("lib.rs"), "pub fn f() {}\n").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                formatter: Arc::new(PlainFormatter {
+                    template: SectionTemplate::new("```{lang} {basename}", Some("```")).unwrap(),
+                }),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.content, "```rust lib.rs\npub fn f() {}\n```");
+        assert_eq!(result.file_body(&result.files[0]), "pub fn f() {}\n");
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                line_numbers: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.ends_with("lib.rs ---\n1 | pub fn f() {}\n"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        let dir = setup_test_dir("line_ranges");
+        let text: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.join("big.rs"), &text).unwrap();
+        let file = dir.join("big.rs");
+
+        let collect = |range: LineRange, line_numbers: bool| {
+            walk_and_collect(
+                std::slice::from_ref(&file),
+                WalkOptions {
+                    line_ranges: vec![(file.clone(), range)],
+                    line_numbers,
+                    max_file_size: 10,
+                    ..WalkOptions::default()
+                },
+            )
+            .unwrap()
+        };
+
+        // Only the slice is read, so the file may be larger than max_file_size
+        let result = collect(LineRange { start: 9, end: Some(11) }, false);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].path, PathBuf::from(format!("{}:9-11", file.display())));
+        assert_eq!(result.file_body(&result.files[0]), "line 9\nline 10\nline 11\n");
+
+        let result = collect(LineRange { start: 19, end: None }, true);
+        assert_eq!(result.files[0].path, PathBuf::from(format!("{}:19-20", file.display())));
+        assert_eq!(result.file_body(&result.files[0]), "19 | line 19\n20 | line 20\n");
+
+        let result = collect(LineRange { start: 30, end: Some(40) }, false);
+        assert_eq!(result.file_body(&result.files[0]), "");
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_language_stats() {
+        let dir = setup_test_dir("language_stats");
+        fs::write(dir.join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        fs::write(dir.join("lib.rs"), "pub fn run() {}\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        fs::write(dir.join("NOTES"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert_eq!(
+            result.stats.languages(),
+            [("other", 1, 5), ("rust", 2, 4), ("toml", 1, 2)]
+        );
+        assert!(result.stats.format_stats().contains("Languages: Other 5 lines, Rust 4 lines, TOML 2 lines\n"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_low_memory() {
+        let dir = setup_test_dir("low_memory");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.join("sub/b.txt"), "b".repeat(100)).unwrap();
+
+        let options = || WalkOptions {
+            prefix: Some("Review this.".to_string()),
+            suffix: Some("Thanks.".to_string()),
+            tree: true,
+            toc: true,
+            ..WalkOptions::default()
+        };
+        let in_memory = walk_and_collect(std::slice::from_ref(&dir), options()).unwrap();
+        let mut spooled = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                low_memory: true,
+                ..options()
+            },
+        )
+        .unwrap();
+        assert!(spooled.content.is_empty());
+        let text = spooled.spool.as_mut().unwrap().read_to_string().unwrap();
+        assert_eq!(text, in_memory.content);
+        for (file, expected) in spooled.files.iter().zip(&in_memory.files) {
+            assert_eq!((&file.span, &file.body), (&expected.span, &expected.body));
+        }
+
+        // Only one section is held at a time
+        let largest = spooled.files.iter().map(|file| file.span.len()).max().unwrap();
+        assert_eq!(spooled.stats.peak_buffer(), largest);
+        let total: usize = in_memory.files.iter().map(|file| file.span.len()).sum();
+        assert!(in_memory.stats.peak_buffer() > total);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_phase_times() {
+        let dir = setup_test_dir("phase_times");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        let times = result.stats.phase_times();
+        assert_eq!(times.iter().map(|(phase, _)| *phase).collect::<Vec<_>>(), Phase::ALL);
+        for (phase, elapsed) in times {
+            // Nothing was copied to the clipboard
+            assert_eq!(elapsed.is_zero(), phase == Phase::Clipboard, "{:?}", phase);
+        }
+        let stats = result.stats.format_stats();
+        assert!(stats.contains("\nTime: enumerate "), "{}", stats);
+        assert!(!stats.contains("clipboard"), "{}", stats);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_extension_tokens() {
+        let dir = setup_test_dir("extension_tokens");
+        fs::write(dir.join("main.rs"), "fn main() { run(); }\n".repeat(20)).unwrap();
+        fs::write(dir.join("README.md"), "Read me").unwrap();
+        fs::write(dir.join("NOTES"), "notes").unwrap();
+
+        let options = WalkOptions {
+            model: Some(crate::tokens::model_by_name("gpt-4o").unwrap()),
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(std::slice::from_ref(&dir), options).unwrap();
+        let by_extension = result.stats.extension_tokens();
+        assert_eq!(by_extension.iter().map(|(ext, _)| *ext).collect::<Vec<_>>(), ["rs", "md", ""]);
+        let total: usize = by_extension.iter().map(|(_, tokens)| tokens).sum();
+        assert_eq!(total, result.stats.total_tokens());
+
+        let share = result.stats.context_share().unwrap();
+        assert_eq!(share, total as f64 / 128_000.0);
+        let stats = result.stats.format_stats();
+        assert!(stats.contains("Tokens by extension: .rs ~"), "{}", stats);
+        assert!(stats.contains("(none) ~"), "{}", stats);
+        assert!(stats.contains(&format!("Context: {:.1}% of gpt-4o's 128.0k tokens\n", share * 100.0)), "{}", stats);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_toc() {
+        let dir = setup_test_dir("toc");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        fs::write(dir.join("sub/c.txt"), "c\n".repeat(10)).unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                prefix: Some("Review this.".to_string()),
+                toc: true,
+                tree: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = result.content.lines().collect();
+        assert_eq!(lines[1], "--- Table of contents (line: file) ---");
+        assert_eq!(lines[5], "--- Directory tree ---");
+        // Each entry points at its file's header
+        for (entry, file) in lines[2..5].iter().zip(&result.files) {
+            let (line, path) = entry.trim_start().split_once(": ").unwrap();
+            assert_eq!(path, file.path.display().to_string());
+            let header = lines[line.parse::<usize>().unwrap() - 1];
+            assert_eq!(header, format!("--- {} ---", file.path.display()));
+            assert!(result.content[file.span.clone()].starts_with(header));
+        }
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_prefix_and_suffix() {
+        let dir = setup_test_dir("prefix_suffix");
+        fs::write(dir.join("a.txt"), "a".repeat(40)).unwrap();
+        fs::write(dir.join("b.txt"), "b".repeat(40)).unwrap();
+
+        let options = WalkOptions {
+            prefix: Some("Review this code.".to_string()),
+            suffix: Some("List any bugs.".to_string()),
+            tree: true,
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(std::slice::from_ref(&dir), options.clone()).unwrap();
+        assert!(result.content.starts_with("Review this code.\n--- Directory tree ---\n"));
+        assert!(result.content.ends_with(&format!("{}\nList any bugs.", "b".repeat(40))));
+        for file in &result.files {
+            assert!(result.content[file.span.clone()].starts_with(&format!("--- {} ---\n", file.path.display())));
+        }
+
+        // Both count toward the size limit, leaving room for one file only
+        let section_len = format!("--- {} ---\n", dir.join("a.txt").display()).len() + 40;
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_size: section_len * 2,
+                tree: false,
+                ..options
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files.len(), 1);
+        assert!(result.truncated);
+        assert!(result.content.ends_with("would exceed limit ---\nList any bugs."));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands() {
+        let dir = setup_test_dir("commands");
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                commands: vec!["echo ok".to_string(), "echo failing; exit 2".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        let paths: Vec<&Path> = result.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![dir.join("main.rs").as_path(), Path::new("$ echo ok"), Path::new("$ echo failing; exit 2")]
+        );
+        assert_eq!(result.file_body(&result.files[1]), "ok\n");
+        assert!(result.content.ends_with("--- $ echo failing; exit 2 ---\nfailing\n[exit status 2]"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let dir = setup_test_dir("dedup");
+
+        fs::create_dir_all(dir.join("vendor_copy")).unwrap();
+        fs::write(dir.join("util.js"), "shared helper\n").unwrap();
+        fs::write(dir.join("vendor_copy/util.js"), "shared helper\n").unwrap();
+        fs::write(dir.join("empty_a.txt"), "").unwrap();
+        fs::write(dir.join("empty_b.txt"), "").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert_eq!(result.content.matches("shared helper").count(), 1);
+        let copy = dir.join("vendor_copy/util.js");
+        assert!(result.content.contains(&format!(
+            "--- {} (identical to {}) ---",
+            copy.display(),
+            dir.join("util.js").display()
+        )));
+        let stub = result.files.iter().find(|file| file.path == copy).unwrap();
+        assert_eq!(stub.identical_to, Some(dir.join("util.js")));
+        assert_eq!(result.file_body(stub), "");
+        // Empty files aren't worth a stub
+        assert!(result.files.iter().filter(|file| file.identical_to.is_some()).count() == 1);
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                dedup: false,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.content.matches("shared helper").count(), 2);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_fit() {
+        let dir = setup_test_dir("fit");
+
+        fs::write(dir.join("a.txt"), "small a").unwrap();
+        fs::write(dir.join("b.txt"), "x".repeat(200)).unwrap();
+        fs::write(dir.join("c.txt"), "small c").unwrap();
+
+        // Without fitting, the walk stops at the large file
+        let options = WalkOptions {
+            max_size: 100,
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(std::slice::from_ref(&dir), options.clone()).unwrap();
+        assert!(result.content.contains("small a"));
+        assert!(!result.content.contains("small c"));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions { fit: true, ..options },
+        )
+        .unwrap();
+        assert!(result.content.contains("small a"));
+        assert!(result.content.contains("small c"));
+        assert!(!result.content.contains("xxx"));
+        assert!(result.content.ends_with("left out the largest file to fit ---"));
+        assert_eq!(result.truncated_by, Some(Limit::Size(100)));
+        assert_eq!(result.files.len(), 2);
+        for file in &result.files {
+            assert!(result.content[file.span.clone()].starts_with("--- "));
+        }
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.path.ends_with("b.txt") && entry.reason == SkipReason::Budget
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let dir = setup_test_dir("max_depth");
+
+        fs::create_dir_all(dir.join("level1/level2")).unwrap();
+        fs::write(dir.join("root.txt"), "root file").unwrap();
+        fs::write(dir.join("level1/one.txt"), "level one").unwrap();
+        fs::write(dir.join("level1/level2/two.txt"), "level two").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_depth: Some(0),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("root file"));
+        assert!(!result.content.contains("level one"));
+        assert!(!result.content.contains("level two"));
+
+        // Each level allowed is one more directory descended into
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_depth: Some(1),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("root file"));
+        assert!(result.content.contains("level one"));
+        assert!(!result.content.contains("level two"));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_depth: Some(2),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("level two"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_root_display() {
+        use std::os::unix::fs as unix_fs;
+
+        let dir = setup_test_dir("symlinked_root");
+
+        fs::create_dir_all(dir.join("releases/v1")).unwrap();
+        fs::write(dir.join("releases/v1/app.txt"), "app_content").unwrap();
+        unix_fs::symlink("releases/v1", dir.join("current")).unwrap();
+
+        // Headers use the symlinked name, and the target root is still deduplicated
+        let result = walk_and_collect(
+            &[dir.join("current"), dir.join("releases/v1")],
+            WalkOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.content.matches("app_content").count(), 1);
+        assert!(result.content.contains(&format!(
+            "--- {} ---",
+            dir.join("current/app.txt").display()
+        )));
+
+        // Canonical display resolves the symlink
+        let result = walk_and_collect(
+            &[dir.join("current")],
+            WalkOptions {
+                path_display: PathDisplay::Canonical,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        let canonical = dir.join("releases/v1/app.txt").canonicalize().unwrap();
+        assert!(result.content.contains(&format!("--- {} ---", canonical.display())));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_token_limit() {
+        let dir = setup_test_dir("token_limit");
+
+        for i in 0..10 {
+            fs::write(dir.join(format!("file_{}.txt", i)), "word ".repeat(100)).unwrap();
+        }
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_tokens: Some(250),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.truncated);
+        assert_eq!(result.truncated_by, Some(Limit::Tokens(250)));
+        assert!(result.content.contains("Token limit"));
+        assert!(result.content.contains("file_0.txt"));
+        assert!(!result.content.contains("file_9.txt"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_tree_header() {
+        let dir = setup_test_dir("tree_header");
+
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("top.txt"), "top").unwrap();
+        fs::write(dir.join("sub/inner.txt"), "inner").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                tree: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.starts_with(
+            "--- Directory tree ---\ntest_tree_header\n├── sub\n│   └── inner.txt\n└── top.txt\n"
+        ));
+        assert_eq!(result.files.len(), 2);
+
+        // Spans point past the tree at each file's own section
+        for file in &result.files {
+            let section = &result.content[file.span.clone()];
+            assert!(section.starts_with(&format!("--- {} ---\n", file.path.display())));
+            assert_eq!(section.len(), file.size);
+        }
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_path_regex() {
+        let dir = setup_test_dir("path_regex");
+
+        fs::create_dir_all(dir.join("src/api")).unwrap();
+        fs::create_dir_all(dir.join("src/cli")).unwrap();
+        fs::write(dir.join("src/api/handler.rs"), "api handler").unwrap();
+        fs::write(dir.join("src/api/notes.md"), "api notes").unwrap();
+        fs::write(dir.join("src/cli/main.rs"), "cli main").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                path_regexes: vec![Regex::new(r"^src/(api|core)/.*\.rs$").unwrap()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("api handler"));
+        assert!(!result.content.contains("api notes"));
+        assert!(!result.content.contains("cli main"));
+
+        // Exclusions still win over a matching regex
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                path_regexes: vec![Regex::new(r"\.rs$").unwrap()],
+                exclude_patterns: vec!["handler.rs".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(!result.content.contains("api handler"));
+        assert!(result.content.contains("cli main"));
+
+        // Fuzzy filters narrow files down the same way, any one of them matching
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                fuzzy_filters: vec![FuzzyQuery::new("hndlr").unwrap(), FuzzyQuery::new("cli rs").unwrap()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("api handler"));
+        assert!(!result.content.contains("api notes"));
+        assert!(result.content.contains("cli main"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_include_patterns() {
+        let dir = setup_test_dir("include_patterns");
+
+        fs::create_dir_all(dir.join("src/api")).unwrap();
+        fs::write(dir.join("src/api/handler.rs"), "api handler").unwrap();
+        fs::write(dir.join("src/api/notes.md"), "api notes").unwrap();
+        fs::write(dir.join("Cargo.toml"), "manifest").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                include_patterns: vec!["*.rs".to_string(), "Cargo.toml".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("api handler"));
+        assert!(result.content.contains("manifest"));
+        assert!(!result.content.contains("api notes"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.path.ends_with("src/api/notes.md") && entry.reason == SkipReason::Filtered
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_walk_iter() {
+        let dir = setup_test_dir("walk_iter");
+
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("b.txt"), "second").unwrap();
+        fs::write(dir.join("a.txt"), "first").unwrap();
+        fs::write(dir.join("sub/c.txt"), "nested").unwrap();
+        fs::write(dir.join("image.bin"), [0u8, 1, 2, 3]).unwrap();
+
+        let entries: Vec<FileEntry> = walk_iter(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                include_binary: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap();
+
+        let paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![dir.join("a.txt"), dir.join("b.txt"), dir.join("image.bin"), dir.join("sub/c.txt")]
+        );
+        assert_eq!(entries[0].content, "first");
+        assert_eq!(entries[0].kind, FileKind::Text);
+        assert_eq!(entries[2].kind, FileKind::Binary);
+        assert_eq!(entries[2].size, 4);
+
+        // Limits end the iterator instead of inserting a marker
+        let mut iter = walk_iter(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_size: 40,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.truncated_by(), Some(Limit::Size(40)));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_progress_events() {
+        use std::sync::Mutex;
+
+        let dir = setup_test_dir("progress_events");
+        fs::write(dir.join("a.txt"), "a".repeat(30)).unwrap();
+        fs::write(dir.join("b.txt"), "b".repeat(30)).unwrap();
+        fs::write(dir.join(".hidden"), "secret").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let progress: ProgressCallback = Arc::new(move |event| {
+            let name = match event {
+                ProgressEvent::FileStarted { path } => format!("started {}", path.display()),
+                ProgressEvent::FileIncluded { path, .. } => format!("included {}", path.display()),
+                ProgressEvent::FileSkipped { path, reason } => {
+                    format!("skipped {} ({})", path.display(), reason.category())
+                }
+                ProgressEvent::Truncated { limit } => format!("truncated at {}", limit),
+            };
+            recorded.lock().unwrap().push(name);
+        });
+
+        walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_size: 100,
+                progress: Some(progress),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        let a = dir.join("a.txt");
+        let b = dir.join("b.txt");
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                format!("skipped {} (hidden)", dir.join(".hidden").display()),
+                format!("started {}", a.display()),
+                format!("included {}", a.display()),
+                format!("started {}", b.display()),
+                "truncated at 100 bytes".to_string(),
+                format!("skipped {} (over budget)", b.display()),
+            ]
+        );
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let dir = setup_test_dir("cancel");
+        fs::write(dir.join("a.txt"), "first").unwrap();
+        fs::write(dir.join("b.txt"), "second").unwrap();
+
+        // Cancel as soon as the first file is included
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                progress: Some(Arc::new(move |event| {
+                    if let ProgressEvent::FileIncluded { .. } = event {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                })),
+                cancel: Some(cancel),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.interrupted);
+        assert!(result.truncated);
+        assert!(result.truncated_by.is_none());
+        assert!(result.content.contains("first"));
+        assert!(!result.content.contains("second"));
+        assert!(result.content.ends_with("--- INTERRUPTED: 1 files collected before the walk was stopped ---"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_git_tracked() {
+        let dir = setup_test_dir("git_tracked");
+
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "tracked main").unwrap();
+        fs::write(dir.join("src/scratch.rs"), "untracked scratch").unwrap();
+        fs::write(dir.join("generated.rs"), "tracked despite ignore").unwrap();
+        fs::write(dir.join(".gitignore"), "generated.rs\n").unwrap();
+
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "src/main.rs", ".gitignore"]);
+        git(&["add", "-f", "generated.rs"]);
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                git_tracked: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.contains("tracked main"));
+        assert!(result.content.contains("tracked despite ignore"));
+        assert!(!result.content.contains("untracked scratch"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.path.ends_with("src/scratch.rs") && entry.reason == SkipReason::Untracked
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_diff_ref() {
+        let dir = setup_test_dir("diff_ref");
+
+        fs::write(dir.join("stable.rs"), "stable code").unwrap();
+        fs::write(dir.join("edited.rs"), "old version").unwrap();
+
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "initial"]);
+
+        fs::write(dir.join("edited.rs"), "new version").unwrap();
+        fs::write(dir.join("added.rs"), "brand new file").unwrap();
+
+        let diff = |git_tracked| {
+            walk_and_collect(
+                std::slice::from_ref(&dir),
+                WalkOptions {
+                    diff_ref: Some("HEAD".to_string()),
+                    git_tracked,
+                    ..WalkOptions::default()
+                },
+            )
+        };
+
+        let result = diff(false).unwrap();
+        assert!(result.content.contains("new version"));
+        assert!(result.content.contains("brand new file"));
+        assert!(!result.content.contains("stable code"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::Unchanged {
+                    reference: "HEAD".to_string(),
+                }
+        }));
+
+        // With --git, new untracked files are left out
+        let result = diff(true).unwrap();
+        assert!(result.content.contains("new version"));
+        assert!(!result.content.contains("brand new file"));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                diff_ref: Some("no-such-ref".to_string()),
+                ..WalkOptions::default()
+            },
+        );
+        assert!(result.is_err());
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_modified_since() {
+        let dir = setup_test_dir("modified_since");
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+
+        fs::write(dir.join("old.rs"), "old code").unwrap();
+        fs::write(dir.join("new.rs"), "new code").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(dir.join("old.rs"))
+            .unwrap()
+            .set_modified(now - 10 * day)
+            .unwrap();
+
+        let since = |since_git| {
+            walk_and_collect(
+                std::slice::from_ref(&dir),
+                WalkOptions {
+                    modified_since: Some(now - day),
+                    since_git,
+                    ..WalkOptions::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let result = since(false);
+        assert!(result.content.contains("new code"));
+        assert!(!result.content.contains("old code"));
+        let skipped = result.stats.skipped().iter().find(|entry| entry.path == dir.join("old.rs"));
+        assert_eq!(skipped.map(|entry| &entry.reason), Some(&SkipReason::Mtime));
+
+        // By git history, a file committed long ago is old whatever its modification time
+        let git = |args: &[&str], date: &str| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .output()
+                .unwrap()
+        };
+        git(&["init", "-q"], "");
+        git(&["add", "."], "");
+        git(&["commit", "-q", "-m", "initial"], "2001-01-01T00:00:00Z");
+        fs::write(dir.join("untracked.rs"), "untracked code").unwrap();
+
+        let result = since(true);
+        assert!(!result.content.contains("new code"));
+        assert!(!result.content.contains("old code"));
+        assert!(result.content.contains("untracked code"));
+
+        fs::write(dir.join("new.rs"), "edited code").unwrap();
+        let result = since(true);
+        assert!(result.content.contains("edited code"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_selected() {
+        let dir = setup_test_dir("selected");
+        fs::write(dir.join("picked.rs"), "picked code").unwrap();
+        fs::write(dir.join("dropped.rs"), "dropped code").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                selected: Some(HashSet::from([dir.join("picked.rs")])),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("picked code"));
+        assert!(!result.content.contains("dropped code"));
+        let skipped = result.stats.skipped().iter().find(|entry| entry.path == dir.join("dropped.rs"));
+        assert_eq!(skipped.map(|entry| &entry.reason), Some(&SkipReason::NotSelected));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_unreadable_causes() {
+        let dir = setup_test_dir("unreadable_causes");
+        fs::write(dir.join("good.txt"), "good text").unwrap();
+        // UTF-16 with a byte order mark, then an unpaired surrogate
+        fs::write(dir.join("broken.txt"), [0xFF, 0xFE, 0x00, 0xD8, 0x41, 0x00]).unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(result.content.contains("good text"));
+        assert_eq!(result.stats.unreadable_files(), 1);
+        let skipped = result.stats.skipped().iter().find(|entry| entry.path == dir.join("broken.txt"));
+        let cause = UnreadableCause::InvalidEncoding;
+        assert_eq!(skipped.map(|entry| &entry.reason), Some(&SkipReason::Unreadable { cause }));
+        assert_eq!(result.stats.unreadable_causes(), vec![("invalid encoding".to_string(), 1)]);
+        assert!(result.stats.format_stats().contains("1 unreadable (1 invalid encoding)"));
+        assert!(result.stats.format_skipped().contains("broken.txt - unreadable (invalid encoding)"));
+
+        // The JSON stats carry the same counts, and parse back
+        let json = crate::json::parse(&result.stats.to_json()).unwrap();
+        let count = |key: &str| match json.get(key) {
+            Some(Value::Int(count)) => *count,
+            other => panic!("{}: {:?}", key, other),
+        };
+        assert_eq!(count("files_processed"), 2);
+        assert_eq!(count("text_files"), 1);
+        assert_eq!(count("unreadable_files"), 1);
+        assert_eq!(count("total_bytes"), result.content.len() as u64);
+        assert!(count("peak_buffer_bytes") >= "good text".len() as u64);
+        let causes = json.get("unreadable_causes").and_then(|causes| causes.get("invalid encoding"));
+        assert_eq!(causes, Some(&Value::Int(1)));
+        let Some(Value::Array(skipped)) = json.get("skipped") else {
+            panic!("skipped: {:?}", json.get("skipped"));
+        };
+        assert_eq!(skipped[0].get("category"), Some(&Value::String("unreadable".to_string())));
+        for key in ["total_tokens", "extension_tokens", "model", "phase_secs", "languages", "encoding"] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+
+        let error = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(UnreadableCause::from_io(&error), UnreadableCause::Vanished);
+        let error = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(UnreadableCause::from_io(&error), UnreadableCause::PermissionDenied);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_explain() {
+        let dir = setup_test_dir("explain");
+        fs::create_dir_all(dir.join("logs/old")).unwrap();
+        fs::create_dir_all(dir.join(".config")).unwrap();
+        fs::create_dir_all(dir.join("src/deep/er")).unwrap();
+        fs::write(dir.join(".gitignore"), "# build output\nlogs/\n").unwrap();
+        fs::write(dir.join("logs/old/app.log"), "log").unwrap();
+        fs::write(dir.join(".config/settings.toml"), "settings").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("src/notes.tmp"), "notes").unwrap();
+        fs::write(dir.join("src/big.rs"), "x".repeat(2048)).unwrap();
+        fs::write(dir.join("src/deep/er/lib.rs"), "pub fn f() {}").unwrap();
+        let options = || WalkOptions {
+            max_file_size: 1024,
+            max_depth: Some(1),
+            exclude_patterns: vec!["*.tmp".to_string()],
+            ..WalkOptions::default()
+        };
+        let roots = std::slice::from_ref(&dir);
+
+        assert_eq!(explain(roots, &dir.join("src/main.rs"), options()).unwrap(), Explanation::Included);
+        // A directory left out explains everything below it
+        let reason = SkipReason::Gitignored {
+            source: dir.join(".gitignore"),
+            line: 2,
+            pattern: "logs/".to_string(),
+        };
+        assert_eq!(
+            explain(roots, &dir.join("logs/old/app.log"), options()).unwrap(),
+            Explanation::Skipped { at: dir.join("logs"), reason }
+        );
+        let skipped_at = |path: &str| match explain(roots, &dir.join(path), options()).unwrap() {
+            Explanation::Skipped { at, reason } => (at, reason.category()),
+            other => panic!("{} not skipped: {:?}", path, other),
+        };
+        assert_eq!(skipped_at(".config/settings.toml"), (dir.join(".config"), "hidden"));
+        assert_eq!(skipped_at("src/notes.tmp"), (dir.join("src/notes.tmp"), "excluded"));
+        assert_eq!(skipped_at("src/big.rs"), (dir.join("src/big.rs"), "too large"));
+        assert_eq!(skipped_at("src/deep/er/lib.rs"), (dir.join("src/deep"), "beyond max depth"));
+
+        // Paths from `also` bypass the filters
+        let forcing = WalkOptions {
+            also: vec![dir.join("logs")],
+            ..options()
+        };
+        assert_eq!(
+            explain(roots, &dir.join("logs/old/app.log"), forcing).unwrap(),
+            Explanation::Forced { also: dir.join("logs") }
+        );
+        let elsewhere = [dir.join("src")];
+        assert_eq!(explain(&elsewhere, &dir.join(".gitignore"), options()).unwrap(), Explanation::NotReached);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_default_excludes() {
+        let dir = setup_test_dir("default_excludes");
+
+        fs::create_dir_all(dir.join("node_modules/left-pad")).unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("node_modules/left-pad/index.js"), "dependency code").unwrap();
+        fs::write(dir.join("src/app.js"), "app code").unwrap();
+        fs::write(dir.join("src/app.min.js"), "minified code").unwrap();
+        fs::write(dir.join("package-lock.json"), "lockfile").unwrap();
+        // A file named like an excluded directory is kept
+        fs::write(dir.join("build"), "build script").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(result.content.contains("app code"));
+        assert!(result.content.contains("build script"));
+        assert!(!result.content.contains("dependency code"));
+        assert!(!result.content.contains("minified code"));
+        assert!(!result.content.contains("lockfile"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::DefaultExcluded {
+                    pattern: "node_modules".to_string(),
+                }
+        }));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                default_excludes: false,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("dependency code"));
+        assert!(result.content.contains("lockfile"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_transcoded_files() {
+        let dir = setup_test_dir("transcoded_files");
+
+        fs::write(dir.join("legacy.txt"), b"caf\xE9").unwrap();
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("wide text".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        fs::write(dir.join("wide.txt"), utf16).unwrap();
+        fs::write(dir.join("plain.txt"), "plain").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(result.content.contains(&format!("--- {} (Latin-1) ---\ncafé", dir.join("legacy.txt").display())));
+        assert!(result.content.contains(&format!("--- {} (UTF-16LE) ---\nwide text", dir.join("wide.txt").display())));
+        assert!(result.content.contains(&format!("--- {} ---\nplain", dir.join("plain.txt").display())));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compressed_files() {
+        let dir = setup_test_dir("compressed_files");
+
+        // Compressed with the same tool that decompresses it
+        let compress = |name: &str, content: &str| {
+            fs::write(dir.join(name), content).unwrap();
+            let status = std::process::Command::new("gzip").arg(dir.join(name)).status().unwrap();
+            assert!(status.success());
+        };
+        compress("app.log", "request served\n");
+        compress("big.log", &"x".repeat(2000));
+        fs::write(dir.join("broken.txt.gz"), "not gzip data").unwrap();
+
+        let options = WalkOptions {
+            max_file_size: 1000,
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(std::slice::from_ref(&dir), options).unwrap();
+        let header = format!("--- {} (decompressed from gzip) ---\nrequest served", dir.join("app.log.gz").display());
+        assert!(result.content.contains(&header));
+        assert!(!result.content.contains("xxx"));
+        assert!(!result.content.contains("not gzip data"));
+        let reason_for = |name: &str| {
+            result
+                .stats
+                .skipped()
+                .iter()
+                .find(|entry| entry.path == dir.join(name))
+                .map(|entry| entry.reason.clone())
+        };
+        assert_eq!(reason_for("big.log.gz"), Some(SkipReason::TooLarge { size: 2000, limit: 1000 }));
+        assert_eq!(reason_for("broken.txt.gz"), Some(SkipReason::Binary));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_urls() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // Serves each request the body after its path, as in `/spec.yaml?openapi: 3.0`
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let len = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..len]).to_string();
+                let target = request.split(' ').nth(1).unwrap_or_default();
+                let body = target.split_once('?').map_or("", |(_, body)| body).replace("%20", " ");
+                let status = if body.is_empty() { "404 Not Found" } else { "200 OK" };
+                let response = format!("HTTP/1.0 {}\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        let spec = format!("{}/spec.yaml?openapi:%203.0", base);
+        let big = format!("{}/big.txt?{}", base, "x".repeat(200));
+        let options = WalkOptions {
+            urls: vec![spec.clone(), big.clone()],
+            max_file_size: 100,
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(&[], options).unwrap();
+        assert_eq!(result.content, format!("--- {} ---\nopenapi: 3.0", spec));
+        let skipped = result.stats.skipped();
+        assert_eq!(skipped[0].path, PathBuf::from(&big));
+        assert_eq!(skipped[0].reason, SkipReason::TooLarge { size: 200, limit: 100 });
+
+        let options = WalkOptions {
+            urls: vec![format!("{}/missing", base)],
+            ..WalkOptions::default()
+        };
+        assert!(matches!(walk_and_collect(&[], options), Err(Error::Fetch(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_archives() {
+        let dir = setup_test_dir("archives");
+
+        let contents = dir.join("contents");
+        fs::create_dir_all(contents.join("src")).unwrap();
+        fs::create_dir_all(contents.join("node_modules/pkg")).unwrap();
+        fs::write(contents.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(contents.join("src/.hidden"), "hidden").unwrap();
+        fs::write(contents.join("src/notes.tmp"), "scratch").unwrap();
+        fs::write(contents.join("big.txt"), "x".repeat(2000)).unwrap();
+        fs::write(contents.join("node_modules/pkg/index.js"), "module.exports = 1").unwrap();
+        let archive = dir.join("bundle.tar.gz");
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&contents)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let options = WalkOptions {
+            max_file_size: 1000,
+            exclude_patterns: vec!["*.tmp".to_string()],
+            ..WalkOptions::default()
+        };
+        let result = walk_and_collect(std::slice::from_ref(&archive), options).unwrap();
+        let header = format!("--- {}!/src/main.rs ---\nfn main() {{}}", archive.display());
+        assert!(result.content.contains(&header), "{}", result.content);
+        assert_eq!(result.files.len(), 1);
+        let reason_for = |name: &str| {
+            result
+                .stats
+                .skipped()
+                .iter()
+                .find(|entry| entry.path == archive.join(name))
+                .map(|entry| entry.reason.clone())
+        };
+        assert_eq!(reason_for("src/.hidden"), Some(SkipReason::Hidden));
+        assert_eq!(reason_for("src/notes.tmp"), Some(SkipReason::Excluded { pattern: "*.tmp".to_string() }));
+        assert_eq!(reason_for("big.txt"), Some(SkipReason::TooLarge { size: 2000, limit: 1000 }));
+        assert!(matches!(reason_for("node_modules"), Some(SkipReason::DefaultExcluded { .. })));
+
+        // Only archives named as roots are opened; found in a directory, they are binary
+        fs::remove_dir_all(&contents).unwrap();
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(result.files.is_empty());
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_generated_files() {
+        let dir = setup_test_dir("generated_files");
+
+        fs::create_dir(dir.join("api")).unwrap();
+        fs::write(dir.join(".gitattributes"), "*.pb.go linguist-generated=true\nthird_party/** linguist-vendored\n").unwrap();
+        fs::write(dir.join("api/service.pb.go"), "generated stub").unwrap();
+        fs::write(dir.join("api/service.go"), "handwritten code").unwrap();
+        fs::create_dir(dir.join("third_party")).unwrap();
+        fs::write(dir.join("third_party/lib.go"), "vendored code").unwrap();
+        fs::write(dir.join("api/schema.rs"), "// @generated by build.rs\nschema code").unwrap();
+        fs::write(dir.join("api/client.py"), "# Code generated by protoc. DO NOT EDIT.\nclient code").unwrap();
+        // Markers outside a leading comment don't count
+        fs::write(dir.join("api/notes.md"), "Do not edit the stubs by hand").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert!(result.content.contains("handwritten code"));
+        assert!(!result.content.contains("generated stub"));
+        assert!(!result.content.contains("vendored code"));
+        assert!(!result.content.contains("schema code"));
+        assert!(!result.content.contains("client code"));
+        assert!(result.content.contains("Do not edit the stubs"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::Generated {
+                    attribute: "linguist-generated".to_string(),
+                    source: dir.join(".gitattributes"),
+                }
+        }));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::GeneratedMarker {
+                    marker: "@generated".to_string(),
+                }
+        }));
+        assert_eq!(result.stats.generated_skipped(), 4);
+
+        // Unless named directly
+        let schema = dir.join("api/schema.rs");
+        let result = walk_and_collect(std::slice::from_ref(&schema), WalkOptions::default()).unwrap();
+        assert!(result.content.contains("schema code"));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                include_generated: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(result.content.contains("generated stub"));
+        assert!(result.content.contains("vendored code"));
+        assert!(result.content.contains("client code"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_ignored_directories_not_read() {
+        let dir = setup_test_dir("ignored_dirs_not_read");
+
+        fs::create_dir_all(dir.join("generated/deep")).unwrap();
+        fs::write(dir.join(".gitignore"), "generated/\n").unwrap();
+        fs::write(dir.join("generated/a.txt"), "a").unwrap();
+        fs::write(dir.join("generated/deep/b.txt"), "b").unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let result = walk_and_collect(std::slice::from_ref(&dir), WalkOptions::default()).unwrap();
+        assert_eq!(result.files.len(), 1);
+        // The directory is skipped as a whole; nothing inside it is looked at
+        let generated: Vec<_> = result
+            .stats
+            .skipped()
+            .iter()
+            .filter(|entry| entry.path.starts_with(dir.join("generated")))
+            .map(|entry| entry.path.clone())
+            .collect();
+        assert_eq!(generated, vec![dir.join("generated")]);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_sensitive_files() {
+        let dir = setup_test_dir("sensitive_files");
+
+        fs::create_dir(dir.join("keys")).unwrap();
+        fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        fs::write(dir.join(".env.example"), "SECRET=").unwrap();
+        fs::write(dir.join("keys/id_rsa"), "private key").unwrap();
+        fs::write(dir.join("keys/server.PEM"), "certificate").unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let options = || WalkOptions {
+            include_hidden: true,
+            also: vec![dir.join("keys")],
+            ..WalkOptions::default()
+        };
+        // Not even `--all` or `--also` lets them through
+        let result = walk_and_collect(std::slice::from_ref(&dir), options()).unwrap();
+        let mut included: Vec<_> = result.files.iter().map(|file| file.path.clone()).collect();
+        included.sort();
+        assert_eq!(included, vec![dir.join(".env.example"), dir.join("main.rs")]);
+        let sensitive = result
+            .stats
+            .skipped()
+            .iter()
+            .filter(|entry| matches!(entry.reason, SkipReason::Sensitive { .. }))
+            .count();
+        assert_eq!(sensitive, 3);
+        assert_eq!(result.stats.sensitive_skipped(), 3);
+
+        // Files named directly are exempt
+        let result = walk_and_collect(&[dir.join(".env")], options()).unwrap();
+        assert!(result.content.contains("SECRET=1"));
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                skip_sensitive: false,
+                ..options()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files.len(), 5);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_exclude_regex() {
+        let dir = setup_test_dir("exclude_regex");
+
+        fs::create_dir_all(dir.join("web/__snapshots__")).unwrap();
+        fs::write(dir.join("web/app.ts"), "app code").unwrap();
+        fs::write(dir.join("web/app_test.ts"), "app test").unwrap();
+        fs::write(dir.join("web/app_spec.js"), "app spec").unwrap();
+        fs::write(dir.join("web/__snapshots__/app.snap"), "snapshot").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                exclude_regexes: vec![
+                    Regex::new(r".*_(test|spec)\.(js|ts)$").unwrap(),
+                    Regex::new(r"/__snapshots__$").unwrap(),
+                ],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(result.content.contains("app code"));
+        assert!(!result.content.contains("app test"));
+        assert!(!result.content.contains("app spec"));
+        assert!(!result.content.contains("snapshot"));
+        assert!(result.stats.skipped().iter().any(|entry| {
+            entry.reason
+                == SkipReason::Excluded {
+                    pattern: r".*_(test|spec)\.(js|ts)$".to_string(),
+                }
+        }));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_skip_reasons() {
+        let dir = setup_test_dir("skip_reasons");
+
+        fs::write(dir.join(".gitignore"), "# logs\n*.log\n").unwrap();
+        fs::write(dir.join("debug.log"), "log").unwrap();
+        fs::write(dir.join(".env"), "secret").unwrap();
+        fs::write(dir.join("notes.tmp"), "tmp").unwrap();
+        fs::write(dir.join("big.txt"), "x".repeat(2048)).unwrap();
+        fs::write(dir.join("keep.txt"), "keep").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                max_file_size: 1024,
+                exclude_patterns: vec!["*.tmp".to_string()],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        let reason_for = |name: &str| {
+            result
+                .stats
+                .skipped()
+                .iter()
+                .find(|entry| entry.path == dir.join(name))
+                .map(|entry| entry.reason.clone())
+        };
+
+        assert_eq!(
+            reason_for("debug.log"),
+            Some(SkipReason::Gitignored {
+                source: dir.join(".gitignore"),
+                line: 2,
+                pattern: "*.log".to_string(),
+            })
+        );
+        // Sensitive files are skipped before anything else is checked
+        assert_eq!(
+            reason_for(".env"),
+            Some(SkipReason::Sensitive {
+                pattern: ".env".to_string()
+            })
+        );
+        assert_eq!(
+            reason_for("notes.tmp"),
+            Some(SkipReason::Excluded {
+                pattern: "*.tmp".to_string()
+            })
+        );
+        assert_eq!(
+            reason_for("big.txt"),
+            Some(SkipReason::TooLarge {
+                size: 2048,
+                limit: 1024
+            })
+        );
+        assert_eq!(reason_for("keep.txt"), None);
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_custom_filter() {
+        /// Leaves out files whose name mentions a word
+        struct Mentions(&'static str);
+
+        impl Filter for Mentions {
+            fn name(&self) -> &str {
+                "mentions"
+            }
+
+            fn check(&self, entry: &Entry<'_>, _context: &dyn WalkContext) -> Option<SkipReason> {
+                entry.relative.contains(self.0).then(|| SkipReason::Custom {
+                    filter: self.name().to_string(),
+                    reason: format!("mentions {}", self.0),
+                })
+            }
+        }
+
+        let dir = setup_test_dir("custom_filter");
+        fs::create_dir_all(dir.join("forced")).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("draft_notes.md"), "notes").unwrap();
+        fs::write(dir.join(".draft"), "hidden").unwrap();
+        fs::write(dir.join("forced/draft.md"), "forced").unwrap();
+
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                exclude_patterns: vec!["forced/".to_string()],
+                also: vec![dir.join("forced")],
+                filters: vec![Arc::new(Mentions("draft"))],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        let skipped = result.stats.skipped();
+        let reason_for = |name: &str| {
+            skipped
+                .iter()
+                .find(|entry| entry.path == dir.join(name))
+                .map(|entry| entry.reason.to_string())
+        };
+        assert!(result.content.contains("fn main() {}"));
+        assert_eq!(reason_for("draft_notes.md").as_deref(), Some("mentions draft (filter 'mentions')"));
+        // Built-in filters come first, and custom ones don't apply below `also` paths
+        assert_eq!(reason_for(".draft").as_deref(), Some("hidden"));
+        assert!(result.content.contains("forced"));
+
+        cleanup_test_dir(&dir);
+    }
+
+    #[test]
+    fn test_fn_filter() {
+        use crate::filter::{FilterDecision, FnFilter};
+
+        let dir = setup_test_dir("fn_filter");
+        fs::create_dir_all(dir.join("fixtures")).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("empty.rs"), "").unwrap();
+        fs::write(dir.join("fixtures/data.json"), "{}").unwrap();
+
+        // Leave out empty files and any directory named `fixtures`
+        let filter = FnFilter::new("placeholders", |path, metadata| {
+            if metadata.is_file() && metadata.len() == 0 {
+                FilterDecision::Skip("empty placeholder".to_string())
+            } else if metadata.is_dir() && path.ends_with("fixtures") {
+                FilterDecision::Skip("fixture data".to_string())
+            } else {
+                FilterDecision::Keep
+            }
+        });
+        let result = walk_and_collect(
+            std::slice::from_ref(&dir),
+            WalkOptions {
+                filters: vec![Arc::new(filter)],
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(result.files.len(), 1);
+        let skipped = result.stats.skipped();
+        let reasons: Vec<String> = skipped.iter().map(|entry| entry.reason.to_string()).collect();
+        assert!(reasons.contains(&"empty placeholder (filter 'placeholders')".to_string()));
+        assert!(reasons.contains(&"fixture data (filter 'placeholders')".to_string()));
+        assert!(!skipped.iter().any(|entry| entry.path.ends_with("data.json")));
+
+        cleanup_test_dir(&dir);
     }
 }